@@ -216,6 +216,14 @@ fn parse_overload(overload: &CommandOverload, mut parts: Split<char>)
                     return Err((format!("Failed to parse argument '{}'. Expected a valid integer.", part), i));
                 }
             }
+            CommandDataType::Float => {
+                let result = part.parse();
+                if let Ok(value) = result {
+                    ParsedArgument::Float(value)
+                } else {
+                    return Err((format!("Failed to parse argument '{}'. Expected a valid number.", part), i));
+                }
+            }
             _ => todo!()
         };
 
@@ -1,15 +1,19 @@
 //! Server configuration
 
 use std::{
+    collections::HashMap,
     net::{SocketAddrV4, SocketAddrV6},
     sync::{
         atomic::{AtomicUsize, Ordering},
         Arc,
     },
+    time::Duration,
 };
 
 use proto::bedrock::{CompressionAlgorithm, ThrottleSettings};
+use proto::crypto::{AuthMode, ChainValidation};
 use util::CowString;
+use proto::uuid::Uuid;
 
 use crate::instance::{Instance, IPV4_LOCAL_ADDR};
 
@@ -21,10 +25,116 @@ pub struct Compression {
     pub threshold: u16,
 }
 
+/// Name of the level that clients join when they connect.
+pub const DEFAULT_LEVEL_NAME: &str = "world";
+
 /// Configuration of the level
+#[derive(Debug, Clone)]
 pub struct LevelConfig {
+    /// Name that identifies the level within the instance.
+    pub name: String,
     /// The path to the level.
     pub path: String,
+    /// Maximum amount of unmodified subchunks kept in memory.
+    pub cache_size: usize,
+    /// Generator used for chunks that do not exist yet.
+    pub generator: GeneratorConfig,
+    /// Storage backend that the level data is kept in.
+    pub storage: StorageConfig,
+}
+
+impl LevelConfig {
+    /// Creates a level configuration with the default cache size and generator.
+    pub fn new<N: Into<String>, P: Into<String>>(name: N, path: P) -> LevelConfig {
+        LevelConfig {
+            name: name.into(),
+            path: path.into(),
+            cache_size: 4096,
+            generator: GeneratorConfig::default(),
+            storage: StorageConfig::default(),
+        }
+    }
+}
+
+/// Selects the terrain generator of the level.
+///
+/// Generation is opt-in, by default chunks that do not exist are left empty.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum GeneratorConfig {
+    /// No terrain is generated, chunks that do not exist are empty.
+    #[default]
+    None,
+    /// Superflat terrain with the given layer preset.
+    ///
+    /// See [`SuperflatGenerator`](crate::level::generator::SuperflatGenerator) for the preset format.
+    Flat(String),
+    /// Noise-based terrain seeded with the seed of the world.
+    ///
+    /// See [`NoiseGenerator`](crate::level::generator::NoiseGenerator).
+    Noise,
+}
+
+/// Selects where the chunks of a level are stored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StorageConfig {
+    /// Chunks are stored in the LevelDB database inside of the level directory.
+    #[default]
+    LevelDb,
+    /// Chunks are only kept in memory and are lost when the server shuts down.
+    ///
+    /// The level settings are still read from and written to `level.dat` in the level directory.
+    Memory,
+}
+
+/// Configuration of the resource and behavior packs.
+#[derive(Default)]
+pub struct PackConfig {
+    /// Directory that packs are loaded from.
+    ///
+    /// No packs are loaded if this is not set.
+    pub path: Option<String>,
+    /// Whether clients have to accept the packs to be able to join.
+    pub required: bool,
+    /// Keys of encrypted packs, indexed by pack UUID.
+    ///
+    /// Keys can also be stored next to the pack in a file with a `.key` extension.
+    pub content_keys: HashMap<Uuid, String>,
+}
+
+/// Configuration of world backups.
+#[derive(Debug, Clone)]
+pub struct BackupConfig {
+    /// Directory that backups are written to.
+    ///
+    /// Each level is backed up into a subdirectory named after the level.
+    pub directory: String,
+    /// Amount of backups kept per level, older backups are removed.
+    ///
+    /// A value of 0 keeps all backups.
+    pub retention: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self { directory: String::from("backups"), retention: 5 }
+    }
+}
+
+/// Configuration of world pre-generation.
+///
+/// Pre-generation is throttled so that it does not starve connected players of resources.
+#[derive(Debug, Clone)]
+pub struct PregenConfig {
+    /// Amount of chunk columns that are generated in parallel before pausing.
+    pub batch_size: usize,
+    /// Pause between two batches.
+    pub delay: Duration,
+}
+
+impl Default for PregenConfig {
+    fn default() -> Self {
+        Self { batch_size: 256, delay: Duration::from_millis(50) }
+    }
 }
 
 /// A callback for the message of the day.
@@ -52,8 +162,24 @@ pub struct Config {
     ///
     /// Any client that requests a higher render distance will be capped to this value.
     pub(super) max_render_distance: AtomicUsize,
-    /// Level configuration
+    /// Configuration of the level that clients join when they connect.
     pub(super) level: LevelConfig,
+    /// Configuration of the levels that are loaded alongside the default level.
+    pub(super) additional_levels: Vec<LevelConfig>,
+    /// Pack configuration
+    pub(super) packs: PackConfig,
+    /// Backup configuration.
+    pub(super) backups: BackupConfig,
+    /// Pre-generation configuration.
+    pub(super) pregen: PregenConfig,
+    /// Directory containing block upgrade schemas.
+    ///
+    /// Blocks from older versions of the game that are not upgraded are sent to clients as air.
+    pub(super) block_upgrade_schemas: Option<String>,
+    /// Determines which identity chains are accepted when clients log in.
+    ///
+    /// See [`ChainValidation`] for more info.
+    pub(super) chain_validation: ChainValidation,
     /// Callback that generates a new message of the day.
     pub(super) motd_callback: MotdCallback,
 }
@@ -73,7 +199,13 @@ impl Config {
                 scalar: 0.0,
                 threshold: 0,
             },
-            level: LevelConfig { path: String::from("resources\\level") },
+            level: LevelConfig::new(DEFAULT_LEVEL_NAME, "resources\\level"),
+            additional_levels: Vec::new(),
+            packs: PackConfig::default(),
+            backups: BackupConfig::default(),
+            pregen: PregenConfig::default(),
+            block_upgrade_schemas: None,
+            chain_validation: ChainValidation::default(),
             max_connections: AtomicUsize::new(10),
             max_render_distance: AtomicUsize::new(12),
             motd_callback: Box::new(|_| "Powered by Mirai".into()),
@@ -141,9 +273,57 @@ impl Config {
         self.max_render_distance.store(max, Ordering::Relaxed);
     }
 
+    /// Returns which identity chains are accepted during login.
+    #[inline]
+    pub const fn auth_mode(&self) -> AuthMode {
+        self.chain_validation.mode
+    }
+
+    /// Whether the server only accepts clients that are signed into Xbox Live.
+    #[inline]
+    pub fn online_mode(&self) -> bool {
+        self.chain_validation.mode == AuthMode::Online
+    }
+
+    /// Returns the options used to validate identity chains during login.
+    #[inline]
+    pub const fn chain_validation(&self) -> &ChainValidation {
+        &self.chain_validation
+    }
+
     /// Returns the level configuration.
     #[inline]
     pub const fn level(&self) -> &LevelConfig {
         &self.level
     }
+
+    /// Returns the configuration of the levels that are loaded alongside the default level.
+    #[inline]
+    pub fn additional_levels(&self) -> &[LevelConfig] {
+        &self.additional_levels
+    }
+
+    /// Returns the pack configuration.
+    #[inline]
+    pub const fn packs(&self) -> &PackConfig {
+        &self.packs
+    }
+
+    /// Returns the backup configuration.
+    #[inline]
+    pub const fn backups(&self) -> &BackupConfig {
+        &self.backups
+    }
+
+    /// Returns the pre-generation configuration.
+    #[inline]
+    pub const fn pregen(&self) -> &PregenConfig {
+        &self.pregen
+    }
+
+    /// Returns the directory that block upgrade schemas are loaded from.
+    #[inline]
+    pub fn block_upgrade_schemas(&self) -> Option<&str> {
+        self.block_upgrade_schemas.as_deref()
+    }
 }
@@ -14,16 +14,21 @@ use std::time::Duration;
 use tokio::net::UdpSocket;
 
 use tokio_util::sync::CancellationToken;
+use proto::uuid::Uuid;
 
-use util::{CowString, Deserialize, Joinable, RVec, ReserveTo, Serialize};
+use util::{CowSlice, CowString, Deserialize, Joinable, RVec, ReserveTo, Serialize, Vector};
 
 use crate::command::{self, HandlerOutput, HandlerResult, ParsedCommand};
-use crate::config::Config;
+use crate::config::{Config, GeneratorConfig, LevelConfig, StorageConfig};
+use crate::level::{PregenArea, PregenShape};
 use crate::net::{Clients, ForwardablePacket};
-use level::{BlockStates, CreativeItems, ItemNetworkIds};
+use crate::pack::ResourcePacks;
+use level::{BlockStates, BlockUpgrader, CreativeItems, ItemNetworkIds};
+use proto::crypto::AuthMode;
+use proto::types::Dimension;
 use proto::bedrock::{
-    Command, CommandDataType, CommandEnum, CommandOverload, CommandParameter, CommandPermissionLevel, CreditsStatus, CreditsUpdate, MovePlayer,
-    MovementMode, TeleportCause, CLIENT_VERSION_STRING, PROTOCOL_VERSION,
+    Command, CommandDataType, CommandEnum, CommandOriginType, CommandOutput, CommandOutputMessage, CommandOutputType, CommandOverload,
+    CommandParameter, CommandPermissionLevel, CreditsStatus, CreditsUpdate, TextData, TextMessage, CLIENT_VERSION_STRING, PROTOCOL_VERSION,
 };
 use proto::raknet::{
     IncompatibleProtocol, OpenConnectionReply1, OpenConnectionReply2, OpenConnectionRequest1, OpenConnectionRequest2, UnconnectedPing,
@@ -55,6 +60,136 @@ impl InstanceBuilder {
         self
     }
 
+    /// Sets the name of the level that clients join when they connect.
+    pub fn level_name<N: Into<String>>(mut self, name: N) -> InstanceBuilder {
+        self.0.level.name = name.into();
+        self
+    }
+
+    /// Loads another level alongside the default level.
+    ///
+    /// Players can be moved between levels using [`BedrockClient::transfer_level`](crate::net::BedrockClient::transfer_level).
+    /// Every level has its own gamerules and is saved separately.
+    pub fn add_level(mut self, config: LevelConfig) -> InstanceBuilder {
+        self.0.additional_levels.push(config);
+        self
+    }
+
+    /// Sets the maximum amount of unmodified subchunks that are kept in memory.
+    pub fn level_cache_size(mut self, size: usize) -> InstanceBuilder {
+        self.0.level.cache_size = size;
+        self
+    }
+
+    /// Sets the generator used for chunks that do not exist yet.
+    ///
+    /// No chunks are generated unless a generator is set.
+    /// See [`GeneratorConfig`] for the available generators.
+    pub fn generator(mut self, generator: GeneratorConfig) -> InstanceBuilder {
+        self.0.level.generator = generator;
+        self
+    }
+
+    /// Sets the storage backend that the chunks of the level are kept in.
+    ///
+    /// See [`StorageConfig`] for the available backends.
+    pub fn level_storage(mut self, storage: StorageConfig) -> InstanceBuilder {
+        self.0.level.storage = storage;
+        self
+    }
+
+    /// Sets the directory that resource and behavior packs are loaded from.
+    ///
+    /// Every `.mcpack` and `.zip` archive in this directory is sent to clients when they join.
+    pub fn pack_path<P: Into<String>>(mut self, path: P) -> InstanceBuilder {
+        self.0.packs.path = Some(path.into());
+        self
+    }
+
+    /// Sets the directory that block upgrade schemas are loaded from.
+    ///
+    /// Every `.json` file in the directory is loaded as a schema in the format of the vanilla block upgrade schemas.
+    /// These are used to upgrade blocks from worlds created in older versions of the game.
+    pub fn block_upgrade_schemas<P: Into<String>>(mut self, path: P) -> InstanceBuilder {
+        self.0.block_upgrade_schemas = Some(path.into());
+        self
+    }
+
+    /// Sets the directory that world backups are written to.
+    ///
+    /// This is set to `backups` by default.
+    pub fn backup_directory<P: Into<String>>(mut self, path: P) -> InstanceBuilder {
+        self.0.backups.directory = path.into();
+        self
+    }
+
+    /// Sets the amount of backups that are kept for each level.
+    ///
+    /// When a new backup is made, the oldest backups are removed. A retention of 0 keeps all backups.
+    pub fn backup_retention(mut self, retention: usize) -> InstanceBuilder {
+        self.0.backups.retention = retention;
+        self
+    }
+
+    /// Sets the amount of chunk columns that are generated in parallel during pre-generation.
+    ///
+    /// Larger batches generate faster, but put more load on the server. This is set to 256 by default.
+    pub fn pregen_batch_size(mut self, batch_size: usize) -> InstanceBuilder {
+        self.0.pregen.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Sets the pause between two batches during pre-generation.
+    ///
+    /// This is set to 50 milliseconds by default.
+    pub fn pregen_delay(mut self, delay: Duration) -> InstanceBuilder {
+        self.0.pregen.delay = delay;
+        self
+    }
+
+    /// Sets whether clients have to accept the packs to be able to join.
+    ///
+    /// Clients that refuse required packs are disconnected. Packs are optional by default.
+    pub fn require_packs(mut self, required: bool) -> InstanceBuilder {
+        self.0.packs.required = required;
+        self
+    }
+
+    /// Sets the key used to decrypt the encrypted pack with the given UUID.
+    pub fn pack_content_key<K: Into<String>>(mut self, uuid: Uuid, key: K) -> InstanceBuilder {
+        self.0.packs.content_keys.insert(uuid, key.into());
+        self
+    }
+
+    /// Sets whether clients must be signed into Xbox Live to join.
+    ///
+    /// Online mode is enabled by default. Disabling it also accepts self-signed identity chains,
+    /// which is useful for local development, bots and CI. Clients that log in this way
+    /// receive an offline XUID and UUID derived from their name and are flagged as unauthenticated.
+    pub fn online_mode(mut self, enabled: bool) -> InstanceBuilder {
+        self.0.chain_validation.mode = if enabled { AuthMode::Online } else { AuthMode::Offline };
+        self
+    }
+
+    /// Sets the root keys that are trusted to sign identity chains.
+    ///
+    /// By default only Mojang's current public key is trusted. Use this to add Mojang's new key
+    /// when it is rotated, without having to recompile the server.
+    pub fn trusted_root_keys<I, S>(mut self, keys: I) -> InstanceBuilder
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.0.chain_validation.trusted_keys = keys.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the amount of clock skew that is tolerated when checking the expiry of login tokens.
+    pub fn login_clock_skew(mut self, skew: Duration) -> InstanceBuilder {
+        self.0.chain_validation.clock_skew = skew;
+        self
+    }
+
     /// Sets the IPv4 address of the instance.
     pub fn ipv4_addr<A: Into<SocketAddrV4>>(mut self, addr: A) -> InstanceBuilder {
         self.0.ipv4_addr = addr.into();
@@ -76,8 +211,19 @@ impl InstanceBuilder {
         );
 
         let item_network_ids = ItemNetworkIds::new()?;
-        let block_states = BlockStates::new()?;
+        let mut block_states = BlockStates::new()?;
+        if let Some(path) = &self.0.block_upgrade_schemas {
+            let upgrader = BlockUpgrader::load_dir(path).with_context(|| format!("Unable to load block upgrade schemas from {path}"))?;
+            tracing::info!("Loaded {} block upgrade schemas", upgrader.len());
+
+            block_states.set_upgrader(upgrader);
+        }
         let creative_items = CreativeItems::new(&item_network_ids, &block_states)?;
+        let resource_packs = ResourcePacks::load(&self.0.packs)?;
+
+        if self.0.chain_validation.mode == AuthMode::Offline {
+            tracing::warn!("Online mode is disabled, clients are not required to be signed into Xbox Live");
+        }
 
         let ipv4_socket = UdpSocket::bind(self.0.ipv4_addr).await.context("Unable to create IPv4 UDP socket")?;
         let ipv6_socket = match self.0.ipv6_addr {
@@ -91,12 +237,24 @@ impl InstanceBuilder {
         let running_token = CancellationToken::new();
 
         let command_service = crate::command::Service::new(running_token.clone());
-        let level_service = crate::level::service::Service::new(crate::level::service::ServiceOptions {
-            instance_token: running_token.clone(),
-            level_path: self.0.level.path.clone(),
-        })?;
+        let mut levels: Vec<Arc<crate::level::Service>> = Vec::with_capacity(1 + self.0.additional_levels.len());
+        for config in std::iter::once(&self.0.level).chain(&self.0.additional_levels) {
+            if levels.iter().any(|level| level.name() == config.name) {
+                tracing::error!("Multiple levels are named {}", config.name);
+                anyhow::bail!("Multiple levels are named {}", config.name);
+            }
+
+            levels.push(crate::level::service::Service::new(crate::level::service::ServiceOptions {
+                name: config.name.clone(),
+                instance_token: running_token.clone(),
+                level_path: config.path.clone(),
+                cache_size: config.cache_size,
+                generator: config.generator.clone(),
+                storage: config.storage,
+            })?);
+        }
+        let level_service = Arc::clone(&levels[0]);
 
-        let user_map = Arc::new(Clients::new(Arc::clone(&command_service), Arc::clone(&level_service)));
         let user_map = Arc::new(Clients::new(Arc::clone(&command_service), Arc::clone(&level_service)));
         let instance = Instance {
             ipv4_socket,
@@ -104,6 +262,7 @@ impl InstanceBuilder {
             clients: user_map,
             command_service,
             level_service,
+            levels,
             config: self.0,
 
             raknet_guid: rand::random(),
@@ -114,6 +273,7 @@ impl InstanceBuilder {
 
             // Data
             creative_items,
+            resource_packs,
             block_states,
             item_network_ids,
         };
@@ -146,8 +306,10 @@ pub struct Instance {
     clients: Arc<Clients>,
     /// Keeps track of all available commands.
     command_service: Arc<crate::command::Service>,
-    /// Keeps track of the level state.
+    /// The level that clients join when they connect.
     level_service: Arc<crate::level::service::Service>,
+    /// Every level loaded by this instance, starting with the default level.
+    levels: Vec<Arc<crate::level::service::Service>>,
     /// Keeps track of the current configuration of the server.
     config: Config,
     /// Cancelled when the server has started up successfully.
@@ -162,6 +324,8 @@ pub struct Instance {
     current_motd: RwLock<String>,
 
     pub creative_items: CreativeItems,
+    /// Packs that are sent to clients when they join.
+    pub resource_packs: ResourcePacks,
     pub block_states: BlockStates,
     pub item_network_ids: ItemNetworkIds,
 }
@@ -199,6 +363,17 @@ impl Instance {
         &self.level_service
     }
 
+    /// Gets every level loaded by this instance, starting with the default level.
+    #[inline]
+    pub fn levels(&self) -> &[Arc<crate::level::Service>] {
+        &self.levels
+    }
+
+    /// Gets the level with the given name.
+    pub fn level_by_name(&self, name: &str) -> Option<&Arc<crate::level::Service>> {
+        self.levels.iter().find(|level| level.name() == name)
+    }
+
     /// Gets the client list of this instance.
     #[inline]
     pub const fn clients(&self) -> &Arc<crate::net::Clients> {
@@ -251,7 +426,9 @@ impl Instance {
             // Wait for user map to shut down before cancelling general token.
             this.running_token.cancel();
 
-            this.level_service.join().await?;
+            for level in &this.levels {
+                level.join().await?;
+            }
             this.command_service.join().await?;
 
             // Awaiting shutdown of the IPv4 and IPv6 receivers is not important
@@ -270,36 +447,293 @@ impl Instance {
     pub fn start(self: &Arc<Instance>) -> anyhow::Result<()> {
         self.clients.set_instance(self)?;
         self.command_service.set_instance(self)?;
-        self.level_service.set_instance(self)?;
-
-        // self.command_service.register(
-        //     Command {
-        //         aliases: vec!["teleport".to_owned(), "tp".to_owned()],
-        //         description: "Teleports a player".to_owned(),
-        //         name: "tp".to_owned(),
-        //         overloads: vec![CommandOverload { parameters: Vec::new() }],
-        //         permission_level: CommandPermissionLevel::Normal
-        //     }, |_input, ctx| {
-        //         ctx.caller.send(MovePlayer {
-        //             runtime_id: 1,
-        //             head_yaw: 0.0,
-        //             mode: MovementMode::Teleport,
-        //             on_ground: false,
-        //             pitch: 0.0,
-        //             ridden_runtime_id: 0,
-        //             teleport_cause: TeleportCause::Command,
-        //             teleport_source_type: 0,
-        //             tick: 0,
-        //             translation: (20.0, 30.0, 0.0).into(),
-        //             yaw: 0.0
-        //         }).unwrap();
-
-        //         Ok(command::HandlerOutput {
-        //             message: CowString::new("Teleported"),
-        //             parameters: vec![]
-        //         })
-        //     }
-        // )?;
+        for level in &self.levels {
+            level.set_instance(self)?;
+        }
+
+        self.command_service.register(
+            Command {
+                aliases: vec!["teleport".to_owned()],
+                description: "Teleports a player, optionally to another dimension".to_owned(),
+                name: "tp".to_owned(),
+                overloads: vec![CommandOverload {
+                    parameters: vec![
+                        CommandParameter {
+                            name: "x".to_owned(),
+                            command_enum: None,
+                            data_type: CommandDataType::Float,
+                            optional: false,
+                            options: 0,
+                            suffix: "".to_owned(),
+                        },
+                        CommandParameter {
+                            name: "y".to_owned(),
+                            command_enum: None,
+                            data_type: CommandDataType::Float,
+                            optional: false,
+                            options: 0,
+                            suffix: "".to_owned(),
+                        },
+                        CommandParameter {
+                            name: "z".to_owned(),
+                            command_enum: None,
+                            data_type: CommandDataType::Float,
+                            optional: false,
+                            options: 0,
+                            suffix: "".to_owned(),
+                        },
+                        CommandParameter {
+                            name: "dimension".to_owned(),
+                            command_enum: Some(CommandEnum {
+                                dynamic: false,
+                                enum_id: "dimension".to_owned(),
+                                options: vec!["overworld".to_owned(), "nether".to_owned(), "the_end".to_owned()],
+                            }),
+                            data_type: CommandDataType::String,
+                            optional: true,
+                            options: 0,
+                            suffix: "".to_owned(),
+                        },
+                    ],
+                }],
+                permission_level: CommandPermissionLevel::Normal,
+            },
+            |input, ctx| {
+                let coordinate = |name: &str| input.parameters.get(name).and_then(|arg| arg.as_float()).unwrap_or_default();
+                let position = Vector::from([coordinate("x"), coordinate("y"), coordinate("z")]);
+
+                // Parsing already verified that the dimension is one of the enum options.
+                let dimension = match input.parameters.get("dimension").and_then(|arg| arg.as_string()) {
+                    Some("nether") => Dimension::Nether,
+                    Some("the_end") => Dimension::End,
+                    Some(_) => Dimension::Overworld,
+                    None => ctx.caller.dimension(),
+                };
+
+                if let Err(err) = ctx.caller.teleport(position.clone(), dimension) {
+                    tracing::error!("Failed to teleport player: {err:#}");
+                    return HandlerOutput::new().message("Failed to teleport player").error();
+                }
+
+                Ok(HandlerOutput {
+                    message: format!("Teleported to {} {} {} in {dimension:?}", position.x, position.y, position.z).into(),
+                    parameters: Vec::new(),
+                })
+            },
+        )?;
+
+        self.command_service.register(
+            Command {
+                aliases: Vec::new(),
+                description: "Moves a player to another level".to_owned(),
+                name: "world".to_owned(),
+                overloads: vec![CommandOverload {
+                    parameters: vec![CommandParameter {
+                        name: "level".to_owned(),
+                        command_enum: Some(CommandEnum {
+                            dynamic: false,
+                            enum_id: "level".to_owned(),
+                            options: self.levels.iter().map(|level| level.name().to_owned()).collect(),
+                        }),
+                        data_type: CommandDataType::String,
+                        optional: false,
+                        options: 0,
+                        suffix: "".to_owned(),
+                    }],
+                }],
+                permission_level: CommandPermissionLevel::Normal,
+            },
+            |input, ctx| {
+                // Parsing already verified that the level is one of the enum options.
+                let name = input.parameters.get("level").and_then(|arg| arg.as_string()).unwrap_or_default();
+                let Some(level) = ctx.instance.level_by_name(name) else {
+                    return HandlerOutput::new().message(format!("Level {name} does not exist")).error();
+                };
+
+                if let Err(err) = ctx.caller.transfer_level(Arc::clone(level)) {
+                    tracing::error!("Failed to transfer player: {err:#}");
+                    return HandlerOutput::new().message("Failed to transfer player").error();
+                }
+
+                Ok(HandlerOutput {
+                    message: format!("Moved to level {name}").into(),
+                    parameters: Vec::new(),
+                })
+            },
+        )?;
+
+        self.command_service.register(
+            Command {
+                aliases: Vec::new(),
+                description: "Creates a backup of the current level".to_owned(),
+                name: "backup".to_owned(),
+                overloads: vec![CommandOverload { parameters: Vec::new() }],
+                permission_level: CommandPermissionLevel::Normal,
+            },
+            |_input, ctx| {
+                let level = ctx.caller.level();
+                let name = level.name().to_owned();
+
+                let caller = Arc::clone(&ctx.caller);
+                tokio::spawn(async move {
+                    let message = match level.backup().await {
+                        Ok(path) => format!("Backed up level {} to {}", level.name(), path.display()),
+                        Err(err) => {
+                            tracing::error!("Failed to back up level {}: {err:#}", level.name());
+                            format!("Failed to back up level {}", level.name())
+                        }
+                    };
+
+                    let _: anyhow::Result<()> = caller.send(TextMessage {
+                        data: TextData::Raw { message: &message },
+                        needs_translation: false,
+                        xuid: 0,
+                        platform_chat_id: "",
+                    });
+                });
+
+                Ok(HandlerOutput {
+                    message: format!("Backing up level {name}").into(),
+                    parameters: Vec::new(),
+                })
+            },
+        )?;
+
+        self.command_service.register(
+            Command {
+                aliases: Vec::new(),
+                description: "Generates all chunks within a radius around you ahead of time".to_owned(),
+                name: "pregen".to_owned(),
+                overloads: vec![
+                    CommandOverload {
+                        parameters: vec![
+                            CommandParameter {
+                                name: "radius".to_owned(),
+                                command_enum: None,
+                                data_type: CommandDataType::Int,
+                                optional: false,
+                                options: 0,
+                                suffix: "".to_owned(),
+                            },
+                            CommandParameter {
+                                name: "shape".to_owned(),
+                                command_enum: Some(CommandEnum {
+                                    dynamic: false,
+                                    enum_id: "pregen_shape".to_owned(),
+                                    options: vec!["square".to_owned(), "circle".to_owned()],
+                                }),
+                                data_type: CommandDataType::String,
+                                optional: true,
+                                options: 0,
+                                suffix: "".to_owned(),
+                            },
+                        ],
+                    },
+                    CommandOverload {
+                        parameters: vec![CommandParameter {
+                            name: "action".to_owned(),
+                            command_enum: Some(CommandEnum {
+                                dynamic: false,
+                                enum_id: "pregen_action".to_owned(),
+                                options: vec!["cancel".to_owned()],
+                            }),
+                            data_type: CommandDataType::String,
+                            optional: false,
+                            options: 0,
+                            suffix: "".to_owned(),
+                        }],
+                    },
+                ],
+                permission_level: CommandPermissionLevel::Normal,
+            },
+            |input, ctx| {
+                let level = ctx.caller.level();
+                let name = level.name().to_owned();
+
+                // Parsing already verified that the action is one of the enum options.
+                if input.parameters.contains_key("action") {
+                    if !level.cancel_pregeneration() {
+                        return HandlerOutput::new().message(format!("Level {name} is not being pre-generated")).error();
+                    }
+
+                    return Ok(HandlerOutput {
+                        message: format!("Cancelling pre-generation of level {name}").into(),
+                        parameters: Vec::new(),
+                    });
+                }
+
+                let radius = input.parameters.get("radius").and_then(|arg| arg.as_int()).unwrap_or_default();
+                let Ok(radius) = u32::try_from(radius) else {
+                    return HandlerOutput::new().message("Radius cannot be negative").error();
+                };
+
+                if level.is_pregenerating() {
+                    return HandlerOutput::new().message(format!("Level {name} is already being pre-generated")).error();
+                }
+
+                let shape = match input.parameters.get("shape").and_then(|arg| arg.as_string()) {
+                    Some("circle") => PregenShape::Circle,
+                    _ => PregenShape::Square,
+                };
+                let area = PregenArea {
+                    center: ctx.caller.chunk_position(),
+                    radius,
+                    dimension: ctx.caller.dimension(),
+                    shape,
+                };
+
+                let caller = Arc::clone(&ctx.caller);
+                tokio::spawn(async move {
+                    // The original command request has already been answered, so progress is sent as separate output.
+                    let report = |message: &str, is_success: bool| {
+                        let _: anyhow::Result<()> = caller.send(CommandOutput {
+                            origin: CommandOriginType::Player,
+                            request_id: "",
+                            output_type: CommandOutputType::AllOutput,
+                            success_count: u32::from(is_success),
+                            output: CowSlice::Owned(vec![CommandOutputMessage {
+                                is_success,
+                                message: CowString::new(message),
+                                parameters: CowSlice::Owned(Vec::new()),
+                            }]),
+                        });
+                    };
+
+                    // Only report every 10% to avoid flooding the chat.
+                    let mut reported = 0;
+                    let result = level
+                        .pregenerate(area, |progress| {
+                            let percentage = progress.percentage() / 10 * 10;
+                            if percentage > reported && !progress.is_complete() {
+                                reported = percentage;
+                                report(&format!("Pre-generating level {}: {percentage}%", level.name()), true);
+                            }
+                        })
+                        .await;
+
+                    match result {
+                        Ok(progress) if progress.is_complete() => {
+                            report(&format!("Pre-generated {} chunks in level {}", progress.total, level.name()), true);
+                        }
+                        Ok(progress) => {
+                            report(
+                                &format!("Cancelled pre-generation of level {} after {}/{} chunks", level.name(), progress.completed, progress.total),
+                                true,
+                            );
+                        }
+                        Err(err) => {
+                            tracing::error!("Failed to pre-generate level {}: {err:#}", level.name());
+                            report(&format!("Failed to pre-generate level {}", level.name()), false);
+                        }
+                    }
+                });
+
+                Ok(HandlerOutput {
+                    message: format!("Pre-generating chunks within {radius} chunks in level {name}").into(),
+                    parameters: Vec::new(),
+                })
+            },
+        )?;
 
         self.command_service.register(
             Command {
@@ -0,0 +1,373 @@
+//! Caches decoded subchunks in memory.
+
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use level::{Biomes, SubChunk};
+use parking_lot::Mutex;
+use proto::types::Dimension;
+use tokio::sync::Notify;
+
+use super::io::stream::RegionIndex;
+
+/// Amount of independently locked shards in the cache.
+///
+/// Sharding reduces lock contention when many players are loading chunks at the same time.
+const SHARD_COUNT: usize = 16;
+
+/// Uniquely identifies a subchunk within the level.
+pub type CacheKey = (Dimension, RegionIndex);
+
+/// Identifies a chunk column by dimension and horizontal chunk coordinates.
+pub type ColumnKey = (Dimension, i32, i32);
+
+/// A cached subchunk.
+struct CacheEntry {
+    /// The decoded subchunk.
+    chunk: Arc<SubChunk>,
+    /// Whether this subchunk has been modified and not yet written to disk.
+    ///
+    /// Dirty entries are never evicted.
+    dirty: bool,
+    /// Value of the shard clock when this entry was last accessed.
+    last_used: u64,
+}
+
+/// A single independently locked part of the cache.
+struct Shard {
+    entries: HashMap<CacheKey, CacheEntry>,
+    /// Clean entries ordered by the time they were last accessed, least recently used first.
+    ///
+    /// Dirty entries are not included, as they cannot be evicted.
+    order: BTreeMap<u64, CacheKey>,
+    /// Incremented on every access and used to determine the least recently used entry.
+    clock: u64,
+}
+
+impl Shard {
+    /// Advances the clock and returns the new time.
+    #[inline]
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// Marks an entry as accessed and returns it.
+    fn touch(&mut self, key: &CacheKey) -> Option<&CacheEntry> {
+        let now = self.tick();
+        let entry = self.entries.get_mut(key)?;
+
+        if !entry.dirty {
+            self.order.remove(&entry.last_used);
+            self.order.insert(now, *key);
+        }
+        entry.last_used = now;
+
+        Some(entry)
+    }
+
+    /// Inserts an entry, replacing any existing entry with the same key.
+    fn put(&mut self, key: CacheKey, chunk: Arc<SubChunk>, dirty: bool) -> Option<CacheEntry> {
+        let now = self.tick();
+        let old = self.entries.insert(
+            key,
+            CacheEntry {
+                chunk,
+                dirty,
+                last_used: now,
+            },
+        );
+
+        if let Some(old) = &old {
+            if !old.dirty {
+                self.order.remove(&old.last_used);
+            }
+        }
+        if !dirty {
+            self.order.insert(now, key);
+        }
+
+        old
+    }
+
+    /// Marks a dirty entry as written, allowing it to be evicted again.
+    fn unpin(&mut self, key: &CacheKey) {
+        if let Some(entry) = self.entries.get_mut(key) {
+            entry.dirty = false;
+            self.order.insert(entry.last_used, *key);
+        }
+    }
+
+    /// Evicts clean entries until the shard fits within its capacity, returning how many were evicted.
+    ///
+    /// If all entries are dirty, the shard is allowed to temporarily exceed its capacity.
+    fn evict(&mut self, capacity: usize) -> u64 {
+        let mut evicted = 0;
+        while self.entries.len() > capacity {
+            let Some((_, key)) = self.order.pop_first() else { break };
+
+            self.entries.remove(&key);
+            evicted += 1;
+        }
+
+        evicted
+    }
+}
+
+/// A snapshot of the cache statistics.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct CacheMetrics {
+    /// How many lookups were served from the cache.
+    pub hits: u64,
+    /// How many lookups had to be loaded from disk.
+    pub misses: u64,
+    /// How many entries have been evicted to make room for new ones.
+    pub evictions: u64,
+    /// Current amount of cached subchunks.
+    pub entries: usize,
+    /// Current amount of modified subchunks waiting to be written to disk.
+    pub dirty: usize,
+}
+
+/// A sharded, size-bounded cache of decoded subchunks.
+///
+/// Least recently used subchunks are evicted once a shard is full.
+/// Modified subchunks are pinned in the cache until they have been written to disk,
+/// so that reads always observe the latest changes. The same applies to the biomes of generated chunk columns.
+pub struct SubChunkCache {
+    shards: Box<[Mutex<Shard>]>,
+    /// Maximum amount of entries per shard.
+    shard_capacity: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    /// Amount of dirty entries across all shards.
+    dirty: AtomicUsize,
+    /// Amount of dirty entries at which [`threshold_reached`](Self::threshold_reached) is signalled.
+    dirty_threshold: AtomicUsize,
+    /// Signalled when the amount of dirty entries reaches the threshold.
+    threshold_notify: Notify,
+    /// Biomes of generated chunk columns that have not been written to disk yet.
+    columns: Mutex<HashMap<ColumnKey, Arc<Biomes>>>,
+}
+
+impl SubChunkCache {
+    /// Creates a cache that holds approximately `capacity` clean subchunks.
+    pub fn new(capacity: usize) -> Self {
+        let shard_capacity = capacity.div_ceil(SHARD_COUNT).max(1);
+        let shards = (0..SHARD_COUNT)
+            .map(|_| {
+                Mutex::new(Shard {
+                    entries: HashMap::with_capacity(shard_capacity),
+                    order: BTreeMap::new(),
+                    clock: 0,
+                })
+            })
+            .collect();
+
+        Self {
+            shards,
+            shard_capacity,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            dirty: AtomicUsize::new(0),
+            dirty_threshold: AtomicUsize::new(usize::MAX),
+            threshold_notify: Notify::new(),
+            columns: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sets the amount of dirty entries at which [`threshold_reached`](Self::threshold_reached) resolves.
+    pub fn set_dirty_threshold(&self, threshold: usize) {
+        self.dirty_threshold.store(threshold, Ordering::Relaxed);
+    }
+
+    /// Resolves once a subchunk has been marked dirty while the amount of dirty entries is at or above the threshold.
+    ///
+    /// If the threshold was reached while nobody was waiting, this resolves immediately.
+    pub async fn threshold_reached(&self) {
+        self.threshold_notify.notified().await;
+    }
+
+    /// Registers a new dirty entry and signals the waiting collector if the threshold has been reached.
+    fn add_dirty(&self) {
+        let count = self.dirty.fetch_add(1, Ordering::Relaxed) + 1;
+        if count >= self.dirty_threshold.load(Ordering::Relaxed) {
+            self.threshold_notify.notify_one();
+        }
+    }
+
+    /// Returns the shard that the given key belongs to.
+    fn shard(&self, key: &CacheKey) -> &Mutex<Shard> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+
+        &self.shards[hasher.finish() as usize % SHARD_COUNT]
+    }
+
+    /// Looks up a subchunk in the cache.
+    pub fn get(&self, key: &CacheKey) -> Option<Arc<SubChunk>> {
+        let mut shard = self.shard(key).lock();
+
+        if let Some(entry) = shard.touch(key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+
+            Some(Arc::clone(&entry.chunk))
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            None
+        }
+    }
+
+    /// Inserts a subchunk that was loaded from disk.
+    ///
+    /// If the subchunk has been modified in the meantime, the cached version is kept
+    /// and returned instead.
+    pub fn insert(&self, key: CacheKey, chunk: Arc<SubChunk>) -> Arc<SubChunk> {
+        let mut shard = self.shard(&key).lock();
+
+        if let Some(entry) = shard.touch(&key) {
+            return Arc::clone(&entry.chunk);
+        }
+
+        shard.put(key, Arc::clone(&chunk), false);
+
+        let evicted = shard.evict(self.shard_capacity);
+        self.evictions.fetch_add(evicted, Ordering::Relaxed);
+
+        chunk
+    }
+
+    /// Inserts a modified subchunk, pinning it in the cache until it has been written to disk.
+    pub fn insert_dirty(&self, key: CacheKey, chunk: Arc<SubChunk>) {
+        let mut shard = self.shard(&key).lock();
+        let old = shard.put(key, chunk, true);
+
+        if !old.is_some_and(|entry| entry.dirty) {
+            self.add_dirty();
+        }
+
+        let evicted = shard.evict(self.shard_capacity);
+        self.evictions.fetch_add(evicted, Ordering::Relaxed);
+    }
+
+    /// Inserts a newly generated subchunk, pinning it in the cache until it has been written to disk.
+    ///
+    /// If the subchunk is already cached, the cached version is kept and returned instead.
+    pub fn try_insert_dirty(&self, key: CacheKey, chunk: Arc<SubChunk>) -> Arc<SubChunk> {
+        let mut shard = self.shard(&key).lock();
+
+        if let Some(entry) = shard.touch(&key) {
+            return Arc::clone(&entry.chunk);
+        }
+
+        shard.put(key, Arc::clone(&chunk), true);
+        self.add_dirty();
+
+        let evicted = shard.evict(self.shard_capacity);
+        self.evictions.fetch_add(evicted, Ordering::Relaxed);
+
+        chunk
+    }
+
+    /// Returns the current version of a modified subchunk.
+    ///
+    /// This returns `None` if the subchunk is not dirty.
+    pub fn dirty(&self, key: &CacheKey) -> Option<Arc<SubChunk>> {
+        let shard = self.shard(key).lock();
+        shard
+            .entries
+            .get(key)
+            .filter(|entry| entry.dirty)
+            .map(|entry| Arc::clone(&entry.chunk))
+    }
+
+    /// Returns a snapshot of all subchunks that have not been written to disk yet.
+    pub fn dirty_entries(&self) -> Vec<(CacheKey, Arc<SubChunk>)> {
+        let mut entries = Vec::with_capacity(self.dirty_count());
+        for shard in self.shards.iter() {
+            let shard = shard.lock();
+            entries.extend(
+                shard
+                    .entries
+                    .iter()
+                    .filter(|(_, entry)| entry.dirty)
+                    .map(|(key, entry)| (*key, Arc::clone(&entry.chunk))),
+            );
+        }
+
+        entries
+    }
+
+    /// Amount of subchunks that have not been written to disk yet.
+    #[inline]
+    pub fn dirty_count(&self) -> usize {
+        self.dirty.load(Ordering::Relaxed)
+    }
+
+    /// Marks a subchunk as written to disk, allowing it to be evicted again.
+    ///
+    /// The entry stays dirty if it was modified again after `written` was taken.
+    pub fn mark_clean(&self, key: &CacheKey, written: &Arc<SubChunk>) {
+        let mut shard = self.shard(key).lock();
+        if shard.entries.get(key).is_some_and(|entry| entry.dirty && Arc::ptr_eq(&entry.chunk, written)) {
+            shard.unpin(key);
+            self.dirty.fetch_sub(1, Ordering::Relaxed);
+        }
+
+        let evicted = shard.evict(self.shard_capacity);
+        self.evictions.fetch_add(evicted, Ordering::Relaxed);
+    }
+
+    /// Stores the biomes of a generated chunk column until they have been written to disk.
+    ///
+    /// This replaces the pending biomes of the same column, if there are any.
+    pub fn insert_column(&self, key: ColumnKey, biomes: Biomes) {
+        self.columns.lock().insert(key, Arc::new(biomes));
+    }
+
+    /// Returns the biomes of a generated chunk column if they have not been written to disk yet.
+    pub fn column(&self, key: &ColumnKey) -> Option<Arc<Biomes>> {
+        self.columns.lock().get(key).cloned()
+    }
+
+    /// Returns a snapshot of all chunk columns that have not been written to disk yet.
+    pub fn pending_columns(&self) -> Vec<(ColumnKey, Arc<Biomes>)> {
+        self.columns.lock().iter().map(|(key, biomes)| (*key, Arc::clone(biomes))).collect()
+    }
+
+    /// Amount of chunk columns that have not been written to disk yet.
+    pub fn pending_column_count(&self) -> usize {
+        self.columns.lock().len()
+    }
+
+    /// Marks a chunk column as written to disk.
+    ///
+    /// The column stays pending if it was replaced after `written` was taken.
+    pub fn mark_column_written(&self, key: &ColumnKey, written: &Arc<Biomes>) {
+        let mut columns = self.columns.lock();
+        if columns.get(key).is_some_and(|biomes| Arc::ptr_eq(biomes, written)) {
+            columns.remove(key);
+        }
+    }
+
+    /// Returns the current cache statistics.
+    pub fn metrics(&self) -> CacheMetrics {
+        let entries = self.shards.iter().map(|shard| shard.lock().entries.len()).sum();
+
+        CacheMetrics {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            entries,
+            dirty: self.dirty_count(),
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use level::{BiomeEncoding, Biomes, PaletteEntry, SubChunk, SubChunkVersion, SubStorage};
+use proto::types::Dimension;
+use util::Vector;
+
+use super::{subchunk_range, GeneratedColumn, Generator, BLOCK_VERSION};
+
+/// Preset used by the superflat generator if no other preset has been configured.
+pub const DEFAULT_FLAT_PRESET: &str = "minecraft:bedrock[infiniburn_bit=false],2*minecraft:dirt[dirt_type=normal],minecraft:grass;1";
+
+/// Biome used if the preset does not specify one. This is the ID of the plains biome.
+const DEFAULT_BIOME: u32 = 1;
+
+/// Maximum amount of layers in a preset. This is the height of the overworld.
+const MAX_LAYERS: usize = 384;
+
+/// Generates a flat world consisting of horizontal layers of blocks.
+///
+/// The layers are described by a preset string of the form `layers;biome`.
+/// The layers are a comma separated list of blocks starting at the bottom of the world.
+/// A block can be repeated by prefixing it with a count and block states can be specified between brackets.
+/// The biome is a numeric biome ID and is optional.
+///
+/// For example: `minecraft:bedrock[infiniburn_bit=false],2*minecraft:dirt[dirt_type=normal],minecraft:grass;1`.
+#[derive(Debug)]
+pub struct SuperflatGenerator {
+    /// The block at each height, starting at the bottom of the world.
+    layers: Vec<PaletteEntry>,
+    /// Biome of the entire world.
+    biome: u32,
+}
+
+impl SuperflatGenerator {
+    /// Creates a superflat generator from the given preset string.
+    pub fn from_preset(preset: &str) -> anyhow::Result<Self> {
+        let (layer_str, biome) = match preset.split_once(';') {
+            Some((layers, biome)) => {
+                let Ok(biome) = biome.trim().parse() else {
+                    anyhow::bail!("Invalid biome ID in superflat preset: {biome}");
+                };
+
+                (layers, biome)
+            }
+            None => (preset, DEFAULT_BIOME),
+        };
+
+        let mut layers = Vec::new();
+        for layer in layer_str.split(',').map(str::trim) {
+            let (count, block) = match layer.split_once('*') {
+                Some((count, block)) => {
+                    let Ok(count) = count.trim().parse::<usize>() else {
+                        anyhow::bail!("Invalid layer count in superflat preset: {count}");
+                    };
+
+                    (count, block.trim())
+                }
+                None => (1, layer),
+            };
+
+            let entry = parse_block(block)?;
+            if layers.len() + count > MAX_LAYERS {
+                anyhow::bail!("Superflat preset cannot contain more than {MAX_LAYERS} layers");
+            }
+
+            layers.extend(std::iter::repeat(entry).take(count));
+        }
+
+        Ok(Self { layers, biome })
+    }
+
+    /// Generates the subchunk at the given index, relative to the bottom of the world.
+    ///
+    /// Returns `None` if the subchunk consists entirely of air.
+    fn generate_subchunk(&self, relative: usize, index: i8) -> Option<SubChunk> {
+        let base = relative * 16;
+        if base >= self.layers.len() {
+            return None;
+        }
+
+        let mut palette = vec![PaletteEntry::air(Some(BLOCK_VERSION))];
+        let mut heights = [0u16; 16];
+
+        for (y, height) in heights.iter_mut().enumerate() {
+            let Some(entry) = self.layers.get(base + y) else { break };
+
+            *height = if let Some(i) = palette.iter().position(|e| e.same_state(entry)) {
+                i as u16
+            } else {
+                palette.push(entry.clone());
+                (palette.len() - 1) as u16
+            };
+        }
+
+        let mut indices = Box::new([0; 4096]);
+        for (offset, index) in indices.iter_mut().enumerate() {
+            // The vertical coordinate is stored in the lowest four bits, see `level::to_offset`.
+            *index = heights[offset & 0xf];
+        }
+
+        Some(SubChunk {
+            version: SubChunkVersion::Limitless,
+            index,
+            layers: vec![SubStorage { indices, palette }],
+        })
+    }
+}
+
+impl Default for SuperflatGenerator {
+    fn default() -> Self {
+        // The default preset is known to be valid.
+        #[allow(clippy::unwrap_used)]
+        Self::from_preset(DEFAULT_FLAT_PRESET).unwrap()
+    }
+}
+
+impl Generator for SuperflatGenerator {
+    fn generate(&self, _coordinates: Vector<i32, 2>, dimension: Dimension) -> anyhow::Result<GeneratedColumn> {
+        let range = subchunk_range(dimension);
+
+        let subchunks = range
+            .clone()
+            .enumerate()
+            .filter_map(|(relative, index)| self.generate_subchunk(relative, index))
+            .collect();
+
+        let height = self.layers.len() as u16;
+        let biomes = Biomes {
+            heightmap: Box::new([[height; 16]; 16]),
+            fragments: range.map(|_| BiomeEncoding::Single(self.biome)).collect(),
+        };
+
+        Ok(GeneratedColumn { subchunks, biomes })
+    }
+}
+
+/// Parses a single block of the form `name[state=value,...]`.
+fn parse_block(block: &str) -> anyhow::Result<PaletteEntry> {
+    let (name, states) = match block.split_once('[') {
+        Some((name, states)) => {
+            let Some(states) = states.strip_suffix(']') else {
+                anyhow::bail!("Unterminated block states in superflat preset: {block}");
+            };
+
+            (name.trim(), states)
+        }
+        None => (block, ""),
+    };
+
+    if name.is_empty() {
+        anyhow::bail!("Superflat preset contains an empty layer");
+    }
+
+    let name = if name.contains(':') { name.to_owned() } else { format!("minecraft:{name}") };
+
+    let mut map = HashMap::new();
+    for state in states.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let Some((key, value)) = state.split_once('=') else {
+            anyhow::bail!("Invalid block state in superflat preset: {state}");
+        };
+
+        let value = value.trim();
+        let value = match value {
+            "true" => nbt::Value::Byte(1),
+            "false" => nbt::Value::Byte(0),
+            _ => value
+                .parse()
+                .map_or_else(|_| nbt::Value::String(value.trim_matches('"').to_owned()), nbt::Value::Int),
+        };
+
+        map.insert(key.trim().to_owned(), value);
+    }
+
+    Ok(PaletteEntry {
+        name,
+        version: Some(BLOCK_VERSION),
+        states: map,
+    })
+}
@@ -0,0 +1,36 @@
+//! Generates terrain for chunks that do not exist yet.
+
+mod flat;
+mod noise;
+
+pub use flat::*;
+pub use level::subchunk_range;
+pub use noise::*;
+
+use level::{Biomes, SubChunk};
+use proto::types::Dimension;
+use util::Vector;
+
+/// Block version that generated blocks are stored with.
+pub const BLOCK_VERSION: [u8; 4] = [1, 20, 60, 1];
+
+/// A fully generated chunk column.
+pub struct GeneratedColumn {
+    /// The subchunks of the column.
+    ///
+    /// Subchunks that are not included are filled with air.
+    pub subchunks: Vec<SubChunk>,
+    /// The biomes of the column.
+    pub biomes: Biomes,
+}
+
+/// Generates chunk columns that have not been generated before.
+///
+/// The level service calls the generator whenever a chunk is requested that does not exist on disk.
+pub trait Generator: Send + Sync {
+    /// Generates the chunk column at the given chunk coordinates.
+    ///
+    /// Generators should be deterministic, as the same column might be requested
+    /// multiple times before it has been written to disk.
+    fn generate(&self, coordinates: Vector<i32, 2>, dimension: Dimension) -> anyhow::Result<GeneratedColumn>;
+}
@@ -0,0 +1,642 @@
+use std::ops::Range;
+
+use level::{BiomeEncoding, BiomeStorage, Biomes, PaletteEntry, SubChunk, SubChunkVersion, SubStorage};
+use proto::types::Dimension;
+use util::Vector;
+
+use super::{subchunk_range, GeneratedColumn, Generator, BLOCK_VERSION};
+
+/// Lowest vertical coordinate of the overworld.
+const MIN_Y: i32 = -64;
+/// Height of the overworld in blocks.
+const HEIGHT: usize = 384;
+/// Height of the sea surface.
+const SEA_LEVEL: i32 = 62;
+
+/// Overworld biomes produced by the noise generator.
+///
+/// The discriminants are the IDs of the biomes in the `BiomeDefinitionList`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Biome {
+    /// The `ocean` biome.
+    Ocean = 0,
+    /// The `plains` biome.
+    Plains = 1,
+    /// The `desert` biome.
+    Desert = 2,
+    /// The `extreme_hills` biome.
+    ExtremeHills = 3,
+    /// The `forest` biome.
+    Forest = 4,
+    /// The `taiga` biome.
+    Taiga = 5,
+    /// The `hell` biome, also known as the nether wastes.
+    Hell = 8,
+    /// The `the_end` biome.
+    TheEnd = 9,
+    /// The `ice_plains` biome.
+    IcePlains = 12,
+    /// The `beach` biome.
+    Beach = 16,
+    /// The `jungle` biome.
+    Jungle = 21,
+    /// The `deep_ocean` biome.
+    DeepOcean = 24,
+    /// The `birch_forest` biome.
+    BirchForest = 27,
+    /// The `savanna` biome.
+    Savanna = 35,
+}
+
+impl Biome {
+    /// All biomes that can be produced by the generator.
+    pub const ALL: [Biome; 14] = [
+        Biome::Ocean,
+        Biome::Plains,
+        Biome::Desert,
+        Biome::ExtremeHills,
+        Biome::Forest,
+        Biome::Taiga,
+        Biome::Hell,
+        Biome::TheEnd,
+        Biome::IcePlains,
+        Biome::Beach,
+        Biome::Jungle,
+        Biome::DeepOcean,
+        Biome::BirchForest,
+        Biome::Savanna,
+    ];
+
+    /// Numeric ID of the biome.
+    #[inline]
+    pub const fn id(self) -> u32 {
+        self as u32
+    }
+
+    /// Name of the biome in the `BiomeDefinitionList`.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Biome::Ocean => "ocean",
+            Biome::Plains => "plains",
+            Biome::Desert => "desert",
+            Biome::ExtremeHills => "extreme_hills",
+            Biome::Forest => "forest",
+            Biome::Taiga => "taiga",
+            Biome::Hell => "hell",
+            Biome::TheEnd => "the_end",
+            Biome::IcePlains => "ice_plains",
+            Biome::Beach => "beach",
+            Biome::Jungle => "jungle",
+            Biome::DeepOcean => "deep_ocean",
+            Biome::BirchForest => "birch_forest",
+            Biome::Savanna => "savanna",
+        }
+    }
+
+    /// Selects the biome for a column with the given surface height, temperature and humidity.
+    fn select(height: i32, temperature: f64, humidity: f64) -> Biome {
+        if height < SEA_LEVEL - 20 {
+            Biome::DeepOcean
+        } else if height < SEA_LEVEL {
+            Biome::Ocean
+        } else if height <= SEA_LEVEL + 1 {
+            Biome::Beach
+        } else if height > SEA_LEVEL + 40 {
+            Biome::ExtremeHills
+        } else if temperature < -0.25 {
+            Biome::IcePlains
+        } else if temperature < -0.1 {
+            Biome::Taiga
+        } else if temperature > 0.25 && humidity < -0.1 {
+            Biome::Desert
+        } else if temperature > 0.25 && humidity > 0.1 {
+            Biome::Jungle
+        } else if temperature > 0.15 {
+            Biome::Savanna
+        } else if humidity > 0.15 {
+            Biome::Forest
+        } else if humidity > 0.05 {
+            Biome::BirchForest
+        } else {
+            Biome::Plains
+        }
+    }
+}
+
+/// Indices into the block palette of the generator.
+mod block {
+    pub const AIR: u16 = 0;
+    pub const BEDROCK: u16 = 1;
+    pub const STONE: u16 = 2;
+    pub const DEEPSLATE: u16 = 3;
+    pub const DIRT: u16 = 4;
+    pub const GRASS: u16 = 5;
+    pub const SAND: u16 = 6;
+    pub const SANDSTONE: u16 = 7;
+    pub const GRAVEL: u16 = 8;
+    pub const WATER: u16 = 9;
+    pub const SNOW: u16 = 10;
+    pub const COAL_ORE: u16 = 11;
+    pub const IRON_ORE: u16 = 13;
+    pub const COPPER_ORE: u16 = 15;
+    pub const GOLD_ORE: u16 = 17;
+    pub const REDSTONE_ORE: u16 = 19;
+    pub const DIAMOND_ORE: u16 = 21;
+    pub const LAPIS_ORE: u16 = 23;
+}
+
+/// Blocks used by the generator, in the order of the indices in [`block`].
+///
+/// Every ore is immediately followed by its deepslate variant.
+const PALETTE: &[(&str, Option<(&str, &str)>)] = &[
+    ("minecraft:air", None),
+    ("minecraft:bedrock", None),
+    ("minecraft:stone", None),
+    ("minecraft:deepslate", Some(("pillar_axis", "y"))),
+    ("minecraft:dirt", Some(("dirt_type", "normal"))),
+    ("minecraft:grass", None),
+    ("minecraft:sand", Some(("sand_type", "normal"))),
+    ("minecraft:sandstone", Some(("sand_stone_type", "default"))),
+    ("minecraft:gravel", None),
+    ("minecraft:water", None),
+    ("minecraft:snow", None),
+    ("minecraft:coal_ore", None),
+    ("minecraft:deepslate_coal_ore", None),
+    ("minecraft:iron_ore", None),
+    ("minecraft:deepslate_iron_ore", None),
+    ("minecraft:copper_ore", None),
+    ("minecraft:deepslate_copper_ore", None),
+    ("minecraft:gold_ore", None),
+    ("minecraft:deepslate_gold_ore", None),
+    ("minecraft:redstone_ore", None),
+    ("minecraft:deepslate_redstone_ore", None),
+    ("minecraft:diamond_ore", None),
+    ("minecraft:deepslate_diamond_ore", None),
+    ("minecraft:lapis_ore", None),
+    ("minecraft:deepslate_lapis_ore", None),
+];
+
+/// Describes how an ore is distributed.
+struct OreVein {
+    /// Palette index of the ore. The deepslate variant directly follows it.
+    block: u16,
+    /// Vertical range in which veins are placed.
+    range: Range<i32>,
+    /// Amount of veins per chunk.
+    count: usize,
+    /// Amount of blocks in a vein.
+    size: usize,
+}
+
+/// Ore distribution in the overworld.
+const ORES: &[OreVein] = &[
+    OreVein { block: block::COAL_ORE, range: 0..192, count: 20, size: 12 },
+    OreVein { block: block::IRON_ORE, range: -24..80, count: 12, size: 8 },
+    OreVein { block: block::COPPER_ORE, range: -16..112, count: 8, size: 10 },
+    OreVein { block: block::GOLD_ORE, range: -64..32, count: 4, size: 8 },
+    OreVein { block: block::REDSTONE_ORE, range: -64..16, count: 6, size: 7 },
+    OreVein { block: block::LAPIS_ORE, range: -64..64, count: 2, size: 6 },
+    OreVein { block: block::DIAMOND_ORE, range: -64..16, count: 2, size: 5 },
+];
+
+/// A deterministic pseudo-random number generator (SplitMix64).
+///
+/// This is used instead of the generators in `rand` because their output is not guaranteed to be
+/// stable across versions, which would change the terrain of existing worlds.
+struct Random(u64);
+
+impl Random {
+    /// Creates a generator with the given seed.
+    const fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// Returns the next random number.
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a random number in the given range.
+    fn next_range(&mut self, range: Range<i32>) -> i32 {
+        let len = (range.end - range.start) as u64;
+        range.start + (self.next_u64() % len) as i32
+    }
+}
+
+/// Seeded three-dimensional Perlin noise.
+struct Perlin {
+    /// Permutation table, repeated twice to avoid wrapping indices.
+    permutation: Box<[u8; 512]>,
+}
+
+impl Perlin {
+    /// Creates a new noise function, shuffling the permutation table with the given random generator.
+    fn new(random: &mut Random) -> Self {
+        let mut table = [0u8; 256];
+        for (i, value) in table.iter_mut().enumerate() {
+            *value = i as u8;
+        }
+
+        for i in (1..256).rev() {
+            let j = (random.next_u64() % (i as u64 + 1)) as usize;
+            table.swap(i, j);
+        }
+
+        let mut permutation = Box::new([0u8; 512]);
+        for (i, value) in permutation.iter_mut().enumerate() {
+            *value = table[i & 0xff];
+        }
+
+        Self { permutation }
+    }
+
+    /// Samples the noise at the given position. The result is roughly in the range [-1, 1].
+    fn sample(&self, x: f64, y: f64, z: f64) -> f64 {
+        let (xf, yf, zf) = (x.floor(), y.floor(), z.floor());
+        let (xi, yi, zi) = ((xf as i64 & 0xff) as usize, (yf as i64 & 0xff) as usize, (zf as i64 & 0xff) as usize);
+        let (x, y, z) = (x - xf, y - yf, z - zf);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let p = &self.permutation;
+        let a = p[xi] as usize + yi;
+        let aa = p[a] as usize + zi;
+        let ab = p[a + 1] as usize + zi;
+        let b = p[xi + 1] as usize + yi;
+        let ba = p[b] as usize + zi;
+        let bb = p[b + 1] as usize + zi;
+
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, grad(p[aa], x, y, z), grad(p[ba], x - 1.0, y, z)),
+                lerp(u, grad(p[ab], x, y - 1.0, z), grad(p[bb], x - 1.0, y - 1.0, z)),
+            ),
+            lerp(
+                v,
+                lerp(u, grad(p[aa + 1], x, y, z - 1.0), grad(p[ba + 1], x - 1.0, y, z - 1.0)),
+                lerp(u, grad(p[ab + 1], x, y - 1.0, z - 1.0), grad(p[bb + 1], x - 1.0, y - 1.0, z - 1.0)),
+            ),
+        )
+    }
+}
+
+/// Smoothing curve used to interpolate between lattice points.
+#[inline]
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+/// Linearly interpolates between `a` and `b`.
+#[inline]
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+/// Computes the dot product of a pseudo-random gradient vector and the distance vector.
+#[inline]
+fn grad(hash: u8, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+/// Fractal noise consisting of multiple octaves of Perlin noise.
+struct Octaves {
+    octaves: Vec<Perlin>,
+}
+
+impl Octaves {
+    /// Creates fractal noise with the given amount of octaves.
+    fn new(random: &mut Random, count: usize) -> Self {
+        Self {
+            octaves: (0..count).map(|_| Perlin::new(random)).collect(),
+        }
+    }
+
+    /// Samples the noise in two dimensions. The result is roughly in the range [-1, 1].
+    fn sample(&self, x: f64, z: f64) -> f64 {
+        let mut total = 0.0;
+        let mut amplitude = 1.0;
+        let mut frequency = 1.0;
+        let mut max = 0.0;
+
+        for octave in &self.octaves {
+            total += octave.sample(x * frequency, 0.0, z * frequency) * amplitude;
+            max += amplitude;
+
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+
+        total / max
+    }
+}
+
+/// Generates overworld terrain using seeded noise.
+///
+/// The terrain consists of continents and oceans with varying surface heights, biomes based on
+/// the height, temperature and humidity, winding caves and ore veins.
+/// The same seed always generates the same terrain.
+pub struct NoiseGenerator {
+    /// Seed of the world.
+    seed: i64,
+    /// Determines the large-scale shape of the continents.
+    continents: Octaves,
+    /// Adds small-scale height variation.
+    detail: Octaves,
+    /// Temperature used to select biomes.
+    temperature: Octaves,
+    /// Humidity used to select biomes.
+    humidity: Octaves,
+    /// Caves are carved where both cave noises are close to zero.
+    caves: [Perlin; 2],
+    /// Blocks used by the generator, see [`block`].
+    palette: Vec<PaletteEntry>,
+}
+
+impl NoiseGenerator {
+    /// Creates a generator for the given world seed.
+    pub fn new(seed: i64) -> Self {
+        let mut random = Random::new(seed as u64);
+
+        let palette = PALETTE
+            .iter()
+            .map(|(name, state)| PaletteEntry {
+                name: (*name).to_owned(),
+                version: Some(BLOCK_VERSION),
+                states: state
+                    .iter()
+                    .map(|(key, value)| ((*key).to_owned(), nbt::Value::String((*value).to_owned())))
+                    .chain((*name == "minecraft:water").then(|| (String::from("liquid_depth"), nbt::Value::Int(0))))
+                    .chain((*name == "minecraft:bedrock").then(|| (String::from("infiniburn_bit"), nbt::Value::Byte(0))))
+                    .collect(),
+            })
+            .collect();
+
+        Self {
+            seed,
+            continents: Octaves::new(&mut random, 5),
+            detail: Octaves::new(&mut random, 4),
+            temperature: Octaves::new(&mut random, 2),
+            humidity: Octaves::new(&mut random, 2),
+            caves: [Perlin::new(&mut random), Perlin::new(&mut random)],
+            palette,
+        }
+    }
+
+    /// Seed of the world this generator generates.
+    #[inline]
+    pub const fn seed(&self) -> i64 {
+        self.seed
+    }
+
+    /// Computes the surface height and biome of the column at the given block coordinates.
+    pub fn surface(&self, x: i32, z: i32) -> (i32, Biome) {
+        let (x, z) = (f64::from(x), f64::from(z));
+
+        let continentalness = self.continents.sample(x / 640.0, z / 640.0);
+        let detail = self.detail.sample(x / 96.0, z / 96.0);
+        // Inland areas become increasingly mountainous.
+        let mountains = ((continentalness - 0.1).max(0.0) * 5.0).powi(2);
+
+        let height = f64::from(SEA_LEVEL) + 10.0 + continentalness * 80.0 + detail * (8.0 + mountains * 40.0);
+        let height = (height as i32).clamp(MIN_Y + 8, MIN_Y + HEIGHT as i32 - 16);
+
+        let temperature = self.temperature.sample(x / 1024.0, z / 1024.0);
+        let humidity = self.humidity.sample(x / 1024.0, z / 1024.0);
+
+        (height, Biome::select(height, temperature, humidity))
+    }
+
+    /// Whether a cave is carved out at the given block coordinates.
+    fn is_cave(&self, x: i32, y: i32, z: i32) -> bool {
+        let (x, y, z) = (f64::from(x) / 48.0, f64::from(y) / 24.0, f64::from(z) / 48.0);
+        let a = self.caves[0].sample(x, y, z);
+        let b = self.caves[1].sample(x, y, z);
+
+        a * a + b * b < 0.006
+    }
+
+    /// Returns the block at the given depth below the surface of a column.
+    fn surface_block(biome: Biome, surface: i32, depth: i32) -> u16 {
+        let underwater = surface < SEA_LEVEL;
+        match biome {
+            Biome::Ocean | Biome::DeepOcean if surface < SEA_LEVEL - 12 => block::GRAVEL,
+            Biome::Ocean | Biome::DeepOcean | Biome::Beach => block::SAND,
+            Biome::Desert if depth > 3 => block::SANDSTONE,
+            Biome::Desert => block::SAND,
+            Biome::ExtremeHills if surface > SEA_LEVEL + 70 => block::STONE,
+            _ if depth == 0 && !underwater => {
+                if biome == Biome::IcePlains {
+                    block::SNOW
+                } else {
+                    block::GRASS
+                }
+            }
+            _ => block::DIRT,
+        }
+    }
+
+    /// Places ore veins in the column.
+    fn place_ores(&self, coordinates: &Vector<i32, 2>, blocks: &mut [u16]) {
+        let seed = (self.seed as u64)
+            ^ (coordinates.x as u64).wrapping_mul(0x2545_f491_4f6c_dd1d)
+            ^ (coordinates.y as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        let mut random = Random::new(seed);
+
+        for ore in ORES {
+            for _ in 0..ore.count {
+                let mut x = random.next_range(0..16);
+                let mut y = random.next_range(ore.range.clone());
+                let mut z = random.next_range(0..16);
+
+                for _ in 0..ore.size {
+                    if (0..16).contains(&x) && (0..16).contains(&z) && (MIN_Y..MIN_Y + HEIGHT as i32).contains(&y) {
+                        let index = column_index(x as usize, (y - MIN_Y) as usize, z as usize);
+                        match blocks[index] {
+                            block::STONE => blocks[index] = ore.block,
+                            block::DEEPSLATE => blocks[index] = ore.block + 1,
+                            _ => (),
+                        }
+                    }
+
+                    x += random.next_range(-1..2);
+                    y += random.next_range(-1..2);
+                    z += random.next_range(-1..2);
+                }
+            }
+        }
+    }
+
+    /// Splits the blocks of a column into subchunks, skipping subchunks that only contain air.
+    fn split_subchunks(&self, blocks: &[u16]) -> Vec<SubChunk> {
+        let range = subchunk_range(Dimension::Overworld);
+
+        let mut subchunks = Vec::new();
+        for (relative, index) in range.enumerate() {
+            let base = relative * 16;
+
+            let mut remap = vec![u16::MAX; self.palette.len()];
+            let mut palette = Vec::new();
+            let mut indices = Box::new([0u16; 4096]);
+
+            for (offset, local) in indices.iter_mut().enumerate() {
+                // Offsets are laid out as XZY, see `level::to_offset`.
+                let (x, z, y) = (offset >> 8, (offset >> 4) & 0xf, offset & 0xf);
+                let block = blocks[column_index(x, base + y, z)] as usize;
+
+                if remap[block] == u16::MAX {
+                    remap[block] = palette.len() as u16;
+                    palette.push(self.palette[block].clone());
+                }
+
+                *local = remap[block];
+            }
+
+            if palette.len() == 1 && remap[block::AIR as usize] == 0 {
+                continue;
+            }
+
+            subchunks.push(SubChunk {
+                version: SubChunkVersion::Limitless,
+                index,
+                layers: vec![SubStorage { indices, palette }],
+            });
+        }
+
+        subchunks
+    }
+
+    /// Creates the biome data of a column.
+    fn biomes(heights: &[[i32; 16]; 16], biomes: &[[Biome; 16]; 16]) -> Biomes {
+        let mut heightmap = Box::new([[0u16; 16]; 16]);
+        for (x, column) in heights.iter().enumerate() {
+            for (z, height) in column.iter().enumerate() {
+                heightmap[z][x] = (height - MIN_Y + 1) as u16;
+            }
+        }
+
+        let first = biomes[0][0];
+        let uniform = biomes.iter().flatten().all(|biome| *biome == first);
+
+        let range = subchunk_range(Dimension::Overworld);
+        let fragments = range
+            .map(|_| {
+                if uniform {
+                    return BiomeEncoding::Single(first.id());
+                }
+
+                let mut palette: Vec<u32> = Vec::new();
+                let mut indices = Box::new([0u16; 4096]);
+                for (offset, index) in indices.iter_mut().enumerate() {
+                    let id = biomes[offset >> 8][(offset >> 4) & 0xf].id();
+                    *index = palette.iter().position(|p| *p == id).unwrap_or_else(|| {
+                        palette.push(id);
+                        palette.len() - 1
+                    }) as u16;
+                }
+
+                BiomeEncoding::Paletted(BiomeStorage { indices, palette })
+            })
+            .collect();
+
+        Biomes { heightmap, fragments }
+    }
+
+    /// Generates an overworld column.
+    fn generate_overworld(&self, coordinates: &Vector<i32, 2>) -> GeneratedColumn {
+        let mut blocks = vec![block::AIR; 16 * 16 * HEIGHT];
+        let mut heights = [[0; 16]; 16];
+        let mut biomes = [[Biome::Plains; 16]; 16];
+
+        for x in 0..16 {
+            for z in 0..16 {
+                let world_x = coordinates.x * 16 + x as i32;
+                let world_z = coordinates.y * 16 + z as i32;
+
+                let (surface, biome) = self.surface(world_x, world_z);
+                biomes[x][z] = biome;
+
+                let mut top = MIN_Y;
+                for y in MIN_Y..=surface.max(SEA_LEVEL) {
+                    let depth = surface - y;
+                    let block = if y < MIN_Y + 5 && bedrock_hash(self.seed, world_x, y, world_z) % 5 >= (y - MIN_Y) as u64 {
+                        block::BEDROCK
+                    } else if depth < 0 {
+                        block::WATER
+                    } else if depth > 5 && y > MIN_Y + 4 && self.is_cave(world_x, y, world_z) {
+                        block::AIR
+                    } else if depth <= 3 || (biome == Biome::Desert && depth <= 6) {
+                        Self::surface_block(biome, surface, depth)
+                    } else if y < 0 {
+                        block::DEEPSLATE
+                    } else {
+                        block::STONE
+                    };
+
+                    if block != block::AIR {
+                        top = y;
+                    }
+
+                    blocks[column_index(x, (y - MIN_Y) as usize, z)] = block;
+                }
+
+                heights[x][z] = top;
+            }
+        }
+
+        self.place_ores(coordinates, &mut blocks);
+
+        GeneratedColumn {
+            subchunks: self.split_subchunks(&blocks),
+            biomes: Self::biomes(&heights, &biomes),
+        }
+    }
+}
+
+impl Generator for NoiseGenerator {
+    fn generate(&self, coordinates: Vector<i32, 2>, dimension: Dimension) -> anyhow::Result<GeneratedColumn> {
+        let biome = match dimension {
+            Dimension::Overworld => return Ok(self.generate_overworld(&coordinates)),
+            Dimension::Nether => Biome::Hell,
+            Dimension::End => Biome::TheEnd,
+        };
+
+        // Only the overworld is generated, the other dimensions are left empty.
+        let range = subchunk_range(dimension);
+        Ok(GeneratedColumn {
+            subchunks: Vec::new(),
+            biomes: Biomes {
+                heightmap: Box::new([[0; 16]; 16]),
+                fragments: range.map(|_| BiomeEncoding::Single(biome.id())).collect(),
+            },
+        })
+    }
+}
+
+/// Index of a block in the column block buffer.
+#[inline]
+const fn column_index(x: usize, y: usize, z: usize) -> usize {
+    (x * 16 + z) * HEIGHT + y
+}
+
+/// Hashes a position to determine the shape of the bedrock floor.
+fn bedrock_hash(seed: i64, x: i32, y: i32, z: i32) -> u64 {
+    let mut random = Random::new((seed as u64) ^ (x as u64).wrapping_mul(0x1f1f_1f1f) ^ (z as u64).wrapping_mul(0x2b2b_2b2b_2b2b) ^ y as u64);
+    random.next_u64()
+}
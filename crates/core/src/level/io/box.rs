@@ -38,13 +38,13 @@ impl BoxRegion {
     /// Using an index out of bounds will simply return a coordinate outside of the region.
     /// However, the coordinate will likely be incorrect because different regions use incompatible indices.
     pub fn as_coord_unchecked(&self, mut index: usize) -> Vector<i32, 3> {
-        let x = index as i32 % (self.xrange.len() as i32) - self.xrange.start;
+        let x = (index % self.xrange.len()) as i32 + self.xrange.start;
         index /= self.xrange.len();
 
-        let y = index as i32 % (self.yrange.len() as i32) - self.yrange.start;
+        let y = (index % self.yrange.len()) as i32 + self.yrange.start;
         index /= self.yrange.len();
 
-        let z = index as i32 - self.zrange.start;
+        let z = index as i32 + self.zrange.start;
 
         Vector::from([x, y, z])
     }
@@ -56,7 +56,11 @@ impl BoxRegion {
     /// Using a coordinate out of bounds will simply return a index outside of the region.
     /// However, the index will likely be incorrect because different regions use incompatible indices.
     pub fn as_index_unchecked(&self, coord: &Vector<i32, 3>) -> usize {
-        (coord.x * (self.yrange.len() as i32 * self.zrange.len() as i32) + coord.y * (self.zrange.len() as i32) + coord.z) as usize
+        let x = (coord.x - self.xrange.start) as usize;
+        let y = (coord.y - self.yrange.start) as usize;
+        let z = (coord.z - self.zrange.start) as usize;
+
+        (z * self.yrange.len() + y) * self.xrange.len() + x
     }
 
     fn from_bounds_inner(bound1: Vector<i32, 3>, bound2: Vector<i32, 3>, dimension: Dimension) -> Self {
@@ -112,7 +116,7 @@ impl Region for BoxRegion {
     }
 
     fn as_coord(&self, index: usize) -> Option<Vector<i32, 3>> {
-        (index <= self.len()).then(|| self.as_coord_unchecked(index))
+        (index < self.len()).then(|| self.as_coord_unchecked(index))
     }
 
     fn dimension(&self) -> Dimension {
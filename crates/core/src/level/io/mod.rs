@@ -1,7 +1,7 @@
 pub mod r#box;
-pub mod io;
 pub mod point;
 pub mod radial;
 pub mod region;
 pub mod sink;
+pub mod slice;
 pub mod stream;
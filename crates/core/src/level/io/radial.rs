@@ -3,6 +3,7 @@ use rayon::iter::IntoParallelIterator;
 use util::Vector;
 
 use std::ops::Range;
+use std::sync::Arc;
 
 use super::region::{Region, RegionIter};
 
@@ -13,20 +14,57 @@ pub struct RadialRegion {
     radius: usize,
     vertical: Range<i32>,
     dimension: Dimension,
+    /// Amount of chunk columns preceding each row, followed by the total amount of columns.
+    ///
+    /// Shared between clones, as regions are cloned whenever they are split up for parallel iteration.
+    row_offsets: Arc<[usize]>,
 }
 
 impl RadialRegion {
     /// Creates a radial region around a central point.
     pub fn from_center<C: Into<Vector<i32, 2>>>(center: C, radius: usize, vertical: Range<i32>, dimension: Dimension) -> Self {
+        let radius_i32 = radius as i32;
+        let mut row_offsets = Vec::with_capacity(2 * radius + 2);
+        let mut offset = 0;
+        row_offsets.push(offset);
+        for row in -radius_i32..=radius_i32 {
+            offset += 2 * half_width(radius, row) as usize + 1;
+            row_offsets.push(offset);
+        }
+
         Self {
             center: center.into(),
             radius,
             vertical,
             dimension,
+            row_offsets: row_offsets.into(),
         }
     }
 }
 
+/// Half the width of the row at the given vertical offset from the center of a circle.
+#[inline]
+fn half_width(radius: usize, row: i32) -> i32 {
+    let radius = radius as u64;
+    let row = u64::from(row.unsigned_abs());
+
+    isqrt(radius * radius - row * row) as i32
+}
+
+/// Square root rounded down to the nearest integer.
+fn isqrt(value: u64) -> u64 {
+    let mut root = (value as f64).sqrt() as u64;
+    // The floating point square root can be off by one for large values.
+    while root * root > value {
+        root -= 1;
+    }
+    while (root + 1) * (root + 1) <= value {
+        root += 1;
+    }
+
+    root
+}
+
 impl IntoIterator for RadialRegion {
     type IntoIter = RegionIter<Self>;
     type Item = Vector<i32, 3>;
@@ -53,34 +91,57 @@ impl IntoParallelIterator for RadialRegion {
     }
 }
 
+impl RadialRegion {
+    /// Half the width of the row at the given vertical offset from the center.
+    #[inline]
+    fn half_width(&self, row: i32) -> i32 {
+        half_width(self.radius, row)
+    }
+
+    /// Amount of chunk columns contained in this region.
+    #[inline]
+    fn columns(&self) -> usize {
+        self.row_offsets.last().copied().unwrap_or(0)
+    }
+}
+
 impl Region for RadialRegion {
     fn as_coord(&self, index: usize) -> Option<Vector<i32, 3>> {
-        let y = (index % (self.len() / self.vertical.len())) as i32 + self.vertical.start;
+        if index >= self.len() {
+            return None;
+        }
 
-        let row_size = |row: usize| -> usize { 2 * (((self.radius.pow(2) - row.pow(2)) as f32).sqrt()).floor() as usize + 1 };
+        // Every column contains all vertical subchunks, so the vertical coordinate changes the fastest.
+        let y = (index % self.vertical.len()) as i32 + self.vertical.start;
+        let column = index / self.vertical.len();
 
-        let mut count = 0;
-        let mut last = 0;
+        // The first offset is always 0, so at least one offset is not greater than the column.
+        let row_index = self.row_offsets.partition_point(|&offset| offset <= column) - 1;
+        let row = row_index as i32 - self.radius as i32;
 
-        for row in 0..self.radius * 2 + 1 {
-            count += row_size((self.radius as i32 - row as i32).unsigned_abs() as usize);
-            if index < count {
-                let mut coord = Vector::from([(index - last) as i32, y, row as i32]);
+        // By default the coordinates are centered around (0, 0), move it to the given center point.
+        let x = (column - self.row_offsets[row_index]) as i32 - self.half_width(row) + self.center.x;
+        let z = row + self.center.y;
 
-                // By default the coordinates are centered around (0, 0), move it to the given center point.
-                coord.x += self.center.x;
-                coord.z += self.center.y;
+        Some(Vector::from([x, y, z]))
+    }
 
-                return Some(coord);
-            }
-            last = count;
+    fn as_index(&self, coord: &Vector<i32, 3>) -> Option<usize> {
+        if !self.vertical.contains(&coord.y) {
+            return None;
         }
 
-        None
-    }
+        let radius = self.radius as i32;
+        let row = coord.z - self.center.y;
+        let offset = coord.x - self.center.x;
+        if row.abs() > radius || offset.abs() > self.half_width(row) {
+            return None;
+        }
 
-    fn as_index(&self, _coord: &Vector<i32, 3>) -> Option<usize> {
-        todo!()
+        let preceding = self.row_offsets[(row + radius) as usize];
+        let column = preceding + (offset + self.half_width(row)) as usize;
+
+        Some(column * self.vertical.len() + (coord.y - self.vertical.start) as usize)
     }
 
     fn dimension(&self) -> Dimension {
@@ -88,11 +149,6 @@ impl Region for RadialRegion {
     }
 
     fn len(&self) -> usize {
-        // Using the sum from Gauss's circle problem.
-
-        1 + 4 * self.radius
-            + 4 * (1..self.radius)
-                .map(|i: usize| (((self.radius.pow(2) - i.pow(2)) as f32).sqrt()).floor() as usize)
-                .sum::<usize>()
+        self.columns() * self.vertical.len()
     }
 }
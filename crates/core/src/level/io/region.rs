@@ -146,7 +146,7 @@ impl<T: Region> DoubleEndedIterator for RegionIter<T> {
     fn next_back(&mut self) -> Option<Self::Item> {
         if self.len() > 0 {
             self.back_index -= 1;
-            self.region.as_coord(self.back_index + 1)
+            self.region.as_coord(self.back_index)
         } else {
             None
         }
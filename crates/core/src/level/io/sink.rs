@@ -1,224 +1,212 @@
 use std::{
     future::Future,
     pin::Pin,
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
-    },
-    task::{Context, Poll, Waker},
+    sync::Arc,
+    task::{ready, Context, Poll},
+    time::Duration,
 };
 
 use futures::Sink;
-use level::provider::Provider;
-use parking_lot::Mutex;
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use tokio::sync::mpsc;
-use tokio_util::sync::CancellationToken;
-use util::Joinable;
-
-use super::stream::IndexedSubChunk;
-
-/// Future that resolves when [`FlushState`] transitions into a busy state.
-pub struct Flushing<'state> {
-    state: &'state FlushStateInner,
-}
-
-impl<'state> Future for Flushing<'state> {
-    type Output = ();
-
-    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
-        if self.state.is_complete() {
-            // Register flush waker
-            self.state.busy_wakers.lock().push(cx.waker().clone());
-            return Poll::Pending;
-        }
-
-        Poll::Ready(())
-    }
-}
-
-struct FlushStateInner {
-    /// Tasks that should be woken when processing finishes.
-    idle_wakers: Mutex<Vec<Waker>>,
-    /// Tasks that should be woken when the state triggers processing.
-    busy_wakers: Mutex<Vec<Waker>>,
-    /// Whether the state is currently in idle mode.
-    completed: AtomicBool,
-}
-
-impl FlushStateInner {
-    pub fn is_complete(&self) -> bool {
-        self.completed.load(Ordering::SeqCst)
-    }
-}
-
-/// Token that can be in two states.
-///
-/// State changes can be awaited.
-#[derive(Clone)]
-pub struct FlushState {
-    inner: Arc<FlushStateInner>,
-}
-
-impl FlushState {
-    /// Creates a new asynchronous state.
-    pub fn new() -> Self {
-        Self {
-            inner: Arc::new(FlushStateInner {
-                idle_wakers: Mutex::new(Vec::new()),
-                busy_wakers: Mutex::new(Vec::new()),
-                completed: AtomicBool::new(true),
-            }),
-        }
-    }
-
-    /// Transitions the state to idle and wakes all waiting tasks.
-    pub fn finish(&self) {
-        self.inner.completed.store(true, Ordering::SeqCst);
-
-        let wakers = {
-            let mut lock = self.inner.idle_wakers.lock();
-            std::mem::take(&mut *lock)
-        };
-
-        for waker in wakers {
-            waker.wake();
-        }
-    }
-
-    /// Transitions the state to busy and wakes all waiting tasks.
-    pub fn flush(&self) {
-        self.inner.completed.store(false, Ordering::SeqCst);
-
-        let wakers = {
-            let mut lock = self.inner.busy_wakers.lock();
-            std::mem::take(&mut *lock)
-        };
-
-        for waker in wakers {
-            waker.wake();
-        }
-    }
-
-    /// Resolves when the state transitions into processing.
-    pub fn flushing(&self) -> Flushing {
-        Flushing { state: &self.inner }
-    }
-
-    // /// Resolves when the state transitions into idle.
-    // pub fn idle(&self) -> Idle {
-    //     Idle { state: &self.inner }
-    // }
-
-    /// Whether the state is currently idle.
-    pub fn is_complete(&self) -> bool {
-        self.inner.is_complete()
-    }
-}
-
-impl Default for FlushState {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-impl Future for FlushState {
-    type Output = ();
-
-    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
-        if !self.inner.completed.load(Ordering::SeqCst) {
-            self.inner.idle_wakers.lock().push(cx.waker().clone());
-            return Poll::Pending;
-        }
-
-        Poll::Ready(())
-    }
+use level::provider::{Provider, CHUNK_VERSION};
+use level::Storage;
+use tokio::sync::{mpsc, oneshot};
+use tokio_util::sync::{CancellationToken, PollSender};
+use util::{Joinable, Vector};
+
+use super::super::cache::SubChunkCache;
+use super::stream::{IndexedColumn, IndexedSubChunk};
+
+/// Interval at which the collector writes its pending changes to disk.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A request sent from a [`RegionSink`] to its [`Collector`].
+enum Request {
+    /// Stores a modified subchunk.
+    Write(IndexedSubChunk),
+    /// Stores the metadata of a newly generated chunk column.
+    Column(IndexedColumn),
+    /// Writes all pending changes to disk, notifying the sender once they have been written.
+    Flush(oneshot::Sender<anyhow::Result<()>>),
 }
 
 /// Collects all subchunk updates and writes them to disk periodically.
+///
+/// Subchunks are written to disk when the amount of pending subchunks reaches the threshold,
+/// when the flush interval elapses, when a sink is flushed and when the server shuts down.
+///
+/// Modified subchunks and the biomes of generated columns are kept in the [`SubChunkCache`] until they have been written,
+/// so that they can still be read in the meantime.
 pub struct Collector {
-    producer: mpsc::Sender<IndexedSubChunk>,
-    provider: Arc<Provider>,
-    state: FlushState,
+    /// Sends requests to the collection task.
+    producer: mpsc::Sender<Request>,
+    /// Cancelled once the final flush has completed.
     shutdown_token: CancellationToken,
 }
 
 impl Collector {
-    pub(crate) fn new(provider: Arc<Provider>, instance_token: CancellationToken, collector_size: usize) -> Self {
+    /// Creates a new collector that flushes once `collector_size` distinct subchunks have been modified.
+    pub(crate) fn new(
+        provider: Arc<Provider<dyn Storage>>,
+        cache: Arc<SubChunkCache>,
+        instance_token: CancellationToken,
+        collector_size: usize,
+    ) -> Self {
         let (producer, consumer) = mpsc::channel(collector_size);
-        let state = FlushState::new();
+        // Subchunks can also be marked dirty directly in the cache, without sending a request.
+        cache.set_dirty_threshold(collector_size);
         let shutdown_token = CancellationToken::new();
 
         tokio::spawn(Collector::collection(
-            instance_token.clone(),
+            provider,
+            cache,
+            instance_token,
             shutdown_token.clone(),
             consumer,
-            state.clone(),
             collector_size,
         ));
 
-        Self {
-            producer,
-            provider,
-            state,
-            shutdown_token,
-        }
+        Self { producer, shutdown_token }
     }
 
     /// Creates a new sink that can be used to write into this collector.
     pub fn create_sink(&self) -> RegionSink {
         RegionSink {
-            producer: self.producer.clone(),
-            state: self.state.clone(),
+            producer: PollSender::new(self.producer.clone()),
+            pending_flush: None,
         }
     }
 
     async fn collection(
+        provider: Arc<Provider<dyn Storage>>,
+        cache: Arc<SubChunkCache>,
         instance_token: CancellationToken,
         shutdown_token: CancellationToken,
-        mut receiver: mpsc::Receiver<IndexedSubChunk>,
-        state: FlushState,
+        mut receiver: mpsc::Receiver<Request>,
         collector_size: usize,
     ) {
+        // Modified subchunks and generated columns are stored in the cache until they have been written.
+        // Multiple modifications of the same subchunk therefore only result in a single write.
+        let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
         loop {
             tokio::select! {
-                _ = state.flushing() => {
-                    // Empty channel and collect all changes.
-                    let collected = Collector::collect(&mut receiver, collector_size);
-
-                    // Resume normal sink operations.
-                    state.finish();
-
-                    Collector::flush(collected);
+                request = receiver.recv() => {
+                    let Some(request) = request else { break };
+                    match request {
+                        Request::Flush(notify) => {
+                            let result = Collector::flush(&provider, &cache).await;
+                            // The sink might have been dropped while waiting.
+                            let _: Result<(), _> = notify.send(result);
+                        }
+                        request => {
+                            Collector::store(&cache, request);
+                            if cache.dirty_count() + cache.pending_column_count() >= collector_size {
+                                // Errors have already been logged.
+                                let _: anyhow::Result<()> = Collector::flush(&provider, &cache).await;
+                            }
+                        }
+                    }
                 },
-                _ = instance_token.cancelled() => {
-                    shutdown_token.cancel();
-                    break
-                }
+                _ = cache.threshold_reached() => {
+                    if cache.dirty_count() + cache.pending_column_count() >= collector_size {
+                        let _: anyhow::Result<()> = Collector::flush(&provider, &cache).await;
+                    }
+                },
+                _ = interval.tick() => {
+                    let _: anyhow::Result<()> = Collector::flush(&provider, &cache).await;
+                },
+                _ = instance_token.cancelled() => break
             }
         }
 
-        // Final flush before closing to prevent data loss
-        let collected = Collector::collect(&mut receiver, collector_size);
-        Collector::flush(collected);
+        // Process any remaining requests and perform a final flush to prevent data loss.
+        receiver.close();
+        let mut waiting = Vec::new();
+        while let Ok(request) = receiver.try_recv() {
+            match request {
+                Request::Flush(notify) => waiting.push(notify),
+                request => Collector::store(&cache, request),
+            }
+        }
 
+        let result = Collector::flush(&provider, &cache).await;
+        for notify in waiting {
+            let _: Result<(), _> = notify.send(result.as_ref().map(|_| ()).map_err(|err| anyhow::anyhow!("{err:#}")));
+        }
+
+        shutdown_token.cancel();
         tracing::info!("Level sink closed");
     }
 
-    #[inline]
-    fn collect(receiver: &mut mpsc::Receiver<IndexedSubChunk>, collector_size: usize) -> Vec<IndexedSubChunk> {
-        let mut buffered = Vec::with_capacity(collector_size);
-        while let Ok(recv) = receiver.try_recv() {
-            buffered.push(recv);
+    /// Stores a write request until the next flush.
+    fn store(cache: &SubChunkCache, request: Request) {
+        match request {
+            Request::Write(chunk) => cache.insert_dirty((chunk.dimension, chunk.index), chunk.data),
+            Request::Column(column) => {
+                cache.insert_column((column.dimension, column.coordinates.x, column.coordinates.y), column.biomes);
+            }
+            Request::Flush(_) => unreachable!("Flush requests are handled by the collection task"),
         }
-
-        buffered
     }
 
-    fn flush(data: Vec<IndexedSubChunk>) {
-        rayon::spawn(|| {
-            data.into_par_iter().for_each(|chunk| {});
-        });
+    /// Writes all modified subchunks and generated chunk columns to disk in a single batch.
+    ///
+    /// Written subchunks and columns are unpinned from the cache, unless they were modified again in the meantime.
+    /// If writing fails, the changes remain pending.
+    async fn flush(provider: &Arc<Provider<dyn Storage>>, cache: &Arc<SubChunkCache>) -> anyhow::Result<()> {
+        if cache.dirty_count() == 0 && cache.pending_column_count() == 0 {
+            return Ok(());
+        }
+
+        // Take a snapshot of the current version of every modified subchunk and generated column.
+        let chunks = cache.dirty_entries();
+        let pending_columns = cache.pending_columns();
+
+        let count = chunks.len();
+        let provider = Arc::clone(provider);
+
+        let result = tokio::task::spawn_blocking(move || {
+            let result = (|| {
+                let mut batch = Provider::batch();
+                for ((dimension, index), chunk) in &chunks {
+                    Provider::batch_subchunk(&mut batch, Vector::<i32, 3>::from(*index), *dimension, chunk)?;
+                }
+
+                for ((dimension, x, z), biomes) in &pending_columns {
+                    Provider::batch_biomes(&mut batch, [*x, *z], *dimension, biomes)?;
+                    Provider::batch_version(&mut batch, [*x, *z], *dimension, CHUNK_VERSION)?;
+                }
+
+                provider.execute(&batch)
+            })();
+
+            (result, chunks, pending_columns)
+        })
+        .await;
+
+        match result {
+            Ok((Ok(()), chunks, pending_columns)) => {
+                for (key, chunk) in &chunks {
+                    cache.mark_clean(key, chunk);
+                }
+                for (key, biomes) in &pending_columns {
+                    cache.mark_column_written(key, biomes);
+                }
+
+                tracing::debug!("Wrote {count} subchunks to disk");
+                Ok(())
+            }
+            Ok((Err(err), _, _)) => {
+                // The subchunks and columns are still pinned in the cache, so they will be retried on the next flush.
+                tracing::error!("Failed to write {count} subchunks to disk: {err:#}");
+                anyhow::bail!("Failed to write {count} subchunks to disk: {err:#}");
+            }
+            Err(err) => {
+                tracing::error!("Subchunk writer panicked: {err}");
+                anyhow::bail!("Subchunk writer panicked: {err}");
+            }
+        }
     }
 }
 
@@ -232,48 +220,102 @@ impl Joinable for Collector {
 /// All unreferenced subchunks are thrown into this sink
 /// and will automatically be written to disk at a fixed interval or
 /// when the sink is filled up.
+///
+/// Flushing the sink resolves once all changes sent so far have been written to disk.
 pub struct RegionSink {
-    producer: mpsc::Sender<IndexedSubChunk>,
-    state: FlushState,
+    /// Sends requests to the collector.
+    producer: PollSender<Request>,
+    /// Resolves when a requested flush has completed.
+    pending_flush: Option<oneshot::Receiver<anyhow::Result<()>>>,
 }
 
-impl Sink<IndexedSubChunk> for RegionSink {
-    type Error = anyhow::Error;
+impl RegionSink {
+    /// Waits for the collector to have space available.
+    fn poll_reserve(&mut self, cx: &mut Context) -> Poll<anyhow::Result<()>> {
+        self.producer
+            .poll_reserve(cx)
+            .map_err(|_| anyhow::anyhow!("Level collector has been shut down"))
+    }
 
-    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<anyhow::Result<()>> {
-        // Check whether collector has space.
-        // If not, notify it to flush.
-        if self.producer.capacity() == 0 {
-            self.state.flush();
-
-            // SAFETY: This is safe because the state objects it not moved while this pin is used.
-            let pin = unsafe { Pin::new_unchecked(&mut self.state) };
-            return pin.poll(cx).map(|_| Ok(()));
+    /// Sends a request to the collector. Space must have been reserved using [`poll_reserve`](Self::poll_reserve).
+    fn send_request(&mut self, request: Request) -> anyhow::Result<()> {
+        self.producer
+            .send_item(request)
+            .map_err(|_| anyhow::anyhow!("Level collector has been shut down"))
+    }
+
+    /// Requests a flush and waits for it to complete.
+    fn poll_flush_collector(&mut self, cx: &mut Context) -> Poll<anyhow::Result<()>> {
+        if self.pending_flush.is_none() {
+            if ready!(self.producer.poll_reserve(cx)).is_err() {
+                // The collector has already performed its final flush.
+                return Poll::Ready(Ok(()));
+            }
+
+            let (sender, receiver) = oneshot::channel();
+            if self.producer.send_item(Request::Flush(sender)).is_err() {
+                return Poll::Ready(Ok(()));
+            }
+
+            self.pending_flush = Some(receiver);
         }
 
+        let Some(receiver) = self.pending_flush.as_mut() else {
+            unreachable!("Pending flush was set above");
+        };
+
+        let result = ready!(Pin::new(receiver).poll(cx));
+        self.pending_flush = None;
+
+        // If the collector dropped the request, it has shut down and performed its final flush.
+        Poll::Ready(result.unwrap_or(Ok(())))
+    }
+
+    /// Flushes the collector and closes the sink.
+    fn poll_close_collector(&mut self, cx: &mut Context) -> Poll<anyhow::Result<()>> {
+        ready!(self.poll_flush_collector(cx))?;
+        self.producer.close();
+
         Poll::Ready(Ok(()))
     }
+}
 
-    fn start_send(self: Pin<&mut Self>, item: IndexedSubChunk) -> anyhow::Result<()> {
-        println!("Send");
-        self.producer.try_send(item)?;
-        Ok(())
+impl Sink<IndexedSubChunk> for RegionSink {
+    type Error = anyhow::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<anyhow::Result<()>> {
+        self.poll_reserve(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: IndexedSubChunk) -> anyhow::Result<()> {
+        self.send_request(Request::Write(item))
     }
 
     fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<anyhow::Result<()>> {
-        if self.producer.capacity() == self.producer.max_capacity() {
-            // Don't flush when the collector is empty.
-            return Poll::Ready(Ok(()));
-        }
+        self.poll_flush_collector(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<anyhow::Result<()>> {
+        self.poll_close_collector(cx)
+    }
+}
 
-        self.state.flush();
+impl Sink<IndexedColumn> for RegionSink {
+    type Error = anyhow::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<anyhow::Result<()>> {
+        self.poll_reserve(cx)
+    }
 
-        // SAFETY: The state object is not moved while this pin is active.
-        let pin = unsafe { Pin::new_unchecked(&mut self.state) };
-        pin.poll(cx).map(|_| Ok(()))
+    fn start_send(mut self: Pin<&mut Self>, item: IndexedColumn) -> anyhow::Result<()> {
+        self.send_request(Request::Column(item))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<anyhow::Result<()>> {
+        self.poll_flush_collector(cx)
     }
 
-    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<anyhow::Result<()>> {
-        self.poll_flush(cx)
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<anyhow::Result<()>> {
+        self.poll_close_collector(cx)
     }
 }
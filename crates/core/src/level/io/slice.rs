@@ -0,0 +1,80 @@
+//! Regions that cover part of another region.
+
+use std::ops::Range;
+
+use proto::types::Dimension;
+use rayon::iter::IntoParallelIterator;
+use util::Vector;
+
+use super::region::{Region, RegionIter};
+
+/// A contiguous range of indices of another region.
+///
+/// This makes it possible to process a large region in smaller parts.
+#[derive(Clone)]
+pub struct RegionSlice<R: Region> {
+    region: R,
+    start: usize,
+    len: usize,
+}
+
+impl<R: Region> RegionSlice<R> {
+    /// Creates a slice containing the given range of indices of a region.
+    ///
+    /// The range is clamped to the length of the region.
+    pub fn new(region: R, range: Range<usize>) -> Self {
+        let end = range.end.min(region.len());
+        let start = range.start.min(end);
+
+        Self { region, start, len: end - start }
+    }
+}
+
+impl<R: Region> IntoIterator for RegionSlice<R> {
+    type IntoIter = RegionIter<Self>;
+    type Item = Vector<i32, 3>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        RegionIter {
+            front_index: 0,
+            back_index: self.len(),
+            region: self,
+        }
+    }
+}
+
+impl<R: Region> IntoParallelIterator for RegionSlice<R> {
+    type Iter = RegionIter<Self>;
+    type Item = Vector<i32, 3>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        RegionIter {
+            front_index: 0,
+            back_index: self.len(),
+            region: self,
+        }
+    }
+}
+
+impl<R: Region> Region for RegionSlice<R> {
+    fn as_index(&self, coord: &Vector<i32, 3>) -> Option<usize> {
+        let index = self.region.as_index(coord)?;
+        (self.start..self.start + self.len).contains(&index).then(|| index - self.start)
+    }
+
+    fn as_coord(&self, index: usize) -> Option<Vector<i32, 3>> {
+        if index >= self.len {
+            return None;
+        }
+
+        self.region.as_coord(self.start + index)
+    }
+
+    fn dimension(&self) -> Dimension {
+        self.region.dimension()
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
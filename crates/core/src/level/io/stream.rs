@@ -1,10 +1,12 @@
 use std::{
     pin::Pin,
+    sync::Arc,
     task::{ready, Context, Poll},
 };
 
 use futures::Stream;
-use level::SubChunk;
+use level::{Biomes, SubChunk};
+use proto::types::Dimension;
 use tokio::sync::mpsc;
 use util::Vector;
 
@@ -13,20 +15,32 @@ use util::Vector;
 /// First 6 bits are the vertical index,
 /// then 29 bits for the x-coordinate
 /// and 29 bits for the z-coordinate.
+///
+/// All coordinates are stored in two's complement, so negative coordinates are supported.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct RegionIndex(u64);
 
+/// Mask for the horizontal coordinates.
+const XZ_MASK: u64 = (1 << 29) - 1;
+/// Mask for the vertical coordinate.
+const Y_MASK: u64 = (1 << 6) - 1;
+
+/// Sign extends a value of the given bit width.
+#[inline]
+const fn sign_extend(value: u64, bits: u32) -> i32 {
+    let shift = 64 - bits;
+    ((value << shift) as i64 >> shift) as i32
+}
+
 impl From<Vector<i32, 3>> for RegionIndex {
     fn from(value: Vector<i32, 3>) -> Self {
-        const XZ_MASK: u64 = 2u64.pow(29) - 1;
-
-        assert!((value.y as u64) < 63, "Region Y-coordinate too large");
-        assert!((value.x as u64) < XZ_MASK, "Region X-coordinate too large");
-        assert!((value.z as u64) < XZ_MASK, "Region Z-coordinate too large");
+        assert!((-32..32).contains(&value.y), "Region Y-coordinate out of range");
+        assert!((-(1 << 28)..(1 << 28)).contains(&value.x), "Region X-coordinate out of range");
+        assert!((-(1 << 28)..(1 << 28)).contains(&value.z), "Region Z-coordinate out of range");
 
-        let mut index = (value.y as u64) << 58;
-        index |= ((value.x as u64) & XZ_MASK) << 29;
-        index |= (value.z as u64) & XZ_MASK;
+        let mut index = (value.y as u64 & Y_MASK) << 58;
+        index |= (value.x as u64 & XZ_MASK) << 29;
+        index |= value.z as u64 & XZ_MASK;
 
         RegionIndex(index)
     }
@@ -34,12 +48,10 @@ impl From<Vector<i32, 3>> for RegionIndex {
 
 impl From<RegionIndex> for Vector<i32, 3> {
     fn from(value: RegionIndex) -> Self {
-        const XZ_MASK: u64 = 2u64.pow(29) - 1;
-
         let index = value.0;
-        let y = (index >> 58) as i32;
-        let x = ((index >> 29) & XZ_MASK) as i32;
-        let z = (index & XZ_MASK) as i32;
+        let y = sign_extend(index >> 58, 6);
+        let x = sign_extend((index >> 29) & XZ_MASK, 29);
+        let z = sign_extend(index & XZ_MASK, 29);
 
         Vector::from([x, y, z])
     }
@@ -50,8 +62,27 @@ impl From<RegionIndex> for Vector<i32, 3> {
 pub struct IndexedSubChunk {
     /// The region index.
     pub index: RegionIndex,
+    /// Dimension the subchunk is located in.
+    pub dimension: Dimension,
     /// The subchunk data.
-    pub data: SubChunk,
+    ///
+    /// This is shared with the subchunk cache. To modify it, clone the subchunk and
+    /// write the new version through a [`RegionSink`](super::sink::RegionSink).
+    pub data: Arc<SubChunk>,
+}
+
+/// Metadata of a newly generated chunk column.
+///
+/// Writing this into a [`RegionSink`](super::sink::RegionSink) stores the biomes of the column
+/// and marks the column as generated.
+#[derive(Debug)]
+pub struct IndexedColumn {
+    /// Horizontal chunk coordinates of the column.
+    pub coordinates: Vector<i32, 2>,
+    /// Dimension the column is located in.
+    pub dimension: Dimension,
+    /// Biomes of the column.
+    pub biomes: Biomes,
 }
 
 /// Streams subchunk data as it is produced by an iterator.
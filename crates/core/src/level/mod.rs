@@ -1,10 +1,16 @@
 //! Implements basic Minecraft level functionality.
 
+pub mod cache;
+pub mod generator;
 pub mod io;
 pub mod net;
+pub mod pregen;
 pub mod rule;
 pub mod service;
 pub mod viewer;
 
+mod structure;
+
+pub use pregen::*;
 pub use service::*;
 pub use viewer::*;
@@ -1,48 +1,129 @@
-use std::ops::Range;
+use std::{ops::Range, sync::Arc};
 
-use level::{BlockStates, SubChunk};
+use level::{BiomeEncoding, Biomes, BlockEntity, BlockStates, SubChunk};
+use proto::types::Dimension;
 use util::BinaryWrite;
 
-use crate::level::viewer::ChunkOffset;
+use super::ser::NetworkChunkExt;
+use crate::level::generator::subchunk_range;
 
+/// Biome used for columns that have no biome data. This is the ID of the plains biome.
+const DEFAULT_BIOME: u32 = 1;
+
+/// A full column of subchunks as it is sent to clients.
 pub struct ChunkColumn {
-    pub subchunks: Vec<(ChunkOffset, Option<SubChunk>)>,
+    /// Subchunks in this column, ordered from bottom to top.
+    ///
+    /// Subchunks that have not been loaded are `None` and are treated as air.
+    pub subchunks: Vec<Option<Arc<SubChunk>>>,
+    /// Range of vertical block coordinates covered by this column.
     pub range: Range<i16>,
+    /// Biomes of this column, if they are known.
+    pub biomes: Option<Biomes>,
+    /// Block entities in this column.
+    ///
+    /// These are not part of the serialised column and are sent separately.
+    pub block_entities: Vec<BlockEntity>,
+    /// Lowest vertical coordinate in each column of blocks that is not covered by an occluding block.
+    ///
+    /// Indexed by `[x][z]`. Only valid after [`generate_heightmap`](Self::generate_heightmap) has been called.
     heightmap: Box<[[i16; 16]; 16]>,
 }
 
 impl ChunkColumn {
-    pub fn empty() -> ChunkColumn {
+    /// Creates a column without any subchunks in the given dimension.
+    pub fn new(dimension: Dimension) -> ChunkColumn {
+        let range = subchunk_range(dimension);
+
         ChunkColumn {
-            subchunks: Vec::new(),
-            range: 0..0,
-            heightmap: Box::new([[0; 16]; 16]),
+            subchunks: vec![None; range.len()],
+            range: i16::from(range.start) * 16..i16::from(range.end) * 16,
+            biomes: None,
+            block_entities: Vec::new(),
+            heightmap: Box::new([[i16::from(range.start) * 16; 16]; 16]),
         }
     }
 
-    pub fn heightmap(&self) -> &Box<[[i16; 16]; 16]> {
+    /// Heightmap of this column. See [`generate_heightmap`](Self::generate_heightmap).
+    pub fn heightmap(&self) -> &[[i16; 16]; 16] {
         &self.heightmap
     }
 
+    /// Inserts a subchunk into the column at the position given by its index.
+    ///
+    /// Returns `false` if the subchunk lies outside of the column.
+    pub fn insert(&mut self, subchunk: Arc<SubChunk>) -> bool {
+        let y = i16::from(subchunk.index) * 16;
+        if !self.range.contains(&y) {
+            return false;
+        }
+
+        let index = self.y_to_index(y) as usize;
+        self.subchunks[index] = Some(subchunk);
+        true
+    }
+
+    /// Returns the subchunk with the given vertical subchunk index.
+    pub fn subchunk(&self, index: i8) -> Option<&Arc<SubChunk>> {
+        let y = i16::from(index) * 16;
+        if !self.range.contains(&y) {
+            return None;
+        }
+
+        self.subchunks[self.y_to_index(y) as usize].as_ref()
+    }
+
+    /// Computes the heightmap of this column.
+    ///
+    /// For every column of blocks, this finds the topmost block that occludes sky light across all subchunks.
+    /// The heightmap stores the coordinate directly above this block,
+    /// or the bottom of the column if it contains no occluding blocks.
     pub fn generate_heightmap(&mut self) {
-        for x in 0..16 {
-            for z in 0..16 {
-                let mut top_found = false;
+        *self.heightmap = [[self.range.start; 16]; 16];
+        // Columns of blocks whose topmost occluding block has been found.
+        let mut found = [[false; 16]; 16];
+        let mut remaining = 256;
+
+        for (index, subchunk) in self.subchunks.iter().enumerate().rev() {
+            let Some(subchunk) = subchunk else { continue };
+            let base = self.index_to_y(index as u16);
+
+            // Determine once per palette entry whether it occludes.
+            let layers: Vec<_> = subchunk
+                .layers()
+                .iter()
+                .filter(|layer| !layer.is_empty())
+                .map(|layer| (layer, layer.palette().iter().map(|entry| entry.occludes()).collect::<Vec<_>>()))
+                .collect();
+
+            if layers.is_empty() {
+                continue;
+            }
 
-                for sub in self.subchunks.iter().rev().filter_map(|(_, sub)| sub.as_ref()) {
-                    if top_found {
-                        break;
+            for x in 0..16u8 {
+                for z in 0..16u8 {
+                    if found[x as usize][z as usize] {
+                        continue;
                     }
 
-                    for y in (0..16).rev() {
-                        let block = sub.layer(0).unwrap().get((x, y, z));
-                        dbg!(block);
+                    for y in (0..16u8).rev() {
+                        let offset = level::to_offset([x, y, z].into());
+                        let occludes = layers.iter().any(|(layer, occluding)| occluding[layer.indices[offset] as usize]);
+
+                        if occludes {
+                            self.heightmap[x as usize][z as usize] = base + i16::from(y) + 1;
+                            found[x as usize][z as usize] = true;
+                            remaining -= 1;
+                            break;
+                        }
                     }
                 }
             }
-        }
 
-        // todo!()
+            if remaining == 0 {
+                break;
+            }
+        }
     }
 
     /// Converts a vertical coordinate to a subchunk index in this column.
@@ -50,22 +131,66 @@ impl ChunkColumn {
         ((y - self.range.start) / 16) as u16
     }
 
+    /// Converts a subchunk index in this column to the vertical coordinate of its bottom.
     pub fn index_to_y(&self, index: u16) -> i16 {
         (index * 16) as i16 + self.range.start
     }
 
+    /// Amount of subchunks that have to be sent to include all non-empty subchunks.
+    pub fn subchunk_count(&self) -> usize {
+        self.subchunks
+            .iter()
+            .rposition(|subchunk| subchunk.as_ref().is_some_and(|subchunk| !subchunk.is_empty()))
+            .map_or(0, |index| index + 1)
+    }
+}
+
+impl NetworkChunkExt for ChunkColumn {
+    /// Serialises the column into the format used by the `LevelChunk` packet.
+    ///
+    /// This contains the first [`subchunk_count`](Self::subchunk_count) subchunks,
+    /// followed by the biomes of every subchunk and the border blocks.
     fn serialize_network_in<W>(&self, states: &BlockStates, mut writer: W) -> anyhow::Result<()>
     where
         W: BinaryWrite,
     {
-        // let entries = Vec::with_capacity(self.subchunks.len());
-        // for subchunk in &self.subchunks {
-        //     if let Some(subchunk) = subchunk {
-        //     } else {
-        //         entries.push(SubChunkEntry { result: SubChunkResult::NotFound });
-        //     }
-        // }
-
-        todo!()
+        for (index, subchunk) in self.subchunks.iter().take(self.subchunk_count()).enumerate() {
+            match subchunk {
+                Some(subchunk) => subchunk.serialize_network_in(states, &mut writer)?,
+                None => {
+                    let y = self.index_to_y(index as u16) / 16;
+                    SubChunk::empty(y as i8).serialize_network_in(states, &mut writer)?;
+                }
+            }
+        }
+
+        let fragments = self.biomes.as_ref().map(Biomes::fragments).unwrap_or_default();
+        for index in 0..self.subchunks.len() {
+            match fragments.get(index) {
+                Some(BiomeEncoding::Paletted(biome)) => {
+                    level::serialize_packed_array(&mut writer, &biome.indices, biome.palette.len(), true)?;
+
+                    writer.write_var_i32(biome.palette.len() as i32)?;
+                    for entry in &biome.palette {
+                        writer.write_var_i32(*entry as i32)?;
+                    }
+                }
+                Some(BiomeEncoding::Single(single)) => {
+                    writer.write_u8(1)?;
+                    writer.write_var_i32(*single as i32)?;
+                }
+                // The bottom subchunk has nothing to inherit from.
+                None if index == 0 => {
+                    writer.write_u8(1)?;
+                    writer.write_var_i32(DEFAULT_BIOME as i32)?;
+                }
+                Some(BiomeEncoding::Inherit) | None => writer.write_u8(0x7f << 1 | 1)?,
+            }
+        }
+
+        // Border blocks are only used in education edition.
+        writer.write_u8(0)?;
+
+        Ok(())
     }
 }
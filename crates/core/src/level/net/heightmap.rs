@@ -22,7 +22,7 @@ impl Heightmap {
             for z in 0..16 {
                 // Index of coordinate in current subchunk.
                 let block_idx = ((z as u16) << 4 | (x as u16)) as usize;
-                // Y-coordinate of highest block in column.
+                // Y-coordinate directly above the highest occluding block in the column.
                 let y = chunk_column.heightmap()[x][z];
                 // Index of subchunk that the highest block is located in.
                 let other_idx = chunk_column.y_to_index(y);
@@ -1,4 +1,4 @@
-mod ser;
+pub(crate) mod ser;
 
 pub mod column;
 pub mod heightmap;
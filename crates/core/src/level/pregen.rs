@@ -0,0 +1,194 @@
+//! Generates chunks ahead of time so that they do not have to be generated while players explore.
+
+use std::sync::Arc;
+
+use futures::{SinkExt, StreamExt};
+use proto::types::Dimension;
+use tokio_util::sync::CancellationToken;
+use util::Vector;
+
+use super::generator::subchunk_range;
+use super::io::r#box::BoxRegion;
+use super::io::radial::RadialRegion;
+use super::io::region::Region;
+use super::io::slice::RegionSlice;
+use super::io::stream::IndexedSubChunk;
+use super::Service;
+
+/// Chunk coordinates must stay below this value to be usable in region requests.
+const MAX_CHUNK_COORDINATE: i64 = 1 << 28;
+
+/// Shape of the area that is pre-generated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PregenShape {
+    /// Every chunk that is at most `radius` chunks away from the center on both axes.
+    #[default]
+    Square,
+    /// Every chunk within a circle of `radius` chunks around the center.
+    Circle,
+}
+
+/// Area of a level that is pre-generated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PregenArea {
+    /// Chunk coordinates of the center of the area.
+    pub center: Vector<i32, 2>,
+    /// Radius of the area in chunks.
+    pub radius: u32,
+    /// Dimension to generate chunks in.
+    pub dimension: Dimension,
+    /// Shape of the area.
+    pub shape: PregenShape,
+}
+
+/// Progress of a pre-generation task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PregenProgress {
+    /// Amount of chunk columns that have been processed, including ones that already existed.
+    pub completed: usize,
+    /// Amount of chunk columns in the area.
+    pub total: usize,
+}
+
+impl PregenProgress {
+    /// Whether every chunk column in the area has been processed.
+    #[inline]
+    pub const fn is_complete(&self) -> bool {
+        self.completed >= self.total
+    }
+
+    /// Percentage of the chunk columns that have been processed.
+    pub fn percentage(&self) -> usize {
+        if self.total == 0 {
+            return 100;
+        }
+
+        self.completed * 100 / self.total
+    }
+}
+
+impl Service {
+    /// Generates and stores every chunk in the given area that does not exist yet.
+    ///
+    /// The area is generated in batches on the rayon thread pool. After every batch, the generated chunks are
+    /// written to disk, `progress` is called and the task pauses according to the
+    /// [pre-generation configuration](crate::config::PregenConfig).
+    ///
+    /// Only one area can be pre-generated per level at the same time. The task can be stopped using
+    /// [`cancel_pregeneration`](Self::cancel_pregeneration) and stops automatically when the server shuts down.
+    ///
+    /// # Returns
+    ///
+    /// The progress at the time the task finished. This is incomplete if the task was cancelled.
+    pub async fn pregenerate<F>(self: &Arc<Service>, area: PregenArea, mut progress: F) -> anyhow::Result<PregenProgress>
+    where
+        F: FnMut(PregenProgress) + Send,
+    {
+        let limit = i64::from(area.center.x.unsigned_abs().max(area.center.y.unsigned_abs())) + i64::from(area.radius);
+        if limit >= MAX_CHUNK_COORDINATE {
+            anyhow::bail!("Pre-generation area with radius {} around {:?} is out of range", area.radius, area.center);
+        }
+
+        if self.generator().is_none() {
+            anyhow::bail!("Level {} has no generator, so no chunks can be pre-generated", self.name());
+        }
+
+        let token = {
+            let mut current = self.pregen.lock();
+            if current.is_some() {
+                anyhow::bail!("Level {} is already being pre-generated", self.name());
+            }
+
+            let token = self.instance_token.child_token();
+            *current = Some(token.clone());
+            token
+        };
+
+        // Requesting the lowest subchunk generates the entire column.
+        let y = i32::from(subchunk_range(area.dimension).start);
+        let radius = area.radius as i32;
+        let (x, z) = (area.center.x, area.center.y);
+
+        let result = match area.shape {
+            PregenShape::Square => {
+                let region = BoxRegion::from_bounds((x - radius, y, z - radius), (x + radius, y, z + radius), area.dimension);
+                self.pregenerate_region(region, &token, &mut progress).await
+            }
+            PregenShape::Circle => {
+                let region = RadialRegion::from_center(area.center.clone(), area.radius as usize, y..y + 1, area.dimension);
+                self.pregenerate_region(region, &token, &mut progress).await
+            }
+        };
+
+        *self.pregen.lock() = None;
+        result
+    }
+
+    /// Cancels the pre-generation task of this level.
+    ///
+    /// Chunks that have been generated so far are still written to disk.
+    /// Returns whether a task was running.
+    pub fn cancel_pregeneration(&self) -> bool {
+        let current = self.pregen.lock();
+        if let Some(token) = current.as_ref() {
+            token.cancel();
+        }
+
+        current.is_some()
+    }
+
+    /// Whether an area of this level is currently being pre-generated.
+    pub fn is_pregenerating(&self) -> bool {
+        self.pregen.lock().is_some()
+    }
+
+    /// Requests every subchunk in the region in batches, writing the generated chunks to disk after every batch.
+    async fn pregenerate_region<R, F>(self: &Arc<Service>, region: R, token: &CancellationToken, progress: &mut F) -> anyhow::Result<PregenProgress>
+    where
+        R: Region,
+        F: FnMut(PregenProgress) + Send,
+    {
+        let config = self.instance().config().pregen().clone();
+        let total = region.len();
+        let mut completed = 0;
+        let mut sink = self.region_sink();
+
+        tracing::info!("Pre-generating {total} chunks in level {}", self.name());
+
+        for start in (0..total).step_by(config.batch_size.max(1)) {
+            let batch = RegionSlice::new(region.clone(), start..start + config.batch_size);
+            let mut stream = self.region(batch);
+
+            // Dropping the stream stops the remaining subchunks of the batch from being generated.
+            loop {
+                tokio::select! {
+                    item = stream.next() => match item {
+                        Some(_) => completed += 1,
+                        None => break,
+                    },
+                    _ = token.cancelled() => break,
+                }
+            }
+            drop(stream);
+
+            // Store the chunks that have been generated so far, even if the task was cancelled.
+            SinkExt::<IndexedSubChunk>::flush(&mut sink).await?;
+
+            let current = PregenProgress { completed, total };
+            progress(current);
+
+            if token.is_cancelled() {
+                tracing::info!("Cancelled pre-generation of level {} at {completed}/{total} chunks", self.name());
+                return Ok(current);
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(config.delay) => {},
+                _ = token.cancelled() => {},
+            }
+        }
+
+        tracing::info!("Finished pre-generating {total} chunks in level {}", self.name());
+        Ok(PregenProgress { completed, total })
+    }
+}
@@ -2,27 +2,48 @@ use super::io::sink::RegionSink;
 use super::io::stream::{IndexedSubChunk, RegionIndex};
 use std::{
     any::TypeId,
-    sync::{Arc, OnceLock, Weak},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock, Weak,
+    },
+    time::Duration,
 };
 
 use dashmap::DashMap;
-use level::{provider::Provider, SubChunk};
+use futures::SinkExt;
+use level::{Biomes, BlockEntity, LegacyBlockMapping, LevelSettings, LightEngine, LightProperties, MemoryStorage, PaletteEntry, Storage, SubChunk, SubStorage};
+use parking_lot::{Mutex, RwLock, RwLockReadGuard};
+use proto::bedrock::{BlockActorData, Difficulty, GameRule, UpdateBlock, UpdateBlockFlags};
 use proto::types::Dimension;
 use rayon::iter::ParallelIterator;
 use tokio::sync::mpsc::{self, error::SendError};
 use tokio_util::sync::CancellationToken;
-use util::{Joinable, Vector};
+use util::{BlockPosition, Joinable, Vector};
 
+use crate::config::{GeneratorConfig, StorageConfig};
 use crate::instance::Instance;
 
 use super::{
+    cache::{CacheMetrics, SubChunkCache},
+    generator::{subchunk_range, Generator, NoiseGenerator, SuperflatGenerator},
     io::{region::Region, sink::Collector, stream::RegionStream},
-    rule::{Rule, RuleValue},
+    rule::*,
 };
 
 pub struct ServiceOptions {
+    /// Name that identifies the level within the instance.
+    pub name: String,
+    /// Cancelled when the server shuts down.
     pub instance_token: CancellationToken,
+    /// Path to the level directory.
     pub level_path: String,
+    /// Maximum amount of unmodified subchunks kept in memory.
+    pub cache_size: usize,
+    /// Generator used for chunks that do not exist yet.
+    pub generator: GeneratorConfig,
+    /// Storage backend that the chunks are kept in.
+    pub storage: StorageConfig,
 }
 
 /// Threshold for the service to switch from singular to batching mode.
@@ -30,36 +51,102 @@ pub struct ServiceOptions {
 /// with a parallel iterator and threadpool.
 const REGION_PARALLEL_THRESHOLD: usize = 100;
 
-/// Manages the world of the server.
+/// Maximum amount of chunk columns per dimension whose light is kept in memory.
+const LIGHT_COLUMN_LIMIT: usize = 1024;
+
+/// Spawn height used when the level does not specify a spawn height.
+const DEFAULT_SPAWN_HEIGHT: i32 = 64;
+
+/// Interval at which modified level settings are written to disk.
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Manages a single world of the server.
 pub struct Service {
+    /// Name that identifies the level within the instance.
+    name: String,
     /// Cancelled when the whole server is shutting down. This will then signal to this
     /// service to shut down as well.
-    instance_token: CancellationToken,
+    pub(super) instance_token: CancellationToken,
     /// Cancelled once this service has fully shut down.
     shutdown_token: CancellationToken,
     /// Reference to the parent instance.
     instance: OnceLock<Weak<Instance>>,
     /// Provides level data from disk.
-    pub(super) provider: Arc<level::provider::Provider>,
+    pub(super) provider: Arc<level::provider::Provider<dyn Storage>>,
     /// Collects subchunk changes using sinks and writes them to disk periodically.
     collector: Collector,
+    /// Keeps recently used and modified subchunks in memory.
+    pub(super) cache: Arc<SubChunkCache>,
     /// Current gamerule values.
     /// The gamerules are stored by TypeId to allow for user-defined gamerules.
     gamerules: DashMap<TypeId, RuleValue>,
+    /// Settings loaded from the `level.dat` file.
+    settings: RwLock<LevelSettings>,
+    /// Whether the settings have been modified since they were last saved.
+    settings_dirty: AtomicBool,
+    /// Prevents concurrent block modifications from overwriting each other's changes.
+    pub(super) block_lock: Mutex<()>,
+    /// Light of the overworld, nether and end, in that order.
+    light: [Mutex<LightEngine>; 3],
+    /// Generates chunks that do not exist yet.
+    generator: RwLock<Option<Arc<dyn Generator>>>,
+    /// Cancels the pre-generation task that is currently running, if there is one.
+    pub(super) pregen: Mutex<Option<CancellationToken>>,
 }
 
 impl Service {
     pub(crate) fn new(options: ServiceOptions) -> anyhow::Result<Arc<Service>> {
-        let provider = Arc::new(level::provider::Provider::open(&options.level_path)?);
+        let provider: Arc<level::provider::Provider<dyn Storage>> = match options.storage {
+            StorageConfig::LevelDb => Arc::new(level::provider::Provider::open(&options.level_path)?),
+            StorageConfig::Memory => Arc::new(level::provider::Provider::with_storage(&options.level_path, MemoryStorage::new())),
+        };
+        let settings = match provider.settings() {
+            Ok(settings) => settings,
+            Err(err) => {
+                tracing::error!("Failed to read level settings: {err:#}");
+                anyhow::bail!("Failed to read level settings: {err:#}");
+            }
+        };
+
+        let generator: Option<Arc<dyn Generator>> = match &options.generator {
+            GeneratorConfig::None => None,
+            GeneratorConfig::Flat(preset) => match SuperflatGenerator::from_preset(preset) {
+                Ok(generator) => Some(Arc::new(generator)),
+                Err(err) => {
+                    tracing::error!("Invalid superflat preset: {err:#}");
+                    anyhow::bail!("Invalid superflat preset: {err:#}");
+                }
+            },
+            GeneratorConfig::Noise => Some(Arc::new(NoiseGenerator::new(settings.random_seed))),
+        };
 
+        let cache = Arc::new(SubChunkCache::new(options.cache_size));
         let service = Arc::new(Service {
-            collector: Collector::new(Arc::clone(&provider), options.instance_token.clone(), 100),
+            name: options.name,
+            collector: Collector::new(Arc::clone(&provider), Arc::clone(&cache), options.instance_token.clone(), 100),
+            cache,
             instance_token: options.instance_token,
             shutdown_token: CancellationToken::new(),
             instance: OnceLock::new(),
             provider,
             gamerules: DashMap::new(),
+            settings: RwLock::new(settings),
+            settings_dirty: AtomicBool::new(false),
+            block_lock: Mutex::new(()),
+            light: [
+                Mutex::new(LightEngine::new(subchunk_range(Dimension::Overworld), true)),
+                Mutex::new(LightEngine::new(subchunk_range(Dimension::Nether), false)),
+                Mutex::new(LightEngine::new(subchunk_range(Dimension::End), false)),
+            ],
+            generator: RwLock::new(generator),
+            pregen: Mutex::new(None),
         });
+        service.load_gamerules();
+        // Loading the gamerules does not modify the settings.
+        service.settings_dirty.store(false, Ordering::Relaxed);
+
+        tokio::spawn(Arc::clone(&service).autosave());
+
         Ok(service)
     }
 
@@ -70,6 +157,198 @@ impl Service {
             .map_err(|_| anyhow::anyhow!("Level service instance was already set"))
     }
 
+    /// Returns the instance that owns this service.
+    pub(crate) fn instance(&self) -> Arc<Instance> {
+        // The instance is set before any clients can connect or commands can be executed.
+        #[allow(clippy::unwrap_used)]
+        self.instance.get().unwrap().upgrade().unwrap()
+    }
+
+    /// Name that identifies the level within the instance.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the settings loaded from the `level.dat` file.
+    #[inline]
+    pub fn settings(&self) -> RwLockReadGuard<'_, LevelSettings> {
+        self.settings.read()
+    }
+
+    /// Modifies the level settings.
+    ///
+    /// The changes are written to disk on the next autosave or when the server shuts down.
+    pub fn update_settings<F>(&self, f: F)
+    where
+        F: FnOnce(&mut LevelSettings),
+    {
+        f(&mut self.settings.write());
+        self.settings_dirty.store(true, Ordering::Relaxed);
+    }
+
+    /// Sets the current time of day in ticks.
+    pub fn set_time(&self, time: i64) {
+        self.update_settings(|settings| settings.time = time);
+    }
+
+    /// Sets the intensity of the rain and the amount of ticks until it changes.
+    pub fn set_rain(&self, level: f32, time: i32) {
+        self.update_settings(|settings| {
+            settings.rain_level = level;
+            settings.rain_time = time;
+        });
+    }
+
+    /// Sets the intensity of lightning and the amount of ticks until it changes.
+    pub fn set_lightning(&self, level: f32, time: i32) {
+        self.update_settings(|settings| {
+            settings.lightning_level = level;
+            settings.lightning_time = time;
+        });
+    }
+
+    /// Sets the world spawn point.
+    pub fn set_spawn(&self, position: BlockPosition) {
+        self.update_settings(|settings| {
+            settings.spawn_x = position.x;
+            settings.spawn_y = position.y as i32;
+            settings.spawn_z = position.z;
+        });
+    }
+
+    /// Position that players spawn at when they enter this level.
+    pub fn spawn_position(&self) -> Vector<f32, 3> {
+        let (spawn_x, spawn_z) = {
+            let settings = self.settings.read();
+            (settings.spawn_x, settings.spawn_z)
+        };
+
+        let spawn_y = self.spawn_height();
+        Vector::from([spawn_x as f32 + 0.5, spawn_y as f32, spawn_z as f32 + 0.5])
+    }
+
+    /// Height of the world spawn point.
+    ///
+    /// Bedrock stores a spawn height of `i16::MAX` when it should search for a safe spawn point.
+    /// In that case the spawn point is placed on top of the highest block in the spawn column,
+    /// generating the column if required.
+    pub fn spawn_height(&self) -> i32 {
+        let (spawn_x, spawn_y, spawn_z) = {
+            let settings = self.settings.read();
+            (settings.spawn_x, settings.spawn_y, settings.spawn_z)
+        };
+
+        if spawn_y != i16::MAX as i32 {
+            return spawn_y;
+        }
+
+        match self.highest_block(spawn_x, spawn_z, Dimension::Overworld) {
+            Ok(Some(height)) => height + 1,
+            Ok(None) => DEFAULT_SPAWN_HEIGHT,
+            Err(err) => {
+                tracing::warn!("Failed to find the height of the spawn point: {err:#}");
+                DEFAULT_SPAWN_HEIGHT
+            }
+        }
+    }
+
+    /// Returns the height of the highest block that is not air in the given column.
+    fn highest_block(&self, x: i32, z: i32, dimension: Dimension) -> anyhow::Result<Option<i32>> {
+        let local = Vector::from([(x & 0xf) as u8, 0, (z & 0xf) as u8]);
+        for index in subchunk_range(dimension).rev() {
+            let subchunk = self.subchunk([x >> 4, i32::from(index), z >> 4], dimension)?;
+            let Some(layer) = subchunk.layer(0) else { continue };
+            if layer.is_empty() {
+                continue;
+            }
+
+            for y in (0..16u8).rev() {
+                if layer[[local.x, y, local.z]].name != "minecraft:air" {
+                    return Ok(Some(i32::from(index) * 16 + i32::from(y)));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Sets the difficulty of the level.
+    pub fn set_difficulty(&self, difficulty: Difficulty) {
+        self.update_settings(|settings| settings.difficulty = difficulty as i32);
+    }
+
+    /// Writes the level settings to disk, including the current gamerule values.
+    pub fn save_settings(&self) -> anyhow::Result<()> {
+        self.settings_dirty.store(false, Ordering::Relaxed);
+
+        let mut settings = self.settings.write();
+        self.store_gamerules(&mut settings);
+
+        if let Err(err) = self.provider.save_settings(&settings) {
+            self.settings_dirty.store(true, Ordering::Relaxed);
+
+            tracing::error!("Failed to save level settings: {err:#}");
+            anyhow::bail!("Failed to save level settings: {err:#}");
+        }
+
+        Ok(())
+    }
+
+    /// Creates a backup of the level while the server keeps running.
+    ///
+    /// All pending changes and the level settings are written to disk first. The backup is stored in a
+    /// timestamped directory inside of `<backup directory>/<level name>`, after which older backups are removed
+    /// according to the configured retention.
+    ///
+    /// # Returns
+    ///
+    /// The path of the created backup.
+    pub async fn backup(self: &Arc<Service>) -> anyhow::Result<PathBuf> {
+        let mut sink = self.region_sink();
+        SinkExt::<IndexedSubChunk>::flush(&mut sink).await?;
+        self.save_settings()?;
+
+        let config = self.instance().config().backups().clone();
+        let root = Path::new(&config.directory).join(&self.name);
+
+        let provider = Arc::clone(&self.provider);
+        let path = tokio::task::spawn_blocking(move || {
+            let path = provider.backup(&root)?;
+            for removed in level::prune_backups(&root, config.retention)? {
+                tracing::debug!("Removed old backup {}", removed.display());
+            }
+
+            anyhow::Ok(path)
+        })
+        .await??;
+
+        tracing::info!("Backed up level {} to {}", self.name, path.display());
+        Ok(path)
+    }
+
+    /// Periodically writes modified settings to disk and performs a final save when the server shuts down.
+    async fn autosave(self: Arc<Service>) {
+        let mut interval = tokio::time::interval(AUTOSAVE_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    if self.settings_dirty.load(Ordering::Relaxed) {
+                        // Errors have already been logged.
+                        let _: anyhow::Result<()> = self.save_settings();
+                    }
+                }
+                _ = self.instance_token.cancelled() => break
+            }
+        }
+
+        // Always save on shutdown so that the latest gamerules are stored as well.
+        let _: anyhow::Result<()> = self.save_settings();
+        self.shutdown_token.cancel();
+    }
+
     /// Requests chunks using the specified region iterator.
     pub fn region<R: Region>(self: &Arc<Service>, region: R) -> RegionStream
     where
@@ -82,6 +361,403 @@ impl Service {
         }
     }
 
+    /// Returns the block at the given world coordinates.
+    ///
+    /// Blocks in subchunks that have not been generated yet are reported as air.
+    pub fn block<V>(&self, position: V, dimension: Dimension) -> anyhow::Result<PaletteEntry>
+    where
+        V: Into<Vector<i32, 3>>,
+    {
+        let position = position.into();
+        let (index, local) = Self::split_position(&position)?;
+
+        let subchunk = self.subchunk(index, dimension)?;
+        match subchunk.layer(0) {
+            Some(layer) if !layer.is_empty() => Ok(layer[local].clone()),
+            _ => Ok(PaletteEntry::air(None)),
+        }
+    }
+
+    /// Replaces the block at the given world coordinates.
+    ///
+    /// The modified subchunk is stored in the cache until the collector writes it to disk
+    /// and the change is sent to every client that has the chunk in view.
+    /// Reads made after this function returns observe the new block, even if it has not been written to disk yet.
+    pub fn set_block<V>(&self, position: V, dimension: Dimension, block: PaletteEntry) -> anyhow::Result<()>
+    where
+        V: Into<Vector<i32, 3>>,
+    {
+        let position = position.into();
+        let (index, local) = Self::split_world_position(&position, dimension)?;
+
+        let instance = self.instance();
+        let Some(runtime_id) = instance.block_states.state(&block) else {
+            tracing::error!("Attempted to place unknown block state {}", block.name);
+            anyhow::bail!("Attempted to place unknown block state {}", block.name);
+        };
+
+        let had_block_entity = {
+            // Prevents concurrent modifications of the same subchunk from overwriting each other.
+            let _guard = self.block_lock.lock();
+
+            let mut subchunk = SubChunk::clone(&*self.subchunk(index.clone(), dimension)?);
+            let had_block_entity = match subchunk.layer(0) {
+                Some(layer) if !layer.is_empty() => level::has_block_entity(&layer[local.clone()].name),
+                _ => false,
+            };
+
+            if subchunk.layers.is_empty() {
+                subchunk.layers.push(SubStorage::empty());
+            }
+            subchunk[0].set(local, block);
+
+            let subchunk = Arc::new(subchunk);
+            self.light_engine(dimension).lock().update_block(&position, &subchunk);
+
+            // The subchunk is pinned in the cache until the collector has written it to disk.
+            let key = (dimension, RegionIndex::from(index.clone()));
+            self.cache.insert_dirty(key, subchunk);
+
+            had_block_entity
+        };
+
+        instance.clients().broadcast_to_viewers(
+            self,
+            Vector::from([index.x, index.z]),
+            dimension,
+            UpdateBlock {
+                // Negative heights are sent as their two's complement representation.
+                position: BlockPosition::new(position.x, position.y as u32, position.z),
+                block_runtime_id: runtime_id,
+                flags: UpdateBlockFlags::UpdateNetwork as u32,
+                layer: 0,
+            },
+        )?;
+
+        // Block entities cannot outlive the block they belong to.
+        // Only blocks that can have a block entity have to be looked up in storage.
+        if had_block_entity {
+            self.remove_block_entity(position, dimension)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns every block entity in the given chunk.
+    pub fn block_entities<I>(&self, coordinates: I, dimension: Dimension) -> anyhow::Result<Vec<BlockEntity>>
+    where
+        I: Into<Vector<i32, 2>>,
+    {
+        self.provider.block_entities(coordinates, dimension)
+    }
+
+    /// Returns the block entity at the given world coordinates, if there is one.
+    pub fn block_entity<V>(&self, position: V, dimension: Dimension) -> anyhow::Result<Option<BlockEntity>>
+    where
+        V: Into<Vector<i32, 3>>,
+    {
+        let position = position.into();
+        let entities = self.block_entities([position.x >> 4, position.z >> 4], dimension)?;
+
+        Ok(entities.into_iter().find(|entity| entity.position == position))
+    }
+
+    /// Stores a block entity, replacing any existing block entity at the same position.
+    ///
+    /// Unlike subchunks, block entities are written to disk immediately.
+    /// The new data is sent to every client that has the chunk in view.
+    pub fn set_block_entity(&self, entity: BlockEntity, dimension: Dimension) -> anyhow::Result<()> {
+        let position = entity.position.clone();
+        Self::split_world_position(&position, dimension)?;
+
+        let coordinates = Vector::from([position.x >> 4, position.z >> 4]);
+        let packet = BlockActorData {
+            position: BlockPosition::new(position.x, position.y as u32, position.z),
+            nbt: entity.to_nbt(),
+        };
+
+        self.modify_block_entities(&coordinates, dimension, |entities| {
+            entities.retain(|existing| existing.position != position);
+            entities.push(entity);
+            true
+        })?;
+
+        self.instance().clients().broadcast_to_viewers(self, coordinates, dimension, packet)
+    }
+
+    /// Removes the block entity at the given world coordinates.
+    ///
+    /// Returns the removed block entity, if there was one.
+    pub fn remove_block_entity<V>(&self, position: V, dimension: Dimension) -> anyhow::Result<Option<BlockEntity>>
+    where
+        V: Into<Vector<i32, 3>>,
+    {
+        let position = position.into();
+        Self::split_world_position(&position, dimension)?;
+        let coordinates = Vector::from([position.x >> 4, position.z >> 4]);
+
+        let mut removed = None;
+        self.modify_block_entities(&coordinates, dimension, |entities| {
+            let index = entities.iter().position(|entity| entity.position == position);
+            removed = index.map(|index| entities.swap_remove(index));
+            removed.is_some()
+        })?;
+
+        Ok(removed)
+    }
+
+    /// Applies a modification to the block entities of a chunk and writes them back to disk.
+    ///
+    /// The closure returns whether it modified the list, nothing is written if it did not.
+    pub(super) fn modify_block_entities<F>(&self, coordinates: &Vector<i32, 2>, dimension: Dimension, f: F) -> anyhow::Result<()>
+    where
+        F: FnOnce(&mut Vec<BlockEntity>) -> bool,
+    {
+        // Prevents concurrent modifications of the same chunk from overwriting each other.
+        let _guard = self.block_lock.lock();
+
+        let mut entities = self.provider.block_entities(coordinates.clone(), dimension)?;
+        if !f(&mut entities) {
+            return Ok(());
+        }
+
+        let mut batch = level::provider::Provider::batch();
+        level::provider::Provider::batch_block_entities(&mut batch, coordinates.clone(), dimension, &entities)?;
+
+        if let Err(err) = self.provider.execute(&batch) {
+            tracing::error!("Failed to write block entities: {err:#}");
+            anyhow::bail!("Failed to write block entities: {err:#}");
+        }
+
+        Ok(())
+    }
+
+    /// Returns the sky light level at the given world coordinates, computing the light of the column if required.
+    pub fn sky_light<V>(&self, position: V, dimension: Dimension) -> anyhow::Result<u8>
+    where
+        V: Into<Vector<i32, 3>>,
+    {
+        let position = position.into();
+        self.light_column(Vector::from([position.x >> 4, position.z >> 4]), dimension)?;
+
+        Ok(self.light_engine(dimension).lock().sky_light(&position).unwrap_or(0))
+    }
+
+    /// Returns the block light level at the given world coordinates, computing the light of the column if required.
+    pub fn block_light<V>(&self, position: V, dimension: Dimension) -> anyhow::Result<u8>
+    where
+        V: Into<Vector<i32, 3>>,
+    {
+        let position = position.into();
+        self.light_column(Vector::from([position.x >> 4, position.z >> 4]), dimension)?;
+
+        Ok(self.light_engine(dimension).lock().block_light(&position).unwrap_or(0))
+    }
+
+    /// Overrides the light properties of a block, such as the light emitted by a custom block.
+    ///
+    /// This only affects chunks whose light is computed after calling this function.
+    pub fn set_light_properties<S: Into<String>>(&self, name: S, properties: LightProperties) {
+        let name = name.into();
+        for engine in &self.light {
+            engine.lock().set_properties(name.clone(), properties);
+        }
+    }
+
+    /// Computes the light of the given column if it has not been computed yet.
+    ///
+    /// Light is kept for the most recently lit columns, older columns are relit when they are requested again.
+    fn light_column(&self, coordinates: Vector<i32, 2>, dimension: Dimension) -> anyhow::Result<()> {
+        if self.light_engine(dimension).lock().is_lit(&coordinates) {
+            return Ok(());
+        }
+
+        // Load the subchunks before taking the locks, as this might have to generate the column.
+        self.column_subchunks(&coordinates, dimension)?;
+
+        // Prevents blocks from being modified while the column is lit.
+        let _guard = self.block_lock.lock();
+        let mut engine = self.light_engine(dimension).lock();
+        if !engine.is_lit(&coordinates) {
+            // The subchunks are cached now, this only makes sure that no modifications are missed.
+            let subchunks = self.column_subchunks(&coordinates, dimension)?;
+            engine.light_column(&coordinates, subchunks.iter().map(AsRef::as_ref));
+            engine.evict(LIGHT_COLUMN_LIMIT);
+        }
+
+        Ok(())
+    }
+
+    /// Returns every subchunk in the given column.
+    fn column_subchunks(&self, coordinates: &Vector<i32, 2>, dimension: Dimension) -> anyhow::Result<Vec<Arc<SubChunk>>> {
+        subchunk_range(dimension)
+            .map(|y| self.subchunk([coordinates.x, i32::from(y), coordinates.y], dimension))
+            .collect()
+    }
+
+    /// Returns the light engine of the given dimension.
+    #[inline]
+    pub(super) fn light_engine(&self, dimension: Dimension) -> &Mutex<LightEngine> {
+        match dimension {
+            Dimension::Overworld => &self.light[0],
+            Dimension::Nether => &self.light[1],
+            Dimension::End => &self.light[2],
+        }
+    }
+
+    /// Splits world coordinates into subchunk coordinates and a position inside of that subchunk.
+    fn split_position(position: &Vector<i32, 3>) -> anyhow::Result<(Vector<i32, 3>, Vector<u8, 3>)> {
+        let index = Vector::from([position.x >> 4, position.y >> 4, position.z >> 4]);
+        if i8::try_from(index.y).is_err() {
+            anyhow::bail!("Block height {} is out of range", position.y);
+        }
+
+        let local = Vector::from([(position.x & 0xf) as u8, (position.y & 0xf) as u8, (position.z & 0xf) as u8]);
+        Ok((index, local))
+    }
+
+    /// Like [`split_position`](Self::split_position), but also fails if the position is outside of the world.
+    ///
+    /// Used by modifications, which would otherwise create subchunks that are written to disk outside of the world.
+    fn split_world_position(position: &Vector<i32, 3>, dimension: Dimension) -> anyhow::Result<(Vector<i32, 3>, Vector<u8, 3>)> {
+        let (index, local) = Self::split_position(position)?;
+        if !i8::try_from(index.y).is_ok_and(|y| subchunk_range(dimension).contains(&y)) {
+            anyhow::bail!("Block height {} is outside of the world in {dimension:?}", position.y);
+        }
+
+        Ok((index, local))
+    }
+
+    /// Returns the subchunk at the given subchunk coordinates, loading it from disk if it is not cached.
+    ///
+    /// If the chunk has not been generated yet, it is generated using the current [`Generator`].
+    /// Subchunks that do not exist are returned as empty subchunks.
+    pub fn subchunk<V>(&self, index: V, dimension: Dimension) -> anyhow::Result<Arc<SubChunk>>
+    where
+        V: Into<Vector<i32, 3>>,
+    {
+        let index = index.into();
+        let key = (dimension, RegionIndex::from(index.clone()));
+        if let Some(subchunk) = self.cache.get(&key) {
+            return Ok(subchunk);
+        }
+
+        if let Some(subchunk) = self.provider.stored_subchunk(index.clone(), dimension)? {
+            return Ok(self.cache.insert(key, Arc::new(subchunk)));
+        }
+
+        match self.legacy_terrain(&index, dimension)? {
+            Some(subchunk) => Ok(subchunk),
+            None => self.generate(&index, dimension),
+        }
+    }
+
+    /// Loads the subchunk from a column stored in the legacy terrain format, if there is one.
+    ///
+    /// The whole column is decoded at once, so all of its subchunks are cached rather than only the requested one.
+    fn legacy_terrain(&self, index: &Vector<i32, 3>, dimension: Dimension) -> anyhow::Result<Option<Arc<SubChunk>>> {
+        // Chunks from before subchunks were introduced only cover the bottom 8 subchunks.
+        let Ok(requested @ 0..=7) = usize::try_from(index.y) else {
+            return Ok(None);
+        };
+
+        let Some(terrain) = self.provider.legacy_terrain([index.x, index.z], dimension, LegacyBlockMapping::builtin())? else {
+            return Ok(None);
+        };
+
+        let mut loaded = None;
+        for (y, subchunk) in terrain.subchunks.into_iter().enumerate() {
+            let key = (dimension, RegionIndex::from(Vector::from([index.x, y as i32, index.z])));
+            let subchunk = self.cache.insert(key, Arc::new(subchunk));
+            if y == requested {
+                loaded = Some(subchunk);
+            }
+        }
+
+        Ok(loaded)
+    }
+
+    /// Generates the chunk column containing the given subchunk if it has not been generated before.
+    ///
+    /// The generated subchunks are pinned in the cache until the collector has written them to disk.
+    /// The biomes of the column are kept in the cache as well, until the collector writes them together with its version.
+    fn generate(&self, index: &Vector<i32, 3>, dimension: Dimension) -> anyhow::Result<Arc<SubChunk>> {
+        let key = (dimension, RegionIndex::from(index.clone()));
+        let empty = || self.cache.insert(key, Arc::new(SubChunk::empty(index.y as i8)));
+
+        let Some(generator) = self.generator() else {
+            return Ok(empty());
+        };
+
+        let in_range = i8::try_from(index.y).is_ok_and(|y| subchunk_range(dimension).contains(&y));
+        if !in_range || self.provider.version([index.x, index.z], dimension)?.is_some() {
+            // The subchunk is outside of the world or part of a column that has already been generated.
+            return Ok(empty());
+        }
+
+        let coordinates = Vector::from([index.x, index.z]);
+        let column = generator.generate(coordinates.clone(), dimension)?;
+
+        // Subchunks that the generator left out are stored as air, so that requesting them again
+        // does not generate the column again before its version has been written.
+        let mut subchunks: Vec<Option<SubChunk>> = subchunk_range(dimension).map(|_| None).collect();
+        let bottom = subchunk_range(dimension).start;
+        for subchunk in column.subchunks {
+            if let Some(slot) = subchunk.index.checked_sub(bottom).and_then(|offset| subchunks.get_mut(offset as usize)) {
+                *slot = Some(subchunk);
+            }
+        }
+
+        let mut requested = None;
+        for (y, subchunk) in subchunk_range(dimension).zip(subchunks) {
+            let subchunk = subchunk.unwrap_or_else(|| SubChunk::empty(y));
+            let y = i32::from(y);
+            let subkey = (dimension, RegionIndex::from(Vector::from([index.x, y, index.z])));
+
+            // Keep existing subchunks in case the column was generated and modified concurrently.
+            let subchunk = self.cache.try_insert_dirty(subkey, Arc::new(subchunk));
+            if y == index.y {
+                requested = Some(subchunk);
+            }
+        }
+
+        // The biomes are kept in the cache until the collector has written them, together with the version of the column.
+        self.cache.insert_column((dimension, coordinates.x, coordinates.y), column.biomes);
+        Ok(requested.unwrap_or_else(empty))
+    }
+
+    /// Returns the biomes of the given chunk column.
+    ///
+    /// Biomes of generated columns that have not been written to disk yet are returned as well.
+    pub fn biomes<V>(&self, coordinates: V, dimension: Dimension) -> anyhow::Result<Option<Biomes>>
+    where
+        V: Into<Vector<i32, 2>>,
+    {
+        let coordinates = coordinates.into();
+        if let Some(biomes) = self.cache.column(&(dimension, coordinates.x, coordinates.y)) {
+            return Ok(Some(Biomes::clone(&biomes)));
+        }
+
+        self.provider.biomes(coordinates, dimension)
+    }
+
+    /// Returns the generator that is used to generate new chunks.
+    pub fn generator(&self) -> Option<Arc<dyn Generator>> {
+        self.generator.read().clone()
+    }
+
+    /// Sets the generator that is used to generate new chunks.
+    ///
+    /// If no generator is set, chunks that do not exist are left empty.
+    pub fn set_generator(&self, generator: Option<Arc<dyn Generator>>) {
+        *self.generator.write() = generator;
+    }
+
+    /// Returns the hit and miss statistics of the subchunk cache.
+    pub fn cache_metrics(&self) -> CacheMetrics {
+        self.cache.metrics()
+    }
+
     /// Creates a new [`RegionSink`]. A region sink allows you to save modified subchunks to disk.
     pub fn region_sink(&self) -> RegionSink {
         self.collector.create_sink()
@@ -91,7 +767,7 @@ impl Service {
     ///
     /// This function is used for smaller regions that do not benefit from
     /// parallel processing.
-    fn request_sequential_region<R: Region>(&self, region: R) -> RegionStream
+    fn request_sequential_region<R: Region>(self: &Arc<Self>, region: R) -> RegionStream
     where
         R::IntoIter: Send,
     {
@@ -101,11 +777,11 @@ impl Service {
 
         let (sender, receiver) = mpsc::channel(len);
 
-        let provider = Arc::clone(&self.provider);
+        let service = Arc::clone(self);
         tokio::task::spawn_blocking(move || {
             // If this returns an error, the receiver has closed so we can stop processing.
             let _: Result<(), SendError<IndexedSubChunk>> = iter.try_for_each(|item| {
-                let indexed = Self::for_each_subchunk(item, dim, &provider);
+                let indexed = service.for_each_subchunk(item, dim);
                 sender.blocking_send(indexed)
             });
         });
@@ -123,17 +799,17 @@ impl Service {
     ///
     /// The parallel iterator is not used for small regions because the overhead is larger
     /// than the performance boost for small regions.
-    fn request_parallel_region<R: Region>(&self, region: R) -> RegionStream {
+    fn request_parallel_region<R: Region>(self: &Arc<Self>, region: R) -> RegionStream {
         let len = region.len();
         let dim = region.dimension();
         let iter = region.into_par_iter();
         let (sender, receiver) = mpsc::channel(len);
 
-        let provider = Arc::clone(&self.provider);
+        let service = Arc::clone(self);
         rayon::spawn(move || {
             // If this returns an error, the receiver has closed so we can stop processing.
             let _: Result<(), SendError<IndexedSubChunk>> = iter.try_for_each(|item| {
-                let indexed = Self::for_each_subchunk(item, dim, &provider);
+                let indexed = service.for_each_subchunk(item, dim);
                 sender.blocking_send(indexed)
             });
         });
@@ -144,21 +820,20 @@ impl Service {
     /// Operation performed on each subchunk. This is put into a separate function because both
     /// the sequential and parallel iterator perform the exact same operations.
     #[inline]
-    fn for_each_subchunk(item: Vector<i32, 3>, dimension: Dimension, provider: &Provider) -> IndexedSubChunk {
-        let subchunk = provider.subchunk([item.x, item.y, item.z], dimension);
-
-        let subchunk = match subchunk {
-            Ok(Some(chunk)) => chunk,
-            Ok(None) => SubChunk::empty(item.y as i8),
+    fn for_each_subchunk(&self, item: Vector<i32, 3>, dimension: Dimension) -> IndexedSubChunk {
+        let data = match self.subchunk(item.clone(), dimension) {
+            Ok(subchunk) => subchunk,
             Err(e) => {
+                // Subchunks that failed to load are not cached so that loading them is retried later.
                 tracing::error!("Failed to load subchunk at {item:?}: {e:#}. Replacing it with an empty one...");
-                SubChunk::empty(item.y as i8)
+                Arc::new(SubChunk::empty(item.y as i8))
             }
         };
 
         IndexedSubChunk {
             index: RegionIndex::from(item),
-            data: subchunk,
+            dimension,
+            data,
         }
     }
 
@@ -178,6 +853,7 @@ impl Service {
     {
         let value = RuleValue::from(value);
         let old = self.gamerules.insert(TypeId::of::<R>(), value);
+        self.settings_dirty.store(true, Ordering::Relaxed);
 
         let Some(old) = old else { return R::Value::default() };
 
@@ -204,11 +880,124 @@ impl Service {
 
         (*kv.value()).into()
     }
+
+    /// Initialises the vanilla gamerules with the values stored in the level settings.
+    fn load_gamerules(&self) {
+        let settings = self.settings.read();
+
+        self.set_gamerule::<CommandBlocksEnabled>(settings.command_blocks_enabled);
+        self.set_gamerule::<CommandBlockOutput>(settings.command_block_output);
+        self.set_gamerule::<DaylightCycle>(settings.daylight_lock);
+        self.set_gamerule::<EntityDrops>(settings.entity_drops);
+        self.set_gamerule::<FireTick>(settings.fire_tick);
+        self.set_gamerule::<Insomnia>(settings.insomnia);
+        self.set_gamerule::<ImmediateRespawn>(settings.immediate_respawn);
+        self.set_gamerule::<LimitedCrafting>(settings.limited_crafting);
+        self.set_gamerule::<MobLoot>(settings.mob_loot);
+        self.set_gamerule::<MobSpawning>(settings.mob_spawning);
+        self.set_gamerule::<TileDrops>(settings.tile_drops);
+        self.set_gamerule::<WeatherCycle>(settings.weather_cycle);
+        self.set_gamerule::<DrowningDamage>(settings.drowning_damage);
+        self.set_gamerule::<FallDamage>(settings.fall_damage);
+        self.set_gamerule::<FireDamage>(settings.fire_damage);
+        self.set_gamerule::<FreezeDamage>(settings.freeze_damage);
+        self.set_gamerule::<FunctionCommandLimit>(settings.function_command_limit);
+        self.set_gamerule::<KeepInventory>(settings.keep_inventory);
+        self.set_gamerule::<MaxCommandChainLength>(settings.max_command_chain_length);
+        self.set_gamerule::<MobGriefing>(settings.mob_griefing);
+        self.set_gamerule::<NaturalRegeneration>(settings.natural_regeneration);
+        self.set_gamerule::<PlayersSleepingPercentage>(settings.sleeping_percentage);
+        self.set_gamerule::<Pvp>(settings.pvp);
+        self.set_gamerule::<RandomTickSpeed>(settings.random_tick_speed);
+        self.set_gamerule::<RecipesUnlock>(settings.recipes_unlock);
+        self.set_gamerule::<RespawnBlocksExplode>(settings.respawn_blocks_explode);
+        self.set_gamerule::<SendCommandFeedback>(settings.send_command_feedback);
+        self.set_gamerule::<ShowBorderEffect>(settings.show_border_effect);
+        self.set_gamerule::<ShowCoordinates>(settings.show_coordinates);
+        self.set_gamerule::<ShowDeathMessages>(settings.show_death_messages);
+        self.set_gamerule::<ShowTags>(settings.show_tags);
+        self.set_gamerule::<SpawnRadius>(settings.spawn_radius);
+        self.set_gamerule::<TntExplodes>(settings.tnt_explodes);
+    }
+
+    /// Copies the current values of the vanilla gamerules into the level settings.
+    fn store_gamerules(&self, settings: &mut LevelSettings) {
+        settings.command_blocks_enabled = self.gamerule::<CommandBlocksEnabled>();
+        settings.command_block_output = self.gamerule::<CommandBlockOutput>();
+        settings.daylight_lock = self.gamerule::<DaylightCycle>();
+        settings.entity_drops = self.gamerule::<EntityDrops>();
+        settings.fire_tick = self.gamerule::<FireTick>();
+        settings.insomnia = self.gamerule::<Insomnia>();
+        settings.immediate_respawn = self.gamerule::<ImmediateRespawn>();
+        settings.limited_crafting = self.gamerule::<LimitedCrafting>();
+        settings.mob_loot = self.gamerule::<MobLoot>();
+        settings.mob_spawning = self.gamerule::<MobSpawning>();
+        settings.tile_drops = self.gamerule::<TileDrops>();
+        settings.weather_cycle = self.gamerule::<WeatherCycle>();
+        settings.drowning_damage = self.gamerule::<DrowningDamage>();
+        settings.fall_damage = self.gamerule::<FallDamage>();
+        settings.fire_damage = self.gamerule::<FireDamage>();
+        settings.freeze_damage = self.gamerule::<FreezeDamage>();
+        settings.function_command_limit = self.gamerule::<FunctionCommandLimit>();
+        settings.keep_inventory = self.gamerule::<KeepInventory>();
+        settings.max_command_chain_length = self.gamerule::<MaxCommandChainLength>();
+        settings.mob_griefing = self.gamerule::<MobGriefing>();
+        settings.natural_regeneration = self.gamerule::<NaturalRegeneration>();
+        settings.sleeping_percentage = self.gamerule::<PlayersSleepingPercentage>();
+        settings.pvp = self.gamerule::<Pvp>();
+        settings.random_tick_speed = self.gamerule::<RandomTickSpeed>();
+        settings.recipes_unlock = self.gamerule::<RecipesUnlock>();
+        settings.respawn_blocks_explode = self.gamerule::<RespawnBlocksExplode>();
+        settings.send_command_feedback = self.gamerule::<SendCommandFeedback>();
+        settings.show_border_effect = self.gamerule::<ShowBorderEffect>();
+        settings.show_coordinates = self.gamerule::<ShowCoordinates>();
+        settings.show_death_messages = self.gamerule::<ShowDeathMessages>();
+        settings.show_tags = self.gamerule::<ShowTags>();
+        settings.spawn_radius = self.gamerule::<SpawnRadius>();
+        settings.tnt_explodes = self.gamerule::<TntExplodes>();
+    }
+
+    /// Returns the current values of all vanilla gamerules that are known to the client.
+    pub fn game_rules(&self) -> Vec<GameRule> {
+        vec![
+            GameRule::CommandBlocksEnabled(self.gamerule::<CommandBlocksEnabled>()),
+            GameRule::CommandBlockOutput(self.gamerule::<CommandBlockOutput>()),
+            GameRule::DaylightCycle(self.gamerule::<DaylightCycle>()),
+            GameRule::EntityDrops(self.gamerule::<EntityDrops>()),
+            GameRule::FireTick(self.gamerule::<FireTick>()),
+            GameRule::Insomnia(self.gamerule::<Insomnia>()),
+            GameRule::ImmediateRespawn(self.gamerule::<ImmediateRespawn>()),
+            GameRule::MobLoot(self.gamerule::<MobLoot>()),
+            GameRule::MobSpawning(self.gamerule::<MobSpawning>()),
+            GameRule::TileDrops(self.gamerule::<TileDrops>()),
+            GameRule::WeatherCycle(self.gamerule::<WeatherCycle>()),
+            GameRule::DrowningDamage(self.gamerule::<DrowningDamage>()),
+            GameRule::FallDamage(self.gamerule::<FallDamage>()),
+            GameRule::FireDamage(self.gamerule::<FireDamage>()),
+            GameRule::FreezeDamage(self.gamerule::<FreezeDamage>()),
+            GameRule::FunctionCommandLimit(self.gamerule::<FunctionCommandLimit>()),
+            GameRule::KeepInventory(self.gamerule::<KeepInventory>()),
+            GameRule::MaxCommandChainLength(self.gamerule::<MaxCommandChainLength>()),
+            GameRule::MobGriefing(self.gamerule::<MobGriefing>()),
+            GameRule::NaturalRegeneration(self.gamerule::<NaturalRegeneration>()),
+            GameRule::Pvp(self.gamerule::<Pvp>()),
+            GameRule::RandomTickSpeed(self.gamerule::<RandomTickSpeed>()),
+            GameRule::RespawnBlocksExplode(self.gamerule::<RespawnBlocksExplode>()),
+            GameRule::SendCommandFeedback(self.gamerule::<SendCommandFeedback>()),
+            GameRule::ShowBorderEffect(self.gamerule::<ShowBorderEffect>()),
+            GameRule::ShowCoordinates(self.gamerule::<ShowCoordinates>()),
+            GameRule::ShowDeathMessages(self.gamerule::<ShowDeathMessages>()),
+            GameRule::ShowTags(self.gamerule::<ShowTags>()),
+            GameRule::SpawnRadius(self.gamerule::<SpawnRadius>()),
+            GameRule::TntExplodes(self.gamerule::<TntExplodes>()),
+        ]
+    }
 }
 
 impl Joinable for Service {
     async fn join(&self) -> anyhow::Result<()> {
         self.collector.join().await?;
+        self.shutdown_token.cancelled().await;
 
         Ok(())
     }
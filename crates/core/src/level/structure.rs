@@ -0,0 +1,145 @@
+//! Extracts and places structures while the server is running.
+
+use std::sync::Arc;
+
+use level::{Structure, StructureMirror, StructureRotation, SubChunk};
+use proto::bedrock::{BlockActorData, UpdateBlock, UpdateBlockFlags};
+use proto::types::Dimension;
+use util::{BlockPosition, Vector};
+
+use super::generator::subchunk_range;
+use super::io::stream::RegionIndex;
+use super::Service;
+
+/// Placements that change more blocks than this are sent to clients by sending the affected columns again,
+/// instead of sending every block separately.
+const BLOCK_UPDATE_THRESHOLD: usize = 512;
+
+impl Service {
+    /// Copies a cuboid of blocks from the level into a structure.
+    ///
+    /// Both corners are inclusive and may be given in any order.
+    /// Blocks are read through the subchunk cache, so modifications that have not been written to disk yet are included.
+    /// Block entities and actors inside the cuboid are included as well.
+    pub fn extract_structure(&self, from: Vector<i32, 3>, to: Vector<i32, 3>, dimension: Dimension) -> anyhow::Result<Structure> {
+        let mut structure = Structure::extract(from, to, |index| self.structure_subchunk(index, dimension))?;
+
+        for column in structure.columns().collect::<Vec<_>>() {
+            structure.include_block_entities(self.block_entities(column.clone(), dimension)?);
+            structure.include_actors(self.provider.actors(column, dimension)?);
+        }
+
+        Ok(structure)
+    }
+
+    /// Places a structure in the level with its lowest corner at `origin`.
+    ///
+    /// See [`Structure::place`] for how the structure is transformed.
+    /// The modified subchunks are stored in the cache until the collector writes them to disk,
+    /// existing block entities in the affected blocks are replaced by those in the structure
+    /// and the changes are sent to every client that has the affected chunks in view.
+    /// Small placements are sent block by block, for larger ones the affected columns are sent again.
+    pub fn place_structure(
+        &self,
+        structure: &Structure,
+        origin: Vector<i32, 3>,
+        dimension: Dimension,
+        rotation: StructureRotation,
+        mirror: StructureMirror,
+    ) -> anyhow::Result<()> {
+        let instance = self.instance();
+
+        let (placement, columns, runtime_ids) = {
+            // Prevents concurrent modifications of the same subchunks from overwriting each other.
+            let _guard = self.block_lock.lock();
+
+            let mut placement = structure.place(origin, rotation, mirror, |index| {
+                if !i8::try_from(index.y).is_ok_and(|y| subchunk_range(dimension).contains(&y)) {
+                    tracing::error!("Structure block in subchunk {index:?} is outside of the world");
+                    anyhow::bail!("Structure block in subchunk {index:?} is outside of the world");
+                }
+
+                self.subchunk(index, dimension).map(Some)
+            })?;
+
+            let runtime_ids = placement
+                .palette
+                .iter()
+                .map(|entry| match instance.block_states.state(entry) {
+                    Some(runtime_id) => Ok(runtime_id),
+                    None => {
+                        tracing::error!("Attempted to place unknown block state {}", entry.name);
+                        anyhow::bail!("Attempted to place unknown block state {}", entry.name);
+                    }
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            let columns = placement.columns();
+            for (index, subchunk) in std::mem::take(&mut placement.subchunks) {
+                // The subchunk is pinned in the cache until the collector has written it to disk.
+                self.cache.insert_dirty((dimension, RegionIndex::from(index.clone())), Arc::new(subchunk));
+                // The light of the column is computed again when it is requested.
+                self.light_engine(dimension).lock().unload_column(&Vector::from([index.x, index.z]));
+            }
+
+            (placement, columns, runtime_ids)
+        };
+
+        let replaced = placement.replaced();
+        for column in &columns {
+            self.modify_block_entities(column, dimension, |entities| {
+                // Nothing has to be written if the column neither had nor gets any block entities.
+                let had_entities = !entities.is_empty();
+                placement.merge_block_entities(column, &replaced, entities);
+                had_entities || !entities.is_empty()
+            })?;
+        }
+
+        let clients = instance.clients();
+        if placement.blocks.len() > BLOCK_UPDATE_THRESHOLD {
+            // The columns include the new block entities.
+            clients.resend_columns(self, dimension, &columns);
+            return Ok(());
+        }
+
+        for block in &placement.blocks {
+            let position = &block.position;
+            clients.broadcast_to_viewers(
+                self,
+                Vector::from([position.x >> 4, position.z >> 4]),
+                dimension,
+                UpdateBlock {
+                    // Negative heights are sent as their two's complement representation.
+                    position: BlockPosition::new(position.x, position.y as u32, position.z),
+                    block_runtime_id: runtime_ids[block.block as usize],
+                    flags: UpdateBlockFlags::UpdateNetwork as u32,
+                    layer: block.layer as u32,
+                },
+            )?;
+        }
+
+        for entity in &placement.block_entities {
+            let position = &entity.position;
+            clients.broadcast_to_viewers(
+                self,
+                Vector::from([position.x >> 4, position.z >> 4]),
+                dimension,
+                BlockActorData {
+                    position: BlockPosition::new(position.x, position.y as u32, position.z),
+                    nbt: entity.to_nbt(),
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Loads a subchunk for a structure, subchunks outside of the world do not exist.
+    fn structure_subchunk(&self, index: Vector<i32, 3>, dimension: Dimension) -> anyhow::Result<Option<Arc<SubChunk>>> {
+        if !i8::try_from(index.y).is_ok_and(|y| subchunk_range(dimension).contains(&y)) {
+            return Ok(None);
+        }
+
+        self.subchunk(index, dimension).map(Some)
+    }
+}
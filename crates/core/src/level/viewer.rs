@@ -1,31 +1,38 @@
 use std::{
-    collections::HashMap,
+    collections::{hash_map::Entry, HashMap, HashSet},
     sync::{
-        atomic::{AtomicI32, AtomicU16, Ordering},
+        atomic::{AtomicI32, AtomicU16, AtomicU32, Ordering},
         Arc,
     },
 };
 
-use futures::{future, StreamExt};
-use level::SubChunk;
+use futures::future;
 use nohash_hasher::BuildNoHashHasher;
+use parking_lot::{Mutex, RwLock};
 use proto::{
-    bedrock::{HeightmapType, SubChunkEntry, SubChunkResponse, SubChunkResult},
+    bedrock::{SubChunkEntry, SubChunkResponse, SubChunkResult},
     types::Dimension,
 };
 use util::Vector;
 
 use super::io::point::PointRegion;
 use super::io::r#box::BoxRegion;
+use super::generator::subchunk_range;
 use super::net::column::ChunkColumn;
 use super::net::heightmap::Heightmap;
+use super::net::ser::NetworkChunkExt;
 use super::Service;
 
 pub type ChunkOffset = Vector<i8, 3>;
 
 pub struct Viewer {
-    pub service: Arc<Service>,
+    /// Level that this viewer is currently in.
+    level: RwLock<Arc<Service>>,
     radius: AtomicU16,
+    /// Dimension that this viewer is currently in.
+    dimension: AtomicU32,
+    /// Columns that have been sent to this viewer and are still in view.
+    sent: Mutex<HashSet<Vector<i32, 2>>>,
 
     // The current position of this viewer in chunk coordinates.
     current_x: AtomicI32,
@@ -33,26 +40,32 @@ pub struct Viewer {
 }
 
 impl Viewer {
-    pub const fn new(service: Arc<Service>) -> Viewer {
+    pub fn new(service: Arc<Service>) -> Viewer {
         Viewer {
-            service,
+            level: RwLock::new(service),
             radius: AtomicU16::new(0),
+            dimension: AtomicU32::new(Dimension::Overworld as u32),
+            sent: Mutex::new(HashSet::new()),
             current_x: AtomicI32::new(0),
             current_z: AtomicI32::new(0),
         }
     }
 
     /// Updates the position of this viewer.
-    pub fn update_position(&self, position: Vector<f32, 2>) {
+    ///
+    /// Returns whether the viewer moved to a different chunk.
+    pub fn update_position(&self, position: Vector<f32, 2>) -> bool {
         // Transform player coordinates to chunk coordinates.
-        let chunk_x = (position.x / 16.0).ceil() as i32;
-        let chunk_z = (position.y / 16.0).ceil() as i32;
+        let chunk_x = (position.x / 16.0).floor() as i32;
+        let chunk_z = (position.y / 16.0).floor() as i32;
 
-        self.current_x.store(chunk_x, Ordering::Relaxed);
-        self.current_z.store(chunk_z, Ordering::Relaxed);
+        let old_x = self.current_x.swap(chunk_x, Ordering::Relaxed);
+        let old_z = self.current_z.swap(chunk_z, Ordering::Relaxed);
 
         // Update view if required
         self.on_view_update();
+
+        old_x != chunk_x || old_z != chunk_z
     }
 
     /// Updates the render distance of this viewer
@@ -62,82 +75,174 @@ impl Viewer {
         self.on_view_update();
     }
 
-    fn create_entry(&self, base: Vector<i32, 3>, offset: ChunkOffset, full_chunk: &ChunkColumn) -> anyhow::Result<SubChunkEntry> {
-        let absolute_y = base.y + offset.y as i32;
-        let subchunk_index = full_chunk.y_to_index(absolute_y as i16);
+    /// Render distance of this viewer in chunks.
+    #[inline]
+    pub fn radius(&self) -> u16 {
+        self.radius.load(Ordering::Relaxed)
+    }
+
+    /// Current position of this viewer in chunk coordinates.
+    #[inline]
+    pub fn position(&self) -> Vector<i32, 2> {
+        Vector::from([self.current_x.load(Ordering::Relaxed), self.current_z.load(Ordering::Relaxed)])
+    }
+
+    /// Level that this viewer is currently in.
+    #[inline]
+    pub fn level(&self) -> Arc<Service> {
+        Arc::clone(&self.level.read())
+    }
+
+    /// Moves this viewer to another level.
+    ///
+    /// All columns are forgotten, so that the new level is streamed from scratch.
+    pub fn set_level(&self, level: Arc<Service>) {
+        *self.level.write() = level;
+        self.sent.lock().clear();
+    }
+
+    /// Whether this viewer is currently in the given level.
+    pub fn in_level(&self, level: &Service) -> bool {
+        std::ptr::eq(Arc::as_ptr(&self.level.read()), level)
+    }
+
+    /// Dimension that this viewer is currently in.
+    pub fn dimension(&self) -> Dimension {
+        Dimension::try_from(self.dimension.load(Ordering::Relaxed)).unwrap_or(Dimension::Overworld)
+    }
+
+    /// Moves this viewer to another dimension.
+    ///
+    /// All columns are forgotten, so that the new dimension is streamed from scratch.
+    pub fn set_dimension(&self, dimension: Dimension) {
+        self.dimension.store(dimension as u32, Ordering::Relaxed);
+        self.sent.lock().clear();
+    }
+
+    /// Whether the given chunk is within the render distance of this viewer.
+    pub fn in_view(&self, chunk: &Vector<i32, 2>, dimension: Dimension) -> bool {
+        if dimension != self.dimension() {
+            return false;
+        }
+
+        let radius = i64::from(self.radius.load(Ordering::Relaxed));
+        let dx = i64::from(chunk.x) - i64::from(self.current_x.load(Ordering::Relaxed));
+        let dz = i64::from(chunk.y) - i64::from(self.current_z.load(Ordering::Relaxed));
+
+        dx * dx + dz * dz <= radius * radius
+    }
+
+    /// Returns the columns in view that have not been sent yet, ordered from nearest to furthest.
+    ///
+    /// The returned columns are marked as sent and columns that are no longer in view are forgotten,
+    /// so that they are sent again when they come back into view.
+    pub fn pending_columns(&self) -> Vec<Vector<i32, 2>> {
+        let dimension = self.dimension();
+        let center = self.position();
+        let radius = i32::from(self.radius());
+
+        let mut sent = self.sent.lock();
+        sent.retain(|column| self.in_view(column, dimension));
 
-        let heightmap = Heightmap::new(subchunk_index, full_chunk);
-        let entry = SubChunkEntry {
-            result: SubChunkResult::Success,
-            offset,
-            heightmap_type: heightmap.map_type,
-            heightmap: heightmap.data,
-            blob_hash: todo!(),
-            payload: todo!(),
-        };
+        let mut pending = Vec::new();
+        for x in center.x - radius..=center.x + radius {
+            for z in center.y - radius..=center.y + radius {
+                let column = Vector::from([x, z]);
+                if self.in_view(&column, dimension) && sent.insert(column.clone()) {
+                    pending.push(column);
+                }
+            }
+        }
 
-        Ok(entry)
+        let distance = |column: &Vector<i32, 2>| (column.x - center.x).pow(2) + (column.y - center.y).pow(2);
+        pending.sort_by_key(distance);
+        pending
     }
 
+    /// Marks the given columns as not sent, so that they are sent again by the next stream.
+    ///
+    /// Returns whether any of the columns had been sent to this viewer.
+    pub fn forget_columns(&self, columns: &HashSet<Vector<i32, 2>>) -> bool {
+        let mut sent = self.sent.lock();
+        columns.iter().fold(false, |forgotten, column| sent.remove(column) || forgotten)
+    }
+
+    /// Loads the requested subchunks and creates a response for the client.
+    ///
+    /// The offsets are relative to `base`. Every column that contains a requested subchunk
+    /// is loaded entirely, so that the heightmaps of the subchunks can be computed.
     pub fn load_offsets(&self, base: Vector<i32, 3>, offsets: &[ChunkOffset], dimension: Dimension) -> anyhow::Result<SubChunkResponse> {
+        let level = self.level();
+        let instance = level.instance();
+
         // Group all subchunks into chunk columns,
         // with the map indices being two concatenated 32-bit integers representing X and Z coords.
         let mut col_map: HashMap<i64, ChunkColumn, BuildNoHashHasher<i64>> = HashMap::with_hasher(std::hash::BuildHasherDefault::default());
+
+        let mut entries = Vec::with_capacity(offsets.len());
         for offset in offsets {
             let abs_coord: Vector<i32, 3> = (base.x + offset.x as i32, base.y + offset.y as i32, base.z + offset.z as i32).into();
 
-            let xz = (abs_coord.x as i64) | (abs_coord.z as i64) >> 32;
-            let col = col_map.entry(xz).or_insert_with(ChunkColumn::empty);
+            let xz = (abs_coord.x as i64) << 32 | (abs_coord.z as u32 as i64);
+            let col = match col_map.entry(xz) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(self.load_column(Vector::from([abs_coord.x, abs_coord.z]), dimension)?),
+            };
 
-            match self.load(abs_coord.clone(), dimension) {
-                Ok(opt) => {
-                    col.subchunks.push((offset.clone(), opt));
-                }
-                Err(e) => {
-                    tracing::error!("Failed to load subchunk at {abs_coord:?}: {e}");
-                    col.subchunks.push((offset.clone(), None));
-                }
-            }
-        }
+            let Some(sub) = i8::try_from(abs_coord.y).ok().and_then(|y| col.subchunk(y)) else {
+                entries.push(SubChunkEntry {
+                    result: SubChunkResult::OutOfBounds,
+                    offset: offset.clone(),
+                    ..Default::default()
+                });
+                continue;
+            };
 
-        // Generate all heightmaps now that the columns are finalised.
-        // TODO: Could maybe benefit from parallelisation depending on the offset count?
-        col_map.values_mut().for_each(ChunkColumn::generate_heightmap);
+            let subchunk_idx = col.y_to_index(abs_coord.y as i16 * 16);
+            let heightmap = Heightmap::new(subchunk_idx, col);
 
-        let mut entries = Vec::with_capacity(offsets.len());
-        for col in col_map.values() {
-            for (offset, opt) in &col.subchunks {
-                if let Some(sub) = opt {
-                    let subchunk_idx = base.y + offset.y as i32 - col.range.start as i32;
-                    dbg!(subchunk_idx);
-                    let heightmap = Heightmap::new(subchunk_idx as u16, col);
-                    dbg!(&heightmap);
-
-                    let payload = todo!();
-                    entries.push(SubChunkEntry {
-                        offset: offset.clone(),
-                        result: SubChunkResult::Success,
-                        heightmap_type: heightmap.map_type,
-                        heightmap: heightmap.data,
-                        blob_hash: 0,
-                        payload,
-                    });
-                } else {
-                    entries.push(SubChunkEntry {
-                        result: SubChunkResult::AllAir,
-                        offset: offset.clone(),
-                        ..Default::default()
-                    });
-                }
+            if sub.is_empty() {
+                entries.push(SubChunkEntry {
+                    result: SubChunkResult::AllAir,
+                    offset: offset.clone(),
+                    heightmap_type: heightmap.map_type,
+                    heightmap: heightmap.data,
+                    ..Default::default()
+                });
+            } else {
+                entries.push(SubChunkEntry {
+                    offset: offset.clone(),
+                    result: SubChunkResult::Success,
+                    heightmap_type: heightmap.map_type,
+                    heightmap: heightmap.data,
+                    blob_hash: 0,
+                    payload: sub.serialize_network(&instance.block_states)?,
+                });
             }
         }
 
-        todo!()
+        Ok(SubChunkResponse {
+            cache_enabled: false,
+            dimension,
+            position: base,
+            entries,
+        })
     }
 
-    #[inline]
-    pub fn load(&self, pos: Vector<i32, 3>, dimension: Dimension) -> anyhow::Result<Option<SubChunk>> {
-        self.service.provider.subchunk(pos, dimension)
+    /// Loads every subchunk and block entity in the given column and computes its heightmap.
+    pub fn load_column(&self, coordinates: Vector<i32, 2>, dimension: Dimension) -> anyhow::Result<ChunkColumn> {
+        let level = self.level();
+        let mut column = ChunkColumn::new(dimension);
+        for y in subchunk_range(dimension) {
+            let subchunk = level.subchunk([coordinates.x, i32::from(y), coordinates.y], dimension)?;
+            column.insert(subchunk);
+        }
+
+        column.biomes = level.biomes(coordinates.clone(), dimension)?;
+        column.block_entities = level.block_entities(coordinates, dimension)?;
+        column.generate_heightmap();
+
+        Ok(column)
     }
 
     fn on_view_update(&self) {
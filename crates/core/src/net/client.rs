@@ -12,7 +12,7 @@ use flate2::write::DeflateEncoder;
 use parking_lot::RwLock;
 use raknet::{BroadcastPacket, Frame, FrameBatch, RakNetClient, RakNetCommand, SendConfig, DEFAULT_SEND_CONFIG};
 use tokio::sync::{broadcast, mpsc};
-use proto::bedrock::{Animate, CacheStatus, ChunkRadiusRequest, ClientToServerHandshake, CommandPermissionLevel, CommandRequest, CompressionAlgorithm, ConnectedPacket, ContainerClose, Disconnect, DisconnectReason, FormResponseData, GameMode, Header, Interact, InventoryTransaction, Login, MobEquipment, MovePlayer, PermissionLevel, PlayerAction, PlayerAuthInput, RequestAbility, RequestNetworkSettings, ResourcePackClientResponse, SetInventoryOptions, SetLocalPlayerAsInitialized, SettingsCommand, Skin, TextMessage, TickSync, UpdateSkin, ViolationWarning, CONNECTED_PACKET_ID};
+use proto::bedrock::{Animate, BlockActorData, CacheStatus, ChunkRadiusRequest, ClientToServerHandshake, CommandPermissionLevel, CommandRequest, CompressionAlgorithm, ConnectedPacket, ContainerClose, Disconnect, DisconnectReason, FormResponseData, GameMode, Header, Interact, InventoryTransaction, Login, MobEquipment, MovePlayer, PermissionLevel, PlayerAction, PlayerAuthInput, RequestAbility, RequestNetworkSettings, ResourcePackChunkRequest, ResourcePackClientResponse, SetInventoryOptions, SetLocalPlayerAsInitialized, SettingsCommand, Skin, TextMessage, TickSync, UpdateSkin, ViolationWarning, CONNECTED_PACKET_ID};
 use proto::crypto::{Encryptor, BedrockIdentity, BedrockClientInfo};
 use proto::uuid::Uuid;
 
@@ -394,7 +394,9 @@ impl BedrockClient {
         packet.drain(0..(start_len - remaining));
         
         let expected = self.expected();
-        if expected != u32::MAX && header.id != expected {
+        // Pack chunks are requested while the server is still waiting for the client to finish the pack exchange.
+        let downloading = expected == ResourcePackClientResponse::ID && header.id == ResourcePackChunkRequest::ID;
+        if expected != u32::MAX && header.id != expected && !downloading {
             // Server received an unexpected packet.
             tracing::warn!(
                 "Client sent unexpected packet while logging in (expected {:#04x}, got {:#04x})",
@@ -422,6 +424,9 @@ impl BedrockClient {
                 ResourcePackClientResponse::ID => {
                     this.handle_resource_client_response(packet).context("while handling ResourcePackClientResponse")
                 }
+                ResourcePackChunkRequest::ID => {
+                    this.handle_resource_chunk_request(packet).context("while handling ResourcePackChunkRequest")
+                }
                 ViolationWarning::ID => this.handle_violation_warning(packet).context("while handling ViolationWarning"),
                 ChunkRadiusRequest::ID => this.handle_chunk_radius_request(packet).context("while handling ChunkRadiusRequest"),
                 Interact::ID => this.handle_interaction(packet).context("while handling Interact"),
@@ -431,6 +436,7 @@ impl BedrockClient {
                 }
                 MovePlayer::ID => this.handle_move_player(packet),
                 PlayerAction::ID => this.handle_player_action(packet),
+                BlockActorData::ID => this.handle_block_actor_data(packet).context("while handling BlockActorData"),
                 RequestAbility::ID => this.handle_ability_request(packet),
                 Animate::ID => this.handle_animation(packet),
                 // Command request does not return a result because it does not fail.
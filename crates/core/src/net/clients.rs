@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::net::SocketAddr;
 use std::sync::{Arc, OnceLock, Weak};
 use std::time::Duration;
@@ -8,7 +9,8 @@ use dashmap::DashMap;
 use proto::uuid::Uuid;
 use raknet::{BroadcastPacket, RakNetCreateDescription, RakNetClient};
 use proto::bedrock::{ConnectedPacket, Disconnect, DisconnectReason};
-use util::{RVec, Joinable, Serialize};
+use proto::types::Dimension;
+use util::{RVec, Joinable, Serialize, Vector};
 
 use tokio::sync::{broadcast, mpsc};
 use tokio::task::{JoinHandle, JoinSet};
@@ -192,6 +194,42 @@ impl Clients {
         Ok(())
     }
 
+    /// Sends the given packet to every client in the given level that has the given chunk in view.
+    pub fn broadcast_to_viewers<T>(&self, level: &crate::level::Service, chunk: Vector<i32, 2>, dimension: Dimension, packet: T) -> anyhow::Result<()>
+    where
+        T: ConnectedPacket + Serialize + Clone
+    {
+        for client in self.connected_map.iter() {
+            let client = &client.value().state;
+            if !client.viewer.in_level(level) || !client.viewer.in_view(&chunk, dimension) {
+                continue
+            }
+
+            if let Err(err) = client.send(packet.clone()) {
+                tracing::error!("Failed to send packet to viewer {}: {err:#}", client.raknet.address);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sends the given columns again to every client in the given level that has them in view.
+    ///
+    /// This is used for large modifications, for which sending every changed block separately would be slower.
+    pub fn resend_columns(&self, level: &crate::level::Service, dimension: Dimension, columns: &HashSet<Vector<i32, 2>>) {
+        for client in self.connected_map.iter() {
+            let client = &client.value().state;
+            if !client.viewer.in_level(level) || client.viewer.dimension() != dimension {
+                continue
+            }
+
+            // Columns that the client has not received yet are sent with the changes included anyway.
+            if client.viewer.forget_columns(columns) {
+                client.stream_chunks();
+            }
+        }
+    }
+
     /// How many clients are currently in the process of logging in.
     #[inline]
     pub fn total_connecting(&self) -> usize {
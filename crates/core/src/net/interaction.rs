@@ -1,7 +1,8 @@
 use std::sync::atomic::Ordering;
 
-use proto::bedrock::{ABILITY_FLYING, AbilityData, AbilityLayer, AbilityType, ContainerClose, ContainerOpen, ContainerType, GameMode, Interact, InteractAction, INVENTORY_WINDOW_ID, MovePlayer, PlayerAction, PlayerActionType, UpdateAbilities, ABILITY_FLAG_END};
-use util::{RVec, Deserialize};
+use proto::bedrock::{ABILITY_FLYING, BlockActorData, AbilityData, AbilityLayer, AbilityType, ContainerClose, ContainerOpen, ContainerType, GameMode, Interact, InteractAction, INVENTORY_WINDOW_ID, MovePlayer, PlayerAction, PlayerActionType, UpdateAbilities, ABILITY_FLAG_END};
+use level::BlockEntity;
+use util::{RVec, Deserialize, Vector};
 
 use super::BedrockClient;
 
@@ -56,10 +57,48 @@ impl BedrockClient {
         match request.action {
             PlayerActionType::StartFlying => self.action_start_flying(request),
             PlayerActionType::StopFlying => self.action_stop_flying(request),
+            PlayerActionType::DimensionChangeAcknowledgement => {
+                tracing::debug!("Client finished changing dimension");
+                Ok(())
+            }
             _ => Ok(())
         }
     }
 
+    /// Handles a [`BlockActorData`] packet.
+    ///
+    /// The client sends this when it modifies a block entity, such as when a sign is edited.
+    pub fn handle_block_actor_data(&self, packet: RVec) -> anyhow::Result<()> {
+        let request = BlockActorData::deserialize(packet.as_ref())?;
+
+        // Negative heights are sent as their two's complement representation.
+        let position = Vector::from([request.position.x, request.position.y as i32, request.position.z]);
+        let dimension = self.viewer.dimension();
+        if !self.viewer.in_view(&Vector::from([position.x >> 4, position.z >> 4]), dimension) {
+            tracing::warn!("Client attempted to modify block entity outside of its view");
+            anyhow::bail!("Client attempted to modify block entity outside of its view");
+        }
+
+        let entity = BlockEntity::from_nbt(request.nbt)?;
+        if entity.position != position {
+            anyhow::bail!("Block entity position does not match packet position");
+        }
+
+        // Clients can only edit the text of existing signs, the rest of the data is kept.
+        let service = self.viewer.level();
+        let Some(mut existing) = service.block_entity(position.clone(), dimension)? else {
+            tracing::warn!("Client attempted to modify non-existent block entity {}", entity.id);
+            anyhow::bail!("Client attempted to modify non-existent block entity {}", entity.id);
+        };
+
+        if let Err(err) = existing.apply_client_edit(&entity) {
+            tracing::warn!("Rejected block entity edit at {position:?}: {err:#}");
+            return Err(err);
+        }
+
+        service.set_block_entity(existing, dimension)
+    }
+
     // Actions
     // ======================================================================================
 
@@ -1,24 +1,52 @@
-use level::PaletteEntry;
+use level::{Abilities, LevelSettings, PaletteEntry};
 use proto::bedrock::{
+    AbilityData, AbilityLayer, AbilityType, UpdateAbilities, ABILITY_ATTACK_MOBS, ABILITY_ATTACK_PLAYERS, ABILITY_BUILD,
+    ABILITY_DOORS_AND_SWITCHES, ABILITY_FLAG_END, ABILITY_FLYING, ABILITY_INSTANT_BUILD, ABILITY_INVULNERABLE, ABILITY_LIGHTNING,
+    ABILITY_MAYFLY, ABILITY_MINE, ABILITY_OPEN_CONTAINERS, ABILITY_OPERATOR_COMMANDS, ABILITY_TELEPORT,
     BiomeDefinitionList, BroadcastIntent, CacheStatus, ChatRestrictionLevel, ChunkRadiusReply, ChunkRadiusRequest, ClientToServerHandshake,
-    ConnectedPacket, CreativeContent, Difficulty, DisconnectReason, EditorWorldType, ExperimentData, GameMode, GameRule, HeightmapType,
-    InventoryTransaction, ItemInstance, LevelChunk, Login, NetworkChunkPublisherUpdate, NetworkSettings, PermissionLevel, PlayStatus,
-    PlayerMovementSettings, PlayerMovementType, PropertyData, RequestNetworkSettings, ResourcePackClientResponse, ResourcePackStack,
-    ResourcePacksInfo, ServerToClientHandshake, SetLocalPlayerAsInitialized, SpawnBiomeType, StartGame, Status, SubChunkEntry, SubChunkRequestMode,
+    ConnectedPacket, CreativeContent, Difficulty, DisconnectReason, EditorWorldType, ExperimentData, GameMode, HeightmapType,
+    InventoryTransaction, ItemInstance, LevelChunk, Login, NetworkChunkPublisherUpdate, NetworkSettings, PlayStatus,
+    PlayerMovementSettings, PlayerMovementType, PropertyData, RequestNetworkSettings, ResourcePackChunkData, ResourcePackChunkRequest,
+    ResourcePackClientResponse, ResourcePackDataInfo, ResourcePackStack, ResourcePackStatus, ServerToClientHandshake, SetLocalPlayerAsInitialized, SpawnBiomeType, StartGame, Status, SubChunkEntry, SubChunkRequestMode,
     SubChunkResponse, SubChunkResult, TextData, TextMessage, TransactionAction, TransactionSourceType, TransactionType, UpdateBlock,
     UpdateBlockFlags, ViolationWarning, WindowId, WorldGenerator, CLIENT_VERSION_STRING, PROTOCOL_VERSION,
 };
-use proto::crypto::Encryptor;
+use proto::crypto::{ChainError, Encryptor};
 use proto::types::Dimension;
 use std::collections::HashMap;
 use std::sync::atomic::Ordering;
+use std::sync::Arc;
 
 use util::{BlockPosition, Deserialize, RVec, Vector};
 
+use crate::level::rule::DaylightCycle;
 use crate::net::PlayerData;
+use crate::pack::{PackKind, PACK_CHUNK_SIZE};
 
 use super::BedrockClient;
 
+/// Converts the default abilities stored in the level settings into ability flags.
+fn ability_flags(abilities: &Abilities, game_mode: GameMode) -> u32 {
+    let flags = [
+        (abilities.build, ABILITY_BUILD),
+        (abilities.mine, ABILITY_MINE),
+        (abilities.doors_and_switches, ABILITY_DOORS_AND_SWITCHES),
+        (abilities.open_containers, ABILITY_OPEN_CONTAINERS),
+        (abilities.attack_players, ABILITY_ATTACK_PLAYERS),
+        (abilities.attack_mobs, ABILITY_ATTACK_MOBS),
+        (abilities.op, ABILITY_OPERATOR_COMMANDS),
+        (abilities.teleport, ABILITY_TELEPORT),
+        (abilities.invulnerable, ABILITY_INVULNERABLE),
+        (abilities.flying, ABILITY_FLYING),
+        // Creative players are always allowed to fly.
+        (abilities.mayfly || game_mode == GameMode::Creative, ABILITY_MAYFLY),
+        (abilities.instant_build, ABILITY_INSTANT_BUILD),
+        (abilities.lightning, ABILITY_LIGHTNING),
+    ];
+
+    flags.iter().filter(|(enabled, _)| *enabled).fold(0, |acc, (_, flag)| acc | flag)
+}
+
 impl BedrockClient {
     /// Handles a [`CacheStatus`] packet.
     /// This stores the result in the [`Session::cache_support`] field.
@@ -73,10 +101,11 @@ impl BedrockClient {
 
         tracing::debug!("Player fully initialised");
 
-        self.send(NetworkChunkPublisherUpdate { position: (0, 0, 0).into(), radius: 12 })?;
-
-        let res = self.viewer.load_offsets((0, 0, 0).into(), &[(0, 0, 0).into()], Dimension::Overworld)?;
-        tracing::debug!("res {res:?}");
+        let center = self.viewer.position();
+        self.send(NetworkChunkPublisherUpdate {
+            position: Vector::from([center.x * 16 + 8, 0, center.y * 16 + 8]),
+            radius: u32::from(self.viewer.radius()) * 16,
+        })?;
 
         // self.send(LevelChunk {
         //     blob_hashes: None,
@@ -124,7 +153,11 @@ impl BedrockClient {
             // let level_chunk = self.level_manager.request_biomes(Vector::from([0, 0]), Dimension::Overworld)?;
             // dbg!(level_chunk);
 
-            tracing::info!("{} has joined the server", self.name()?);
+            if self.identity()?.authenticated {
+                tracing::info!("{} has joined the server", self.name()?);
+            } else {
+                tracing::info!("{} has joined the server (unauthenticated)", self.name()?);
+            }
             self.broadcast(TextMessage {
                 data: TextData::Translation {
                     parameters: vec![&format!("§e{}", self.name()?)],
@@ -153,7 +186,7 @@ impl BedrockClient {
             username = %self.name().unwrap_or("<unknown>")
         )
     )]
-    pub fn handle_chunk_radius_request(&self, packet: RVec) -> anyhow::Result<()> {
+    pub fn handle_chunk_radius_request(self: &Arc<Self>, packet: RVec) -> anyhow::Result<()> {
         let request = ChunkRadiusRequest::deserialize(packet.as_ref())?;
 
         // FIXME: Use render distance configured with builder instead of SERVER_CONFIG global.
@@ -163,6 +196,7 @@ impl BedrockClient {
         self.send(ChunkRadiusReply { allowed_radius })?;
 
         self.viewer.update_radius(allowed_radius as u16);
+        self.stream_chunks();
 
         // self.player().viewer.set_radius(allowed_radius);
 
@@ -170,87 +204,207 @@ impl BedrockClient {
     }
 
     /// Handles a [`ResourcePackClientResponse`] packet.
+    #[tracing::instrument(
+        skip_all,
+        name = "BedrockUser::handle_resource_client_response",
+        fields(
+            username = %self.name().unwrap_or("<unknown>")
+        )
+    )]
     pub fn handle_resource_client_response(&self, packet: RVec) -> anyhow::Result<()> {
-        self.expected.store(u32::MAX, Ordering::SeqCst);
+        let request = ResourcePackClientResponse::deserialize(packet.as_ref())?;
+        tracing::debug!("Received resource pack client response: {:?}", request.status);
+
+        let packs = &self.instance().resource_packs;
+        let level = self.viewer.level();
+        let settings = level.settings();
+        let experiments = Self::experiments(&settings);
+
+        match request.status {
+            ResourcePackStatus::None => Ok(()),
+            ResourcePackStatus::Refused => {
+                if packs.required() {
+                    return self.kick_with_reason(
+                        "You must accept the resource packs to join this server",
+                        DisconnectReason::ResourcePackProblem,
+                    );
+                }
+
+                // The client does not want the packs, continue without them.
+                self.send(ResourcePackStack {
+                    forced_to_accept: false,
+                    resource_packs: &[],
+                    behavior_packs: &[],
+                    game_version: CLIENT_VERSION_STRING,
+                    experiments: &experiments,
+                    experiments_previously_toggled: settings.experiments.experiments_ever_used,
+                    includes_editor_packs: false,
+                })
+            }
+            ResourcePackStatus::SendPacks => {
+                for id in request.pack_ids {
+                    let Some(pack) = packs.get(id) else {
+                        tracing::warn!("Client requested unknown pack {id}");
+                        return self.kick_with_reason("Requested unknown resource pack", DisconnectReason::ResourcePackProblem);
+                    };
+
+                    self.send(ResourcePackDataInfo {
+                        pack_id: pack.id(),
+                        max_chunk_size: PACK_CHUNK_SIZE as u32,
+                        chunk_count: pack.chunk_count(),
+                        pack_size: pack.size(),
+                        hash: pack.hash(),
+                        premium: false,
+                        pack_type: pack.pack_type(),
+                    })?;
+                }
+
+                Ok(())
+            }
+            ResourcePackStatus::HaveAllPacks => {
+                let resource_packs = packs.stack(PackKind::Resources);
+                let behavior_packs = packs.stack(PackKind::Behavior);
+
+                self.send(ResourcePackStack {
+                    forced_to_accept: packs.required() && !packs.is_empty(),
+                    resource_packs: &resource_packs,
+                    behavior_packs: &behavior_packs,
+                    game_version: CLIENT_VERSION_STRING,
+                    experiments: &experiments,
+                    experiments_previously_toggled: settings.experiments.experiments_ever_used,
+                    includes_editor_packs: false,
+                })
+            }
+            ResourcePackStatus::Completed => {
+                // Starting the game reads the settings again.
+                drop(settings);
+
+                self.expected.store(u32::MAX, Ordering::SeqCst);
+                self.start_game()
+            }
+        }
+    }
+
+    /// Handles a [`ResourcePackChunkRequest`] packet.
+    #[tracing::instrument(
+        skip_all,
+        name = "BedrockUser::handle_resource_chunk_request",
+        fields(
+            username = %self.name().unwrap_or("<unknown>")
+        )
+    )]
+    pub fn handle_resource_chunk_request(&self, packet: RVec) -> anyhow::Result<()> {
+        let request = ResourcePackChunkRequest::deserialize(packet.as_ref())?;
 
-        let _request = ResourcePackClientResponse::deserialize(packet.as_ref())?;
-        tracing::debug!("Received resource pack client response");
+        let instance = self.instance();
+        let chunk = instance.resource_packs.get(request.pack_id).and_then(|pack| {
+            pack.chunk(request.chunk_index).map(|(offset, data)| (pack, offset, data))
+        });
 
-        // TODO: Implement resource packs.
+        let Some((pack, offset, data)) = chunk else {
+            tracing::warn!("Client requested invalid chunk {} of pack {}", request.chunk_index, request.pack_id);
+            return self.kick_with_reason("Requested invalid resource pack chunk", DisconnectReason::ResourcePackProblem);
+        };
 
+        self.send(ResourcePackChunkData {
+            pack_id: pack.id(),
+            chunk_index: request.chunk_index,
+            offset,
+            data,
+        })
+    }
+
+    /// Returns the experiments that are enabled in the level.
+    fn experiments(settings: &LevelSettings) -> Vec<ExperimentData<'_>> {
+        settings.experiments.enabled().map(|name| ExperimentData { name, enabled: true }).collect()
+    }
+
+    /// Sends the packets required to let the client spawn into the world.
+    fn start_game(&self) -> anyhow::Result<()> {
+        let instance = self.instance();
+        let level = self.viewer.level();
+        let position = level.spawn_position();
+        let spawn_height = level.spawn_height();
+        let settings = level.settings();
+        let player = self.player()?;
+
+        let world_game_mode = GameMode::try_from(settings.game_mode).unwrap_or(GameMode::Survival);
+        let game_mode = if settings.force_game_mode { world_game_mode } else { player.gamemode() };
+
+        // Negative heights are sent as their two's complement representation.
+        let world_spawn = BlockPosition::new(settings.spawn_x, spawn_height as u32, settings.spawn_z);
+        // The time is only locked when the daylight cycle has been disabled.
+        let day_cycle_lock_time = if level.gamerule::<DaylightCycle>() { 0 } else { settings.time as i32 };
+        let experiments = Self::experiments(&settings);
+        self.viewer.update_position(Vector::from([position.x, position.z]));
+
+        let game_rules = level.game_rules();
         let start_game = StartGame {
-            entity_id: 1,
-            runtime_id: 1,
-            game_mode: self.player()?.gamemode(),
-            position: Vector::from([0.0, 6.0, 0.0]),
+            entity_id: player.runtime_id() as i64,
+            runtime_id: player.runtime_id(),
+            game_mode,
+            position,
             rotation: Vector::from([0.0, 0.0]),
-            world_seed: 0,
+            world_seed: settings.random_seed as u64,
             spawn_biome_type: SpawnBiomeType::Default,
             custom_biome_name: "plains",
             dimension: Dimension::Overworld,
-            generator: WorldGenerator::Infinite,
-            world_game_mode: GameMode::Survival,
+            generator: WorldGenerator::try_from(settings.generator).unwrap_or(WorldGenerator::Infinite),
+            world_game_mode,
             hardcore: false,
-            difficulty: Difficulty::Normal,
-            world_spawn: BlockPosition::new(0, 60, 0),
+            difficulty: Difficulty::try_from(settings.difficulty).unwrap_or(Difficulty::Normal),
+            world_spawn,
             achievements_disabled: true,
-            editor_world_type: EditorWorldType::NotEditor,
-            created_in_editor: false,
-            exported_from_editor: false,
-            day_cycle_lock_time: 0,
-            education_features_enabled: true,
-            rain_level: 0.0,
-            lightning_level: 0.0,
-            confirmed_platform_locked_content: false,
-            broadcast_to_lan: true,
-            xbox_broadcast_intent: BroadcastIntent::Public,
-            platform_broadcast_intent: BroadcastIntent::Public,
-            enable_commands: true,
-            texture_packs_required: true,
-            // FIXME: Reimplement with new level interface.
-            // game_rules: &self.level.get_game_rules(),
-            game_rules: &[GameRule::ShowCoordinates(true)],
-            experiments: &[],
-            experiments_previously_enabled: false,
-            bonus_chest_enabled: false,
-            starter_map_enabled: false,
-            permission_level: PermissionLevel::Operator,
-            server_chunk_tick_range: 12,
-            has_locked_behavior_pack: false,
-            has_locked_resource_pack: false,
-            is_from_locked_world_template: false,
-            use_msa_gamertags_only: false,
-            is_from_world_template: false,
-            is_world_template_option_locked: false,
-            only_spawn_v1_villagers: false,
+            editor_world_type: EditorWorldType::try_from(settings.editor_world_type).unwrap_or(EditorWorldType::NotEditor),
+            created_in_editor: settings.created_in_editor,
+            exported_from_editor: settings.exported_from_editor,
+            day_cycle_lock_time,
+            education_features_enabled: settings.education_features_enabled,
+            rain_level: settings.rain_level,
+            lightning_level: settings.lightning_level,
+            confirmed_platform_locked_content: settings.confirmed_platform_locked_content,
+            broadcast_to_lan: settings.lan_broadcast,
+            xbox_broadcast_intent: BroadcastIntent::try_from(settings.xbox_broadcast_intent).unwrap_or(BroadcastIntent::Public),
+            platform_broadcast_intent: BroadcastIntent::try_from(settings.platform_broadcast_intent).unwrap_or(BroadcastIntent::Public),
+            enable_commands: settings.commands_enabled,
+            texture_packs_required: instance.resource_packs.required(),
+            game_rules: &game_rules,
+            experiments: &experiments,
+            experiments_previously_enabled: settings.experiments.experiments_ever_used,
+            bonus_chest_enabled: settings.bonus_chest_enabled,
+            starter_map_enabled: settings.start_with_map_enabled,
+            permission_level: player.permission_level(),
+            server_chunk_tick_range: settings.server_chunk_tick_range,
+            has_locked_behavior_pack: settings.has_locked_behavior_pack,
+            has_locked_resource_pack: settings.has_locked_resource_pack,
+            is_from_locked_world_template: settings.is_from_locked_template,
+            use_msa_gamertags_only: settings.use_msa_gamertags_only,
+            is_from_world_template: settings.is_from_world_template,
+            is_world_template_option_locked: settings.is_world_template_option_locked,
+            only_spawn_v1_villagers: settings.spawn_v1_villagers,
             persona_disabled: false,
             custom_skins_disabled: false,
             emote_chat_muted: false,
-            limited_world_width: 0,
-            limited_world_height: 0,
+            limited_world_width: settings.limited_world_width,
+            limited_world_height: settings.limited_world_depth,
             force_experimental_gameplay: false,
             chat_restriction_level: ChatRestrictionLevel::None,
             disable_player_interactions: false,
-            level_id: "",
-            level_name: "Mirai Dedicated Server",
+            level_id: &settings.level_name,
+            level_name: instance.config().name(),
             template_content_identity: "",
             movement_settings: PlayerMovementSettings {
                 movement_type: PlayerMovementType::ServerAuthoritative,
                 rewind_history_size: 0,
                 server_authoritative_breaking: true,
             },
-            time: 0,
+            time: settings.time,
             enchantment_seed: 0,
-            // block_properties: &[BlockEntry {
-            //     name: "minecraft:bedrock".to_owned(),
-            //     properties: HashMap::from([("infiniburn_bit".to_owned(), nbt::Value::Byte(0))]),
-            // }],
             block_properties: &[],
             item_properties: &[],
             property_data: PropertyData {},
             server_authoritative_inventory: false,
             game_version: CLIENT_VERSION_STRING,
-            // property_data: nbt::Value::Compound(HashMap::new()),
             server_block_state_checksum: 0,
             world_template_id: 0,
             client_side_generation: false,
@@ -259,6 +413,22 @@ impl BedrockClient {
         };
         self.send(start_game)?;
 
+        // Abilities are not part of the start game packet and have to be sent separately.
+        let abilities = &settings.abilities;
+        self.send(UpdateAbilities(AbilityData {
+            unique_id: player.runtime_id(),
+            permission_level: player.permission_level(),
+            command_permission_level: player.command_permission_level(),
+            layers: vec![AbilityLayer {
+                ability_type: AbilityType::Base,
+                abilities: ABILITY_FLAG_END - 1,
+                values: ability_flags(abilities, game_mode),
+                fly_speed: abilities.fly_speed,
+                walk_speed: abilities.walk_speed,
+            }],
+        }))?;
+        drop(settings);
+
         self.send(BiomeDefinitionList)?;
 
         let available_commands = self.commands.available_commands();
@@ -295,29 +465,9 @@ impl BedrockClient {
         let response = PlayStatus { status: Status::LoginSuccess };
         self.send(response)?;
 
-        // TODO: Implement resource packs
-        // FIXME: Sometimes these two packets might be sent in different ticks instead of being batched together.
-        // This will result in the client sending a resource pack response which we do not want.
-        let pack_info = ResourcePacksInfo {
-            required: false,
-            scripting_enabled: false,
-            forcing_server_packs: false,
-            has_addons: false,
-            behavior_info: &[],
-            resource_info: &[],
-        };
-        self.send(pack_info)?;
-
-        let pack_stack = ResourcePackStack {
-            forced_to_accept: false,
-            resource_packs: &[],
-            behavior_packs: &[],
-            game_version: CLIENT_VERSION_STRING,
-            experiments: &[],
-            experiments_previously_toggled: false,
-            includes_editor_packs: false,
-        };
-        self.send(pack_stack)?;
+        // The client responds with the packs it is missing, or with `HaveAllPacks` if there are none.
+        let instance = self.instance();
+        self.send(instance.resource_packs.info())?;
 
         Ok(())
     }
@@ -334,14 +484,30 @@ impl BedrockClient {
     pub async fn handle_login(&self, packet: RVec) -> anyhow::Result<()> {
         self.expected.store(ClientToServerHandshake::ID, Ordering::SeqCst);
 
-        let Ok(request) = Login::deserialize(packet.as_ref()) else {
-            // Kick the player when login fails. This is for security reasons.
-            // An error during login could mean the user is trying to impersonate someone else.
-            self.kick_with_reason("Login failed", DisconnectReason::BadPacket)?;
-            anyhow::bail!("Client failed to login")
+        let request = {
+            let instance = self.instance();
+            Login::deserialize_with(&mut packet.as_ref(), instance.config().chain_validation())
+        };
+
+        let request = match request {
+            Ok(request) => request,
+            Err(err) => {
+                // Kick the player when login fails. This is for security reasons.
+                // An error during login could mean the user is trying to impersonate someone else.
+                let reason = err.downcast_ref::<ChainError>().map_or(DisconnectReason::BadPacket, ChainError::disconnect_reason);
+                self.kick_with_reason("Login failed", reason)?;
+                anyhow::bail!("Client failed to login: {err:#}")
+            }
         };
 
         tracing::Span::current().record("username", &request.identity.name);
+        if !request.identity.authenticated {
+            tracing::warn!(
+                "{} logged in without Xbox Live authentication (offline XUID {})",
+                request.identity.name,
+                request.identity.xuid
+            );
+        }
 
         let Ok((encryptor, jwt)) = Encryptor::new(&request.identity.public_key) else {
             self.kick_with_reason("Encryption failed", DisconnectReason::BadPacket)?;
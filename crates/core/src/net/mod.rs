@@ -10,5 +10,6 @@ glob_export!(client);
 glob_export!(clients);
 glob_export!(login);
 glob_export!(interaction);
+glob_export!(movement);
 glob_export!(handlers);
 glob_export!(forwardable);
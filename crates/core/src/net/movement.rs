@@ -0,0 +1,162 @@
+use std::sync::Arc;
+
+use proto::bedrock::{BlockActorData, ChangeDimension, GameRulesChanged, LevelChunk, MovePlayer, MovementMode, NetworkChunkPublisherUpdate, SubChunkRequestMode, TeleportCause};
+use proto::types::Dimension;
+use util::{BlockPosition, Vector};
+
+use crate::level::net::ser::NetworkChunkExt;
+
+use super::BedrockClient;
+
+impl BedrockClient {
+    /// Dimension that the player is currently in.
+    #[inline]
+    pub fn dimension(&self) -> Dimension {
+        self.viewer.dimension()
+    }
+
+    /// Coordinates of the chunk that the player is currently in.
+    #[inline]
+    pub fn chunk_position(&self) -> Vector<i32, 2> {
+        self.viewer.position()
+    }
+
+    /// Teleports the player to the given position, moving them to another dimension if required.
+    pub fn teleport(self: &Arc<Self>, position: Vector<f32, 3>, dimension: Dimension) -> anyhow::Result<()> {
+        if dimension != self.viewer.dimension() {
+            return self.change_dimension(dimension, position);
+        }
+
+        self.send_teleport(position.clone())?;
+        if self.viewer.update_position(Vector::from([position.x, position.z])) {
+            self.stream_chunks();
+        }
+
+        Ok(())
+    }
+
+    /// Returns the level that the player is currently in.
+    #[inline]
+    pub fn level(&self) -> Arc<crate::level::Service> {
+        self.viewer.level()
+    }
+
+    /// Moves the player to the spawn point of another level without reconnecting.
+    ///
+    /// The client receives the gamerules of the new level and every chunk around the spawn point is sent again.
+    pub fn transfer_level(self: &Arc<Self>, level: Arc<crate::level::Service>) -> anyhow::Result<()> {
+        let position = level.spawn_position();
+        let game_rules = level.game_rules();
+        let name = level.name().to_owned();
+
+        self.viewer.set_level(level);
+        self.send(GameRulesChanged { game_rules: &game_rules })?;
+
+        // Levels are always entered in the overworld.
+        if self.dimension() == Dimension::Overworld {
+            self.send_teleport(position.clone())?;
+            self.viewer.update_position(Vector::from([position.x, position.z]));
+            self.stream_chunks();
+        } else {
+            self.change_dimension(Dimension::Overworld, position)?;
+        }
+
+        tracing::debug!("Moved player to level {name}");
+        Ok(())
+    }
+
+    /// Tells the client that it has been teleported to the given position.
+    fn send_teleport(&self, position: Vector<f32, 3>) -> anyhow::Result<()> {
+        let player = self.player()?;
+        self.send(MovePlayer {
+            runtime_id: player.runtime_id(),
+            translation: position,
+            pitch: player.rotation.x,
+            yaw: player.rotation.y,
+            head_yaw: player.rotation.z,
+            mode: MovementMode::Teleport,
+            on_ground: false,
+            ridden_runtime_id: 0,
+            teleport_cause: TeleportCause::Command,
+            teleport_source_type: 0,
+            tick: 0,
+        })
+    }
+
+    /// Moves the player to another dimension.
+    ///
+    /// The client discards every chunk it has loaded, so the chunks around the player
+    /// are streamed again from the new dimension.
+    pub fn change_dimension(self: &Arc<Self>, dimension: Dimension, position: Vector<f32, 3>) -> anyhow::Result<()> {
+        self.send(ChangeDimension {
+            dimension,
+            position: position.clone(),
+            respawn: false,
+        })?;
+
+        self.viewer.set_dimension(dimension);
+        self.viewer.update_position(Vector::from([position.x, position.z]));
+        self.stream_chunks();
+
+        tracing::debug!("Moved player to {dimension:?}");
+        Ok(())
+    }
+
+    /// Sends every column in view that has not been sent to the client yet.
+    ///
+    /// Columns are loaded on a blocking thread, so this function returns immediately.
+    pub fn stream_chunks(self: &Arc<Self>) {
+        let columns = self.viewer.pending_columns();
+        let dimension = self.viewer.dimension();
+
+        let this = Arc::clone(self);
+        tokio::task::spawn_blocking(move || {
+            let center = this.viewer.position();
+            let publisher = NetworkChunkPublisherUpdate {
+                position: Vector::from([center.x * 16 + 8, 0, center.y * 16 + 8]),
+                radius: u32::from(this.viewer.radius()) * 16,
+            };
+
+            if let Err(err) = this.send(publisher) {
+                tracing::error!("Failed to update chunk publisher: {err:#}");
+                return;
+            }
+
+            for coordinates in columns {
+                // The player moved to another dimension while these columns were being sent.
+                if this.viewer.dimension() != dimension {
+                    break;
+                }
+
+                if let Err(err) = this.send_column(coordinates.clone(), dimension) {
+                    tracing::error!("Failed to send column {coordinates:?}: {err:#}");
+                }
+            }
+        });
+    }
+
+    /// Sends a full column to the client, followed by the block entities in that column.
+    fn send_column(&self, coordinates: Vector<i32, 2>, dimension: Dimension) -> anyhow::Result<()> {
+        let column = self.viewer.load_column(coordinates.clone(), dimension)?;
+
+        self.send(LevelChunk {
+            coordinates,
+            dimension,
+            request_mode: SubChunkRequestMode::Legacy,
+            highest_sub_chunk: 0,
+            sub_chunk_count: column.subchunk_count() as u32,
+            blob_hashes: None,
+            raw_payload: column.serialize_network(&self.instance().block_states)?,
+        })?;
+
+        for entity in &column.block_entities {
+            self.send(BlockActorData {
+                // Negative heights are sent as their two's complement representation.
+                position: BlockPosition::new(entity.position.x, entity.position.y as u32, entity.position.z),
+                nbt: entity.to_nbt(),
+            })?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+//! Minimal reader for the zip archives that packs are distributed in.
+
+use std::io::Read;
+
+use flate2::read::DeflateDecoder;
+
+/// Signature of the end of central directory record.
+const END_OF_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+/// Signature of a central directory file header.
+const DIRECTORY_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+/// Signature of a local file header.
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+/// Size of the end of central directory record without its comment.
+const END_OF_DIRECTORY_SIZE: usize = 22;
+/// Size of a central directory file header without its variable length fields.
+const DIRECTORY_HEADER_SIZE: usize = 46;
+/// Size of a local file header without its variable length fields.
+const LOCAL_HEADER_SIZE: usize = 30;
+
+/// Maximum amount of bytes that is reserved up front when decompressing a file.
+const MAX_SIZE_HINT: usize = 1024 * 1024;
+
+/// Entry stored with no compression.
+const METHOD_STORED: u16 = 0;
+/// Entry compressed using deflate.
+const METHOD_DEFLATE: u16 = 8;
+
+/// A file contained in an [`Archive`].
+#[derive(Debug)]
+pub struct Entry {
+    /// Path of the file within the archive.
+    pub name: String,
+    /// Compression method used for this file.
+    method: u16,
+    /// Size of the file in the archive.
+    compressed_size: usize,
+    /// Size of the file after decompression.
+    uncompressed_size: usize,
+    /// Offset of the local file header.
+    header_offset: usize,
+}
+
+/// A read-only view into a zip archive.
+///
+/// Only the features required to read pack manifests are supported: stored and deflated entries
+/// without encryption or zip64 extensions.
+pub struct Archive<'a> {
+    /// The raw archive.
+    data: &'a [u8],
+    /// Files found in the central directory.
+    entries: Vec<Entry>,
+}
+
+impl<'a> Archive<'a> {
+    /// Reads the central directory of the given archive.
+    pub fn new(data: &'a [u8]) -> anyhow::Result<Archive<'a>> {
+        let Some(end) = Self::find_end_of_directory(data) else {
+            anyhow::bail!("Archive does not contain an end of central directory record");
+        };
+
+        let count = read_u16(data, end + 10)? as usize;
+        let mut offset = read_u32(data, end + 16)? as usize;
+
+        let mut entries = Vec::with_capacity(count);
+        for _ in 0..count {
+            if read_u32(data, offset)? != DIRECTORY_HEADER_SIGNATURE {
+                anyhow::bail!("Invalid central directory header at offset {offset}");
+            }
+
+            let flags = read_u16(data, offset + 8)?;
+            let method = read_u16(data, offset + 10)?;
+            let compressed_size = read_u32(data, offset + 20)? as usize;
+            let uncompressed_size = read_u32(data, offset + 24)? as usize;
+            let name_len = read_u16(data, offset + 28)? as usize;
+            let extra_len = read_u16(data, offset + 30)? as usize;
+            let comment_len = read_u16(data, offset + 32)? as usize;
+            let header_offset = read_u32(data, offset + 42)? as usize;
+
+            let name = slice(data, offset + DIRECTORY_HEADER_SIZE, name_len)?;
+            let name = String::from_utf8_lossy(name).replace('\\', "/");
+
+            // Files encrypted using zip encryption cannot be read.
+            // This is different from pack encryption, which leaves the zip itself intact.
+            if flags & 1 == 0 {
+                entries.push(Entry {
+                    name,
+                    method,
+                    compressed_size,
+                    uncompressed_size,
+                    header_offset,
+                });
+            }
+
+            offset += DIRECTORY_HEADER_SIZE + name_len + extra_len + comment_len;
+        }
+
+        Ok(Archive { data, entries })
+    }
+
+    /// Returns all files in the archive.
+    #[inline]
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Finds a file by its path within the archive.
+    pub fn find(&self, name: &str) -> Option<&Entry> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+
+    /// Decompresses the given file.
+    pub fn read(&self, entry: &Entry) -> anyhow::Result<Vec<u8>> {
+        let offset = entry.header_offset;
+        if read_u32(self.data, offset)? != LOCAL_HEADER_SIGNATURE {
+            anyhow::bail!("Invalid local file header for {}", entry.name);
+        }
+
+        let name_len = read_u16(self.data, offset + 26)? as usize;
+        let extra_len = read_u16(self.data, offset + 28)? as usize;
+        let compressed = slice(self.data, offset + LOCAL_HEADER_SIZE + name_len + extra_len, entry.compressed_size)?;
+
+        match entry.method {
+            METHOD_STORED => Ok(compressed.to_vec()),
+            METHOD_DEFLATE => {
+                // The size in the header is not trusted, so it is only used as a hint for small files.
+                let mut output = Vec::with_capacity(entry.uncompressed_size.min(MAX_SIZE_HINT));
+                DeflateDecoder::new(compressed).read_to_end(&mut output)?;
+
+                Ok(output)
+            }
+            method => anyhow::bail!("Unsupported compression method {method} used for {}", entry.name),
+        }
+    }
+
+    /// Searches backwards for the end of central directory record.
+    /// The record is followed by a comment of at most `u16::MAX` bytes.
+    fn find_end_of_directory(data: &[u8]) -> Option<usize> {
+        let last = data.len().checked_sub(END_OF_DIRECTORY_SIZE)?;
+        let first = last.saturating_sub(u16::MAX as usize);
+
+        (first..=last).rev().find(|&i| read_u32(data, i).ok() == Some(END_OF_DIRECTORY_SIGNATURE))
+    }
+}
+
+/// Returns `len` bytes starting at `offset`, failing if the archive is truncated.
+fn slice(data: &[u8], offset: usize, len: usize) -> anyhow::Result<&[u8]> {
+    offset
+        .checked_add(len)
+        .and_then(|end| data.get(offset..end))
+        .ok_or_else(|| anyhow::anyhow!("Archive is truncated"))
+}
+
+/// Reads a little endian `u16` at the given offset.
+fn read_u16(data: &[u8], offset: usize) -> anyhow::Result<u16> {
+    let bytes = slice(data, offset, 2)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+/// Reads a little endian `u32` at the given offset.
+fn read_u32(data: &[u8], offset: usize) -> anyhow::Result<u32> {
+    let bytes = slice(data, offset, 4)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
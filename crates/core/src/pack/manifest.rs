@@ -0,0 +1,119 @@
+//! Parsing of pack manifests.
+
+use serde_json::Value;
+use proto::uuid::Uuid;
+
+/// Kind of content contained in a pack, determined by the modules in its manifest.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PackKind {
+    /// A resource pack, containing textures, models and sounds.
+    Resources,
+    /// A behavior pack, containing data and scripts.
+    Behavior,
+}
+
+/// A dependency on another pack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    /// UUID of the required pack.
+    pub uuid: Uuid,
+    /// Required version of the pack.
+    pub version: String,
+}
+
+/// Contents of a `manifest.json` file.
+#[derive(Debug, Clone)]
+pub struct Manifest {
+    /// Display name of the pack.
+    pub name: String,
+    /// UUID of the pack.
+    pub uuid: Uuid,
+    /// Version of the pack, formatted as `major.minor.patch`.
+    pub version: String,
+    /// What type of content this pack contains.
+    pub kind: PackKind,
+    /// Whether this pack contains scripts.
+    pub has_scripts: bool,
+    /// Packs that this pack depends on.
+    ///
+    /// Dependencies on script modules such as `@minecraft/server` are not included.
+    pub dependencies: Vec<Dependency>,
+}
+
+impl Manifest {
+    /// Parses a manifest from its JSON representation.
+    pub fn parse(data: &[u8]) -> anyhow::Result<Manifest> {
+        let root: Value = serde_json::from_slice(data)?;
+
+        let Some(header) = root.get("header") else {
+            anyhow::bail!("Manifest is missing a header");
+        };
+
+        let name = header.get("name").and_then(Value::as_str).unwrap_or_default().to_owned();
+        let uuid = parse_uuid(header.get("uuid"))?;
+        let version = parse_version(header.get("version"))?;
+
+        let mut kind = None;
+        let mut has_scripts = false;
+        for module in root.get("modules").and_then(Value::as_array).into_iter().flatten() {
+            match module.get("type").and_then(Value::as_str) {
+                Some("resources") => kind = kind.or(Some(PackKind::Resources)),
+                Some("data") => kind = Some(PackKind::Behavior),
+                Some("script" | "javascript" | "client_data") => {
+                    kind = Some(PackKind::Behavior);
+                    has_scripts = true;
+                }
+                _ => {}
+            }
+        }
+
+        let Some(kind) = kind else {
+            anyhow::bail!("Manifest does not contain any resource, data or script modules");
+        };
+
+        let mut dependencies = Vec::new();
+        for dependency in root.get("dependencies").and_then(Value::as_array).into_iter().flatten() {
+            // Script module dependencies are identified by a module name instead.
+            if dependency.get("uuid").is_none() {
+                continue
+            }
+
+            dependencies.push(Dependency {
+                uuid: parse_uuid(dependency.get("uuid"))?,
+                version: parse_version(dependency.get("version"))?,
+            });
+        }
+
+        Ok(Manifest { name, uuid, version, kind, has_scripts, dependencies })
+    }
+}
+
+/// Parses a UUID field.
+fn parse_uuid(value: Option<&Value>) -> anyhow::Result<Uuid> {
+    let Some(uuid) = value.and_then(Value::as_str) else {
+        anyhow::bail!("Manifest is missing a UUID");
+    };
+
+    Ok(Uuid::parse_str(uuid)?)
+}
+
+/// Parses a version field.
+///
+/// Versions are either stored as an array of three integers or as a string.
+fn parse_version(value: Option<&Value>) -> anyhow::Result<String> {
+    match value {
+        Some(Value::Array(parts)) => {
+            let parts = parts
+                .iter()
+                .map(|p| p.as_u64().map(|p| p.to_string()))
+                .collect::<Option<Vec<_>>>();
+
+            match parts {
+                Some(parts) if parts.len() == 3 => Ok(parts.join(".")),
+                _ => anyhow::bail!("Manifest version must consist of three integers"),
+            }
+        }
+        Some(Value::String(version)) => Ok(version.clone()),
+        _ => anyhow::bail!("Manifest is missing a version"),
+    }
+}
@@ -0,0 +1,337 @@
+//! Loading and distribution of resource and behavior packs.
+//!
+//! Packs are read from `.mcpack` and `.zip` archives in the configured pack directory.
+//! Encrypted packs are supported by placing the content key next to the archive in a file with
+//! the same name and a `.key` extension, i.e. `pack.mcpack.key`.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use proto::bedrock::{BehaviorPack, ResourcePack, ResourcePackStackEntry, ResourcePackType, ResourcePacksInfo};
+use sha2::{Digest, Sha256};
+use proto::uuid::Uuid;
+
+use crate::config::PackConfig;
+
+mod archive;
+mod manifest;
+
+pub use archive::*;
+pub use manifest::*;
+
+/// Maximum size of a single chunk sent using [`ResourcePackChunkData`](proto::bedrock::ResourcePackChunkData).
+pub const PACK_CHUNK_SIZE: usize = 256 * 1024;
+
+/// Length of a valid content key.
+const CONTENT_KEY_LEN: usize = 32;
+
+/// A pack that can be sent to clients.
+pub struct Pack {
+    /// Manifest of the pack.
+    manifest: Manifest,
+    /// UUID of the pack as a string.
+    uuid: String,
+    /// ID of the pack, formatted as `uuid_version`.
+    id: String,
+    /// The compressed archive.
+    data: Vec<u8>,
+    /// SHA-256 hash of the archive.
+    hash: [u8; 32],
+    /// Key used to decrypt the pack, empty if the pack is not encrypted.
+    content_key: String,
+}
+
+impl Pack {
+    /// Loads a pack from a compressed archive.
+    pub fn from_archive(data: Vec<u8>) -> anyhow::Result<Pack> {
+        let archive = Archive::new(&data)?;
+
+        // The manifest is located either at the root of the archive or in a single top level directory.
+        let entry = archive
+            .entries()
+            .iter()
+            .filter(|e| e.name == "manifest.json" || e.name.ends_with("/manifest.json"))
+            .min_by_key(|e| e.name.matches('/').count());
+
+        let Some(entry) = entry else {
+            anyhow::bail!("Pack does not contain a manifest.json");
+        };
+
+        let manifest = Manifest::parse(&archive.read(entry)?)?;
+
+        let hash = Sha256::digest(&data).into();
+        let uuid = manifest.uuid.to_string();
+        let id = format!("{uuid}_{}", manifest.version);
+
+        Ok(Pack { manifest, uuid, id, data, hash, content_key: String::new() })
+    }
+
+    /// Sets the key that clients use to decrypt this pack.
+    pub fn with_content_key(mut self, key: String) -> anyhow::Result<Pack> {
+        if key.len() != CONTENT_KEY_LEN {
+            anyhow::bail!(
+                "Content key for pack {} must be {CONTENT_KEY_LEN} characters long, found {}",
+                self.manifest.uuid,
+                key.len()
+            );
+        }
+
+        self.content_key = key;
+        Ok(self)
+    }
+
+    /// Returns the manifest of this pack.
+    #[inline]
+    pub const fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// Returns the ID of this pack, formatted as `uuid_version`.
+    #[inline]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the size of the archive in bytes.
+    #[inline]
+    pub fn size(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    /// Returns the SHA-256 hash of the archive.
+    #[inline]
+    pub const fn hash(&self) -> &[u8; 32] {
+        &self.hash
+    }
+
+    /// Whether this pack is encrypted.
+    #[inline]
+    pub fn encrypted(&self) -> bool {
+        !self.content_key.is_empty()
+    }
+
+    /// Returns the type that this pack is announced as.
+    pub const fn pack_type(&self) -> ResourcePackType {
+        match self.manifest.kind {
+            PackKind::Resources => ResourcePackType::Resources,
+            PackKind::Behavior => ResourcePackType::Behavior,
+        }
+    }
+
+    /// Returns the amount of chunks the archive is split into.
+    pub fn chunk_count(&self) -> u32 {
+        self.data.len().div_ceil(PACK_CHUNK_SIZE) as u32
+    }
+
+    /// Returns the offset and contents of a single chunk.
+    pub fn chunk(&self, index: u32) -> Option<(u64, &[u8])> {
+        let start = (index as usize).checked_mul(PACK_CHUNK_SIZE)?;
+        if start >= self.data.len() {
+            return None
+        }
+
+        let end = (start + PACK_CHUNK_SIZE).min(self.data.len());
+        Some((start as u64, &self.data[start..end]))
+    }
+
+    /// Identity used by the client to look up the content key of encrypted packs.
+    fn content_identity(&self) -> String {
+        if self.encrypted() { self.uuid.clone() } else { String::new() }
+    }
+}
+
+/// Collection of packs that are sent to clients when they join.
+pub struct ResourcePacks {
+    /// All loaded packs, ordered such that dependencies come before the packs that depend on them.
+    packs: Vec<Pack>,
+    /// Whether clients have to accept the packs to be able to join.
+    required: bool,
+    /// Resource pack info sent in [`ResourcePacksInfo`].
+    resource_info: Vec<ResourcePack>,
+    /// Behavior pack info sent in [`ResourcePacksInfo`].
+    behavior_info: Vec<BehaviorPack>,
+}
+
+impl ResourcePacks {
+    /// Creates an empty collection.
+    pub const fn new(required: bool) -> ResourcePacks {
+        ResourcePacks { packs: Vec::new(), required, resource_info: Vec::new(), behavior_info: Vec::new() }
+    }
+
+    /// Loads all packs in the configured directory.
+    pub fn load(config: &PackConfig) -> anyhow::Result<ResourcePacks> {
+        let mut collection = ResourcePacks::new(config.required);
+        let Some(path) = &config.path else {
+            return Ok(collection)
+        };
+
+        let path = Path::new(path);
+        if !path.is_dir() {
+            tracing::warn!("Pack directory {} does not exist, no packs will be loaded", path.display());
+            return Ok(collection)
+        }
+
+        let mut packs = Vec::new();
+        for entry in std::fs::read_dir(path)? {
+            let file = entry?.path();
+            let is_archive = file
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| e.eq_ignore_ascii_case("mcpack") || e.eq_ignore_ascii_case("zip"));
+
+            if !is_archive {
+                continue
+            }
+
+            let data = std::fs::read(&file)?;
+            let mut key_file = file.clone().into_os_string();
+            key_file.push(".key");
+
+            let key_file = match std::fs::read_to_string(key_file) {
+                Ok(key) => Some(key.trim().to_owned()),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+                Err(err) => return Err(err.into()),
+            };
+
+            let pack = Pack::from_archive(data).and_then(|pack| {
+                let content_key = key_file.or_else(|| config.content_keys.get(&pack.manifest.uuid).cloned());
+                match content_key {
+                    Some(key) => pack.with_content_key(key),
+                    None => Ok(pack),
+                }
+            });
+
+            match pack {
+                Ok(pack) => {
+                    tracing::info!("Loaded pack {} ({})", pack.manifest.name, pack.id);
+                    packs.push(pack);
+                }
+                Err(err) => {
+                    tracing::error!("Failed to load pack {}: {err:#}", file.display());
+                    anyhow::bail!("Failed to load pack {}: {err:#}", file.display());
+                }
+            }
+        }
+
+        for pack in Self::sort_dependencies(packs)? {
+            collection.insert(pack);
+        }
+
+        Ok(collection)
+    }
+
+    /// Orders packs such that every pack is preceded by its dependencies.
+    pub(crate) fn sort_dependencies(packs: Vec<Pack>) -> anyhow::Result<Vec<Pack>> {
+        let mut indices = HashMap::with_capacity(packs.len());
+        for (i, pack) in packs.iter().enumerate() {
+            if indices.insert(pack.manifest.uuid, i).is_some() {
+                anyhow::bail!("Found multiple packs with UUID {}", pack.manifest.uuid);
+            }
+        }
+
+        fn visit(i: usize, packs: &[Pack], indices: &HashMap<Uuid, usize>, visited: &mut HashSet<usize>, order: &mut Vec<usize>) {
+            if !visited.insert(i) {
+                return
+            }
+
+            for dependency in &packs[i].manifest.dependencies {
+                match indices.get(&dependency.uuid) {
+                    Some(&j) => visit(j, packs, indices, visited, order),
+                    None => tracing::warn!(
+                        "Pack {} depends on {} version {}, which is not loaded",
+                        packs[i].manifest.name, dependency.uuid, dependency.version
+                    ),
+                }
+            }
+
+            order.push(i);
+        }
+
+        let mut visited = HashSet::with_capacity(packs.len());
+        let mut order = Vec::with_capacity(packs.len());
+        for i in 0..packs.len() {
+            visit(i, &packs, &indices, &mut visited, &mut order);
+        }
+
+        let mut packs = packs.into_iter().map(Some).collect::<Vec<_>>();
+        Ok(order.into_iter().filter_map(|i| packs[i].take()).collect())
+    }
+
+    /// Adds a pack to the collection.
+    pub fn insert(&mut self, pack: Pack) {
+        match pack.manifest.kind {
+            PackKind::Resources => self.resource_info.push(ResourcePack {
+                uuid: pack.uuid.clone(),
+                version: pack.manifest.version.clone(),
+                size: pack.size(),
+                content_key: pack.content_key.clone(),
+                subpack_name: String::new(),
+                content_identity: pack.content_identity(),
+                has_scripts: pack.manifest.has_scripts,
+                rtx_enabled: false,
+            }),
+            PackKind::Behavior => self.behavior_info.push(BehaviorPack {
+                uuid: pack.uuid.clone(),
+                version: pack.manifest.version.clone(),
+                size: pack.size(),
+                content_key: pack.content_key.clone(),
+                subpack_name: String::new(),
+                content_identity: pack.content_identity(),
+                has_scripts: pack.manifest.has_scripts,
+            }),
+        }
+
+        self.packs.push(pack);
+    }
+
+    /// Whether clients are required to accept the packs.
+    #[inline]
+    pub const fn required(&self) -> bool {
+        self.required
+    }
+
+    /// Whether there are no packs loaded.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.packs.is_empty()
+    }
+
+    /// Returns all loaded packs.
+    #[inline]
+    pub fn packs(&self) -> &[Pack] {
+        &self.packs
+    }
+
+    /// Looks up a pack by its ID.
+    ///
+    /// The ID is either formatted as `uuid_version` or only consists of the UUID.
+    pub fn get(&self, id: &str) -> Option<&Pack> {
+        let uuid = id.split_once('_').map_or(id, |(uuid, _)| uuid);
+        self.packs.iter().find(|p| p.id == id || p.uuid == uuid)
+    }
+
+    /// Creates the [`ResourcePacksInfo`] packet advertising these packs.
+    pub fn info(&self) -> ResourcePacksInfo<'_> {
+        ResourcePacksInfo {
+            required: self.required && !self.is_empty(),
+            scripting_enabled: self.packs.iter().any(|p| p.manifest.has_scripts),
+            forcing_server_packs: false,
+            has_addons: !self.behavior_info.is_empty(),
+            behavior_info: &self.behavior_info,
+            resource_info: &self.resource_info,
+        }
+    }
+
+    /// Returns the entries of the resource pack stack for packs of the given kind.
+    pub fn stack(&self, kind: PackKind) -> Vec<ResourcePackStackEntry<'_>> {
+        self.packs
+            .iter()
+            .filter(|p| p.manifest.kind == kind)
+            .map(|p| ResourcePackStackEntry {
+                pack_id: &p.uuid,
+                pack_version: &p.manifest.version,
+                subpack_name: "",
+            })
+            .collect()
+    }
+}
@@ -23,3 +23,736 @@ fn header() {
 
     assert_eq!(Header::deserialize(buffer.as_ref()).unwrap(), header);
 }
+
+#[test]
+fn subchunk_cache() {
+    use std::sync::Arc;
+
+    use crate::level::cache::SubChunkCache;
+    use crate::level::io::stream::RegionIndex;
+    use level::SubChunk;
+    use proto::types::Dimension;
+    use util::Vector;
+
+    let key = |x: i32| (Dimension::Overworld, RegionIndex::from(Vector::from([x, 0, 0])));
+
+    // One entry per shard at most.
+    let cache = SubChunkCache::new(16);
+    for x in 0..64 {
+        cache.insert(key(x), Arc::new(SubChunk::empty(0)));
+    }
+
+    let metrics = cache.metrics();
+    assert!(metrics.entries <= 16);
+    assert_eq!(metrics.evictions, 64 - metrics.entries as u64);
+
+    // Dirty entries are pinned until they have been written.
+    let modified = Arc::new(SubChunk::empty(0));
+    for x in 100..164 {
+        cache.insert_dirty(key(x), Arc::clone(&modified));
+    }
+    for x in 0..64 {
+        cache.insert(key(x), Arc::new(SubChunk::empty(0)));
+    }
+
+    assert!(cache.get(&key(100)).is_some());
+    assert_eq!(cache.metrics().dirty, 64);
+
+    cache.mark_clean(&key(100), &modified);
+    assert!(cache.dirty(&key(100)).is_none());
+    assert_eq!(cache.metrics().dirty, 63);
+
+    // Recently used entries are kept, even if they were inserted first.
+    let cache = SubChunkCache::new(32);
+    cache.insert(key(0), Arc::new(SubChunk::empty(0)));
+    for x in 1..256 {
+        assert!(cache.get(&key(0)).is_some());
+        cache.insert(key(x), Arc::new(SubChunk::empty(0)));
+    }
+    assert!(cache.get(&key(0)).is_some());
+}
+
+/// Storage whose batch writes fail while `fail` is set.
+struct FlakyStorage {
+    inner: level::MemoryStorage,
+    fail: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl level::Storage for FlakyStorage {
+    fn get_raw(&self, raw_key: &[u8]) -> anyhow::Result<Option<level::database::Guard<'_>>> {
+        self.inner.get_raw(raw_key)
+    }
+
+    fn put_raw(&self, raw_key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        self.inner.put_raw(raw_key, value)
+    }
+
+    fn delete_raw(&self, raw_key: &[u8]) -> anyhow::Result<()> {
+        self.inner.delete_raw(raw_key)
+    }
+
+    fn execute(&self, batch: &level::WriteBatch) -> anyhow::Result<()> {
+        if self.fail.load(std::sync::atomic::Ordering::Relaxed) {
+            anyhow::bail!("Storage is unavailable");
+        }
+
+        self.inner.execute(batch)
+    }
+
+    fn iter_prefix<'a>(&'a self, prefix: &[u8]) -> Box<dyn Iterator<Item = (level::database::Guard<'a>, level::database::Guard<'a>)> + 'a> {
+        self.inner.iter_prefix(prefix)
+    }
+
+    fn iter_range<'a>(&'a self, start: &[u8], end: &[u8]) -> Box<dyn Iterator<Item = (level::database::Guard<'a>, level::database::Guard<'a>)> + 'a> {
+        self.inner.iter_range(start, end)
+    }
+}
+
+#[test]
+fn region_collector() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use futures::SinkExt;
+    use level::provider::Provider;
+    use level::{BiomeEncoding, Biomes, MemoryStorage, Storage, SubChunk};
+    use proto::types::Dimension;
+    use tokio_util::sync::CancellationToken;
+    use util::{Joinable, Vector};
+
+    use crate::level::cache::SubChunkCache;
+    use crate::level::io::sink::Collector;
+    use crate::level::io::stream::{IndexedColumn, IndexedSubChunk, RegionIndex};
+
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    runtime.block_on(async {
+        let fail = Arc::new(AtomicBool::new(false));
+        let storage = FlakyStorage {
+            inner: MemoryStorage::new(),
+            fail: Arc::clone(&fail),
+        };
+        let provider: Arc<Provider<dyn Storage>> = Arc::new(Provider::with_storage("memory", storage));
+        let cache = Arc::new(SubChunkCache::new(64));
+        let token = CancellationToken::new();
+        let collector = Collector::new(Arc::clone(&provider), Arc::clone(&cache), token.clone(), 4);
+
+        let key = |x: i32| (Dimension::Overworld, RegionIndex::from(Vector::from([x, 0, 0])));
+        let write = |x: i32| IndexedSubChunk {
+            index: RegionIndex::from(Vector::from([x, 0, 0])),
+            dimension: Dimension::Overworld,
+            data: Arc::new(SubChunk::empty(0)),
+        };
+        let stored = |x: i32| provider.subchunk([x, 0, 0], Dimension::Overworld).unwrap().is_some();
+
+        // Flushing the sink resolves once the changes have been written.
+        let mut sink = collector.create_sink();
+        sink.feed(write(0)).await.unwrap();
+        sink.feed(IndexedColumn {
+            coordinates: Vector::from([0, 0]),
+            dimension: Dimension::Overworld,
+            biomes: Biomes {
+                heightmap: Box::new([[0; 16]; 16]),
+                fragments: vec![BiomeEncoding::Single(1)],
+            },
+        })
+        .await
+        .unwrap();
+        SinkExt::<IndexedSubChunk>::flush(&mut sink).await.unwrap();
+
+        assert!(stored(0));
+        assert!(provider.version([0, 0], Dimension::Overworld).unwrap().is_some());
+        assert_eq!(cache.dirty_count(), 0);
+
+        // Subchunks that are modified directly in the cache are written once the threshold is reached,
+        // without waiting for a request or the flush interval.
+        for x in 1..5 {
+            cache.insert_dirty(key(x), Arc::new(SubChunk::empty(0)));
+        }
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while cache.dirty_count() > 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Collector did not flush after reaching the threshold");
+        assert!((1..5).all(stored));
+
+        // Failed writes remain pending and are retried by the next flush.
+        fail.store(true, Ordering::Relaxed);
+        sink.feed(write(10)).await.unwrap();
+        assert!(SinkExt::<IndexedSubChunk>::flush(&mut sink).await.is_err());
+        assert!(!stored(10));
+        assert!(cache.dirty(&key(10)).is_some());
+
+        fail.store(false, Ordering::Relaxed);
+        SinkExt::<IndexedSubChunk>::flush(&mut sink).await.unwrap();
+        assert!(stored(10));
+        assert_eq!(cache.dirty_count(), 0);
+
+        // Pending changes are written by the final flush when the server shuts down.
+        cache.insert_dirty(key(20), Arc::new(SubChunk::empty(0)));
+        token.cancel();
+        collector.join().await.unwrap();
+        assert!(stored(20));
+
+        // Flushing after the collector has shut down succeeds, as the final flush has already happened.
+        SinkExt::<IndexedSubChunk>::flush(&mut sink).await.unwrap();
+    });
+}
+
+#[test]
+fn generate_once() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use proto::types::Dimension;
+    use tokio_util::sync::CancellationToken;
+    use util::Vector;
+
+    use crate::config::{GeneratorConfig, StorageConfig};
+    use crate::level::generator::{GeneratedColumn, Generator, SuperflatGenerator};
+    use crate::level::{Service, ServiceOptions};
+
+    /// Counts how many columns have been generated.
+    struct CountingGenerator {
+        inner: SuperflatGenerator,
+        count: AtomicUsize,
+    }
+
+    impl Generator for CountingGenerator {
+        fn generate(&self, coordinates: Vector<i32, 2>, dimension: Dimension) -> anyhow::Result<GeneratedColumn> {
+            self.count.fetch_add(1, Ordering::Relaxed);
+            self.inner.generate(coordinates, dimension)
+        }
+    }
+
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    runtime.block_on(async {
+        let service = Service::new(ServiceOptions {
+            name: String::from("test"),
+            instance_token: CancellationToken::new(),
+            level_path: String::from("../../resources/level"),
+            cache_size: 16,
+            generator: GeneratorConfig::None,
+            storage: StorageConfig::Memory,
+        })
+        .unwrap();
+
+        let generator = Arc::new(CountingGenerator {
+            inner: SuperflatGenerator::default(),
+            count: AtomicUsize::new(0),
+        });
+        service.set_generator(Some(Arc::clone(&generator) as Arc<dyn Generator>));
+
+        // The superflat generator does not produce this subchunk, as it only contains air.
+        for _ in 0..2 {
+            let subchunk = service.subchunk([3, 10, -2], Dimension::Overworld).unwrap();
+            assert_eq!(subchunk.index, 10);
+
+            // Fill the cache with subchunks above the world, which are not generated.
+            for x in 0..256 {
+                service.subchunk([x, 25, 0], Dimension::Overworld).unwrap();
+            }
+        }
+        assert_eq!(generator.count.load(Ordering::Relaxed), 1);
+
+        // The biomes of the column can be read before they have been written to disk.
+        let expected = generator.inner.generate(Vector::from([3, -2]), Dimension::Overworld).unwrap().biomes;
+        assert_eq!(service.biomes([3, -2], Dimension::Overworld).unwrap(), Some(expected));
+    });
+}
+
+#[test]
+fn block_outside_world() {
+    use std::collections::HashMap;
+
+    use level::{BlockEntity, PaletteEntry};
+    use proto::types::Dimension;
+    use tokio_util::sync::CancellationToken;
+    use util::Vector;
+
+    use crate::config::{GeneratorConfig, StorageConfig};
+    use crate::level::{Service, ServiceOptions};
+
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    runtime.block_on(async {
+        let service = Service::new(ServiceOptions {
+            name: String::from("test"),
+            instance_token: CancellationToken::new(),
+            level_path: String::from("../../resources/level"),
+            cache_size: 16,
+            generator: GeneratorConfig::None,
+            storage: StorageConfig::Memory,
+        })
+        .unwrap();
+
+        let stone = PaletteEntry {
+            name: String::from("minecraft:stone"),
+            version: None,
+            states: HashMap::new(),
+        };
+        // Both heights fit in a subchunk index, but are above the overworld and nether respectively.
+        assert!(service.set_block([0, 1000, 0], Dimension::Overworld, stone.clone()).is_err());
+        assert!(service.set_block([0, 200, 0], Dimension::Nether, stone).is_err());
+        assert!(service.remove_block_entity([0, -100, 0], Dimension::Overworld).is_err());
+
+        let sign = BlockEntity {
+            id: String::from("Sign"),
+            position: Vector::from([0, 200, 0]),
+            is_movable: true,
+            custom_name: None,
+            data: HashMap::new(),
+        };
+        assert!(service.set_block_entity(sign, Dimension::Nether).is_err());
+        assert_eq!(service.cache_metrics().dirty, 0);
+    });
+}
+
+#[test]
+fn pregen_without_generator() {
+    use proto::types::Dimension;
+    use tokio_util::sync::CancellationToken;
+    use util::Vector;
+
+    use crate::config::{GeneratorConfig, StorageConfig};
+    use crate::level::pregen::{PregenArea, PregenShape};
+    use crate::level::{Service, ServiceOptions};
+
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    runtime.block_on(async {
+        let service = Service::new(ServiceOptions {
+            name: String::from("test"),
+            instance_token: CancellationToken::new(),
+            level_path: String::from("../../resources/level"),
+            cache_size: 16,
+            generator: GeneratorConfig::None,
+            storage: StorageConfig::Memory,
+        })
+        .unwrap();
+
+        let area = PregenArea {
+            center: Vector::from([0, 0]),
+            radius: 4,
+            dimension: Dimension::Overworld,
+            shape: PregenShape::Circle,
+        };
+        assert!(service.pregenerate(area, |_| {}).await.is_err());
+        assert!(!service.is_pregenerating());
+    });
+}
+
+#[test]
+fn superflat_preset() {
+    use crate::level::generator::{Generator, SuperflatGenerator};
+    use proto::types::Dimension;
+    use util::Vector;
+
+    let generator = SuperflatGenerator::from_preset("minecraft:bedrock,2*dirt[dirt_type=normal],minecraft:grass;4").unwrap();
+    let column = generator.generate(Vector::from([0, 0]), Dimension::Overworld).unwrap();
+
+    // Only the bottom subchunk contains blocks.
+    assert_eq!(column.subchunks.len(), 1);
+    assert_eq!(column.subchunks[0].index, -4);
+    assert_eq!(column.biomes.fragments.len(), 24);
+
+    let layer = &column.subchunks[0][0];
+    assert_eq!(layer[[0, 0, 0]].name, "minecraft:bedrock");
+    assert_eq!(layer[[3, 2, 5]].name, "minecraft:dirt");
+    assert_eq!(layer[[15, 3, 15]].name, "minecraft:grass");
+    assert_eq!(layer[[0, 4, 0]].name, "minecraft:air");
+
+    assert!(SuperflatGenerator::from_preset("minecraft:stone;plains").is_err());
+    assert!(SuperflatGenerator::from_preset("minecraft:stone[broken").is_err());
+}
+
+#[test]
+fn noise_generator() {
+    use crate::level::generator::{Biome, Generator, NoiseGenerator};
+    use proto::types::Dimension;
+    use util::Vector;
+
+    let mut biomes_nbt: &[u8] = include_bytes!("../include/biomes.nbt");
+    let biomes: nbt::Value = nbt::from_var_bytes(&mut biomes_nbt).unwrap().0;
+    let definitions = biomes.as_compound().unwrap();
+    for biome in Biome::ALL {
+        assert!(definitions.contains_key(biome.name()), "unknown biome {}", biome.name());
+    }
+
+    let first = NoiseGenerator::new(42).generate(Vector::from([5, -3]), Dimension::Overworld).unwrap();
+    let second = NoiseGenerator::new(42).generate(Vector::from([5, -3]), Dimension::Overworld).unwrap();
+    assert_eq!(first.subchunks, second.subchunks);
+    assert_eq!(first.biomes.heightmap, second.biomes.heightmap);
+    assert_eq!(first.biomes.fragments.len(), 24);
+
+    let other = NoiseGenerator::new(43).generate(Vector::from([5, -3]), Dimension::Overworld).unwrap();
+    assert_ne!(first.subchunks, other.subchunks);
+
+    // The bottom of the world is always bedrock.
+    let bottom = first.subchunks.iter().find(|subchunk| subchunk.index == -4).unwrap();
+    assert_eq!(bottom[0][[7, 0, 7]].name, "minecraft:bedrock");
+}
+
+#[test]
+fn column_heightmap() {
+    use std::sync::Arc;
+
+    use crate::level::net::{column::ChunkColumn, heightmap::Heightmap};
+    use level::{PaletteEntry, SubChunk};
+    use proto::bedrock::HeightmapType;
+    use proto::types::Dimension;
+
+    let block = |name: &str| PaletteEntry {
+        name: name.to_owned(),
+        version: None,
+        states: Default::default(),
+    };
+
+    let mut lower = SubChunk::empty(0);
+    lower[0].set([0, 5, 0], block("minecraft:stone"));
+    lower[0].set([1, 15, 1], block("minecraft:stone"));
+
+    let mut upper = SubChunk::empty(1);
+    // Glass does not occlude, so the stone below it is the top block.
+    upper[0].set([0, 3, 0], block("minecraft:glass"));
+
+    let mut column = ChunkColumn::new(Dimension::Overworld);
+    assert!(column.insert(Arc::new(lower)));
+    assert!(column.insert(Arc::new(upper)));
+    assert!(!column.insert(Arc::new(SubChunk::empty(20))));
+    column.generate_heightmap();
+
+    assert_eq!(column.heightmap()[0][0], 6);
+    assert_eq!(column.heightmap()[1][1], 16);
+    assert_eq!(column.heightmap()[2][2], -64);
+    assert_eq!(column.subchunk_count(), 6);
+
+    let heightmap = Heightmap::new(column.y_to_index(0), &column);
+    assert_eq!(heightmap.map_type, HeightmapType::WithData);
+
+    let data = heightmap.data.unwrap();
+    assert_eq!(data[0], 6);
+    assert_eq!(data[1 << 4 | 1], 16);
+    assert_eq!(data[2 << 4 | 2], -1);
+
+    let above = Heightmap::new(column.y_to_index(32), &column);
+    assert_eq!(above.map_type, HeightmapType::TooHigh);
+}
+
+#[test]
+fn float_arguments() {
+    use proto::bedrock::{Command, CommandDataType, CommandOverload, CommandParameter, CommandPermissionLevel};
+
+    use crate::command::ParsedCommand;
+
+    let parameter = |name: &str| CommandParameter {
+        name: name.to_owned(),
+        data_type: CommandDataType::Float,
+        optional: false,
+        options: 0,
+        command_enum: None,
+        suffix: String::new(),
+    };
+
+    let syntax = Command {
+        name: "tp".to_owned(),
+        description: String::new(),
+        permission_level: CommandPermissionLevel::Normal,
+        aliases: Vec::new(),
+        overloads: vec![CommandOverload { parameters: vec![parameter("x"), parameter("y")] }],
+    };
+
+    let parsed = ParsedCommand::default_parser(&syntax, "/tp 1.5 -64").unwrap();
+    assert_eq!(parsed.parameters["x"].as_float(), Some(1.5));
+    assert_eq!(parsed.parameters["y"].as_float(), Some(-64.0));
+
+    assert!(ParsedCommand::default_parser(&syntax, "/tp 1.5 up").is_err());
+}
+
+/// Builds a zip archive containing the given files, which are deflated if `deflate` is set.
+fn zip_archive(files: &[(&str, &[u8], bool)]) -> Vec<u8> {
+    use std::io::Write;
+
+    use flate2::{write::DeflateEncoder, Compression};
+
+    let mut data = Vec::new();
+    let mut directory = Vec::new();
+    for &(name, contents, deflate) in files {
+        let (method, stored) = if deflate {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(contents).unwrap();
+            (8u16, encoder.finish().unwrap())
+        } else {
+            (0u16, contents.to_vec())
+        };
+
+        let offset = data.len() as u32;
+        data.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        data.extend_from_slice(&[20, 0, 0, 0]);
+        data.extend_from_slice(&method.to_le_bytes());
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(&(stored.len() as u32).to_le_bytes());
+        data.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+        data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(name.as_bytes());
+        data.extend_from_slice(&stored);
+
+        directory.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        directory.extend_from_slice(&[20, 0, 20, 0, 0, 0]);
+        directory.extend_from_slice(&method.to_le_bytes());
+        directory.extend_from_slice(&[0; 8]);
+        directory.extend_from_slice(&(stored.len() as u32).to_le_bytes());
+        directory.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+        directory.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        directory.extend_from_slice(&[0; 12]);
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+    }
+
+    let directory_offset = data.len() as u32;
+    data.extend_from_slice(&directory);
+    data.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    data.extend_from_slice(&[0; 4]);
+    data.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    data.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    data.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    data.extend_from_slice(&directory_offset.to_le_bytes());
+    data.extend_from_slice(&0u16.to_le_bytes());
+    data
+}
+
+/// Creates a manifest for a resource pack with the given UUID and dependencies.
+fn pack_manifest(uuid: &str, dependencies: &[&str]) -> String {
+    let dependencies = dependencies
+        .iter()
+        .map(|uuid| serde_json::json!({ "uuid": uuid, "version": [1, 0, 0] }))
+        .collect::<Vec<_>>();
+
+    serde_json::json!({
+        "format_version": 2,
+        "header": { "name": uuid, "uuid": uuid, "version": [1, 0, 0] },
+        "modules": [{ "type": "resources", "uuid": "5b1e8f0c-0d3e-4f4e-9f0a-8f0c2a1d3e4f", "version": [1, 0, 0] }],
+        "dependencies": dependencies,
+    })
+    .to_string()
+}
+
+#[test]
+fn pack_archive() {
+    use crate::pack::Archive;
+
+    let text = b"Lorem ipsum dolor sit amet, lorem ipsum dolor sit amet.".repeat(16);
+    let data = zip_archive(&[("stored.txt", b"stored", false), ("pack\\deflated.txt", &text, true)]);
+
+    let archive = Archive::new(&data).unwrap();
+    assert_eq!(archive.entries().len(), 2);
+    assert_eq!(archive.read(archive.find("stored.txt").unwrap()).unwrap(), b"stored");
+    // Windows path separators are normalised.
+    assert_eq!(archive.read(archive.find("pack/deflated.txt").unwrap()).unwrap(), text);
+
+    // Truncating the archive removes the end of central directory record.
+    assert!(Archive::new(&data[..data.len() - 4]).is_err());
+    assert!(Archive::new(&[]).is_err());
+
+    // A central directory that claims more entries than it contains.
+    let mut truncated = data.clone();
+    let end = truncated.len() - 22;
+    truncated[end + 10..end + 12].copy_from_slice(&3u16.to_le_bytes());
+    assert!(Archive::new(&truncated).is_err());
+
+    // A central directory offset that points outside of the archive.
+    let mut corrupt = data.clone();
+    corrupt[end + 16..end + 20].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(Archive::new(&corrupt).is_err());
+
+    // A central directory offset that does not point to a directory header.
+    let mut corrupt = data.clone();
+    corrupt[end + 16..end + 20].copy_from_slice(&0u32.to_le_bytes());
+    assert!(Archive::new(&corrupt).is_err());
+
+    // A local header offset that points outside of the archive.
+    let mut corrupt = data;
+    let directory = u32::from_le_bytes(corrupt[end + 16..end + 20].try_into().unwrap()) as usize;
+    corrupt[directory + 42..directory + 46].copy_from_slice(&u32::MAX.to_le_bytes());
+    let archive = Archive::new(&corrupt).unwrap();
+    assert!(archive.read(&archive.entries()[0]).is_err());
+}
+
+#[test]
+fn pack_manifest_parsing() {
+    use crate::pack::{Manifest, PackKind};
+
+    let uuid = "0f9b3a58-5d0a-4c1e-8b7d-2e4a6c8e0a12";
+    let manifest = Manifest::parse(pack_manifest(uuid, &[uuid]).as_bytes()).unwrap();
+    assert_eq!(manifest.uuid.to_string(), uuid);
+    assert_eq!(manifest.version, "1.0.0");
+    assert_eq!(manifest.kind, PackKind::Resources);
+    assert_eq!(manifest.dependencies.len(), 1);
+
+    let without_header = serde_json::json!({ "modules": [{ "type": "resources" }] }).to_string();
+    assert!(Manifest::parse(without_header.as_bytes()).is_err());
+
+    let bad_uuid = pack_manifest("not-a-uuid", &[]);
+    assert!(Manifest::parse(bad_uuid.as_bytes()).is_err());
+
+    let bad_dependency = pack_manifest(uuid, &["0f9b3a58"]);
+    assert!(Manifest::parse(bad_dependency.as_bytes()).is_err());
+
+    let bad_version = serde_json::json!({
+        "header": { "uuid": uuid, "version": [1, 0] },
+        "modules": [{ "type": "resources" }],
+    })
+    .to_string();
+    assert!(Manifest::parse(bad_version.as_bytes()).is_err());
+
+    let without_modules = serde_json::json!({ "header": { "uuid": uuid, "version": "1.0.0" } }).to_string();
+    assert!(Manifest::parse(without_modules.as_bytes()).is_err());
+
+    assert!(Manifest::parse(b"{ \"header\": ").is_err());
+}
+
+#[test]
+fn pack_dependencies() {
+    use crate::pack::{Pack, ResourcePacks};
+
+    let pack = |uuid: &str, dependencies: &[&str]| {
+        let manifest = pack_manifest(uuid, dependencies);
+        Pack::from_archive(zip_archive(&[("manifest.json", manifest.as_bytes(), true)])).unwrap()
+    };
+    let uuids = |packs: &[Pack]| packs.iter().map(|pack| pack.manifest().uuid.to_string()).collect::<Vec<_>>();
+
+    let a = "00000000-0000-4000-8000-00000000000a";
+    let b = "00000000-0000-4000-8000-00000000000b";
+    let c = "00000000-0000-4000-8000-00000000000c";
+    let missing = "00000000-0000-4000-8000-0000000000ff";
+
+    // Dependencies come before the packs that depend on them.
+    let sorted = ResourcePacks::sort_dependencies(vec![pack(a, &[b]), pack(b, &[c]), pack(c, &[])]).unwrap();
+    assert_eq!(uuids(&sorted), [c, b, a]);
+
+    // Missing dependencies are skipped.
+    let sorted = ResourcePacks::sort_dependencies(vec![pack(a, &[missing]), pack(b, &[a])]).unwrap();
+    assert_eq!(uuids(&sorted), [a, b]);
+
+    // Cycles do not cause infinite recursion and every pack is kept exactly once.
+    let sorted = ResourcePacks::sort_dependencies(vec![pack(a, &[b]), pack(b, &[c]), pack(c, &[a])]).unwrap();
+    let mut found = uuids(&sorted);
+    found.sort();
+    assert_eq!(found, [a, b, c]);
+
+    let sorted = ResourcePacks::sort_dependencies(vec![pack(a, &[a])]).unwrap();
+    assert_eq!(uuids(&sorted), [a]);
+
+    assert!(ResourcePacks::sort_dependencies(vec![pack(a, &[]), pack(a, &[])]).is_err());
+}
+
+#[test]
+fn region_indices() {
+    use std::collections::HashSet;
+
+    use proto::types::Dimension;
+    use util::Vector;
+
+    use crate::level::io::r#box::BoxRegion;
+    use crate::level::io::radial::RadialRegion;
+    use crate::level::io::region::Region;
+
+    fn check_roundtrip<R: Region>(region: &R, expected: &HashSet<Vector<i32, 3>>) {
+        assert_eq!(region.len(), expected.len());
+
+        let mut seen = HashSet::new();
+        for index in 0..region.len() {
+            let coord = region.as_coord(index).unwrap();
+            assert!(expected.contains(&coord), "{coord:?} is not part of the region");
+            assert!(seen.insert(coord.clone()), "{coord:?} was returned twice");
+            assert_eq!(region.as_index(&coord), Some(index));
+        }
+        assert_eq!(region.as_coord(region.len()), None);
+        assert_eq!(region.clone().into_iter().collect::<HashSet<_>>(), seen);
+    }
+
+    let region = BoxRegion::from_bounds([3, -2, 5], [-1, 1, 7], Dimension::Overworld);
+    let mut expected = HashSet::new();
+    for x in -1..=3 {
+        for y in -2..=1 {
+            for z in 5..=7 {
+                expected.insert(Vector::from([x, y, z]));
+            }
+        }
+    }
+    check_roundtrip(&region, &expected);
+    assert_eq!(region.as_index(&Vector::from([4, 0, 6])), None);
+    assert_eq!(region.as_index(&Vector::from([0, 2, 6])), None);
+
+    for (center, radius) in [([0, 0], 0), ([0, 0], 1), ([0, 0], 5), ([12, -7], 3), ([-100, 40], 8)] {
+        let region = RadialRegion::from_center(center, radius, -2..3, Dimension::Nether);
+        let radius = radius as i32;
+
+        let mut expected = HashSet::new();
+        for x in -radius..=radius {
+            for z in -radius..=radius {
+                if x * x + z * z <= radius * radius {
+                    for y in -2..3 {
+                        expected.insert(Vector::from([center[0] + x, y, center[1] + z]));
+                    }
+                }
+            }
+        }
+        check_roundtrip(&region, &expected);
+
+        assert_eq!(region.as_index(&Vector::from([center[0] + radius + 1, 0, center[1]])), None);
+        assert_eq!(region.as_index(&Vector::from([center[0], 3, center[1]])), None);
+    }
+
+    // Large regions, such as the ones used for pre-generation, are indexed without visiting every row.
+    let radius: i32 = 3000;
+    let region = RadialRegion::from_center([40, -70], radius as usize, 0..1, Dimension::Overworld);
+    let expected: usize = (-radius..=radius)
+        .map(|z| (-radius..=radius).filter(|x| x * x + z * z <= radius * radius).count())
+        .sum();
+    assert_eq!(region.len(), expected);
+    for index in (0..region.len()).step_by(997).chain([region.len() - 1]) {
+        let coord = region.as_coord(index).unwrap();
+        let (x, z) = (coord.x - 40, coord.z + 70);
+        assert!(x * x + z * z <= radius * radius, "{coord:?} is not part of the region");
+        assert_eq!(region.as_index(&coord), Some(index));
+    }
+}
+
+#[test]
+fn region_slice() {
+    use proto::types::Dimension;
+    use util::Vector;
+
+    use crate::level::io::r#box::BoxRegion;
+    use crate::level::io::region::Region;
+    use crate::level::io::slice::RegionSlice;
+
+    // Ten subchunks along the x-axis.
+    let region = BoxRegion::from_bounds([0, 0, 0], [9, 0, 0], Dimension::Overworld);
+
+    let slice = RegionSlice::new(region.clone(), 3..6);
+    assert_eq!(slice.len(), 3);
+    assert_eq!(slice.as_coord(0), Some(Vector::from([3, 0, 0])));
+    assert_eq!(slice.as_coord(3), None);
+    assert_eq!(slice.as_index(&Vector::from([5, 0, 0])), Some(2));
+    assert_eq!(slice.as_index(&Vector::from([6, 0, 0])), None);
+    assert_eq!(slice.as_index(&Vector::from([2, 0, 0])), None);
+    assert_eq!(slice.into_iter().map(|coord| coord.x).collect::<Vec<_>>(), vec![3, 4, 5]);
+
+    // Ranges that extend past the end of the region are clamped.
+    let slice = RegionSlice::new(region.clone(), 8..20);
+    assert_eq!(slice.len(), 2);
+    assert_eq!(slice.into_iter().map(|coord| coord.x).collect::<Vec<_>>(), vec![8, 9]);
+
+    let slice = RegionSlice::new(region.clone(), 15..20);
+    assert!(slice.is_empty());
+    assert_eq!(slice.as_coord(0), None);
+
+    // An inverted range is empty instead of underflowing.
+    #[allow(clippy::reversed_empty_ranges)]
+    let slice = RegionSlice::new(region, 6..2);
+    assert!(slice.is_empty());
+    assert_eq!(slice.into_iter().count(), 0);
+}
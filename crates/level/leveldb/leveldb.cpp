@@ -186,6 +186,24 @@ LevelResult db_delete(void *database_ptr, const char *key, int key_size)
     return result;
 }
 
+void db_suspend_compaction(void *database_ptr)
+{
+    auto database = reinterpret_cast<Database *>(database_ptr);
+    database->database->SuspendCompaction();
+}
+
+void db_resume_compaction(void *database_ptr)
+{
+    auto database = reinterpret_cast<Database *>(database_ptr);
+    database->database->ResumeCompaction();
+}
+
+void db_compact(void *database_ptr)
+{
+    auto database = reinterpret_cast<Database *>(database_ptr);
+    database->database->CompactRange(nullptr, nullptr);
+}
+
 void buffer_destroy(char *array) { delete[] array; }
 
 SizedData iter_new(void *database)
@@ -240,6 +258,12 @@ void iter_next(void *iter_raw)
     iter->Next();
 }
 
+void iter_seek(void *iter_raw, const char *key, int key_size)
+{
+    auto iter = reinterpret_cast<leveldb::Iterator *>(iter_raw);
+    iter->Seek(leveldb::Slice(key, key_size));
+}
+
 bool iter_valid(const void *iter_raw)
 {
     auto iter = reinterpret_cast<const leveldb::Iterator *>(iter_raw);
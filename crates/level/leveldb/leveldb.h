@@ -43,6 +43,17 @@ struct LevelResult db_put(void *database_ptr, const char *key,
 /// Removes a key from the database.
 struct LevelResult db_delete(void *database_ptr, const char *key, int key_size);
 
+// Suspends background compaction, so that no table files are created or removed.
+// Writes still go to the log file.
+void db_suspend_compaction(void *database);
+
+// Resumes background compaction after a call to db_suspend_compaction.
+void db_resume_compaction(void *database);
+
+// Compacts the entire key range of the database.
+// This blocks until the compaction has finished.
+void db_compact(void *database);
+
 // Deallocates a string previously allocated by another function.
 void buffer_destroy(char *array);
 
@@ -68,6 +79,10 @@ bool iter_valid(const void *iter);
 // This position could be invalid.
 void iter_next(void *iter);
 
+// Moves the iterator to the first key that is at or after the given key.
+// This position could be invalid.
+void iter_seek(void *iter, const char *key, int key_size);
+
 // Batched writes
 // //////////////////////////////////////
 
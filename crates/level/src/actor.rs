@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use util::{BinaryRead, Vector};
+
+/// An actor (also known as an entity) stored in a chunk, such as a mob, item frame or armour stand.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Actor {
+    /// Type of the actor, such as `minecraft:cow`.
+    pub identifier: String,
+    /// ID that uniquely identifies this actor in the level.
+    ///
+    /// This is also used as the [`ACTOR_PREFIX`](crate::ACTOR_PREFIX) key of the actor.
+    pub unique_id: i64,
+    /// Position of the actor in world coordinates.
+    pub position: Vector<f32, 3>,
+    /// Yaw and pitch of the actor in degrees.
+    pub rotation: Vector<f32, 2>,
+    /// Velocity of the actor in blocks per tick.
+    pub motion: Vector<f32, 3>,
+    /// Remaining properties that are specific to the type of actor.
+    pub data: HashMap<String, nbt::Value>,
+}
+
+impl Actor {
+    /// Creates an actor from its NBT representation.
+    pub fn from_nbt(mut nbt: HashMap<String, nbt::Value>) -> anyhow::Result<Self> {
+        let Some(nbt::Value::String(identifier)) = nbt.remove("identifier") else {
+            anyhow::bail!("Actor is missing its identifier");
+        };
+
+        let Some(nbt::Value::Long(unique_id)) = nbt.remove("UniqueID") else {
+            anyhow::bail!("Actor {identifier} is missing its unique ID");
+        };
+
+        let Some(position) = nbt.remove("Pos").and_then(|pos| float_list(&pos)) else {
+            anyhow::bail!("Actor {identifier} is missing its position");
+        };
+
+        // Some actors, such as item frames, do not move and have no rotation or motion.
+        let rotation = nbt.remove("Rotation").and_then(|rot| float_list(&rot)).unwrap_or_default();
+        let motion = nbt.remove("Motion").and_then(|motion| float_list(&motion)).unwrap_or_default();
+
+        Ok(Self {
+            identifier,
+            unique_id,
+            position: Vector::from(position),
+            rotation: Vector::from(rotation),
+            motion: Vector::from(motion),
+            data: nbt,
+        })
+    }
+
+    /// Converts the actor into its NBT representation.
+    pub fn to_nbt(&self) -> HashMap<String, nbt::Value> {
+        let mut nbt = self.data.clone();
+
+        let list = |values: &[f32]| nbt::Value::List(values.iter().copied().map(nbt::Value::Float).collect());
+
+        nbt.insert(String::from("identifier"), nbt::Value::String(self.identifier.clone()));
+        nbt.insert(String::from("UniqueID"), nbt::Value::Long(self.unique_id));
+        nbt.insert(String::from("Pos"), list(self.position.as_ref()));
+        nbt.insert(String::from("Rotation"), list(self.rotation.as_ref()));
+        nbt.insert(String::from("Motion"), list(self.motion.as_ref()));
+
+        nbt
+    }
+
+    /// Reads all actors stored in a chunk using the legacy [`Entity`](crate::KeyType::Entity) format.
+    pub(crate) fn deserialize_legacy<'a, R>(mut reader: R) -> anyhow::Result<Vec<Self>>
+    where
+        R: BinaryRead<'a>,
+    {
+        let mut actors = Vec::new();
+        while !reader.eof() {
+            let (nbt, _) = nbt::from_le_bytes(&mut reader)?;
+            actors.push(Self::from_nbt(nbt)?);
+        }
+
+        Ok(actors)
+    }
+
+    /// Reads the list of actor IDs stored in a [`DIGEST_PREFIX`](crate::DIGEST_PREFIX) key.
+    pub fn deserialize_digest<'a, R>(mut reader: R) -> anyhow::Result<Vec<i64>>
+    where
+        R: BinaryRead<'a>,
+    {
+        let mut ids = Vec::with_capacity(reader.remaining() / 8);
+        while !reader.eof() {
+            ids.push(reader.read_i64_le()?);
+        }
+
+        Ok(ids)
+    }
+}
+
+/// Converts an NBT list of floats into an array.
+fn float_list<const N: usize>(value: &nbt::Value) -> Option<[f32; N]> {
+    let list = value.as_list()?;
+    if list.len() != N {
+        return None;
+    }
+
+    let mut array = [0.0; N];
+    for (entry, value) in array.iter_mut().zip(list) {
+        let nbt::Value::Float(value) = value else { return None };
+        *entry = *value;
+    }
+
+    Some(array)
+}
@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+/// First data version that uses the 1.13 "flattened" block states.
+const FLATTENING_VERSION: i32 = 1519;
+/// First data version where packed indices no longer span multiple longs (20w17a).
+const NON_SPANNING_VERSION: i32 = 2529;
+
+/// A Java Edition block state, such as `minecraft:oak_log[axis=y]`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct JavaBlockState {
+    /// Name of the block.
+    #[serde(rename = "Name")]
+    pub name: String,
+    /// Block-specific properties.
+    #[serde(rename = "Properties", default)]
+    pub properties: HashMap<String, String>,
+}
+
+impl JavaBlockState {
+    /// Creates the canonical string representation of this state, with the properties sorted by name.
+    pub fn to_state_string(&self) -> String {
+        if self.properties.is_empty() {
+            return self.name.clone();
+        }
+
+        let mut properties = self.properties.iter().map(|(k, v)| format!("{k}={v}")).collect::<Vec<_>>();
+        properties.sort_unstable();
+
+        format!("{}[{}]", self.name, properties.join(","))
+    }
+}
+
+/// Biomes of a Java chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JavaBiomes {
+    /// The chunk does not contain any biome data.
+    None,
+    /// Pre-1.18 numeric biome IDs.
+    ///
+    /// This either contains 256 IDs for every column (pre-1.15)
+    /// or 1024 IDs for every 4x4x4 cell in a 256 blocks high chunk.
+    Legacy(Vec<i32>),
+    /// Post-1.18 named biomes stored in each section, see [`JavaSection::biome_palette`].
+    Sectioned,
+}
+
+/// A 16x16x16 section of a Java chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JavaSection {
+    /// Vertical index of the section.
+    pub y: i8,
+    /// Block states used in this section.
+    pub palette: Vec<JavaBlockState>,
+    /// Indices into the palette, in YZX order.
+    pub indices: Box<[u16; 4096]>,
+    /// Biomes used in this section, only present in post-1.18 chunks.
+    pub biome_palette: Vec<String>,
+    /// Indices into the biome palette for every 4x4x4 cell, in YZX order.
+    pub biome_indices: Box<[u16; 64]>,
+}
+
+/// A decoded Java Edition chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JavaChunk {
+    /// Data version of the game that last saved this chunk.
+    pub data_version: i32,
+    /// Whether the chunk has been fully generated.
+    ///
+    /// Chunks that are still being generated (proto-chunks) should not be imported.
+    pub is_full: bool,
+    /// Sections that contain blocks.
+    pub sections: Vec<JavaSection>,
+    /// Biomes of the chunk.
+    pub biomes: JavaBiomes,
+}
+
+/// Block states of a post-1.18 section.
+#[derive(Deserialize)]
+struct RawContainer<T> {
+    palette: Vec<T>,
+    data: Option<Vec<i64>>,
+}
+
+/// A post-1.18 section.
+#[derive(Deserialize)]
+struct RawSection {
+    #[serde(rename = "Y")]
+    y: i8,
+    block_states: Option<RawContainer<JavaBlockState>>,
+    biomes: Option<RawContainer<String>>,
+}
+
+/// A pre-1.18 section.
+#[derive(Deserialize)]
+struct RawLegacySection {
+    #[serde(rename = "Y")]
+    y: i8,
+    #[serde(rename = "Palette")]
+    palette: Option<Vec<JavaBlockState>>,
+    #[serde(rename = "BlockStates")]
+    block_states: Option<Vec<i64>>,
+}
+
+/// The `Level` compound of pre-1.18 chunks.
+#[derive(Deserialize)]
+struct RawLevel {
+    #[serde(rename = "Status")]
+    status: Option<String>,
+    #[serde(rename = "Sections")]
+    sections: Option<Vec<RawLegacySection>>,
+    #[serde(rename = "Biomes")]
+    biomes: Option<Vec<i32>>,
+}
+
+/// Root compound of a chunk.
+#[derive(Deserialize)]
+struct RawChunk {
+    #[serde(rename = "DataVersion")]
+    data_version: Option<i32>,
+    #[serde(rename = "Status")]
+    status: Option<String>,
+    sections: Option<Vec<RawSection>>,
+    #[serde(rename = "Level")]
+    level: Option<RawLevel>,
+}
+
+impl JavaChunk {
+    /// Decodes a chunk from its decompressed big endian NBT representation.
+    pub fn from_nbt(mut data: &[u8]) -> anyhow::Result<Self> {
+        let (raw, _): (RawChunk, _) = nbt::from_be_bytes(&mut data)?;
+
+        let data_version = raw.data_version.unwrap_or(0);
+        if data_version < FLATTENING_VERSION {
+            anyhow::bail!(format!("Chunks from before 1.13 are not supported (data version {data_version})"));
+        }
+
+        let spanning = data_version < NON_SPANNING_VERSION;
+        if let Some(sections) = raw.sections {
+            let sections = sections
+                .into_iter()
+                .filter_map(|section| Self::decode_section(section).transpose())
+                .collect::<anyhow::Result<_>>()?;
+
+            Ok(Self {
+                data_version,
+                is_full: is_full_status(raw.status.as_deref()),
+                sections,
+                biomes: JavaBiomes::Sectioned,
+            })
+        } else if let Some(level) = raw.level {
+            let sections = level
+                .sections
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|section| Self::decode_legacy_section(section, spanning).transpose())
+                .collect::<anyhow::Result<_>>()?;
+
+            Ok(Self {
+                data_version,
+                is_full: is_full_status(level.status.as_deref()),
+                sections,
+                biomes: level.biomes.map_or(JavaBiomes::None, JavaBiomes::Legacy),
+            })
+        } else {
+            anyhow::bail!("Chunk contains neither sections nor a level compound");
+        }
+    }
+
+    /// Decodes a post-1.18 section, returning `None` if the section contains no blocks.
+    fn decode_section(raw: RawSection) -> anyhow::Result<Option<JavaSection>> {
+        // Sections above and below the world only contain lighting.
+        let Some(states) = raw.block_states else {
+            return Ok(None);
+        };
+
+        let indices = unpack_container(&states.palette, states.data.as_deref(), block_bits(states.palette.len()), false)?;
+        let (biome_palette, biome_indices) = match raw.biomes {
+            Some(biomes) => {
+                let bits = ceil_log2(biomes.palette.len());
+                let unpacked = unpack_longs(biomes.data.as_deref().unwrap_or_default(), bits, 64, false)?;
+
+                let mut indices = Box::new([0u16; 64]);
+                indices.copy_from_slice(&unpacked);
+
+                (biomes.palette, indices)
+            }
+            None => (Vec::new(), Box::new([0u16; 64])),
+        };
+
+        Ok(Some(JavaSection {
+            y: raw.y,
+            palette: states.palette,
+            indices,
+            biome_palette,
+            biome_indices,
+        }))
+    }
+
+    /// Decodes a 1.13 - 1.17 section, returning `None` if the section contains no blocks.
+    fn decode_legacy_section(raw: RawLegacySection, spanning: bool) -> anyhow::Result<Option<JavaSection>> {
+        let Some(palette) = raw.palette else {
+            return Ok(None);
+        };
+
+        let indices = unpack_container(&palette, raw.block_states.as_deref(), block_bits(palette.len()), spanning)?;
+        Ok(Some(JavaSection {
+            y: raw.y,
+            palette,
+            indices,
+            biome_palette: Vec::new(),
+            biome_indices: Box::new([0u16; 64]),
+        }))
+    }
+}
+
+/// Whether the given chunk status marks a fully generated chunk.
+fn is_full_status(status: Option<&str>) -> bool {
+    // Chunks without a status are from before the status was introduced and are always complete.
+    matches!(status, None | Some("full" | "minecraft:full" | "postprocessed" | "fullchunk"))
+}
+
+/// Smallest amount of bits that can represent `len` different values.
+#[inline]
+const fn ceil_log2(len: usize) -> u32 {
+    if len <= 1 {
+        0
+    } else {
+        usize::BITS - (len - 1).leading_zeros()
+    }
+}
+
+/// Amount of bits used per block state index, which is at least 4.
+#[inline]
+fn block_bits(len: usize) -> u32 {
+    ceil_log2(len).max(4)
+}
+
+/// Unpacks the block state indices of a section.
+fn unpack_container<T>(palette: &[T], data: Option<&[i64]>, bits: u32, spanning: bool) -> anyhow::Result<Box<[u16; 4096]>> {
+    let mut indices = Box::new([0u16; 4096]);
+    match data {
+        // Sections with a single entry in the palette have no data.
+        None if palette.len() <= 1 => {}
+        None => anyhow::bail!("Section with multiple block states is missing its data"),
+        Some(data) => indices.copy_from_slice(&unpack_longs(data, bits, 4096, spanning)?),
+    }
+
+    if indices.iter().any(|index| *index as usize >= palette.len()) {
+        anyhow::bail!("Section contains an index that is out of the palette range");
+    }
+
+    Ok(indices)
+}
+
+/// Unpacks `count` indices of `bits` bits each from an array of longs.
+///
+/// If `spanning` is true, indices can be split over two longs, as is the case in chunks from before 1.16.
+/// Otherwise, the remaining bits of a long are left unused when the next index does not fit.
+pub fn unpack_longs(data: &[i64], bits: u32, count: usize, spanning: bool) -> anyhow::Result<Vec<u16>> {
+    if bits == 0 {
+        return Ok(vec![0; count]);
+    }
+
+    let bits = bits as usize;
+    let mask = (1u64 << bits) - 1;
+
+    let expected = if spanning {
+        (count * bits + 63) / 64
+    } else {
+        (count + 64 / bits - 1) / (64 / bits)
+    };
+
+    if data.len() < expected {
+        anyhow::bail!(format!("Packed array contains {} longs, expected {expected}", data.len()));
+    }
+
+    let mut indices = Vec::with_capacity(count);
+    if spanning {
+        for i in 0..count {
+            let bit = i * bits;
+            let (word, offset) = (bit / 64, bit % 64);
+
+            let mut value = data[word] as u64 >> offset;
+            if offset + bits > 64 {
+                value |= (data[word + 1] as u64) << (64 - offset);
+            }
+
+            indices.push((value & mask) as u16);
+        }
+    } else {
+        let per_long = 64 / bits;
+        for i in 0..count {
+            let value = data[i / per_long] as u64 >> ((i % per_long) * bits);
+            indices.push((value & mask) as u16);
+        }
+    }
+
+    Ok(indices)
+}
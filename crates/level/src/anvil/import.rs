@@ -0,0 +1,408 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use proto::types::Dimension;
+use util::Vector;
+
+use crate::provider::{Provider, CHUNK_VERSION};
+use crate::{subchunk_range, to_offset, BiomeEncoding, BiomeStorage, Biomes, DataKey, KeyType, PaletteEntry, Storage, SubChunk, SubChunkVersion, SubStorage};
+
+use super::{bedrock_biome, bedrock_legacy_biome, BlockMapping, JavaBiomes, JavaBlockState, JavaChunk, RegionFile};
+
+/// Statistics collected while importing a world.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ImportReport {
+    /// Amount of chunks that were written to the database.
+    pub chunks: usize,
+    /// Amount of chunks that were skipped, because they were incomplete or could not be decoded.
+    pub skipped: usize,
+    /// Java block states that are not in the mapping table, with the amount of sections they occurred in.
+    ///
+    /// These blocks were replaced with [`UNKNOWN_BLOCK`](super::UNKNOWN_BLOCK).
+    pub unmapped_blocks: HashMap<String, usize>,
+    /// Total amount of blocks that were replaced with [`UNKNOWN_BLOCK`](super::UNKNOWN_BLOCK), because their state was not in the mapping table.
+    pub unknown_blocks: usize,
+    /// Java biomes that have no Bedrock equivalent, with the amount of sections they occurred in.
+    ///
+    /// These biomes were imported as plains.
+    pub unmapped_biomes: HashMap<String, usize>,
+}
+
+/// A Java chunk converted to the Bedrock format.
+#[derive(Debug, PartialEq)]
+pub struct ConvertedChunk {
+    /// Subchunks that contain blocks, other subchunks are empty.
+    pub subchunks: Vec<SubChunk>,
+    /// Biomes and heightmap of the chunk.
+    pub biomes: Biomes,
+}
+
+/// Converts Java chunks to Bedrock subchunks and biomes.
+pub struct ChunkConverter {
+    /// Translation table for block states.
+    mapping: BlockMapping,
+    /// Statistics of the chunks converted so far.
+    report: ImportReport,
+}
+
+impl ChunkConverter {
+    /// Creates a converter that uses the given block mapping.
+    pub fn new(mapping: BlockMapping) -> Self {
+        Self { mapping, report: ImportReport::default() }
+    }
+
+    /// Statistics of the chunks converted so far.
+    #[inline]
+    pub const fn report(&self) -> &ImportReport {
+        &self.report
+    }
+
+    /// Converts a chunk.
+    ///
+    /// Sections outside of the height range of the Bedrock dimension are dropped.
+    pub fn convert(&mut self, chunk: &JavaChunk, dimension: Dimension) -> ConvertedChunk {
+        let range = subchunk_range(dimension);
+        let min_y = i32::from(range.start) * 16;
+
+        let mut heightmap = Box::new([[0u16; 16]; 16]);
+        let mut subchunks = Vec::new();
+
+        for section in &chunk.sections {
+            if !range.contains(&section.y) {
+                continue;
+            }
+
+            // Translate the palette, merging Java states that map to the same Bedrock block.
+            let mut palette: Vec<PaletteEntry> = Vec::new();
+            let mut remap = Vec::with_capacity(section.palette.len());
+            let mut waterlogged = Vec::with_capacity(section.palette.len());
+            let mut unmapped = Vec::with_capacity(section.palette.len());
+            for state in &section.palette {
+                let (entry, mapped) = self.translate(state);
+                unmapped.push(!mapped);
+                let index = palette.iter().position(|e| *e == entry).unwrap_or_else(|| {
+                    palette.push(entry);
+                    palette.len() - 1
+                });
+
+                remap.push(index as u16);
+                waterlogged.push(state.properties.get("waterlogged").is_some_and(|w| w == "true"));
+            }
+
+            let mut blocks = Box::new([0u16; 4096]);
+            let mut water: Option<Box<[u16; 4096]>> = None;
+            for (java, index) in section.indices.iter().enumerate() {
+                // Java indices are in YZX order, Bedrock offsets in XZY.
+                let (x, z, y) = (java & 0xf, (java >> 4) & 0xf, java >> 8);
+                let offset = to_offset(Vector::from([x as u8, y as u8, z as u8]));
+
+                let index = *index as usize;
+                blocks[offset] = remap[index];
+
+                if unmapped[index] {
+                    self.report.unknown_blocks += 1;
+                }
+
+                if waterlogged[index] {
+                    water.get_or_insert_with(|| Box::new([0u16; 4096]))[offset] = 1;
+                }
+
+                if !is_air(&palette[remap[index] as usize]) {
+                    let height = (i32::from(section.y) * 16 + y as i32 - min_y + 1) as u16;
+                    heightmap[z][x] = heightmap[z][x].max(height);
+                }
+            }
+
+            if water.is_none() && palette.iter().all(is_air) {
+                continue;
+            }
+
+            let mut layers = vec![SubStorage { indices: blocks, palette }];
+            if let Some(indices) = water {
+                // Waterlogged blocks store their water in the second layer.
+                let water = PaletteEntry {
+                    name: String::from("minecraft:water"),
+                    version: self.mapping.version(),
+                    states: HashMap::from([(String::from("liquid_depth"), nbt::Value::Int(0))]),
+                };
+
+                layers.push(SubStorage {
+                    indices,
+                    palette: vec![PaletteEntry::air(self.mapping.version()), water],
+                });
+            }
+
+            subchunks.push(SubChunk {
+                version: SubChunkVersion::Limitless,
+                index: section.y,
+                layers,
+            });
+        }
+
+        let fragments = self.convert_biomes(chunk, dimension);
+        ConvertedChunk {
+            subchunks,
+            biomes: Biomes { heightmap, fragments },
+        }
+    }
+
+    /// Translates a single block state, recording it in the report if it is not in the mapping.
+    ///
+    /// Also returns whether the state was in the mapping.
+    fn translate(&mut self, state: &JavaBlockState) -> (PaletteEntry, bool) {
+        match self.mapping.translate(state) {
+            Some(entry) => (entry, true),
+            None => {
+                *self.report.unmapped_blocks.entry(state.to_state_string()).or_default() += 1;
+                (self.mapping.unknown(), false)
+            }
+        }
+    }
+
+    /// Creates a biome fragment for every subchunk in the dimension.
+    fn convert_biomes(&mut self, chunk: &JavaChunk, dimension: Dimension) -> Vec<BiomeEncoding> {
+        let mut fragments = subchunk_range(dimension).map(|y| self.section_biomes(chunk, y)).collect::<Vec<_>>();
+
+        // Subchunks without biome data take the biomes of the nearest subchunk below them,
+        // or above them if there is none.
+        for i in 1..fragments.len() {
+            if fragments[i].is_none() {
+                fragments[i] = fragments[i - 1].clone();
+            }
+        }
+
+        let first = fragments.iter().flatten().next().cloned();
+        let fallback = Box::new([default_biome(dimension); 4096]);
+        fragments
+            .into_iter()
+            .map(|ids| {
+                let ids = ids.or_else(|| first.clone()).unwrap_or_else(|| fallback.clone());
+                encode_biomes(&ids)
+            })
+            .collect()
+    }
+
+    /// Reads the Bedrock biome IDs of a subchunk in XZY order, if the chunk has biome data for it.
+    fn section_biomes(&mut self, chunk: &JavaChunk, y: i8) -> Option<Box<[u32; 4096]>> {
+        let mut ids = Box::new([0u32; 4096]);
+        match &chunk.biomes {
+            JavaBiomes::None => return None,
+            JavaBiomes::Sectioned => {
+                let section = chunk.sections.iter().find(|s| s.y == y && !s.biome_palette.is_empty())?;
+                let palette = section
+                    .biome_palette
+                    .iter()
+                    .map(|name| {
+                        bedrock_biome(name).unwrap_or_else(|| {
+                            *self.report.unmapped_biomes.entry(name.clone()).or_default() += 1;
+                            1
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                for (offset, id) in ids.iter_mut().enumerate() {
+                    let (x, z, y) = (offset >> 8, (offset >> 4) & 0xf, offset & 0xf);
+                    let cell = (y >> 2) << 4 | (z >> 2) << 2 | x >> 2;
+                    *id = palette.get(section.biome_indices[cell] as usize).copied().unwrap_or(1);
+                }
+            }
+            // Biomes for every 4x4x4 cell in a 256 blocks high chunk.
+            JavaBiomes::Legacy(legacy) if legacy.len() == 1024 => {
+                if !(0..16).contains(&y) {
+                    return None;
+                }
+
+                for (offset, id) in ids.iter_mut().enumerate() {
+                    let (x, z, local_y) = (offset >> 8, (offset >> 4) & 0xf, offset & 0xf);
+                    let world_y = y as usize * 16 + local_y;
+                    let cell = (world_y >> 2) << 4 | (z >> 2) << 2 | x >> 2;
+                    *id = bedrock_legacy_biome(legacy[cell]);
+                }
+            }
+            // Biomes for every column, these apply to every height.
+            JavaBiomes::Legacy(legacy) if legacy.len() == 256 => {
+                for (offset, id) in ids.iter_mut().enumerate() {
+                    let (x, z) = (offset >> 8, (offset >> 4) & 0xf);
+                    *id = bedrock_legacy_biome(legacy[z << 4 | x]);
+                }
+            }
+            JavaBiomes::Legacy(_) => return None,
+        }
+
+        Some(ids)
+    }
+}
+
+/// Imports Java Edition worlds into a Bedrock level database.
+///
+/// Java worlds store their chunks in `.mca` region files, each containing up to 32 by 32 compressed chunks.
+/// The importer reads these regions, translates the block states using a [`BlockMapping`]
+/// and writes the resulting subchunks and biomes into the database.
+///
+/// ```ignore
+/// let mapping = BlockMapping::from_json(&std::fs::read_to_string("blocks.json")?, Some([1, 20, 60, 1]))?;
+/// let mut importer = Importer::new(&database, mapping);
+/// importer.import_world("java_world")?;
+///
+/// let report = importer.report();
+/// println!("{} blocks were not mapped", report.unknown_blocks);
+/// for (state, count) in &report.unmapped_blocks {
+///     println!("{state} was not mapped ({count} sections)");
+/// }
+/// ```
+pub struct Importer<'a> {
+    /// Storage to write the converted chunks to.
+    database: &'a dyn Storage,
+    /// Converts the chunks and keeps track of the statistics.
+    converter: ChunkConverter,
+}
+
+impl<'a> Importer<'a> {
+    /// Creates an importer that writes into the given storage.
+    pub fn new(database: &'a dyn Storage, mapping: BlockMapping) -> Self {
+        Self {
+            database,
+            converter: ChunkConverter::new(mapping),
+        }
+    }
+
+    /// Statistics of the import so far.
+    #[inline]
+    pub const fn report(&self) -> &ImportReport {
+        self.converter.report()
+    }
+
+    /// Consumes the importer and returns the final statistics.
+    #[inline]
+    pub fn into_report(self) -> ImportReport {
+        self.converter.report
+    }
+
+    /// Imports every region of a Java world directory.
+    ///
+    /// The overworld is read from `region`, the nether from `DIM-1/region` and the end from `DIM1/region`.
+    /// Dimensions that do not exist are skipped.
+    pub fn import_world<P>(&mut self, path: P) -> anyhow::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let dimensions = [
+            ("region", Dimension::Overworld),
+            ("DIM-1/region", Dimension::Nether),
+            ("DIM1/region", Dimension::End),
+        ];
+
+        for (directory, dimension) in dimensions {
+            let directory = path.join(directory);
+            if !directory.is_dir() {
+                continue;
+            }
+
+            for entry in std::fs::read_dir(&directory)? {
+                let file = entry?.path();
+                if file.extension().is_some_and(|ext| ext == "mca") {
+                    self.import_region_file(&file, dimension)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Imports a single `.mca` region file into the given dimension.
+    pub fn import_region_file<P>(&mut self, path: P, dimension: Dimension) -> anyhow::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let region = RegionFile::open(path)?;
+        self.import_region(&region, dimension)
+    }
+
+    /// Imports all chunks in a region.
+    ///
+    /// Chunks that fail to decode are skipped and counted in the report,
+    /// while failures to write to the database abort the import.
+    pub fn import_region(&mut self, region: &RegionFile, dimension: Dimension) -> anyhow::Result<()> {
+        let base = region.coordinates().clone();
+        for local in region.chunks() {
+            let coordinates = Vector::from([base.x * 32 + i32::from(local.x), base.y * 32 + i32::from(local.y)]);
+
+            let chunk = match region
+                .chunk(&local)
+                .and_then(|data| data.map(|data| JavaChunk::from_nbt(&data)).transpose())
+            {
+                Ok(Some(chunk)) if chunk.is_full => chunk,
+                Ok(_) => {
+                    self.converter.report.skipped += 1;
+                    continue;
+                }
+                Err(err) => {
+                    tracing::warn!("Skipping Java chunk {coordinates:?}: {err:#}");
+                    self.converter.report.skipped += 1;
+                    continue;
+                }
+            };
+
+            self.import_chunk(coordinates, &chunk, dimension)?;
+        }
+
+        Ok(())
+    }
+
+    /// Converts a chunk and writes it to the database, replacing any existing subchunks.
+    pub fn import_chunk(&mut self, coordinates: Vector<i32, 2>, chunk: &JavaChunk, dimension: Dimension) -> anyhow::Result<()> {
+        let converted = self.converter.convert(chunk, dimension);
+
+        let mut batch = Provider::batch();
+        for index in subchunk_range(dimension) {
+            match converted.subchunks.iter().find(|sub| sub.index == index) {
+                Some(subchunk) => Provider::batch_subchunk(&mut batch, [coordinates.x, i32::from(index), coordinates.y], dimension, subchunk)?,
+                None => batch.delete_data(DataKey {
+                    coordinates: coordinates.clone(),
+                    dimension,
+                    data: KeyType::SubChunk { index },
+                })?,
+            }
+        }
+
+        Provider::batch_biomes(&mut batch, coordinates.clone(), dimension, &converted.biomes)?;
+        Provider::batch_version(&mut batch, coordinates, dimension, CHUNK_VERSION)?;
+        self.database.execute(&batch)?;
+
+        self.converter.report.chunks += 1;
+        Ok(())
+    }
+}
+
+/// Biome used for subchunks without any biome data.
+pub(crate) const fn default_biome(dimension: Dimension) -> u32 {
+    match dimension {
+        Dimension::Overworld => 1,
+        Dimension::Nether => 8,
+        Dimension::End => 9,
+    }
+}
+
+/// Whether the entry is air.
+fn is_air(entry: &PaletteEntry) -> bool {
+    entry.name == "minecraft:air"
+}
+
+/// Encodes the biome IDs of a subchunk, using a single biome if all IDs are equal.
+fn encode_biomes(ids: &[u32; 4096]) -> BiomeEncoding {
+    if ids.iter().all(|id| *id == ids[0]) {
+        return BiomeEncoding::Single(ids[0]);
+    }
+
+    let mut palette = Vec::new();
+    let mut indices = Box::new([0u16; 4096]);
+    for (index, id) in indices.iter_mut().zip(ids.iter()) {
+        *index = palette.iter().position(|p| p == id).unwrap_or_else(|| {
+            palette.push(*id);
+            palette.len() - 1
+        }) as u16;
+    }
+
+    BiomeEncoding::Paletted(BiomeStorage { indices, palette })
+}
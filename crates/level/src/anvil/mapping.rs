@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::PaletteEntry;
+
+use super::JavaBlockState;
+
+/// Java blocks that are translated to air without needing an entry in the mapping table.
+const AIR_BLOCKS: &[&str] = &["minecraft:air", "minecraft:cave_air", "minecraft:void_air"];
+
+/// Bedrock block used for Java blocks that are not in the mapping table.
+pub const UNKNOWN_BLOCK: &str = "minecraft:unknown";
+
+/// Entry in a JSON block mapping file.
+///
+/// The Geyser `bedrock_identifier` and `bedrock_states` keys are accepted as well.
+#[derive(Deserialize)]
+pub(crate) struct MappingEntry {
+    /// Name of the Bedrock block.
+    #[serde(alias = "bedrock_identifier")]
+    pub name: String,
+    /// States of the Bedrock block.
+    #[serde(default, alias = "bedrock_states")]
+    pub states: HashMap<String, serde_json::Value>,
+}
+
+impl MappingEntry {
+    /// Converts this entry into a palette entry with the given version.
+    ///
+    /// Boolean states are converted to bytes and numbers to ints.
+    /// `key` is the key of the entry in the mapping file and is only used in error messages.
+    pub fn into_palette_entry(self, key: &str, version: Option<[u8; 4]>) -> anyhow::Result<PaletteEntry> {
+        let mut states = HashMap::with_capacity(self.states.len());
+        for (state, value) in self.states {
+            let value = match value {
+                serde_json::Value::Bool(b) => nbt::Value::Byte(b as i8),
+                serde_json::Value::String(s) => nbt::Value::String(s),
+                serde_json::Value::Number(n) => match n.as_i64().and_then(|n| i32::try_from(n).ok()) {
+                    Some(n) => nbt::Value::Int(n),
+                    None => anyhow::bail!(format!("State {state} of {key} is not a 32-bit integer")),
+                },
+                _ => anyhow::bail!(format!("State {state} of {key} has an unsupported type")),
+            };
+
+            states.insert(state, value);
+        }
+
+        Ok(PaletteEntry { name: self.name, version, states })
+    }
+}
+
+/// Translates Java block states to Bedrock palette entries.
+///
+/// States are first looked up using their full state string, such as `minecraft:oak_log[axis=y]`.
+/// If that fails, the entry for the block name without properties is used, if it exists.
+#[derive(Debug, Clone)]
+pub struct BlockMapping {
+    /// Bedrock blocks indexed by their sorted Java state string.
+    states: HashMap<String, PaletteEntry>,
+    /// Version assigned to every translated palette entry.
+    version: Option<[u8; 4]>,
+}
+
+impl BlockMapping {
+    /// Creates an empty mapping.
+    ///
+    /// Every entry created by this mapping will be assigned the given block version.
+    pub fn new(version: Option<[u8; 4]>) -> Self {
+        let mut mapping = Self { states: HashMap::new(), version };
+        for air in AIR_BLOCKS {
+            mapping.insert(air, PaletteEntry::air(version));
+        }
+
+        mapping
+    }
+
+    /// Loads a mapping table from JSON.
+    ///
+    /// The JSON should be an object mapping Java state strings to Bedrock blocks:
+    ///
+    /// ```json
+    /// {
+    ///     "minecraft:oak_log[axis=y]": { "name": "minecraft:oak_log", "states": { "pillar_axis": "y" } }
+    /// }
+    /// ```
+    ///
+    /// Boolean states are converted to bytes and numbers to ints.
+    pub fn from_json(json: &str, version: Option<[u8; 4]>) -> anyhow::Result<Self> {
+        let entries: HashMap<String, MappingEntry> = serde_json::from_str(json)?;
+
+        let mut mapping = Self::new(version);
+        for (java, entry) in entries {
+            let entry = entry.into_palette_entry(&java, version)?;
+            mapping.insert(&java, entry);
+        }
+
+        Ok(mapping)
+    }
+
+    /// Adds a translation for a Java state string, such as `minecraft:stone` or `minecraft:oak_log[axis=y]`.
+    pub fn insert(&mut self, java: &str, bedrock: PaletteEntry) {
+        self.states.insert(canonicalize(java), bedrock);
+    }
+
+    /// Amount of translations in this mapping.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Whether this mapping has no translations.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// Translates a Java block state.
+    ///
+    /// Waterlogged states fall back to their dry variant, as Bedrock stores water in a separate layer.
+    pub fn translate(&self, state: &JavaBlockState) -> Option<PaletteEntry> {
+        if let Some(entry) = self.states.get(&state.to_state_string()) {
+            return Some(entry.clone());
+        }
+
+        if state.properties.get("waterlogged").is_some_and(|w| w == "true") {
+            let mut dry = state.clone();
+            dry.properties.insert(String::from("waterlogged"), String::from("false"));
+            if let Some(entry) = self.states.get(&dry.to_state_string()) {
+                return Some(entry.clone());
+            }
+
+            dry.properties.remove("waterlogged");
+            if let Some(entry) = self.states.get(&dry.to_state_string()) {
+                return Some(entry.clone());
+            }
+        }
+
+        self.states.get(&state.name).cloned()
+    }
+
+    /// Creates the block that Java states without a translation are replaced with.
+    ///
+    /// Bedrock shows this as the `minecraft:unknown` block, which makes missing translations easy to spot.
+    pub fn unknown(&self) -> PaletteEntry {
+        PaletteEntry {
+            name: String::from(UNKNOWN_BLOCK),
+            version: self.version,
+            states: HashMap::new(),
+        }
+    }
+
+    /// Version assigned to translated entries.
+    #[inline]
+    pub const fn version(&self) -> Option<[u8; 4]> {
+        self.version
+    }
+}
+
+/// Sorts the properties of a state string, so that the order in the mapping file does not matter.
+fn canonicalize(java: &str) -> String {
+    let Some((name, properties)) = java.strip_suffix(']').and_then(|s| s.split_once('[')) else {
+        return java.to_owned();
+    };
+
+    let mut properties = properties.split(',').map(str::trim).collect::<Vec<_>>();
+    properties.sort_unstable();
+
+    format!("{name}[{}]", properties.join(","))
+}
+
+/// Translates a Java biome name into a Bedrock biome ID.
+pub fn bedrock_biome(name: &str) -> Option<u32> {
+    let name = name.strip_prefix("minecraft:").unwrap_or(name);
+    Some(match name {
+        "ocean" => 0,
+        "plains" => 1,
+        "desert" => 2,
+        "windswept_hills" => 3,
+        "forest" => 4,
+        "taiga" => 5,
+        "swamp" => 6,
+        "river" => 7,
+        "nether_wastes" => 8,
+        "the_end" | "small_end_islands" | "end_midlands" | "end_highlands" | "end_barrens" => 9,
+        "frozen_river" => 11,
+        "snowy_plains" => 12,
+        "mushroom_fields" => 14,
+        "beach" => 16,
+        "jungle" => 21,
+        "sparse_jungle" => 23,
+        "deep_ocean" => 24,
+        "stony_shore" => 25,
+        "snowy_beach" => 26,
+        "birch_forest" => 27,
+        "dark_forest" => 29,
+        "snowy_taiga" => 30,
+        "old_growth_pine_taiga" => 32,
+        "windswept_forest" => 34,
+        "savanna" => 35,
+        "savanna_plateau" => 36,
+        "badlands" => 37,
+        "wooded_badlands" => 38,
+        "warm_ocean" => 40,
+        "deep_warm_ocean" => 41,
+        "lukewarm_ocean" => 42,
+        "deep_lukewarm_ocean" => 43,
+        "cold_ocean" => 44,
+        "deep_cold_ocean" => 45,
+        "frozen_ocean" => 46,
+        "deep_frozen_ocean" => 47,
+        "bamboo_jungle" => 48,
+        "sunflower_plains" => 129,
+        "windswept_gravelly_hills" => 131,
+        "flower_forest" => 132,
+        "ice_spikes" => 140,
+        "old_growth_birch_forest" => 155,
+        "old_growth_spruce_taiga" => 160,
+        "windswept_savanna" => 163,
+        "eroded_badlands" => 165,
+        "soul_sand_valley" => 178,
+        "crimson_forest" => 179,
+        "warped_forest" => 180,
+        "basalt_deltas" => 181,
+        "jagged_peaks" => 182,
+        "frozen_peaks" => 183,
+        "snowy_slopes" => 184,
+        "grove" => 185,
+        "meadow" => 186,
+        "lush_caves" => 187,
+        "dripstone_caves" => 188,
+        "stony_peaks" => 189,
+        "deep_dark" => 190,
+        "mangrove_swamp" => 191,
+        "cherry_grove" => 192,
+        "the_void" => 1,
+        _ => return None,
+    })
+}
+
+/// Translates a pre-1.18 numeric Java biome ID into a Bedrock biome ID.
+///
+/// Most IDs are shared between editions, only the biomes that were added later differ.
+pub const fn bedrock_legacy_biome(id: i32) -> u32 {
+    match id {
+        // The end islands biomes do not exist in Bedrock.
+        40..=43 => 9,
+        44 => 40,
+        45 => 42,
+        46 => 44,
+        47 => 41,
+        48 => 43,
+        49 => 45,
+        50 => 47,
+        // The void.
+        127 => 1,
+        168 => 48,
+        169 => 49,
+        170 => 178,
+        171 => 179,
+        172 => 180,
+        173 => 181,
+        id if id < 0 => 1,
+        id => id as u32,
+    }
+}
@@ -0,0 +1,9 @@
+mod chunk;
+mod import;
+mod mapping;
+mod region;
+
+pub use chunk::*;
+pub use import::*;
+pub use mapping::*;
+pub use region::*;
@@ -0,0 +1,157 @@
+use std::io::Read;
+use std::path::Path;
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+use util::Vector;
+
+/// Size of a single sector in a region file.
+const SECTOR_SIZE: usize = 4096;
+/// Size of the region header, consisting of the location table and the timestamp table.
+const HEADER_SIZE: usize = 2 * SECTOR_SIZE;
+/// Flag set on the compression type when the chunk is stored in a separate `.mcc` file.
+const EXTERNAL_FLAG: u8 = 0x80;
+
+/// Compression method used for a chunk in a region file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChunkCompression {
+    /// GZip compression (RFC1952), this is not used by vanilla.
+    Gzip = 1,
+    /// Zlib compression (RFC1950), used by default.
+    Zlib = 2,
+    /// The chunk is not compressed.
+    Uncompressed = 3,
+}
+
+impl TryFrom<u8> for ChunkCompression {
+    type Error = anyhow::Error;
+
+    fn try_from(v: u8) -> anyhow::Result<Self> {
+        Ok(match v {
+            1 => Self::Gzip,
+            2 => Self::Zlib,
+            3 => Self::Uncompressed,
+            4 => anyhow::bail!("LZ4 compressed chunks are not supported"),
+            _ => anyhow::bail!(format!("Invalid chunk compression type: {v}")),
+        })
+    }
+}
+
+/// A Java Edition `.mca` region file.
+///
+/// A region contains up to 32 by 32 chunks. The file starts with a table of sector offsets,
+/// followed by the compressed NBT data of each chunk.
+pub struct RegionFile {
+    /// Coordinates of this region, in regions.
+    coordinates: Vector<i32, 2>,
+    /// Raw contents of the file.
+    data: Vec<u8>,
+}
+
+impl RegionFile {
+    /// Reads the region file at the given path.
+    ///
+    /// The coordinates of the region are taken from the file name, which should have the form `r.<x>.<z>.mca`.
+    pub fn open<P>(path: P) -> anyhow::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let Some(coordinates) = Self::parse_name(path) else {
+            tracing::error!("Region file {} does not have a valid name", path.display());
+            anyhow::bail!("Region file {} does not have a valid name", path.display());
+        };
+
+        Self::from_bytes(coordinates, std::fs::read(path)?)
+    }
+
+    /// Creates a region from the raw contents of a region file.
+    pub fn from_bytes(coordinates: Vector<i32, 2>, data: Vec<u8>) -> anyhow::Result<Self> {
+        // Empty region files are created by the game when a region is first touched.
+        if !data.is_empty() && data.len() < HEADER_SIZE {
+            anyhow::bail!(format!("Region file is too small to contain a header: {} bytes", data.len()));
+        }
+
+        Ok(Self { coordinates, data })
+    }
+
+    /// Extracts the region coordinates from a file name of the form `r.<x>.<z>.mca`.
+    pub fn parse_name(path: &Path) -> Option<Vector<i32, 2>> {
+        let name = path.file_name()?.to_str()?;
+        let mut parts = name.strip_prefix("r.")?.strip_suffix(".mca")?.split('.');
+
+        let x = parts.next()?.parse().ok()?;
+        let z = parts.next()?.parse().ok()?;
+
+        parts.next().is_none().then(|| Vector::from([x, z]))
+    }
+
+    /// Coordinates of this region, in regions.
+    #[inline]
+    pub const fn coordinates(&self) -> &Vector<i32, 2> {
+        &self.coordinates
+    }
+
+    /// Returns the local coordinates of all chunks that are present in this region.
+    pub fn chunks(&self) -> Vec<Vector<u8, 2>> {
+        if self.data.is_empty() {
+            return Vec::new();
+        }
+
+        (0..1024)
+            .filter(|index| self.location(*index).0 != 0)
+            .map(|index| Vector::from([(index & 31) as u8, (index >> 5) as u8]))
+            .collect()
+    }
+
+    /// Reads and decompresses the NBT data of the chunk at the given local coordinates.
+    ///
+    /// Returns `None` if the chunk has not been generated.
+    pub fn chunk(&self, local: &Vector<u8, 2>) -> anyhow::Result<Option<Vec<u8>>> {
+        if self.data.is_empty() {
+            return Ok(None);
+        }
+
+        let index = (local.x as usize & 31) | (local.y as usize & 31) << 5;
+        let (sector, count) = self.location(index);
+        if sector == 0 || count == 0 {
+            return Ok(None);
+        }
+
+        let start = sector * SECTOR_SIZE;
+        let Some(header) = self.data.get(start..start + 5) else {
+            anyhow::bail!(format!("Chunk {local:?} points outside of the region file"));
+        };
+
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        if header[4] & EXTERNAL_FLAG != 0 {
+            anyhow::bail!(format!("Chunk {local:?} is stored in an external file, which is not supported"));
+        }
+
+        let compression = ChunkCompression::try_from(header[4])?;
+        // The length includes the compression byte.
+        let Some(payload) = self.data.get(start + 5..start + 4 + length) else {
+            anyhow::bail!(format!("Chunk {local:?} has an invalid length of {length} bytes"));
+        };
+
+        let mut decompressed = Vec::new();
+        match compression {
+            ChunkCompression::Gzip => {
+                GzDecoder::new(payload).read_to_end(&mut decompressed)?;
+            }
+            ChunkCompression::Zlib => {
+                ZlibDecoder::new(payload).read_to_end(&mut decompressed)?;
+            }
+            ChunkCompression::Uncompressed => decompressed.extend_from_slice(payload),
+        }
+
+        Ok(Some(decompressed))
+    }
+
+    /// Reads the sector offset and sector count of a chunk from the location table.
+    fn location(&self, index: usize) -> (usize, usize) {
+        let entry = &self.data[index * 4..index * 4 + 4];
+        let offset = u32::from_be_bytes([0, entry[0], entry[1], entry[2]]) as usize;
+
+        (offset, entry[3] as usize)
+    }
+}
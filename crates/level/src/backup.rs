@@ -0,0 +1,222 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::provider::Provider;
+use crate::Storage;
+
+/// Files in the database directory that are not needed to restore the database.
+const SKIPPED_DB_FILES: &[&str] = &["LOCK", "LOG", "LOG.old"];
+/// Files in the world directory, besides the database, that are included in a backup.
+const WORLD_FILES: &[&str] = &["level.dat", "levelname.txt", "world_icon.jpeg"];
+/// Suffix of backup directories that are still being written.
+const PARTIAL_SUFFIX: &str = ".partial";
+
+impl<S: Storage + ?Sized> Provider<S> {
+    /// Creates a backup of the world while it is in use.
+    ///
+    /// Compaction is suspended while the backup is made, so that no table files are removed during the copy. Every file in the database is copied up to
+    /// the length it had when the backup started, together with `level.dat`.
+    ///
+    /// The backup is written into a new directory inside of `root`, named after the current UTC time
+    /// (e.g. `2024-08-31_14-05-09`), with a counter appended if that name is taken. The backup is first written
+    /// into a `.partial` directory, which is renamed once the backup has completed. Concurrent backups
+    /// always use different directories.
+    /// Any pending changes should be written to the database before calling this.
+    ///
+    /// Only worlds stored in LevelDB can be backed up.
+    ///
+    /// # Returns
+    ///
+    /// The path of the created backup.
+    #[tracing::instrument(skip_all, name = "Provider::backup")]
+    pub fn backup<P>(&self, root: P) -> anyhow::Result<PathBuf>
+    where
+        P: AsRef<Path>,
+    {
+        let root = root.as_ref();
+        std::fs::create_dir_all(root)?;
+
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let name = backup_name(secs);
+
+        // Creating the partial directory claims the name, so that concurrent backups never write into the same directory.
+        let mut counter = 0;
+        let (target, partial) = loop {
+            let target = if counter == 0 { root.join(&name) } else { root.join(format!("{name}-{counter}")) };
+            counter += 1;
+
+            let mut partial = target.clone().into_os_string();
+            partial.push(PARTIAL_SUFFIX);
+            let partial = PathBuf::from(partial);
+
+            if target.exists() {
+                continue;
+            }
+
+            match std::fs::create_dir(&partial) {
+                Ok(()) => break (target, partial),
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(err) => {
+                    tracing::error!("Failed to create backup directory: {err:#}");
+                    return Err(err.into());
+                }
+            }
+        };
+
+        let result = self.copy_world(&partial);
+        if let Err(err) = result {
+            tracing::error!("Failed to back up world: {err:#}");
+            // Do not leave incomplete backups behind.
+            let _: std::io::Result<()> = std::fs::remove_dir_all(&partial);
+
+            return Err(err);
+        }
+
+        std::fs::rename(&partial, &target)?;
+        Ok(target)
+    }
+
+    /// Copies the database and world files into the given directory.
+    fn copy_world(&self, target: &Path) -> anyhow::Result<()> {
+        let db_path = self.path().join("db");
+        let db_target = target.join("db");
+        std::fs::create_dir_all(&db_target)?;
+
+        let Some(database) = self.database().leveldb() else {
+            tracing::error!("Backups are only supported for LevelDB storage");
+            anyhow::bail!("Backups are only supported for LevelDB storage");
+        };
+        let _compaction = database.suspend_compaction();
+
+        // Record the sizes first. Log files keep growing while the server is running,
+        // copying them up to their recorded length results in a consistent state.
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(&db_path)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if !metadata.is_file() {
+                continue;
+            }
+
+            let name = entry.file_name();
+            if SKIPPED_DB_FILES.iter().any(|skipped| name == *skipped) {
+                continue;
+            }
+
+            files.push((name, metadata.len()));
+        }
+
+        for (name, len) in files {
+            let mut source = File::open(db_path.join(&name))?.take(len);
+            let mut destination = File::create(db_target.join(&name))?;
+            std::io::copy(&mut source, &mut destination)?;
+            destination.sync_all()?;
+        }
+
+        for name in WORLD_FILES {
+            let source = self.path().join(name);
+            if source.exists() {
+                std::fs::copy(source, target.join(name))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Removes the oldest backups in `root` until at most `retention` backups remain.
+///
+/// Only directories created by [`Provider::backup`] are considered.
+/// A retention of 0 keeps all backups.
+///
+/// # Returns
+///
+/// The paths of the removed backups.
+pub fn prune_backups<P>(root: P, retention: usize) -> anyhow::Result<Vec<PathBuf>>
+where
+    P: AsRef<Path>,
+{
+    let root = root.as_ref();
+    if retention == 0 || !root.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = Vec::new();
+    for entry in std::fs::read_dir(root)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+
+        let name = entry.file_name();
+        if let Some((timestamp, counter)) = name.to_str().and_then(backup_key) {
+            backups.push((timestamp.to_owned(), counter, entry.path()));
+        }
+    }
+
+    // Timestamps sort chronologically, backups made within the same second are ordered by their counter.
+    backups.sort_unstable();
+    let mut backups = backups.into_iter().map(|(_, _, path)| path).collect::<Vec<_>>();
+
+    let excess = backups.len().saturating_sub(retention);
+    let removed = backups.drain(..excess).collect::<Vec<_>>();
+    for backup in &removed {
+        std::fs::remove_dir_all(backup)?;
+    }
+
+    Ok(removed)
+}
+
+/// Formats a Unix timestamp as a backup directory name, in the form `YYYY-MM-DD_HH-MM-SS` (UTC).
+pub fn backup_name(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let time = secs % 86400;
+    let (year, month, day) = civil_from_days(days);
+
+    format!("{year:04}-{month:02}-{day:02}_{:02}-{:02}-{:02}", time / 3600, time / 60 % 60, time % 60)
+}
+
+/// Splits a directory name created by [`backup_name`] into its timestamp and counter.
+///
+/// Backups made within the same second are suffixed with a counter, backups without one have counter 0.
+/// Returns `None` if the directory is not a backup.
+fn backup_key(name: &str) -> Option<(&str, u64)> {
+    let (timestamp, counter) = (name.get(..19)?, name.get(19..)?);
+
+    let counter = match counter {
+        "" => 0,
+        counter => {
+            let digits = counter.strip_prefix('-')?;
+            if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+
+            digits.parse().ok()?
+        }
+    };
+
+    let valid_timestamp = timestamp.bytes().enumerate().all(|(i, b)| match i {
+        4 | 7 | 13 | 16 => b == b'-',
+        10 => b == b'_',
+        _ => b.is_ascii_digit(),
+    });
+
+    valid_timestamp.then_some((timestamp, counter))
+}
+
+/// Converts days since the Unix epoch into a (year, month, day) date in the proleptic Gregorian calendar.
+const fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    (year, month, day)
+}
@@ -1,23 +1,38 @@
-use std::{
-    ffi::{c_int, c_void},
-    ptr::NonNull,
-};
+use crate::DataKey;
+use util::RVec;
 
-use crate::ffi;
+/// A single operation stored in a [`WriteBatch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchOperation {
+    /// Stores a value at a key.
+    Put {
+        /// Raw key to store the value at.
+        key: Vec<u8>,
+        /// Value to store.
+        value: Vec<u8>,
+    },
+    /// Removes a key.
+    Delete {
+        /// Raw key to remove.
+        key: Vec<u8>,
+    },
+}
 
 /// Combines multiple operations into one large batch.
+///
+/// The operations are applied atomically, in the order they were added, when the batch is executed
+/// by a [`Storage`](crate::Storage).
+#[derive(Debug, Clone, Default)]
 pub struct WriteBatch {
-    pub(crate) ptr: NonNull<c_void>,
+    /// Operations in this batch.
+    operations: Vec<BatchOperation>,
 }
 
 impl WriteBatch {
     /// Creates a new batch.
-    /// This batch can be reused by calling [`clear`](Self::clear) and executed using [`execute`](super::Database::execute).
+    /// This batch can be reused by calling [`clear`](Self::clear) and executed using [`execute`](crate::Storage::execute).
     pub fn new() -> Self {
-        let ptr = unsafe { ffi::batch_new() };
-        Self {
-            ptr: NonNull::new(ptr).expect("batch pointer is null"),
-        }
+        Self::default()
     }
 
     /// Adds a put operation to the batch.
@@ -26,18 +41,22 @@ impl WriteBatch {
         K: AsRef<[u8]>,
         V: AsRef<[u8]>,
     {
-        let key = key.as_ref();
-        let val = val.as_ref();
+        self.operations.push(BatchOperation::Put {
+            key: key.as_ref().to_vec(),
+            value: val.as_ref().to_vec(),
+        });
+    }
 
-        unsafe {
-            ffi::batch_put(
-                self.ptr.as_ptr(),
-                key.as_ptr() as *const i8,
-                key.len() as c_int,
-                val.as_ptr() as *const i8,
-                val.len() as c_int,
-            );
-        }
+    /// Adds a put operation to the batch, using a database key.
+    pub fn put_data<V>(&mut self, key: DataKey, val: V) -> anyhow::Result<()>
+    where
+        V: AsRef<[u8]>,
+    {
+        let mut raw_key = RVec::alloc_with_capacity(key.serialized_size());
+        key.serialize(&mut raw_key)?;
+
+        self.put(raw_key, val);
+        Ok(())
     }
 
     /// Adds a delete operation to the batch.
@@ -45,25 +64,38 @@ impl WriteBatch {
     where
         K: AsRef<[u8]>,
     {
-        let key = key.as_ref();
+        self.operations.push(BatchOperation::Delete { key: key.as_ref().to_vec() });
+    }
 
-        unsafe {
-            ffi::batch_delete(self.ptr.as_ptr(), key.as_ptr() as *const i8, key.len() as c_int);
-        }
+    /// Adds a delete operation to the batch, using a database key.
+    pub fn delete_data(&mut self, key: DataKey) -> anyhow::Result<()> {
+        let mut raw_key = RVec::alloc_with_capacity(key.serialized_size());
+        key.serialize(&mut raw_key)?;
+
+        self.delete(raw_key);
+        Ok(())
     }
 
     /// Clears the batch, removing all stored operations.
     pub fn clear(&mut self) {
-        unsafe {
-            ffi::batch_clear(self.ptr.as_ptr());
-        }
+        self.operations.clear();
+    }
+
+    /// Returns the operations in this batch, in the order they were added.
+    #[inline]
+    pub fn operations(&self) -> &[BatchOperation] {
+        &self.operations
+    }
+
+    /// Amount of operations in this batch.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.operations.len()
     }
-}
 
-impl Drop for WriteBatch {
-    fn drop(&mut self) {
-        unsafe {
-            ffi::batch_destroy(self.ptr.as_ptr());
-        }
+    /// Whether this batch contains no operations.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
     }
 }
@@ -0,0 +1,289 @@
+//! Command line tool to inspect and edit Bedrock worlds.
+//!
+//! The world must not be opened by a server while this tool is running.
+
+use std::collections::HashSet;
+use std::process::ExitCode;
+
+use mirai_level::database::Database;
+use mirai_level::provider::Provider;
+use mirai_level::{Actor, BiomeEncoding, DbKey, KeyType, LevelSettings, SubChunk, WriteBatch};
+use proto::types::Dimension;
+use serde_json::{json, Value};
+use util::Vector;
+
+/// Usage information printed when the arguments are invalid.
+const USAGE: &str = "\
+Usage: mirai-level <world> <command>
+
+Commands:
+    list                                  List the dimensions and the amount of chunks in each
+    dump <x> <z> [dimension]              Print the contents of a chunk as JSON
+    level-dat                             Print the level.dat file as JSON
+    level-dat set <field> <value>         Change a field of level.dat, nested fields are separated by dots
+    prune <radius> [x z] [dimension]      Delete all chunks further than <radius> chunks from (x, z)
+    compact                               Compact the database
+
+Dimensions are `overworld`, `nether` or `end` and default to the overworld.
+The centre of `prune` defaults to the chunk containing the world spawn.";
+
+/// Dimensions in the order they are listed.
+const DIMENSIONS: [Dimension; 3] = [Dimension::Overworld, Dimension::Nether, Dimension::End];
+
+/// Maximum amount of operations in a single batch when pruning chunks.
+const PRUNE_BATCH_SIZE: usize = 4096;
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let [world, command, args @ ..] = args.as_slice() else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+
+    let provider = match Provider::open(world) {
+        Ok(provider) => provider,
+        Err(err) => {
+            eprintln!("Failed to open world {world}: {err:#}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let result = match (command.as_str(), args) {
+        ("list", []) => list(&provider),
+        ("dump", [x, z, dimension @ ..]) if dimension.len() <= 1 => dump(&provider, x, z, dimension.first()),
+        ("level-dat", []) => print_settings(&provider),
+        ("level-dat", [set, field, value]) if set == "set" => edit_settings(&provider, field, value),
+        ("prune", [radius, rest @ ..]) if rest.len() <= 3 => prune(&provider, radius, rest),
+        ("compact", []) => {
+            provider.database().compact();
+            Ok(())
+        }
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Parses a dimension name or ID.
+fn parse_dimension(name: Option<&String>) -> anyhow::Result<Dimension> {
+    Ok(match name.map(String::as_str) {
+        None | Some("overworld" | "0") => Dimension::Overworld,
+        Some("nether" | "1") => Dimension::Nether,
+        Some("end" | "2") => Dimension::End,
+        Some(name) => anyhow::bail!("Unknown dimension: {name}"),
+    })
+}
+
+/// Parses a chunk coordinate.
+fn parse_coordinate(coordinate: &str) -> anyhow::Result<i32> {
+    coordinate.parse().map_err(|_| anyhow::anyhow!("Invalid chunk coordinate: {coordinate}"))
+}
+
+/// Prints the amount of chunks in every dimension.
+fn list(provider: &Provider) -> anyhow::Result<()> {
+    let settings = provider.settings()?;
+    println!("{}", settings.level_name);
+
+    for dimension in DIMENSIONS {
+        let chunks = provider.chunks(dimension).len();
+        println!("{dimension:?}: {chunks} chunks");
+    }
+
+    Ok(())
+}
+
+/// Prints the subchunks, biomes, block entities and actors of a chunk as JSON.
+fn dump(provider: &Provider, x: &str, z: &str, dimension: Option<&String>) -> anyhow::Result<()> {
+    let coordinates = Vector::from([parse_coordinate(x)?, parse_coordinate(z)?]);
+    let dimension = parse_dimension(dimension)?;
+
+    let Some(version) = provider.version(coordinates.clone(), dimension)? else {
+        anyhow::bail!("Chunk {coordinates:?} does not exist in the {dimension:?}");
+    };
+
+    let mut indices = Vec::new();
+    for (key, _) in provider.chunk_entries(coordinates.clone(), dimension)? {
+        match key {
+            KeyType::SubChunk { index } => indices.push(index),
+            KeyType::LegacyTerrain => indices.extend(0..8),
+            _ => {}
+        }
+    }
+
+    let mut subchunks = Vec::with_capacity(indices.len());
+    for index in indices {
+        let position = [coordinates.x, i32::from(index), coordinates.y];
+        if let Some(subchunk) = provider.subchunk(position, dimension)? {
+            subchunks.push(subchunk_json(index, &subchunk));
+        }
+    }
+
+    let biomes = provider.biomes(coordinates.clone(), dimension)?.map(|biomes| {
+        let fragments = biomes
+            .fragments()
+            .iter()
+            .map(|fragment| match fragment {
+                BiomeEncoding::Inherit => json!("inherit"),
+                BiomeEncoding::Single(id) => json!({ "single": id }),
+                BiomeEncoding::Paletted(storage) => json!({
+                    "palette": storage.palette(),
+                    "indices": storage.indices().as_slice(),
+                }),
+            })
+            .collect::<Vec<_>>();
+
+        json!({
+            "heightmap": biomes.heightmap().as_slice(),
+            "fragments": fragments,
+        })
+    });
+
+    let block_entities = provider
+        .block_entities(coordinates.clone(), dimension)?
+        .iter()
+        .map(|entity| serde_json::to_value(entity.to_nbt()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let actors = provider
+        .actors(coordinates.clone(), dimension)?
+        .iter()
+        .map(|actor| serde_json::to_value(actor.to_nbt()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let chunk = json!({
+        "coordinates": [coordinates.x, coordinates.y],
+        "dimension": format!("{dimension:?}"),
+        "version": version,
+        "subchunks": subchunks,
+        "biomes": biomes,
+        "block_entities": block_entities,
+        "actors": actors,
+    });
+
+    println!("{}", serde_json::to_string_pretty(&chunk)?);
+    Ok(())
+}
+
+/// Converts a subchunk into JSON.
+fn subchunk_json(index: i8, subchunk: &SubChunk) -> Value {
+    let layers = subchunk
+        .layers()
+        .iter()
+        .map(|layer| {
+            json!({
+                "palette": layer.palette(),
+                "indices": layer.indices().as_slice(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "index": index,
+        "version": format!("{:?}", subchunk.version()),
+        "layers": layers,
+    })
+}
+
+/// Prints the level settings as JSON.
+fn print_settings(provider: &Provider) -> anyhow::Result<()> {
+    let settings = provider.settings()?;
+    println!("{}", serde_json::to_string_pretty(&settings)?);
+
+    Ok(())
+}
+
+/// Changes a single field of the level settings.
+///
+/// The value is parsed as JSON. If that fails, it is used as a string.
+fn edit_settings(provider: &Provider, field: &str, value: &str) -> anyhow::Result<()> {
+    let mut settings = serde_json::to_value(provider.settings()?)?;
+
+    let mut target = &mut settings;
+    for name in field.split('.') {
+        let Some(child) = target.get_mut(name) else {
+            anyhow::bail!("Unknown level.dat field: {field}");
+        };
+        target = child;
+    }
+
+    let value: Value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_owned()));
+    let old = std::mem::replace(target, value.clone());
+
+    let settings: LevelSettings = match serde_json::from_value(settings) {
+        Ok(settings) => settings,
+        Err(err) => anyhow::bail!("Invalid value for {field} (was {old}): {err}"),
+    };
+    provider.save_settings(&settings)?;
+
+    println!("Changed {field} from {old} to {value}");
+    Ok(())
+}
+
+/// Deletes all chunks that are further than `radius` chunks away from a centre.
+///
+/// Actors that are stored in the deleted chunks are removed as well.
+fn prune(provider: &Provider, radius: &str, args: &[String]) -> anyhow::Result<()> {
+    let radius: i32 = radius.parse().map_err(|_| anyhow::anyhow!("Invalid radius: {radius}"))?;
+    let (center, dimension) = match args {
+        [x, z, dimension @ ..] => (Vector::from([parse_coordinate(x)?, parse_coordinate(z)?]), dimension.first()),
+        dimension => {
+            let settings = provider.settings()?;
+            (Vector::from([settings.spawn_x >> 4, settings.spawn_z >> 4]), dimension.first())
+        }
+    };
+    let dimension = parse_dimension(dimension)?;
+
+    let database = provider.database();
+    let mut batch = WriteBatch::new();
+    let mut removed = HashSet::new();
+
+    for pair in database.iter() {
+        let raw_key = pair.key();
+        let key = DbKey::parse(&raw_key);
+        let Some((coordinates, key_dimension)) = key.chunk() else {
+            continue;
+        };
+
+        let distance = (coordinates.x - center.x).abs().max((coordinates.y - center.y).abs());
+        if key_dimension != dimension || distance <= radius {
+            continue;
+        }
+
+        if let DbKey::Digest { .. } = key {
+            for unique_id in Actor::deserialize_digest(&*pair.value())? {
+                batch.delete(DbKey::Actor { unique_id }.serialize()?);
+            }
+        }
+
+        removed.insert((coordinates.x, coordinates.y));
+        batch.delete(&*raw_key);
+
+        if batch.len() >= PRUNE_BATCH_SIZE {
+            flush(database, &mut batch)?;
+        }
+    }
+    flush(database, &mut batch)?;
+
+    println!(
+        "Deleted {} chunks outside of {radius} chunks from {center:?} in the {dimension:?}",
+        removed.len()
+    );
+    Ok(())
+}
+
+/// Executes and clears the batch.
+fn flush(database: &Database, batch: &mut WriteBatch) -> anyhow::Result<()> {
+    database.execute(batch)?;
+    batch.clear();
+
+    Ok(())
+}
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+
+use util::{BinaryRead, RVec, Vector};
+
+/// Blocks that have a block entity, without the `minecraft:` namespace.
+///
+/// Blocks whose name ends with one of the suffixes in [`BLOCK_ENTITY_SUFFIXES`] are not included.
+const BLOCK_ENTITY_BLOCKS: &[&str] = &[
+    "chest",
+    "trapped_chest",
+    "ender_chest",
+    "barrel",
+    "furnace",
+    "lit_furnace",
+    "blast_furnace",
+    "lit_blast_furnace",
+    "smoker",
+    "lit_smoker",
+    "hopper",
+    "dispenser",
+    "dropper",
+    "crafter",
+    "brewing_stand",
+    "enchanting_table",
+    "beacon",
+    "conduit",
+    "jukebox",
+    "noteblock",
+    "lectern",
+    "bell",
+    "cauldron",
+    "lava_cauldron",
+    "flower_pot",
+    "skull",
+    "bed",
+    "standing_banner",
+    "wall_banner",
+    "frame",
+    "glow_frame",
+    "mob_spawner",
+    "trial_spawner",
+    "vault",
+    "beehive",
+    "bee_nest",
+    "campfire",
+    "soul_campfire",
+    "command_block",
+    "chain_command_block",
+    "repeating_command_block",
+    "structure_block",
+    "jigsaw",
+    "end_portal",
+    "end_gateway",
+    "daylight_detector",
+    "daylight_detector_inverted",
+    "powered_comparator",
+    "unpowered_comparator",
+    "piston",
+    "sticky_piston",
+    "piston_arm_collision",
+    "sticky_piston_arm_collision",
+    "moving_block",
+    "sculk_sensor",
+    "calibrated_sculk_sensor",
+    "sculk_catalyst",
+    "sculk_shrieker",
+    "chiseled_bookshelf",
+    "decorated_pot",
+    "suspicious_sand",
+    "suspicious_gravel",
+    "creaking_heart",
+    "lodestone",
+    "nether_reactor",
+    "chalkboard",
+    "shulker_box",
+];
+
+/// Name suffixes of block variants that all have a block entity, such as the different kinds of signs.
+const BLOCK_ENTITY_SUFFIXES: &[&str] = &["_sign", "_shulker_box", "_head", "_skull"];
+
+/// Whether blocks with the given name have a block entity.
+///
+/// This makes it possible to skip looking up block entities for the vast majority of blocks.
+pub fn has_block_entity(block: &str) -> bool {
+    let name = block.strip_prefix("minecraft:").unwrap_or(block);
+    BLOCK_ENTITY_BLOCKS.contains(&name) || BLOCK_ENTITY_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+}
+
+/// Block entity types that clients are allowed to edit, together with the fields that they can change.
+const CLIENT_EDITABLE: &[(&str, &[&str])] = &[("Sign", &["FrontText", "BackText"]), ("HangingSign", &["FrontText", "BackText"])];
+
+/// A block entity (also known as a block actor), such as a chest, sign or banner.
+///
+/// Block entities store data that does not fit in a block state, such as the items in a chest.
+/// All block entities in a chunk are stored in a single database key as concatenated NBT compounds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockEntity {
+    /// Type of the block entity, such as `Chest` or `Sign`.
+    pub id: String,
+    /// World coordinates of the block that this entity belongs to.
+    pub position: Vector<i32, 3>,
+    /// Whether this block entity can be moved by pistons.
+    pub is_movable: bool,
+    /// Name given to the block entity using an anvil.
+    pub custom_name: Option<String>,
+    /// Remaining properties that are specific to the type of block entity.
+    pub data: HashMap<String, nbt::Value>,
+}
+
+impl BlockEntity {
+    /// Creates a block entity from its NBT representation.
+    pub fn from_nbt(mut nbt: HashMap<String, nbt::Value>) -> anyhow::Result<Self> {
+        let Some(nbt::Value::String(id)) = nbt.remove("id") else {
+            anyhow::bail!("Block entity is missing its identifier");
+        };
+
+        let mut coordinate = |key: &str| match nbt.remove(key) {
+            Some(nbt::Value::Int(value)) => Ok(value),
+            _ => Err(anyhow::anyhow!("Block entity {id} is missing its {key} coordinate")),
+        };
+        let position = Vector::from([coordinate("x")?, coordinate("y")?, coordinate("z")?]);
+
+        let is_movable = !matches!(nbt.remove("isMovable"), Some(nbt::Value::Byte(0)));
+        let custom_name = match nbt.remove("CustomName") {
+            Some(nbt::Value::String(name)) => Some(name),
+            _ => None,
+        };
+
+        Ok(Self {
+            id,
+            position,
+            is_movable,
+            custom_name,
+            data: nbt,
+        })
+    }
+
+    /// Converts the block entity into its NBT representation.
+    pub fn to_nbt(&self) -> HashMap<String, nbt::Value> {
+        let mut nbt = self.data.clone();
+
+        nbt.insert(String::from("id"), nbt::Value::String(self.id.clone()));
+        nbt.insert(String::from("x"), nbt::Value::Int(self.position.x));
+        nbt.insert(String::from("y"), nbt::Value::Int(self.position.y));
+        nbt.insert(String::from("z"), nbt::Value::Int(self.position.z));
+        nbt.insert(String::from("isMovable"), nbt::Value::Byte(i8::from(self.is_movable)));
+
+        if let Some(name) = &self.custom_name {
+            nbt.insert(String::from("CustomName"), nbt::Value::String(name.clone()));
+        }
+
+        nbt
+    }
+
+    /// Applies an edit sent by a client to this block entity.
+    ///
+    /// Clients can only edit the text of signs. Only the text compounds are copied from `edit`,
+    /// all of its other fields are ignored. Edits of any other type of block entity, of a different type
+    /// than this one or of a waxed sign are rejected.
+    pub fn apply_client_edit(&mut self, edit: &BlockEntity) -> anyhow::Result<()> {
+        if edit.id != self.id {
+            anyhow::bail!("Client attempted to change block entity {} into {}", self.id, edit.id);
+        }
+
+        let Some((_, fields)) = CLIENT_EDITABLE.iter().find(|(id, _)| *id == self.id) else {
+            anyhow::bail!("Block entity {} cannot be edited by clients", self.id);
+        };
+
+        if matches!(self.data.get("IsWaxed"), Some(nbt::Value::Byte(1))) {
+            anyhow::bail!("Client attempted to edit a waxed sign");
+        }
+
+        for field in *fields {
+            match edit.data.get(*field) {
+                Some(text @ nbt::Value::Compound(_)) => {
+                    self.data.insert((*field).to_owned(), text.clone());
+                }
+                Some(_) => anyhow::bail!("Block entity field {field} must be a compound"),
+                None => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads all block entities stored in a chunk.
+    pub(crate) fn deserialize_all<'a, R>(mut reader: R) -> anyhow::Result<Vec<Self>>
+    where
+        R: BinaryRead<'a>,
+    {
+        let mut entities = Vec::new();
+        while !reader.eof() {
+            let (nbt, _) = nbt::from_le_bytes(&mut reader)?;
+            entities.push(Self::from_nbt(nbt)?);
+        }
+
+        Ok(entities)
+    }
+
+    /// Serializes all block entities of a chunk into a single buffer.
+    pub(crate) fn serialize_all(entities: &[Self]) -> anyhow::Result<RVec> {
+        let mut buffer = RVec::alloc();
+        for entity in entities {
+            nbt::to_le_bytes_in(&mut buffer, &entity.to_nbt())?;
+        }
+
+        Ok(buffer)
+    }
+}
@@ -10,11 +10,22 @@ use std::{
 };
 
 use crate::ffi::LoadStatus;
-use crate::{ffi, DataKey, WriteBatch};
+use crate::{ffi, BatchOperation, DataKey, WriteBatch};
 
 /// Wraps a LevelDB buffer, ensuring the buffer is deallocated after use.
+///
+/// Storage backends that do not use LevelDB return owned buffers instead.
 #[derive(Debug)]
-pub struct Guard<'a>(&'a mut [u8]);
+pub struct Guard<'a>(GuardData<'a>);
+
+/// Data referenced by a [`Guard`].
+#[derive(Debug)]
+enum GuardData<'a> {
+    /// A buffer allocated by LevelDB.
+    Ffi(&'a mut [u8]),
+    /// A buffer allocated by Rust.
+    Owned(Vec<u8>),
+}
 
 impl<'a> Guard<'a> {
     /// Creates a `Guard` from the given slice.
@@ -29,7 +40,13 @@ impl<'a> Guard<'a> {
     /// The caller must also ensure that the slice is not referenced anywhere else in the program.
     #[inline]
     pub(crate) unsafe fn from_slice(slice: &'a mut [u8]) -> Self {
-        Guard(slice)
+        Guard(GuardData::Ffi(slice))
+    }
+
+    /// Creates a `Guard` that owns its data.
+    #[inline]
+    pub fn owned(data: Vec<u8>) -> Self {
+        Guard(GuardData::Owned(data))
     }
 }
 
@@ -38,24 +55,29 @@ impl<'a> Deref for Guard<'a> {
 
     #[inline]
     fn deref(&self) -> &Self::Target {
-        self.0
+        match &self.0 {
+            GuardData::Ffi(slice) => slice,
+            GuardData::Owned(data) => data,
+        }
     }
 }
 
 impl<'a> AsRef<[u8]> for Guard<'a> {
     #[inline]
     fn as_ref(&self) -> &[u8] {
-        self.0
+        self
     }
 }
 
 impl<'a> Drop for Guard<'a> {
     #[inline]
     fn drop(&mut self) {
-        // SAFETY: The slice in self should have been allocated by the database.
-        // It is safe to delete because the pointer is unique and guaranteed to exist.
-        unsafe {
-            ffi::buffer_destroy(self.0.as_mut_ptr() as *mut i8);
+        if let GuardData::Ffi(slice) = &mut self.0 {
+            // SAFETY: The slice in self should have been allocated by the database.
+            // It is safe to delete because the pointer is unique and guaranteed to exist.
+            unsafe {
+                ffi::buffer_destroy(slice.as_mut_ptr() as *mut i8);
+            }
         }
     }
 }
@@ -70,7 +92,7 @@ pub struct KvRef<'a> {
 impl<'a> KvRef<'a> {
     /// The key associated with this pair.
     #[allow(clippy::missing_panics_doc)] // Panic should never happen.
-    pub fn key(&self) -> Guard {
+    pub fn key(&self) -> Guard<'a> {
         // SAFETY: A Ref should only exist while the iterator is valid.
         // This invariant is upheld by the lifetime 'a.
         unsafe {
@@ -85,7 +107,7 @@ impl<'a> KvRef<'a> {
 
     /// The data associated with this pair.
     #[allow(clippy::missing_panics_doc)] // Panic should never happen.
-    pub fn value(&self) -> Guard {
+    pub fn value(&self) -> Guard<'a> {
         // SAFETY: A Ref should only exist while the iterator is valid.
         // This invariant is upheld by the lifetime 'a.
         unsafe {
@@ -99,12 +121,27 @@ impl<'a> KvRef<'a> {
     }
 }
 
+/// Determines where a [`Keys`] iterator stops.
+#[derive(Debug)]
+enum KeyBound {
+    /// Iterates until the end of the database.
+    Unbounded,
+    /// Iterates while the keys start with the given prefix.
+    Prefix(Vec<u8>),
+    /// Iterates while the keys are smaller than the given key.
+    Before(Vec<u8>),
+}
+
 /// Iterator over keys in a LevelDB database.
 pub struct Keys<'a> {
     /// Current position of the iterator.
     index: usize,
     /// Pointer to the C++ iterator.
     iter: NonNull<c_void>,
+    /// Key at which the iterator stops.
+    bound: KeyBound,
+    /// Whether the iterator has moved past its bound.
+    finished: bool,
     /// Ensures the iterator does not outlive the database.
     _marker: PhantomData<&'a ()>,
 }
@@ -122,15 +159,44 @@ impl<'a> Keys<'a> {
             index: 0,
             // SAFETY: level_iter is guaranteed to not return an error.
             iter: unsafe { NonNull::new_unchecked(result.data) },
+            bound: KeyBound::Unbounded,
+            finished: false,
             _marker: PhantomData,
         }
     }
+
+    /// Creates an iterator over all keys that start with `prefix`.
+    pub fn with_prefix(db: &'a Database, prefix: &[u8]) -> Keys<'a> {
+        let mut keys = Self::new(db);
+        keys.seek(prefix);
+        keys.bound = KeyBound::Prefix(prefix.to_vec());
+        keys
+    }
+
+    /// Creates an iterator over all keys in the range `start..end`.
+    pub fn with_range(db: &'a Database, start: &[u8], end: &[u8]) -> Keys<'a> {
+        let mut keys = Self::new(db);
+        keys.seek(start);
+        keys.bound = KeyBound::Before(end.to_vec());
+        keys
+    }
+
+    /// Moves the iterator to the first key that is at or after `key`.
+    fn seek(&mut self, key: &[u8]) {
+        // SAFETY: The iterator has not been destroyed and the key is not referenced after the call.
+        unsafe { ffi::iter_seek(self.iter.as_ptr(), key.as_ptr() as *const c_char, key.len() as c_int) };
+        self.index = 0;
+    }
 }
 
 impl<'a> Iterator for Keys<'a> {
     type Item = KvRef<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
         if self.index != 0 {
             // SAFETY: `level_iter_next` is safe to call, as long as the iterator has not been destroyed.
             // * The only way to delete the iterator is using the `Drop` implementation of `Self`,
@@ -145,7 +211,21 @@ impl<'a> Iterator for Keys<'a> {
         // Furthermore, the above check ensures the iterator is valid and supports the key and value methods
         // provided by `Ref`.
         let valid = unsafe { ffi::iter_valid(self.iter.as_ptr()) };
-        valid.then_some(KvRef { iter: self.iter, _marker: PhantomData })
+        let pair = valid.then_some(KvRef { iter: self.iter, _marker: PhantomData });
+
+        // Keys are sorted, so once a key is out of bounds, all following keys are as well.
+        let in_bounds = pair.as_ref().is_some_and(|pair| match &self.bound {
+            KeyBound::Unbounded => true,
+            KeyBound::Prefix(prefix) => pair.key().starts_with(prefix),
+            KeyBound::Before(end) => *pair.key() < **end,
+        });
+
+        if in_bounds {
+            pair
+        } else {
+            self.finished = true;
+            None
+        }
     }
 }
 
@@ -203,37 +283,72 @@ impl Database {
         Keys::new(self)
     }
 
+    /// Creates an iterator over all keys that start with `prefix`.
+    ///
+    /// The iterator seeks directly to the first matching key, keys before it are never read.
+    #[inline]
+    pub fn iter_prefix<K>(&self, prefix: K) -> Keys<'_>
+    where
+        K: AsRef<[u8]>,
+    {
+        Keys::with_prefix(self, prefix.as_ref())
+    }
+
+    /// Creates an iterator over all keys in the range `start..end`.
+    #[inline]
+    pub fn iter_range<K1, K2>(&self, start: K1, end: K2) -> Keys<'_>
+    where
+        K1: AsRef<[u8]>,
+        K2: AsRef<[u8]>,
+    {
+        Keys::with_range(self, start.as_ref(), end.as_ref())
+    }
+
     /// Loads the specified value from the database.
     pub fn get(&self, key: DataKey) -> anyhow::Result<Option<Guard>> {
         let mut raw_key = RVec::alloc_with_capacity(key.serialized_size());
         key.serialize(&mut raw_key)?;
 
+        self.get_raw(raw_key)
+    }
+
+    /// Loads the value stored at a raw key from the database.
+    ///
+    /// This is used for keys that are not tied to a chunk, such as [`ACTOR_PREFIX`](crate::ACTOR_PREFIX) keys.
+    pub fn get_raw<K>(&self, raw_key: K) -> anyhow::Result<Option<Guard<'_>>>
+    where
+        K: AsRef<[u8]>,
+    {
+        let raw_key = raw_key.as_ref();
+
         // SAFETY: This function is guaranteed to not modify any arguments.
         // It also does not throw exceptions and returns a valid struct.
         //
         // A LevelDB database is thread-safe, this function can be used by multiple threads.
         unsafe {
             let result = ffi::db_get(self.ptr.as_ptr(), raw_key.as_ptr() as *const c_char, raw_key.len() as c_int);
-            if result.status == LoadStatus::Success {
-                if result.data.is_null() {
-                    tracing::error!("Received world data is a null pointer despite being marked as a successful result");
-                    anyhow::bail!("Received world data is a null pointer");
-                }
+            read_result(result)
+        }
+    }
 
-                // SAFETY: result.data is guaranteed by the caller to be a valid pointer.
-                // result.size is also guaranteed to be the size of the actual array.
-                let data = std::slice::from_raw_parts_mut(result.data as *mut u8, result.size as usize);
+    /// Suspends background compaction until the returned guard is dropped.
+    ///
+    /// While compaction is suspended, no table files are created or removed,
+    /// which allows the database directory to be copied while the database is in use.
+    #[inline]
+    pub fn suspend_compaction(&self) -> CompactionGuard<'_> {
+        // SAFETY: The database pointer is valid. Compaction is resumed when the guard is dropped.
+        unsafe { ffi::db_suspend_compaction(self.ptr.as_ptr()) };
+        CompactionGuard { database: self }
+    }
 
-                // SAFETY: The data passed into the Guard has been allocated in the leveldb FFI code.
-                // It is therefore also required to deallocate the data there, which is what Guard
-                // does.
-                Ok(Some(Guard::from_slice(data)))
-            } else if result.status == LoadStatus::NotFound {
-                Ok(None)
-            } else {
-                Err(translate_ffi_error(result))
-            }
-        }
+    /// Compacts the entire database, removing deleted and overwritten data from disk.
+    ///
+    /// This blocks until the compaction has finished, which can take a while for large worlds.
+    #[inline]
+    pub fn compact(&self) {
+        // SAFETY: The database pointer is valid. Compaction does not invalidate any other objects.
+        unsafe { ffi::db_compact(self.ptr.as_ptr()) };
     }
 
     /// Inserts a new value into the database.
@@ -248,6 +363,16 @@ impl Database {
         let mut raw_key = RVec::alloc_with_capacity(key.serialized_size());
         key.serialize(&mut raw_key)?;
 
+        self.put_raw(raw_key, value)
+    }
+
+    /// Inserts a new value into the database at a raw key.
+    pub fn put_raw<K, V>(&self, raw_key: K, value: V) -> anyhow::Result<()>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        let raw_key = raw_key.as_ref();
         let value = value.as_ref();
 
         // SAFETY: This is safe because the data and lengths come from properly allocated vecs.
@@ -274,10 +399,20 @@ impl Database {
         let mut raw_key = RVec::alloc_with_capacity(key.serialized_size());
         key.serialize(&mut raw_key)?;
 
+        self.delete_raw(raw_key)
+    }
+
+    /// Removes the given raw key from the database.
+    pub fn delete_raw<K>(&self, raw_key: K) -> anyhow::Result<()>
+    where
+        K: AsRef<[u8]>,
+    {
+        let raw_key = raw_key.as_ref();
+
         // SAFETY: This is safe because the data and lengths come from properly allocated vecs.
         // Additionally, the remove method does not keep references to the data after the function has been called.
         unsafe {
-            let result = ffi::db_delete(self.ptr.as_ptr(), raw_key.as_mut_ptr() as *mut c_char, raw_key.len() as c_int);
+            let result = ffi::db_delete(self.ptr.as_ptr(), raw_key.as_ptr() as *const c_char, raw_key.len() as c_int);
 
             if result.status == LoadStatus::Success || result.status == LoadStatus::NotFound {
                 Ok(())
@@ -289,8 +424,11 @@ impl Database {
 
     /// Executes a batch.
     pub fn execute(&self, batch: &WriteBatch) -> anyhow::Result<()> {
+        let ffi_batch = FfiBatch::new(batch)?;
+
+        // SAFETY: Both the database and batch pointers are valid. The batch is destroyed after it has been executed.
         unsafe {
-            let result = ffi::batch_execute(self.ptr.as_ptr(), batch.ptr.as_ptr());
+            let result = ffi::batch_execute(self.ptr.as_ptr(), ffi_batch.ptr.as_ptr());
 
             if result.status == LoadStatus::Success {
                 Ok(())
@@ -301,6 +439,95 @@ impl Database {
     }
 }
 
+/// A LevelDB write batch, created from a [`WriteBatch`] right before it is executed.
+struct FfiBatch {
+    /// Pointer to the C++ batch.
+    ptr: NonNull<c_void>,
+}
+
+impl FfiBatch {
+    /// Copies the operations of the given batch into a new LevelDB batch.
+    fn new(batch: &WriteBatch) -> anyhow::Result<Self> {
+        // SAFETY: This function does not fail, other than returning a null pointer if allocation failed.
+        let Some(ptr) = NonNull::new(unsafe { ffi::batch_new() }) else {
+            anyhow::bail!("Failed to allocate write batch");
+        };
+
+        let ffi_batch = Self { ptr };
+        for operation in batch.operations() {
+            // SAFETY: The batch pointer is valid and the keys and values are not referenced after the calls.
+            // LevelDB copies the data into the batch.
+            unsafe {
+                match operation {
+                    BatchOperation::Put { key, value } => ffi::batch_put(
+                        ptr.as_ptr(),
+                        key.as_ptr() as *const c_char,
+                        key.len() as c_int,
+                        value.as_ptr() as *const c_char,
+                        value.len() as c_int,
+                    ),
+                    BatchOperation::Delete { key } => ffi::batch_delete(ptr.as_ptr(), key.as_ptr() as *const c_char, key.len() as c_int),
+                }
+            }
+        }
+
+        Ok(ffi_batch)
+    }
+}
+
+impl Drop for FfiBatch {
+    #[inline]
+    fn drop(&mut self) {
+        // SAFETY: The batch was created by `batch_new` and is not used after this.
+        unsafe {
+            ffi::batch_destroy(self.ptr.as_ptr());
+        }
+    }
+}
+
+/// Resumes background compaction when dropped, see [`Database::suspend_compaction`].
+pub struct CompactionGuard<'a> {
+    /// Database whose compaction has been suspended.
+    database: &'a Database,
+}
+
+impl<'a> Drop for CompactionGuard<'a> {
+    #[inline]
+    fn drop(&mut self) {
+        // SAFETY: Compaction was suspended when this guard was created.
+        unsafe {
+            ffi::db_resume_compaction(self.database.ptr.as_ptr());
+        }
+    }
+}
+
+/// Converts the result of a read operation into a [`Guard`].
+///
+/// # Safety
+///
+/// The result must have been returned by a LevelDB read function.
+unsafe fn read_result<'a>(result: ffi::LevelResult) -> anyhow::Result<Option<Guard<'a>>> {
+    if result.status == LoadStatus::Success {
+        if result.data.is_null() {
+            tracing::error!("Received world data is a null pointer despite being marked as a successful result");
+            anyhow::bail!("Received world data is a null pointer");
+        }
+
+        // SAFETY: result.data is guaranteed by the caller to be a valid pointer.
+        // result.size is also guaranteed to be the size of the actual array.
+        let data = std::slice::from_raw_parts_mut(result.data as *mut u8, result.size as usize);
+
+        // SAFETY: The data passed into the Guard has been allocated in the leveldb FFI code.
+        // It is therefore also required to deallocate the data there, which is what Guard
+        // does.
+        Ok(Some(Guard::from_slice(data)))
+    } else if result.status == LoadStatus::NotFound {
+        Ok(None)
+    } else {
+        Err(translate_ffi_error(result))
+    }
+}
+
 impl Drop for Database {
     #[inline]
     fn drop(&mut self) {
@@ -45,6 +45,12 @@ extern "C" {
     pub fn db_put(database: *mut c_void, key: *const c_char, key_size: c_int, value: *const c_char, value_size: c_int) -> LevelResult;
     /// Deletes a key from the database.
     pub fn db_delete(database: *mut c_void, key: *const c_char, key_size: c_int) -> LevelResult;
+    /// Suspends background compaction, so that no table files are created or removed.
+    pub fn db_suspend_compaction(database: *mut c_void);
+    /// Resumes background compaction after a call to [`db_suspend_compaction`].
+    pub fn db_resume_compaction(database: *mut c_void);
+    /// Compacts the entire key range of the database, blocking until it has finished.
+    pub fn db_compact(database: *mut c_void);
     /// Deallocates a string previously allocated by another function.
     pub fn buffer_destroy(array: *mut c_char);
     /// Creates an iterator over the database keys.
@@ -59,6 +65,8 @@ extern "C" {
     pub fn iter_value(iter: *const c_void) -> SizedData;
     /// Moves the iterator to the next position.
     pub fn iter_next(iter: *mut c_void);
+    /// Moves the iterator to the first key that is at or after the given key.
+    pub fn iter_seek(iter: *mut c_void, key: *const c_char, key_size: c_int);
     /// Creates a new reusable batch.
     pub fn batch_new() -> *mut c_void;
     /// Adds a delete operation to the batch.
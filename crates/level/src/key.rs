@@ -1,5 +1,7 @@
+use std::io::Write;
+
 use proto::types::Dimension;
-use util::{BinaryRead, BinaryWrite, Vector};
+use util::{BinaryRead, BinaryWrite, RVec, Vector};
 
 /// The `AutonomousEntities` database key.
 pub const AUTONOMOUS_ENTITIES: &[u8] = b"AutonomousEntities";
@@ -18,6 +20,43 @@ pub const SCHEDULER: &[u8] = b"schedulerWT";
 /// The `~local_player` database key.
 pub const LOCAL_PLAYER: &[u8] = b"~local_player";
 
+/// Prefix of the keys that list the actors stored in a chunk.
+///
+/// The prefix is followed by the chunk coordinates and dimension, in the same format as a [`DataKey`]
+/// but without a key type. The value is a list of little-endian 64-bit actor IDs.
+pub const DIGEST_PREFIX: &[u8] = b"digp";
+/// Prefix of the keys that store a single actor.
+///
+/// The prefix is followed by the little-endian 64-bit ID of the actor. The value is the NBT data of the actor.
+pub const ACTOR_PREFIX: &[u8] = b"actorprefix";
+/// Prefix of the keys that store the data of a player.
+///
+/// The prefix is followed by the identifier of the player, e.g. `server_` and a UUID.
+pub const PLAYER_PREFIX: &[u8] = b"player_";
+
+/// Creates the [`DIGEST_PREFIX`] key of the given chunk.
+pub(crate) fn digest_key(coordinates: &Vector<i32, 2>, dimension: Dimension) -> anyhow::Result<RVec> {
+    let mut raw_key = RVec::alloc_with_capacity(DIGEST_PREFIX.len() + 12);
+    raw_key.write_all(DIGEST_PREFIX)?;
+    raw_key.write_i32_le(coordinates.x)?;
+    raw_key.write_i32_le(coordinates.y)?;
+
+    if dimension != Dimension::Overworld {
+        raw_key.write_i32_le(dimension as i32)?;
+    }
+
+    Ok(raw_key)
+}
+
+/// Creates the [`ACTOR_PREFIX`] key of the actor with the given ID.
+pub(crate) fn actor_key(unique_id: i64) -> anyhow::Result<RVec> {
+    let mut raw_key = RVec::alloc_with_capacity(ACTOR_PREFIX.len() + 8);
+    raw_key.write_all(ACTOR_PREFIX)?;
+    raw_key.write_i64_le(unique_id)?;
+
+    Ok(raw_key)
+}
+
 /// Database key prefixes.
 ///
 /// Data from [`Minecraft fandom`](https://minecraft.fandom.com/wiki/Bedrock_Edition_level_format#Chunk_key_format).
@@ -36,7 +75,7 @@ pub enum KeyType {
         /// This index can also be negative, indicating subchunks that are below 0.
         index: i8,
     } = 0x2f,
-    /// The old terrain format.
+    /// The terrain format used before subchunks were introduced, see [`LegacyTerrain`](crate::LegacyTerrain).
     LegacyTerrain = 0x30,
     /// A block entity.
     BlockEntity = 0x31,
@@ -54,6 +93,8 @@ pub enum KeyType {
     HardCodedSpawnAreas = 0x39,
     /// Random tick data.
     RandomTicks = 0x3a,
+    /// Version of the specified chunk, used by worlds from before 1.16.100.
+    LegacyVersion = 0x76,
 }
 
 impl KeyType {
@@ -67,7 +108,7 @@ impl KeyType {
 }
 
 /// A key that can be loaded from the database.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DataKey {
     /// X and Z coordinates of the requested chunk.
     pub coordinates: Vector<i32, 2>,
@@ -111,7 +152,8 @@ impl DataKey {
         let x = reader.read_i32_le()?;
         let z = reader.read_i32_le()?;
 
-        let dimension = if reader.remaining() > 6 {
+        // Overworld keys have at most two bytes left: the key type and the subchunk index.
+        let dimension = if reader.remaining() > 2 {
             Dimension::try_from(reader.read_u32_le()?)?
         } else {
             Dimension::Overworld
@@ -132,9 +174,14 @@ impl DataKey {
             0x38 => KeyType::BorderBlocks,
             0x39 => KeyType::HardCodedSpawnAreas,
             0x3a => KeyType::RandomTicks,
+            0x76 => KeyType::LegacyVersion,
             _ => anyhow::bail!(format!("Invalid key type: {key_ty:x?}")),
         };
 
+        if !reader.eof() {
+            anyhow::bail!(format!("Chunk key contains {} trailing bytes", reader.remaining()));
+        }
+
         Ok(Self {
             coordinates: Vector::from([x, z]),
             dimension,
@@ -142,3 +189,174 @@ impl DataKey {
         })
     }
 }
+
+/// Any key that can be found in the database.
+///
+/// Keys that belong to a chunk are stored as [`DataKey`]s, most other keys are a fixed string or a string
+/// prefix followed by an identifier. Keys that are not recognised are kept as [`DbKey::Other`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DbKey {
+    /// Data of a chunk.
+    Chunk(DataKey),
+    /// List of the actors in a chunk, see [`DIGEST_PREFIX`].
+    Digest {
+        /// X and Z coordinates of the chunk.
+        coordinates: Vector<i32, 2>,
+        /// Dimension of the chunk.
+        dimension: Dimension,
+    },
+    /// A single actor, see [`ACTOR_PREFIX`].
+    Actor {
+        /// Unique ID of the actor.
+        unique_id: i64,
+    },
+    /// Data of a player, see [`PLAYER_PREFIX`].
+    Player {
+        /// Identifier of the player, without the prefix.
+        id: String,
+    },
+    /// The [`AUTONOMOUS_ENTITIES`] key.
+    AutonomousEntities,
+    /// The [`BIOME_DATA`] key.
+    BiomeData,
+    /// The [`CHUNK_METADATA`] key.
+    ChunkMetadata,
+    /// The [`OVERWORLD`] key.
+    Overworld,
+    /// The [`MOB_EVENTS`] key.
+    MobEvents,
+    /// The [`SCOREBOARD`] key.
+    Scoreboard,
+    /// The [`SCHEDULER`] key.
+    Scheduler,
+    /// The [`LOCAL_PLAYER`] key.
+    LocalPlayer,
+    /// A key that is not recognised.
+    Other(Vec<u8>),
+}
+
+impl DbKey {
+    /// Parses a raw database key.
+    ///
+    /// This never fails, unknown keys are returned as [`DbKey::Other`].
+    pub fn parse(raw_key: &[u8]) -> DbKey {
+        const NAMED: &[(&[u8], DbKey)] = &[
+            (AUTONOMOUS_ENTITIES, DbKey::AutonomousEntities),
+            (BIOME_DATA, DbKey::BiomeData),
+            (CHUNK_METADATA, DbKey::ChunkMetadata),
+            (OVERWORLD, DbKey::Overworld),
+            (MOB_EVENTS, DbKey::MobEvents),
+            (SCOREBOARD, DbKey::Scoreboard),
+            (SCHEDULER, DbKey::Scheduler),
+            (LOCAL_PLAYER, DbKey::LocalPlayer),
+        ];
+
+        if let Some((_, key)) = NAMED.iter().find(|(name, _)| *name == raw_key) {
+            return key.clone();
+        }
+
+        if let Some(mut rest) = raw_key.strip_prefix(ACTOR_PREFIX) {
+            if rest.len() == 8 {
+                if let Ok(unique_id) = rest.read_i64_le() {
+                    return DbKey::Actor { unique_id };
+                }
+            }
+        }
+
+        if let Some(mut rest) = raw_key.strip_prefix(DIGEST_PREFIX) {
+            if rest.len() == 8 || rest.len() == 12 {
+                if let Ok(digest) = Self::parse_digest(&mut rest) {
+                    return digest;
+                }
+            }
+        }
+
+        if let Some(rest) = raw_key.strip_prefix(PLAYER_PREFIX) {
+            if let Ok(id) = std::str::from_utf8(rest) {
+                return DbKey::Player { id: id.to_owned() };
+            }
+        }
+
+        // Chunk keys consist of two or three integers, a key type and an optional subchunk index.
+        if matches!(raw_key.len(), 9 | 10 | 13 | 14) {
+            if let Ok(key) = DataKey::deserialize(raw_key) {
+                return DbKey::Chunk(key);
+            }
+        }
+
+        DbKey::Other(raw_key.to_vec())
+    }
+
+    /// Parses the body of a [`DIGEST_PREFIX`] key.
+    fn parse_digest(reader: &mut &[u8]) -> anyhow::Result<DbKey> {
+        let x = reader.read_i32_le()?;
+        let z = reader.read_i32_le()?;
+        let dimension = if reader.is_empty() {
+            Dimension::Overworld
+        } else {
+            Dimension::try_from(reader.read_u32_le()?)?
+        };
+
+        Ok(DbKey::Digest {
+            coordinates: Vector::from([x, z]),
+            dimension,
+        })
+    }
+
+    /// Serialises this key into its raw form.
+    pub fn serialize(&self) -> anyhow::Result<RVec> {
+        let named = match self {
+            DbKey::Chunk(key) => {
+                let mut raw_key = RVec::alloc_with_capacity(key.serialized_size());
+                key.serialize(&mut raw_key)?;
+                return Ok(raw_key);
+            }
+            DbKey::Digest { coordinates, dimension } => return digest_key(coordinates, *dimension),
+            DbKey::Actor { unique_id } => return actor_key(*unique_id),
+            DbKey::Player { id } => {
+                let mut raw_key = RVec::alloc_with_capacity(PLAYER_PREFIX.len() + id.len());
+                raw_key.write_all(PLAYER_PREFIX)?;
+                raw_key.write_all(id.as_bytes())?;
+                return Ok(raw_key);
+            }
+            DbKey::Other(raw) => raw.as_slice(),
+            DbKey::AutonomousEntities => AUTONOMOUS_ENTITIES,
+            DbKey::BiomeData => BIOME_DATA,
+            DbKey::ChunkMetadata => CHUNK_METADATA,
+            DbKey::Overworld => OVERWORLD,
+            DbKey::MobEvents => MOB_EVENTS,
+            DbKey::Scoreboard => SCOREBOARD,
+            DbKey::Scheduler => SCHEDULER,
+            DbKey::LocalPlayer => LOCAL_PLAYER,
+        };
+
+        let mut raw_key = RVec::alloc_with_capacity(named.len());
+        raw_key.write_all(named)?;
+        Ok(raw_key)
+    }
+
+    /// Returns the chunk this key belongs to, if any.
+    pub fn chunk(&self) -> Option<(&Vector<i32, 2>, Dimension)> {
+        match self {
+            DbKey::Chunk(key) => Some((&key.coordinates, key.dimension)),
+            DbKey::Digest { coordinates, dimension } => Some((coordinates, *dimension)),
+            _ => None,
+        }
+    }
+}
+
+/// Creates the prefix shared by all [`DataKey`]s of the given chunk.
+///
+/// Overworld keys of a chunk are a prefix of the keys of the same chunk in other dimensions,
+/// so the keys found with this prefix should still be filtered by dimension.
+pub(crate) fn chunk_prefix(coordinates: &Vector<i32, 2>, dimension: Dimension) -> anyhow::Result<RVec> {
+    let mut prefix = RVec::alloc_with_capacity(12);
+    prefix.write_i32_le(coordinates.x)?;
+    prefix.write_i32_le(coordinates.y)?;
+
+    if dimension != Dimension::Overworld {
+        prefix.write_i32_le(dimension as i32)?;
+    }
+
+    Ok(prefix)
+}
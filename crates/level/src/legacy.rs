@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::anvil::MappingEntry;
+use crate::{PaletteEntry, SubChunk, SubChunkVersion, SubStorage};
+
+/// Block version of the entries in the built-in legacy mapping (1.12.0).
+pub const LEGACY_BLOCK_VERSION: [u8; 4] = [1, 12, 0, 0];
+
+/// Built-in table of legacy block IDs.
+const LEGACY_BLOCK_IDS: &str = include_str!("../include/legacy_block_ids.json");
+
+/// Size of the block ID array of a legacy terrain chunk.
+const TERRAIN_BLOCKS_SIZE: usize = 16 * 16 * 128;
+/// Size of the metadata, sky light and block light arrays of a legacy terrain chunk.
+const TERRAIN_NIBBLES_SIZE: usize = TERRAIN_BLOCKS_SIZE / 2;
+/// Total size of a legacy terrain chunk.
+const TERRAIN_SIZE: usize = TERRAIN_BLOCKS_SIZE + 3 * TERRAIN_NIBBLES_SIZE + 256 + 256 * 4;
+
+/// Translates numeric block IDs and metadata into palette entries.
+///
+/// Worlds from before 1.2.13 store blocks as an ID and a 4-bit metadata value instead of a palette.
+/// Entries can either be registered for a specific metadata value or for all metadata values of an ID.
+#[derive(Debug, Clone)]
+pub struct LegacyBlockMapping {
+    /// Entries for a specific ID and metadata pair, indexed by `id << 4 | meta`.
+    blocks: HashMap<u16, PaletteEntry>,
+    /// Entries used for metadata values that do not have their own entry.
+    defaults: HashMap<u8, PaletteEntry>,
+    /// Entry used for unknown blocks.
+    unknown: PaletteEntry,
+}
+
+impl LegacyBlockMapping {
+    /// Creates an empty mapping.
+    ///
+    /// Blocks that are not in the mapping are translated to `minecraft:info_update` with the given version.
+    pub fn new(version: Option<[u8; 4]>) -> Self {
+        Self {
+            blocks: HashMap::new(),
+            defaults: HashMap::new(),
+            unknown: PaletteEntry {
+                name: String::from("minecraft:info_update"),
+                version,
+                states: HashMap::new(),
+            },
+        }
+    }
+
+    /// Returns the built-in mapping.
+    ///
+    /// This mapping contains every vanilla block ID and the metadata variants of most common blocks.
+    /// The entries use the block states of [`LEGACY_BLOCK_VERSION`].
+    #[allow(clippy::expect_used)] // The built-in table is verified by the tests.
+    pub fn builtin() -> &'static Self {
+        static BUILTIN: OnceLock<LegacyBlockMapping> = OnceLock::new();
+        BUILTIN.get_or_init(|| Self::from_json(LEGACY_BLOCK_IDS, Some(LEGACY_BLOCK_VERSION)).expect("Built-in legacy block mapping is invalid"))
+    }
+
+    /// Loads a mapping table from JSON.
+    ///
+    /// The JSON should be an object mapping `id` or `id:meta` keys to blocks:
+    ///
+    /// ```json
+    /// {
+    ///     "1": { "name": "minecraft:stone", "states": {} },
+    ///     "1:1": { "name": "minecraft:stone", "states": { "stone_type": "granite" } }
+    /// }
+    /// ```
+    ///
+    /// Boolean states are converted to bytes and numbers to ints.
+    pub fn from_json(json: &str, version: Option<[u8; 4]>) -> anyhow::Result<Self> {
+        let entries: HashMap<String, MappingEntry> = serde_json::from_str(json)?;
+
+        let mut mapping = Self::new(version);
+        for (key, entry) in entries {
+            let (id, meta) = match key.split_once(':') {
+                Some((id, meta)) => (id, Some(meta)),
+                None => (key.as_str(), None),
+            };
+
+            let Ok(id) = id.parse::<u8>() else {
+                anyhow::bail!(format!("Invalid legacy block ID: {key}"));
+            };
+
+            let meta = match meta.map(str::parse::<u8>) {
+                Some(Ok(meta)) if meta < 16 => Some(meta),
+                Some(_) => anyhow::bail!(format!("Invalid legacy block metadata: {key}")),
+                None => None,
+            };
+
+            let entry = entry.into_palette_entry(&key, version)?;
+            mapping.insert(id, meta, entry);
+        }
+
+        Ok(mapping)
+    }
+
+    /// Adds a translation for the given block ID.
+    ///
+    /// If `meta` is `None`, the entry is used for every metadata value that has no entry of its own.
+    pub fn insert(&mut self, id: u8, meta: Option<u8>, entry: PaletteEntry) {
+        match meta {
+            Some(meta) => {
+                self.blocks.insert(u16::from(id) << 4 | u16::from(meta & 0xf), entry);
+            }
+            None => {
+                self.defaults.insert(id, entry);
+            }
+        }
+    }
+
+    /// Amount of translations in this mapping.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.blocks.len() + self.defaults.len()
+    }
+
+    /// Whether this mapping has no translations.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty() && self.defaults.is_empty()
+    }
+
+    /// Translates a block ID and metadata value, returning `None` if the ID is unknown.
+    pub fn translate(&self, id: u8, meta: u8) -> Option<&PaletteEntry> {
+        self.blocks
+            .get(&(u16::from(id) << 4 | u16::from(meta & 0xf)))
+            .or_else(|| self.defaults.get(&id))
+    }
+
+    /// Translates a block ID and metadata value, falling back to `minecraft:info_update` if the ID is unknown.
+    pub fn translate_or_unknown(&self, id: u8, meta: u8) -> &PaletteEntry {
+        self.translate(id, meta).unwrap_or(&self.unknown)
+    }
+}
+
+impl SubChunk {
+    /// Creates a subchunk from legacy block IDs and metadata.
+    ///
+    /// Both arrays are in XZY order, see [`to_offset`](crate::to_offset). Every byte of `meta` contains two values,
+    /// the low nibble belongs to the even offset.
+    pub fn from_legacy_ids(index: i8, blocks: &[u8; 4096], meta: &[u8; 2048], mapping: &LegacyBlockMapping) -> Self {
+        let mut lookup: HashMap<u16, u16> = HashMap::new();
+        let mut palette = Vec::new();
+        let mut indices = Box::new([0u16; 4096]);
+
+        for (offset, (&id, index)) in blocks.iter().zip(indices.iter_mut()).enumerate() {
+            let data = meta[offset >> 1] >> ((offset & 1) * 4) & 0xf;
+            *index = *lookup.entry(u16::from(id) << 4 | u16::from(data)).or_insert_with(|| {
+                palette.push(mapping.translate_or_unknown(id, data).clone());
+                (palette.len() - 1) as u16
+            });
+        }
+
+        let mut layer = SubStorage { indices, palette };
+        // Different metadata values can map to the same block.
+        layer.compact();
+
+        Self {
+            version: SubChunkVersion::Limitless,
+            index,
+            layers: vec![layer],
+        }
+    }
+
+    /// Deserializes the body of a subchunk in one of the pre-palette formats (versions 0 and 2 to 7).
+    ///
+    /// These consist of 4096 block IDs followed by 2048 bytes of metadata, optionally followed by light data
+    /// that is ignored. The subchunk index is not stored in these formats and set to 0.
+    pub(crate) fn deserialize_legacy_ids(data: &[u8], mapping: &LegacyBlockMapping) -> anyhow::Result<Self> {
+        let (Some(blocks), Some(meta)) = (data.get(..4096), data.get(4096..4096 + 2048)) else {
+            anyhow::bail!(format!("Legacy sub chunk is too small: {} bytes", data.len()));
+        };
+
+        let mut blocks_array = [0u8; 4096];
+        blocks_array.copy_from_slice(blocks);
+        let mut meta_array = [0u8; 2048];
+        meta_array.copy_from_slice(meta);
+
+        let mut subchunk = Self::from_legacy_ids(0, &blocks_array, &meta_array, mapping);
+        subchunk.version = SubChunkVersion::Limited;
+
+        Ok(subchunk)
+    }
+}
+
+/// A chunk stored in the `LegacyTerrain` format, used by worlds from before 1.0.
+///
+/// These chunks are 128 blocks high and store all of their data in a single value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LegacyTerrain {
+    /// The 8 subchunks that make up the chunk, from bottom to top.
+    pub subchunks: Vec<SubChunk>,
+    /// Height of the highest block in each column, indexed by `z * 16 + x`.
+    pub heightmap: Box<[u8; 256]>,
+    /// Biome ID of each column, indexed by `z * 16 + x`.
+    pub biomes: Box<[u8; 256]>,
+}
+
+impl LegacyTerrain {
+    /// Deserializes a legacy terrain chunk, translating its blocks using the given mapping.
+    ///
+    /// The chunk consists of block IDs, metadata, sky light and block light for every block in XZY order,
+    /// followed by a heightmap and biome colours. The light data is ignored.
+    pub fn deserialize(data: &[u8], mapping: &LegacyBlockMapping) -> anyhow::Result<Self> {
+        if data.len() < TERRAIN_SIZE {
+            anyhow::bail!(format!("Legacy terrain is too small: {} bytes, expected {TERRAIN_SIZE}", data.len()));
+        }
+
+        let (blocks, rest) = data.split_at(TERRAIN_BLOCKS_SIZE);
+        let (meta, rest) = rest.split_at(TERRAIN_NIBBLES_SIZE);
+        // Skip sky light and block light.
+        let rest = &rest[2 * TERRAIN_NIBBLES_SIZE..];
+        let (heights, colours) = rest.split_at(256);
+
+        let mut subchunks = Vec::with_capacity(8);
+        for sy in 0..8 {
+            let mut sub_blocks = [0u8; 4096];
+            let mut sub_meta = [0u8; 2048];
+
+            // Legacy terrain offsets are `x << 11 | z << 7 | y`, every column is 128 blocks high.
+            for column in 0..256 {
+                let source = column * 128 + sy * 16;
+                let target = column * 16;
+
+                sub_blocks[target..target + 16].copy_from_slice(&blocks[source..source + 16]);
+                sub_meta[target / 2..target / 2 + 8].copy_from_slice(&meta[source / 2..source / 2 + 8]);
+            }
+
+            subchunks.push(SubChunk::from_legacy_ids(sy as i8, &sub_blocks, &sub_meta, mapping));
+        }
+
+        let mut heightmap = Box::new([0u8; 256]);
+        heightmap.copy_from_slice(heights);
+
+        // Each colour is a big endian integer with the biome ID in the highest byte.
+        let mut biomes = Box::new([0u8; 256]);
+        for (biome, colour) in biomes.iter_mut().zip(colours.chunks_exact(4)) {
+            *biome = colour[0];
+        }
+
+        Ok(Self { subchunks, heightmap, biomes })
+    }
+}
@@ -163,21 +163,40 @@ where
 #[cfg(test)]
 mod test;
 
+mod actor;
+mod backup;
 mod batch;
 mod biome;
+mod block_entity;
 mod ffi;
 mod key;
+mod legacy;
+mod light;
 mod settings;
 mod states;
+mod storage;
+mod structure;
 mod subchunk;
+mod upgrade;
 
+/// Imports Java Edition worlds stored in the Anvil format.
+pub mod anvil;
 /// Direct access to the LevelDB database.
 pub mod database;
 /// Implements serialization and deserialization for important types.
 pub mod provider;
 
+pub use actor::*;
+pub use backup::*;
 pub use batch::*;
 pub use biome::*;
+pub use block_entity::*;
 pub use key::*;
+pub use legacy::*;
+pub use light::*;
+pub use settings::*;
 pub use states::*;
+pub use storage::*;
+pub use structure::*;
 pub use subchunk::*;
+pub use upgrade::*;
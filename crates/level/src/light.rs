@@ -0,0 +1,814 @@
+//! Computes sky light and block light.
+//!
+//! Bedrock Edition neither stores light in the level database nor sends it to clients,
+//! which compute lighting themselves. The light computed by the [`LightEngine`] is only kept
+//! in memory and is used for server-side logic such as mob spawning.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    ops::Range,
+};
+
+use util::Vector;
+
+use crate::{to_offset, PaletteEntry, SubChunk};
+
+/// Highest possible light level.
+pub const MAX_LIGHT: u8 = 15;
+
+/// Blocks that light passes through unobstructed.
+const TRANSPARENT_BLOCKS: &[&str] = &[
+    "air",
+    "cave_air",
+    "void_air",
+    "light_block",
+    "structure_void",
+    "barrier",
+    "glass",
+    "glass_pane",
+    "tinted_glass",
+    "hard_glass",
+    "hard_glass_pane",
+    "short_grass",
+    "tallgrass",
+    "tall_grass",
+    "fern",
+    "large_fern",
+    "deadbush",
+    "dandelion",
+    "poppy",
+    "red_flower",
+    "yellow_flower",
+    "double_plant",
+    "wheat",
+    "carrots",
+    "potatoes",
+    "beetroot",
+    "sugar_cane",
+    "reeds",
+    "vine",
+    "ladder",
+    "lever",
+    "torch",
+    "redstone_torch",
+    "unlit_redstone_torch",
+    "soul_torch",
+    "redstone_wire",
+    "rail",
+    "golden_rail",
+    "detector_rail",
+    "activator_rail",
+    "tripwire",
+    "tripwire_hook",
+    "snow_layer",
+    "fire",
+    "soul_fire",
+    "end_rod",
+    "flower_pot",
+    "scaffolding",
+    "lantern",
+    "soul_lantern",
+    "chain",
+    "iron_bars",
+];
+
+/// Name suffixes of block families that light passes through unobstructed.
+const TRANSPARENT_SUFFIXES: &[&str] = &[
+    "_stained_glass",
+    "_stained_glass_pane",
+    "_sapling",
+    "_button",
+    "_pressure_plate",
+    "_sign",
+    "_carpet",
+    "_mushroom",
+    "_tulip",
+    "_orchid",
+    "_roots",
+    "_fungus",
+    "_coral_fan",
+    "_slab",
+    "_stairs",
+    "_fence",
+    "_fence_gate",
+    "_door",
+    "_trapdoor",
+    "_wall",
+    "candle",
+];
+
+/// Blocks that only partially reduce light, together with the amount they reduce it by.
+const FILTERING_BLOCKS: &[(&str, u8)] = &[
+    ("water", 2),
+    ("flowing_water", 2),
+    ("ice", 2),
+    ("frosted_ice", 2),
+    ("leaves", 1),
+    ("leaves2", 1),
+    ("web", 1),
+    ("slime", 1),
+    ("honey_block", 1),
+];
+
+/// Blocks that emit light, together with the light level they emit.
+const EMITTING_BLOCKS: &[(&str, u8)] = &[
+    ("beacon", 15),
+    ("conduit", 15),
+    ("end_gateway", 15),
+    ("end_portal", 15),
+    ("fire", 15),
+    ("flowing_lava", 15),
+    ("glowstone", 15),
+    ("jack_o_lantern", 15),
+    ("lantern", 15),
+    ("lava", 15),
+    ("lit_pumpkin", 15),
+    ("lit_redstone_lamp", 15),
+    ("ochre_froglight", 15),
+    ("pearlescent_froglight", 15),
+    ("sea_lantern", 15),
+    ("shroomlight", 15),
+    ("verdant_froglight", 15),
+    ("end_rod", 14),
+    ("torch", 14),
+    ("lit_blast_furnace", 13),
+    ("lit_furnace", 13),
+    ("lit_smoker", 13),
+    ("nether_portal", 11),
+    ("portal", 11),
+    ("crying_obsidian", 10),
+    ("soul_fire", 10),
+    ("soul_lantern", 10),
+    ("soul_torch", 10),
+    ("enchanting_table", 7),
+    ("ender_chest", 7),
+    ("glow_lichen", 7),
+    ("redstone_torch", 7),
+    ("amethyst_cluster", 5),
+    ("magma", 3),
+    ("brewing_stand", 1),
+    ("brown_mushroom", 1),
+    ("dragon_egg", 1),
+    ("end_portal_frame", 1),
+];
+
+/// Describes how a block interacts with light.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct LightProperties {
+    /// Amount by which light is reduced when passing through the block.
+    ///
+    /// Fully transparent blocks have a filter of 0 and opaque blocks have a filter of 15.
+    pub filter: u8,
+    /// Light level emitted by the block.
+    pub emission: u8,
+}
+
+impl LightProperties {
+    /// Packs the properties into a single byte.
+    #[inline]
+    const fn pack(self) -> u8 {
+        min(self.filter, MAX_LIGHT) | min(self.emission, MAX_LIGHT) << 4
+    }
+
+    /// Unpacks properties created by [`pack`](Self::pack).
+    #[inline]
+    const fn unpack(packed: u8) -> Self {
+        Self {
+            filter: packed & 0xf,
+            emission: packed >> 4,
+        }
+    }
+
+    /// Combines the properties of multiple layers at the same position.
+    #[inline]
+    fn combine(self, other: Self) -> Self {
+        Self {
+            filter: self.filter.max(other.filter),
+            emission: self.emission.max(other.emission),
+        }
+    }
+}
+
+/// `Ord::min` is not usable in constant functions.
+#[inline]
+const fn min(a: u8, b: u8) -> u8 {
+    if a < b {
+        a
+    } else {
+        b
+    }
+}
+
+impl PaletteEntry {
+    /// Returns the light properties of this block.
+    pub fn light_properties(&self) -> LightProperties {
+        LightProperties {
+            filter: self.light_filter(),
+            emission: self.light_emission(),
+        }
+    }
+
+    /// Amount by which light is reduced when passing through this block.
+    pub fn light_filter(&self) -> u8 {
+        let name = self.name.strip_prefix("minecraft:").unwrap_or(&self.name);
+
+        if TRANSPARENT_BLOCKS.contains(&name) || TRANSPARENT_SUFFIXES.iter().any(|suffix| name.ends_with(suffix)) {
+            return 0;
+        }
+
+        if let Some((_, filter)) = FILTERING_BLOCKS.iter().find(|(block, _)| *block == name) {
+            return *filter;
+        }
+
+        if name.ends_with("_leaves") {
+            1
+        } else {
+            MAX_LIGHT
+        }
+    }
+
+    /// Light level emitted by this block.
+    pub fn light_emission(&self) -> u8 {
+        let name = self.name.strip_prefix("minecraft:").unwrap_or(&self.name);
+        let state = |key: &str| self.states.get(key).and_then(|value| value.as_i32().or(value.as_i8().map(i32::from)));
+
+        if name == "light_block" {
+            return state("block_light_level").unwrap_or(0).clamp(0, i32::from(MAX_LIGHT)) as u8;
+        }
+
+        if name.ends_with("candle") {
+            // Every candle in the block adds three light levels.
+            return match state("lit") {
+                Some(1) => (3 * (state("candles").unwrap_or(0).clamp(0, 3) + 1)) as u8,
+                _ => 0,
+            };
+        }
+
+        EMITTING_BLOCKS
+            .iter()
+            .find(|(block, _)| *block == name)
+            .map_or(0, |(_, emission)| *emission)
+    }
+}
+
+/// Kind of light that is being propagated.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Channel {
+    /// Light coming from the sky.
+    Sky,
+    /// Light emitted by blocks.
+    Block,
+}
+
+/// Offsets to the six neighbours of a block. The first direction points down.
+const DIRECTIONS: [[i32; 3]; 6] = [[0, -1, 0], [0, 1, 0], [-1, 0, 0], [1, 0, 0], [0, 0, -1], [0, 0, 1]];
+
+/// Light levels of every block in a subchunk, stored as four bits per block.
+#[derive(Debug, Clone)]
+enum Nibbles {
+    /// Every block has the same light level.
+    Uniform(u8),
+    /// Light levels of individual blocks.
+    Data(Box<[u8; 2048]>),
+}
+
+impl Nibbles {
+    /// Returns the light level at the given offset.
+    #[inline]
+    fn get(&self, offset: usize) -> u8 {
+        match self {
+            Self::Uniform(level) => *level,
+            Self::Data(data) => (data[offset >> 1] >> ((offset & 1) * 4)) & 0xf,
+        }
+    }
+
+    /// Sets the light level at the given offset.
+    fn set(&mut self, offset: usize, level: u8) {
+        if let Self::Uniform(current) = self {
+            if *current == level {
+                return;
+            }
+
+            *self = Self::Data(Box::new([*current | *current << 4; 2048]));
+        }
+
+        if let Self::Data(data) = self {
+            let shift = (offset & 1) * 4;
+            data[offset >> 1] = (data[offset >> 1] & !(0xf << shift)) | (level & 0xf) << shift;
+        }
+    }
+}
+
+/// Light data of a single subchunk.
+struct LightSection {
+    /// Packed [`LightProperties`] of every block, or `None` if every block is transparent and emits no light.
+    properties: Option<Box<[u8; 4096]>>,
+    /// Sky light levels.
+    sky: Nibbles,
+    /// Block light levels.
+    block: Nibbles,
+}
+
+impl LightSection {
+    /// Creates a section for a subchunk that only contains air.
+    const fn air() -> Self {
+        Self {
+            properties: None,
+            sky: Nibbles::Uniform(0),
+            block: Nibbles::Uniform(0),
+        }
+    }
+
+    /// Creates an unlit section containing the blocks of the given subchunk.
+    fn new(subchunk: &SubChunk, overrides: &HashMap<String, LightProperties>) -> Self {
+        let mut section = Self::air();
+
+        for layer in subchunk.layers().iter().filter(|layer| !layer.is_empty()) {
+            let palette: Vec<u8> = layer
+                .palette()
+                .iter()
+                .map(|entry| overrides.get(&entry.name).copied().unwrap_or_else(|| entry.light_properties()).pack())
+                .collect();
+
+            if palette.iter().all(|packed| *packed == 0) {
+                continue;
+            }
+
+            let properties = section.properties.get_or_insert_with(|| Box::new([0; 4096]));
+            for (offset, index) in layer.indices.iter().enumerate() {
+                let packed = palette[*index as usize];
+                properties[offset] = LightProperties::unpack(properties[offset])
+                    .combine(LightProperties::unpack(packed))
+                    .pack();
+            }
+        }
+
+        section
+    }
+
+    /// Returns the light properties of the block at the given offset.
+    #[inline]
+    fn properties(&self, offset: usize) -> LightProperties {
+        self.properties
+            .as_ref()
+            .map_or_else(LightProperties::default, |properties| LightProperties::unpack(properties[offset]))
+    }
+
+    /// Returns the light levels of the given channel.
+    #[inline]
+    const fn channel(&self, channel: Channel) -> &Nibbles {
+        match channel {
+            Channel::Sky => &self.sky,
+            Channel::Block => &self.block,
+        }
+    }
+
+    /// Returns the light levels of the given channel.
+    #[inline]
+    fn channel_mut(&mut self, channel: Channel) -> &mut Nibbles {
+        match channel {
+            Channel::Sky => &mut self.sky,
+            Channel::Block => &mut self.block,
+        }
+    }
+}
+
+/// Light data of a chunk column.
+struct LightColumn {
+    /// Sections from bottom to top.
+    sections: Vec<LightSection>,
+    /// Lowest vertical coordinate that receives direct sky light, indexed by `[x][z]`.
+    tops: Box<[[i32; 16]; 16]>,
+}
+
+/// Computes and stores the sky light and block light of chunk columns.
+///
+/// Columns are lit in their entirety using [`light_column`](Self::light_column),
+/// after which light spreads into and out of neighbouring columns that have already been lit.
+/// Block changes are applied incrementally with [`update_block`](Self::update_block).
+pub struct LightEngine {
+    /// Range of vertical subchunk indices in the dimension.
+    range: Range<i8>,
+    /// Whether the dimension has a sky. Sky light is always zero if this is not set.
+    has_sky: bool,
+    /// Light data of all lit columns.
+    columns: HashMap<(i32, i32), LightColumn>,
+    /// Lit columns in the order they were lit, used to unload the oldest columns first.
+    order: VecDeque<(i32, i32)>,
+    /// Light properties of blocks that are not known to the engine, such as custom blocks.
+    overrides: HashMap<String, LightProperties>,
+}
+
+impl LightEngine {
+    /// Creates an engine for a dimension with the given range of vertical subchunk indices.
+    pub fn new(range: Range<i8>, has_sky: bool) -> Self {
+        Self {
+            range,
+            has_sky,
+            columns: HashMap::new(),
+            order: VecDeque::new(),
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// Overrides the light properties of the block with the given name.
+    ///
+    /// This only affects columns that are lit after calling this function.
+    pub fn set_properties<S: Into<String>>(&mut self, name: S, properties: LightProperties) {
+        self.overrides.insert(name.into(), properties);
+    }
+
+    /// Whether the column at the given chunk coordinates has been lit.
+    pub fn is_lit(&self, coordinates: &Vector<i32, 2>) -> bool {
+        self.columns.contains_key(&(coordinates.x, coordinates.y))
+    }
+
+    /// Amount of columns whose light is currently stored.
+    pub fn column_count(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// Removes the light data of the given column.
+    pub fn unload_column(&mut self, coordinates: &Vector<i32, 2>) {
+        let key = (coordinates.x, coordinates.y);
+        if self.columns.remove(&key).is_some() {
+            self.order.retain(|column| *column != key);
+        }
+    }
+
+    /// Unloads the columns that were lit first until at most `max_columns` columns are left.
+    pub fn evict(&mut self, max_columns: usize) {
+        while self.columns.len() > max_columns {
+            let Some(key) = self.order.pop_front() else { break };
+            self.columns.remove(&key);
+        }
+    }
+
+    /// Returns the sky light level at the given world coordinates.
+    ///
+    /// Returns `None` if the column has not been lit.
+    pub fn sky_light(&self, position: &Vector<i32, 3>) -> Option<u8> {
+        self.light_at(Channel::Sky, [position.x, position.y, position.z])
+    }
+
+    /// Returns the block light level at the given world coordinates.
+    ///
+    /// Returns `None` if the column has not been lit.
+    pub fn block_light(&self, position: &Vector<i32, 3>) -> Option<u8> {
+        self.light_at(Channel::Block, [position.x, position.y, position.z])
+    }
+
+    /// Returns the light level at the given position, including positions above and below the world.
+    fn light_at(&self, channel: Channel, position: [i32; 3]) -> Option<u8> {
+        if !self.columns.contains_key(&(position[0] >> 4, position[2] >> 4)) {
+            return None;
+        }
+
+        if let Some(level) = self.light(channel, position) {
+            return Some(level);
+        }
+
+        // The position lies above or below the column.
+        let above = position[1] >= i32::from(self.range.end) * 16;
+
+        Some(if above && channel == Channel::Sky && self.has_sky { MAX_LIGHT } else { 0 })
+    }
+
+    /// Computes the light of a column from the given subchunks.
+    ///
+    /// Subchunks that are not given are treated as air. If the column was lit before, it is relit.
+    pub fn light_column<'a, I>(&mut self, coordinates: &Vector<i32, 2>, subchunks: I)
+    where
+        I: IntoIterator<Item = &'a SubChunk>,
+    {
+        let key = (coordinates.x, coordinates.y);
+        self.unload_column(coordinates);
+
+        let mut sections: Vec<LightSection> = self.range.clone().map(|_| LightSection::air()).collect();
+        for subchunk in subchunks {
+            if let Some(index) = self.section_index(i32::from(subchunk.index)) {
+                sections[index] = LightSection::new(subchunk, &self.overrides);
+            }
+        }
+
+        let bottom = i32::from(self.range.start) * 16;
+        let top = i32::from(self.range.end) * 16;
+        let mut column = LightColumn {
+            sections,
+            tops: Box::new([[top; 16]; 16]),
+        };
+
+        if self.has_sky {
+            Self::light_sky_direct(&mut column, bottom);
+        }
+
+        // Seed block light with every emitting block.
+        let mut block_queue = VecDeque::new();
+        for (index, section) in column.sections.iter_mut().enumerate() {
+            let Some(properties) = &section.properties else { continue };
+
+            for (offset, packed) in properties.iter().enumerate() {
+                let emission = LightProperties::unpack(*packed).emission;
+                if emission > 0 {
+                    section.block.set(offset, emission);
+
+                    let local = crate::from_offset(offset);
+                    block_queue.push_back([
+                        coordinates.x * 16 + i32::from(local.x),
+                        bottom + index as i32 * 16 + i32::from(local.y),
+                        coordinates.y * 16 + i32::from(local.z),
+                    ]);
+                }
+            }
+        }
+
+        self.columns.insert(key, column);
+        self.order.push_back(key);
+
+        // Seed sky light where it can spread sideways into lower neighbours.
+        let mut sky_queue = VecDeque::new();
+        if self.has_sky {
+            for x in 0..16 {
+                for z in 0..16 {
+                    let world = [coordinates.x * 16 + x, coordinates.y * 16 + z];
+                    let Some(own) = self.top(world[0], world[1]) else { continue };
+
+                    let highest_neighbour = DIRECTIONS[2..]
+                        .iter()
+                        .filter_map(|dir| self.top(world[0] + dir[0], world[1] + dir[2]))
+                        .max()
+                        .unwrap_or(own);
+
+                    for y in own..=highest_neighbour.max(own).min(top - 1) {
+                        sky_queue.push_back([world[0], y, world[1]]);
+                    }
+                }
+            }
+        }
+
+        // Let light from neighbouring columns spread into this column.
+        for dir in &DIRECTIONS[2..] {
+            let neighbour = (coordinates.x + dir[0], coordinates.y + dir[2]);
+            if !self.columns.contains_key(&neighbour) {
+                continue;
+            }
+
+            for i in 0..16 {
+                // Position in the neighbouring column that borders this column.
+                let (x, z) = match dir {
+                    [-1, _, _] => (neighbour.0 * 16 + 15, coordinates.y * 16 + i),
+                    [1, _, _] => (neighbour.0 * 16, coordinates.y * 16 + i),
+                    [_, _, -1] => (coordinates.x * 16 + i, neighbour.1 * 16 + 15),
+                    _ => (coordinates.x * 16 + i, neighbour.1 * 16),
+                };
+
+                for y in bottom..top {
+                    if self.light(Channel::Block, [x, y, z]).is_some_and(|level| level > 1) {
+                        block_queue.push_back([x, y, z]);
+                    }
+
+                    if self.has_sky && self.light(Channel::Sky, [x, y, z]).is_some_and(|level| level > 1) {
+                        sky_queue.push_back([x, y, z]);
+                    }
+                }
+            }
+        }
+
+        self.propagate(Channel::Sky, &mut sky_queue);
+        self.propagate(Channel::Block, &mut block_queue);
+    }
+
+    /// Updates the light after the block at the given world coordinates was changed.
+    ///
+    /// `subchunk` is the subchunk containing the block after the modification.
+    /// This does nothing if the column has not been lit.
+    pub fn update_block(&mut self, position: &Vector<i32, 3>, subchunk: &SubChunk) {
+        let position = [position.x, position.y, position.z];
+        let Some(old) = self.properties(position) else { return };
+
+        let local = Vector::from([(position[0] & 0xf) as u8, (position[1] & 0xf) as u8, (position[2] & 0xf) as u8]);
+        let new = subchunk
+            .layers()
+            .iter()
+            .filter(|layer| !layer.is_empty())
+            .filter_map(|layer| layer.get(local.clone()))
+            .map(|entry| self.overrides.get(&entry.name).copied().unwrap_or_else(|| entry.light_properties()))
+            .fold(LightProperties::default(), LightProperties::combine);
+
+        if old == new {
+            return;
+        }
+
+        if let Some((section, offset)) = self.section_mut(position) {
+            let properties = section.properties.get_or_insert_with(|| Box::new([0; 4096]));
+            properties[offset] = new.pack();
+        }
+
+        let channels: &[Channel] = if self.has_sky { &[Channel::Sky, Channel::Block] } else { &[Channel::Block] };
+        for &channel in channels {
+            let Some(level) = self.light(channel, position) else { continue };
+            self.set_light(channel, position, 0);
+
+            let mut relight = VecDeque::new();
+            self.remove(channel, VecDeque::from([(position, level)]), &mut relight);
+
+            // The changed block may now let light from its neighbours pass through.
+            for dir in &DIRECTIONS {
+                relight.push_back([position[0] + dir[0], position[1] + dir[1], position[2] + dir[2]]);
+            }
+
+            match channel {
+                Channel::Sky => self.relight_sky_direct(position[0], position[2], &mut relight),
+                Channel::Block if new.emission > 0 => {
+                    self.set_light(channel, position, new.emission);
+                    relight.push_back(position);
+                }
+                Channel::Block => (),
+            }
+
+            self.propagate(channel, &mut relight);
+        }
+    }
+
+    /// Fills every block that can see the sky with full sky light and records the tops of the column.
+    fn light_sky_direct(column: &mut LightColumn, bottom: i32) {
+        let mut open = [[true; 16]; 16];
+        let mut open_count = 256;
+
+        for (index, section) in column.sections.iter_mut().enumerate().rev() {
+            let base = bottom + index as i32 * 16;
+
+            if section.properties.is_none() && open_count == 256 {
+                // The entire section is transparent and fully exposed to the sky.
+                section.sky = Nibbles::Uniform(MAX_LIGHT);
+                *column.tops = [[base; 16]; 16];
+                continue;
+            }
+
+            for x in 0..16u8 {
+                for z in 0..16u8 {
+                    if !open[x as usize][z as usize] {
+                        continue;
+                    }
+
+                    for y in (0..16u8).rev() {
+                        let offset = to_offset([x, y, z].into());
+                        if section.properties(offset).filter > 0 {
+                            open[x as usize][z as usize] = false;
+                            open_count -= 1;
+                            break;
+                        }
+
+                        section.sky.set(offset, MAX_LIGHT);
+                        column.tops[x as usize][z as usize] = base + i32::from(y);
+                    }
+                }
+            }
+
+            if open_count == 0 {
+                break;
+            }
+        }
+    }
+
+    /// Restores direct sky light in a single column of blocks after a block has changed.
+    fn relight_sky_direct(&mut self, x: i32, z: i32, queue: &mut VecDeque<[i32; 3]>) {
+        let bottom = i32::from(self.range.start) * 16;
+        let mut y = i32::from(self.range.end) * 16 - 1;
+
+        while y >= bottom {
+            match self.properties([x, y, z]) {
+                Some(properties) if properties.filter == 0 => {
+                    if self.light(Channel::Sky, [x, y, z]) != Some(MAX_LIGHT) {
+                        self.set_light(Channel::Sky, [x, y, z], MAX_LIGHT);
+                        queue.push_back([x, y, z]);
+                    }
+                }
+                _ => break,
+            }
+
+            y -= 1;
+        }
+
+        if let Some(column) = self.columns.get_mut(&(x >> 4, z >> 4)) {
+            column.tops[(x & 0xf) as usize][(z & 0xf) as usize] = y + 1;
+        }
+    }
+
+    /// Spreads light from every position in the queue.
+    fn propagate(&mut self, channel: Channel, queue: &mut VecDeque<[i32; 3]>) {
+        while let Some(position) = queue.pop_front() {
+            let Some(level) = self.light(channel, position) else { continue };
+            if level <= 1 {
+                continue;
+            }
+
+            for (direction, dir) in DIRECTIONS.iter().enumerate() {
+                let neighbour = [position[0] + dir[0], position[1] + dir[1], position[2] + dir[2]];
+                let Some(properties) = self.properties(neighbour) else { continue };
+
+                // Sky light travels down without being reduced until it hits a block.
+                let new = if channel == Channel::Sky && direction == 0 && level == MAX_LIGHT && properties.filter == 0 {
+                    MAX_LIGHT
+                } else {
+                    level.saturating_sub(properties.filter.max(1))
+                };
+
+                if self.light(channel, neighbour).is_some_and(|current| new > current) {
+                    self.set_light(channel, neighbour, new);
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+    }
+
+    /// Removes light that originated from the positions in `removals`, which contains the previous light levels.
+    ///
+    /// Positions that have to be relit afterwards are added to `relight`.
+    fn remove(&mut self, channel: Channel, mut removals: VecDeque<([i32; 3], u8)>, relight: &mut VecDeque<[i32; 3]>) {
+        while let Some((position, level)) = removals.pop_front() {
+            for (direction, dir) in DIRECTIONS.iter().enumerate() {
+                let neighbour = [position[0] + dir[0], position[1] + dir[1], position[2] + dir[2]];
+                let Some(current) = self.light(channel, neighbour) else { continue };
+                if current == 0 {
+                    continue;
+                }
+
+                let direct_sky = channel == Channel::Sky && direction == 0 && level == MAX_LIGHT && current == MAX_LIGHT;
+                if current < level || direct_sky {
+                    // Emitting blocks keep their own light.
+                    let emission = match channel {
+                        Channel::Sky => 0,
+                        Channel::Block => self.properties(neighbour).map_or(0, |properties| properties.emission),
+                    };
+
+                    self.set_light(channel, neighbour, emission);
+                    if emission > 0 {
+                        relight.push_back(neighbour);
+                    }
+
+                    removals.push_back((neighbour, current));
+                } else {
+                    relight.push_back(neighbour);
+                }
+            }
+        }
+    }
+
+    /// Returns the lowest vertical coordinate that receives direct sky light at the given block coordinates.
+    fn top(&self, x: i32, z: i32) -> Option<i32> {
+        let column = self.columns.get(&(x >> 4, z >> 4))?;
+        Some(column.tops[(x & 0xf) as usize][(z & 0xf) as usize])
+    }
+
+    /// Converts a vertical subchunk index to an index into the sections of a column.
+    #[inline]
+    fn section_index(&self, y: i32) -> Option<usize> {
+        usize::try_from(y - i32::from(self.range.start))
+            .ok()
+            .filter(|index| *index < self.range.len())
+    }
+
+    /// Returns the section containing the given position and the offset of the position inside of it.
+    fn section(&self, position: [i32; 3]) -> Option<(&LightSection, usize)> {
+        let column = self.columns.get(&(position[0] >> 4, position[2] >> 4))?;
+        let section = column.sections.get(self.section_index(position[1] >> 4)?)?;
+
+        Some((section, Self::offset(position)))
+    }
+
+    /// Returns the section containing the given position and the offset of the position inside of it.
+    fn section_mut(&mut self, position: [i32; 3]) -> Option<(&mut LightSection, usize)> {
+        let index = self.section_index(position[1] >> 4)?;
+        let column = self.columns.get_mut(&(position[0] >> 4, position[2] >> 4))?;
+        let section = column.sections.get_mut(index)?;
+
+        Some((section, Self::offset(position)))
+    }
+
+    /// Offset of a world position inside of its subchunk.
+    #[inline]
+    fn offset(position: [i32; 3]) -> usize {
+        to_offset([(position[0] & 0xf) as u8, (position[1] & 0xf) as u8, (position[2] & 0xf) as u8].into())
+    }
+
+    /// Returns the light properties of the block at the given position.
+    #[inline]
+    fn properties(&self, position: [i32; 3]) -> Option<LightProperties> {
+        self.section(position).map(|(section, offset)| section.properties(offset))
+    }
+
+    /// Returns the light level at the given position.
+    #[inline]
+    fn light(&self, channel: Channel, position: [i32; 3]) -> Option<u8> {
+        self.section(position).map(|(section, offset)| section.channel(channel).get(offset))
+    }
+
+    /// Sets the light level at the given position.
+    #[inline]
+    fn set_light(&mut self, channel: Channel, position: [i32; 3], level: u8) {
+        if let Some((section, offset)) = self.section_mut(position) {
+            section.channel_mut(channel).set(offset, level);
+        }
+    }
+}
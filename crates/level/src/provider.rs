@@ -1,23 +1,30 @@
 // Special keys
 
 use crate::biome::Biomes;
-use crate::database::Database;
+use crate::database::{Database, Guard};
 use crate::settings::LevelSettings;
-use crate::{DataKey, KeyType, SubChunk, WriteBatch};
+use crate::{Actor, BlockEntity, DataKey, DbKey, KeyType, LegacyBlockMapping, LegacyTerrain, Storage, SubChunk, SubChunkVersion, WriteBatch};
 use anyhow::anyhow;
 use proto::types::Dimension;
 use std::path::{Path, PathBuf};
-use util::BinaryRead;
+use std::io::Write;
+use util::{BinaryRead, BinaryWrite, RVec};
 use util::Vector;
 
+/// Chunk version written for newly generated chunks.
+pub const CHUNK_VERSION: u8 = 40;
+
 /// Provides world data.
 ///
 /// This is a wrapper around a database that also deserialises and serialises data.
 /// It does not implement caching of any kind, that is up to the caller.
-pub struct Provider {
-    /// Database to load the data from.
-    database: Database,
+///
+/// The data is stored in a LevelDB [`Database`] by default, but any [`Storage`] can be used.
+pub struct Provider<S: ?Sized = Database> {
+    /// Directory containing the world.
     path: PathBuf,
+    /// Storage to load the data from.
+    database: S,
 }
 
 impl Provider {
@@ -35,6 +42,108 @@ impl Provider {
         Ok(Self { database, path: path.as_ref().to_owned() })
     }
 
+    /// Create a new write batch that can optionally be used in write operations.
+    #[inline]
+    pub fn batch() -> WriteBatch {
+        WriteBatch::new()
+    }
+
+    /// Adds a sub chunk to the given batch.
+    ///
+    /// The sub chunk is only written to disk once the batch is executed using [`execute`](Provider::execute).
+    pub fn batch_subchunk<I>(batch: &mut WriteBatch, coordinates: I, dimension: Dimension, subchunk: &SubChunk) -> anyhow::Result<()>
+    where
+        I: Into<Vector<i32, 3>>,
+    {
+        let coordinates = coordinates.into();
+        let key = DataKey {
+            coordinates: (coordinates.x, coordinates.z).into(),
+            dimension,
+            data: KeyType::SubChunk { index: coordinates.y as i8 },
+        };
+
+        batch.put_data(key, subchunk.serialize_disk()?)
+    }
+
+    /// Adds the version of a chunk to the given batch.
+    ///
+    /// The existence of this key indicates that the chunk has been generated.
+    pub fn batch_version<I>(batch: &mut WriteBatch, coordinates: I, dimension: Dimension, version: u8) -> anyhow::Result<()>
+    where
+        I: Into<Vector<i32, 2>>,
+    {
+        let key = DataKey {
+            coordinates: coordinates.into(),
+            dimension,
+            data: KeyType::ChunkVersion,
+        };
+
+        batch.put_data(key, [version])
+    }
+
+    /// Adds the biomes of a chunk to the given batch.
+    pub fn batch_biomes<I>(batch: &mut WriteBatch, coordinates: I, dimension: Dimension, biomes: &Biomes) -> anyhow::Result<()>
+    where
+        I: Into<Vector<i32, 2>>,
+    {
+        let key = DataKey {
+            coordinates: coordinates.into(),
+            dimension,
+            data: KeyType::Biome3d,
+        };
+
+        let mut buffer = RVec::alloc();
+        biomes.serialize(&mut buffer)?;
+
+        batch.put_data(key, buffer)
+    }
+
+    /// Replaces the block entities of a chunk in the given batch.
+    ///
+    /// The key is removed entirely if `entities` is empty.
+    pub fn batch_block_entities<I>(batch: &mut WriteBatch, coordinates: I, dimension: Dimension, entities: &[BlockEntity]) -> anyhow::Result<()>
+    where
+        I: Into<Vector<i32, 2>>,
+    {
+        let key = DataKey {
+            coordinates: coordinates.into(),
+            dimension,
+            data: KeyType::BlockEntity,
+        };
+
+        if entities.is_empty() {
+            batch.delete_data(key)
+        } else {
+            batch.put_data(key, BlockEntity::serialize_all(entities)?)
+        }
+    }
+}
+
+impl<S: Storage> Provider<S> {
+    /// Creates a provider for the world in the given directory that stores its data in `storage`.
+    ///
+    /// The world settings are still read from and written to `level.dat` in `path`.
+    pub fn with_storage<P>(path: P, storage: S) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self { path: path.as_ref().to_owned(), database: storage }
+    }
+}
+
+impl<S: Storage + ?Sized> Provider<S> {
+    /// Directory containing the world.
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Storage that the world data is stored in.
+    #[inline]
+    pub const fn database(&self) -> &S {
+        &self.database
+    }
+
     /// Gets the world settings, encoded in the `level.dat` file.
     ///
     /// # Errors
@@ -58,6 +167,86 @@ impl Provider {
         Ok(settings)
     }
 
+    /// Writes the world settings to the `level.dat` file.
+    ///
+    /// The settings are first written to a temporary file which then replaces the existing `level.dat`.
+    /// This ensures the file is never left in a partially written state. The previous settings are kept
+    /// in `level.dat_old`.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the settings could not be serialized or written to disk.
+    #[tracing::instrument(skip_all, name = "Provider::save_settings")]
+    pub fn save_settings(&self, settings: &LevelSettings) -> anyhow::Result<()> {
+        let nbt = nbt::to_le_bytes(settings)?;
+
+        let mut raw = Vec::with_capacity(nbt.len() + 8);
+        raw.write_u32_le(settings.storage_version as u32)?;
+        raw.write_u32_le(nbt.len() as u32)?;
+        raw.extend_from_slice(&nbt);
+
+        let path = self.path.join("level.dat");
+        let temp_path = self.path.join("level.dat_new");
+        {
+            let mut file = std::fs::File::create(&temp_path)?;
+            file.write_all(&raw)?;
+            file.sync_all()?;
+        }
+
+        if path.exists() {
+            std::fs::copy(&path, self.path.join("level.dat_old"))?;
+        }
+        std::fs::rename(temp_path, path)?;
+
+        Ok(())
+    }
+
+    /// Returns all entries whose key starts with `prefix`, such as [`PLAYER_PREFIX`](crate::PLAYER_PREFIX).
+    ///
+    /// The storage seeks directly to the first matching key.
+    pub fn entries_with_prefix<'a>(&'a self, prefix: &[u8]) -> impl Iterator<Item = (DbKey, Guard<'a>)> + 'a {
+        self.database.iter_prefix(prefix).map(|(key, value)| (DbKey::parse(&key), value))
+    }
+
+    /// Returns all data stored for the specified chunk, such as its version, subchunks and block entities.
+    ///
+    /// # Arguments
+    ///
+    /// * `coordinates` - X and Z coordinates of the chunk.
+    /// * `dimension` - Dimension of the chunk.
+    pub fn chunk_entries<I>(&self, coordinates: I, dimension: Dimension) -> anyhow::Result<impl Iterator<Item = (KeyType, Guard<'_>)> + '_>
+    where
+        I: Into<Vector<i32, 2>>,
+    {
+        let prefix = crate::chunk_prefix(&coordinates.into(), dimension)?;
+        Ok(self.database.iter_prefix(&prefix).filter_map(move |(key, value)| match DbKey::parse(&key) {
+            // The prefix of an overworld chunk also matches keys of the same chunk in other dimensions.
+            DbKey::Chunk(key) if key.dimension == dimension => Some((key.data, value)),
+            _ => None,
+        }))
+    }
+
+    /// Returns the coordinates of every chunk in the given dimension that has been generated.
+    ///
+    /// This is a full scan: every key in the database is read, although their values are not loaded.
+    /// Chunk keys are not grouped by dimension, so the scan cannot be narrowed using a prefix or range.
+    /// It can take a long time on large worlds and should not be called while the level is being played.
+    pub fn chunks(&self, dimension: Dimension) -> Vec<Vector<i32, 2>> {
+        let mut chunks = Vec::new();
+        for key in self.database.iter_keys(&[]) {
+            if let DbKey::Chunk(key) = DbKey::parse(&key) {
+                let is_chunk = matches!(key.data, KeyType::ChunkVersion | KeyType::LegacyVersion);
+                // A chunk that was upgraded may have both version keys. Keys of a chunk are stored together,
+                // so both are found after each other.
+                if is_chunk && key.dimension == dimension && chunks.last() != Some(&key.coordinates) {
+                    chunks.push(key.coordinates);
+                }
+            }
+        }
+
+        chunks
+    }
+
     /// Load the version of the specified chunk.
     ///
     /// As of writing, the current chunk version is `40`.
@@ -75,12 +264,24 @@ impl Provider {
     where
         I: Into<Vector<i32, 2>>,
     {
+        let coordinates = coordinates.into();
         let key = DataKey {
-            coordinates: coordinates.into(),
+            coordinates: coordinates.clone(),
             dimension,
             data: KeyType::ChunkVersion,
         };
 
+        if let Some(data) = self.database.get(key)? {
+            return Ok(Some(data[0]));
+        }
+
+        // Older worlds store the version under a different key.
+        let key = DataKey {
+            coordinates,
+            dimension,
+            data: KeyType::LegacyVersion,
+        };
+
         self.database.get(key)?.map_or_else(|| Ok(None), |data| Ok(Some(data[0])))
     }
 
@@ -130,6 +331,39 @@ impl Provider {
     /// This method returns `None` if the sub chunk was not found
     /// and an error if the data could not be loaded.
     pub fn subchunk<I>(&self, coordinates: I, dimension: Dimension) -> anyhow::Result<Option<SubChunk>>
+    where
+        I: Into<Vector<i32, 3>>,
+    {
+        let coordinates = coordinates.into();
+        if let Some(sub_chunk) = self.stored_subchunk(coordinates.clone(), dimension)? {
+            return Ok(Some(sub_chunk));
+        }
+
+        // Chunks from before subchunks were introduced only cover the bottom 8 subchunks.
+        let Ok(index @ 0..=7) = usize::try_from(coordinates.y) else {
+            return Ok(None);
+        };
+
+        let terrain = self.legacy_terrain([coordinates.x, coordinates.z], dimension, LegacyBlockMapping::builtin())?;
+        Ok(terrain.map(|mut terrain| terrain.subchunks.swap_remove(index)))
+    }
+
+    /// Loads a sub chunk that is stored under its own key.
+    ///
+    /// Unlike [`subchunk`](Self::subchunk), this does not fall back to the legacy terrain format.
+    /// Callers that load many sub chunks of old worlds should use [`legacy_terrain`](Self::legacy_terrain)
+    /// instead, which decodes all sub chunks of a column at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `coordinates` - Coordinates of the sub chunk.
+    /// * `dimension` - Dimension the chunk should be retrieved from.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `None` if the sub chunk was not found
+    /// and an error if the data could not be loaded.
+    pub fn stored_subchunk<I>(&self, coordinates: I, dimension: Dimension) -> anyhow::Result<Option<SubChunk>>
     where
         I: Into<Vector<i32, 3>>,
     {
@@ -140,17 +374,175 @@ impl Provider {
             data: KeyType::SubChunk { index: coordinates.y as i8 },
         };
 
+        let Some(data) = self.database.get(key)? else {
+            return Ok(None);
+        };
+
+        let mut sub_chunk = SubChunk::deserialize_disk(&*data)?;
+        if sub_chunk.version != SubChunkVersion::Limitless {
+            // Only the limitless format stores the index, the others take it from the key.
+            sub_chunk.index = coordinates.y as i8;
+        }
+
+        Ok(Some(sub_chunk))
+    }
+
+    /// Load a chunk stored in the legacy terrain format.
+    ///
+    /// See [`LegacyTerrain`] for more information. Blocks are translated using the given mapping.
+    ///
+    /// # Arguments
+    ///
+    /// * `coordinates` - X and Z coordinates of the chunk.
+    /// * `dimension` - Dimension the chunk should be retrieved from.
+    /// * `mapping` - Mapping used to translate the block IDs.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `None` if the chunk was not found
+    /// and an error if the data could not be loaded.
+    pub fn legacy_terrain<I>(&self, coordinates: I, dimension: Dimension, mapping: &LegacyBlockMapping) -> anyhow::Result<Option<LegacyTerrain>>
+    where
+        I: Into<Vector<i32, 2>>,
+    {
+        let key = DataKey {
+            coordinates: coordinates.into(),
+            dimension,
+            data: KeyType::LegacyTerrain,
+        };
+
         if let Some(data) = self.database.get(key)? {
-            let sub_chunk = SubChunk::deserialize_disk(&*data)?;
-            Ok(Some(sub_chunk))
+            let terrain = LegacyTerrain::deserialize(&data, mapping)?;
+            Ok(Some(terrain))
         } else {
             Ok(None)
         }
     }
 
-    /// Create a new write batch that can optionally be used in write operations.
-    #[inline]
-    pub fn batch() -> WriteBatch {
-        WriteBatch::new()
+    /// Load the block entities in the specified chunk.
+    ///
+    /// See [`BlockEntity`] for more information.
+    ///
+    /// # Arguments
+    ///
+    /// * `coordinates` - X and Z coordinates of the chunk.
+    /// * `dimension` - Dimension the chunk should be retrieved from.
+    ///
+    /// # Returns
+    ///
+    /// This method returns an empty list if the chunk does not contain any block entities
+    /// and an error if the data could not be loaded.
+    pub fn block_entities<I>(&self, coordinates: I, dimension: Dimension) -> anyhow::Result<Vec<BlockEntity>>
+    where
+        I: Into<Vector<i32, 2>>,
+    {
+        let key = DataKey {
+            coordinates: coordinates.into(),
+            dimension,
+            data: KeyType::BlockEntity,
+        };
+
+        if let Some(data) = self.database.get(key)? {
+            BlockEntity::deserialize_all(&*data)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Load the actors in the specified chunk.
+    ///
+    /// Actors are read from both the current format, where the chunk's [`DIGEST_PREFIX`](crate::DIGEST_PREFIX) key
+    /// lists the IDs of its actors, and the legacy format, where all actors are stored in a single [`KeyType::Entity`] key.
+    ///
+    /// # Arguments
+    ///
+    /// * `coordinates` - X and Z coordinates of the chunk.
+    /// * `dimension` - Dimension the chunk should be retrieved from.
+    ///
+    /// # Returns
+    ///
+    /// This method returns an empty list if the chunk does not contain any actors
+    /// and an error if the data could not be loaded.
+    pub fn actors<I>(&self, coordinates: I, dimension: Dimension) -> anyhow::Result<Vec<Actor>>
+    where
+        I: Into<Vector<i32, 2>>,
+    {
+        let coordinates = coordinates.into();
+
+        let mut actors = Vec::new();
+        for unique_id in self.actor_ids(&coordinates, dimension)? {
+            let Some(data) = self.database.get_raw(&crate::actor_key(unique_id)?)? else {
+                // Vanilla does not always clean up the digest when an actor is removed.
+                tracing::warn!("Actor {unique_id} listed in chunk {coordinates:?} does not exist");
+                continue;
+            };
+
+            let (nbt, _) = nbt::from_le_bytes(&mut &*data)?;
+            actors.push(Actor::from_nbt(nbt)?);
+        }
+
+        let key = DataKey {
+            coordinates,
+            dimension,
+            data: KeyType::Entity,
+        };
+
+        if let Some(data) = self.database.get(key)? {
+            actors.extend(Actor::deserialize_legacy(&*data)?);
+        }
+
+        Ok(actors)
+    }
+
+    /// Returns the IDs of the actors stored in the given chunk using the current format.
+    fn actor_ids(&self, coordinates: &Vector<i32, 2>, dimension: Dimension) -> anyhow::Result<Vec<i64>> {
+        match self.database.get_raw(&crate::digest_key(coordinates, dimension)?)? {
+            Some(data) => Actor::deserialize_digest(&*data),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Replaces the actors of a chunk in the given batch.
+    ///
+    /// Actors are always written in the current format and any legacy actor data of the chunk is removed.
+    /// Actors that were previously stored in this chunk but are not in `actors` are deleted.
+    ///
+    /// Actor records are shared by the entire level. When an actor moves to another chunk,
+    /// the chunk it left should be added to the batch before the chunk it moved to.
+    pub fn batch_actors<I>(&self, batch: &mut WriteBatch, coordinates: I, dimension: Dimension, actors: &[Actor]) -> anyhow::Result<()>
+    where
+        I: Into<Vector<i32, 2>>,
+    {
+        let coordinates = coordinates.into();
+
+        for unique_id in self.actor_ids(&coordinates, dimension)? {
+            if !actors.iter().any(|actor| actor.unique_id == unique_id) {
+                batch.delete(crate::actor_key(unique_id)?);
+            }
+        }
+
+        let mut digest = RVec::alloc_with_capacity(actors.len() * 8);
+        for actor in actors {
+            digest.write_i64_le(actor.unique_id)?;
+            batch.put(crate::actor_key(actor.unique_id)?, nbt::to_le_bytes(&actor.to_nbt())?);
+        }
+
+        let digest_key = crate::digest_key(&coordinates, dimension)?;
+        if actors.is_empty() {
+            batch.delete(digest_key);
+        } else {
+            batch.put(digest_key, digest);
+        }
+
+        batch.delete_data(DataKey {
+            coordinates,
+            dimension,
+            data: KeyType::Entity,
+        })
+    }
+
+    /// Atomically executes all operations in the given batch.
+    pub fn execute(&self, batch: &WriteBatch) -> anyhow::Result<()> {
+        self.database.execute(batch)
     }
 }
@@ -1,220 +1,360 @@
-#[derive(serde::Deserialize, Debug, PartialEq)]
+use std::collections::BTreeMap;
+
+/// Default abilities of players.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
 pub struct Abilities {
+    /// Whether the player can attack mobs.
     #[serde(rename = "attackmobs")]
     pub attack_mobs: bool,
+    /// Whether the player can attack other players.
     #[serde(rename = "attackplayers")]
     pub attack_players: bool,
+    /// Whether the player can place blocks.
     pub build: bool,
+    /// Whether the player can use doors and switches.
     #[serde(rename = "doorsandswitches")]
     pub doors_and_switches: bool,
+    /// Whether the player is currently flying.
     pub flying: bool,
+    /// Whether the player can instantly break blocks.
     #[serde(rename = "instabuild")]
     pub instant_build: bool,
+    /// Whether the player is invulnerable.
     pub invulnerable: bool,
+    /// Whether the player has been struck by lightning.
     pub lightning: bool,
+    /// Whether the player is allowed to fly.
     pub mayfly: bool,
+    /// Whether the player can break blocks.
     pub mine: bool,
+    /// Whether the player is an operator.
     pub op: bool,
+    /// Whether the player can open containers.
     #[serde(rename = "opencontainers")]
     pub open_containers: bool,
+    /// Whether the player can teleport.
     pub teleport: bool,
+    /// Flying speed of the player.
     #[serde(rename = "flySpeed")]
     pub fly_speed: f32,
+    /// Walking speed of the player.
     #[serde(rename = "walkSpeed")]
     pub walk_speed: f32,
 }
 
-#[derive(serde::Deserialize, Debug, PartialEq, Eq)]
-#[serde(deny_unknown_fields)]
+/// Experiment settings of a level.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Experiments {
+    /// Whether experiments have ever been enabled in this world.
     pub experiments_ever_used: bool,
+    /// Whether the world was saved with experiments enabled.
     pub saved_with_toggled_experiments: bool,
+    /// Individual experiments, such as `data_driven_biomes`, indexed by name.
+    ///
+    /// These are stored as bytes, a non-zero value means the experiment is enabled.
+    #[serde(flatten)]
+    pub toggles: BTreeMap<String, i8>,
+}
+
+impl Experiments {
+    /// Names of the experiments that are enabled in this world.
+    pub fn enabled(&self) -> impl Iterator<Item = &str> {
+        self.toggles.iter().filter(|(_, &value)| value != 0).map(|(name, _)| name.as_str())
+    }
 }
 
-#[derive(serde::Deserialize, Debug, PartialEq, Eq)]
+/// World policies of a level.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(deny_unknown_fields)]
 pub struct Policies {
     // Not sure what is supposed to be in here
 }
 
-#[derive(serde::Deserialize, Debug, PartialEq)]
-#[serde(rename_all = "camelCase")]
+/// Level settings, stored in the `level.dat` file.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename = "", rename_all = "camelCase")]
 #[serde(deny_unknown_fields)]
 pub struct LevelSettings {
+    /// Type of editor world, if this world was created in the editor.
     pub editor_world_type: i32,
+    /// Whether the world was created in the editor.
     #[serde(rename = "isCreatedInEditor")]
     pub created_in_editor: bool,
+    /// Whether the world was exported from the editor.
     #[serde(rename = "isExportedFromEditor")]
     pub exported_from_editor: bool,
+    /// Whether a random seed may be used for this world.
     #[serde(rename = "isRandomSeedAllowed")]
     pub random_seed_allowed: bool,
+    /// Percentage of players that must sleep to skip the night.
     #[serde(rename = "playerssleepingpercentage")]
     pub sleeping_percentage: i32,
+    /// Whether recipes have to be unlocked before they can be used.
     #[serde(rename = "recipesunlock")]
     pub recipes_unlock: bool,
+    /// Whether cheats are enabled.
     pub cheats_enabled: bool,
+    /// Current intensity of lightning.
     pub lightning_level: f32,
+    /// Ticks until the lightning state changes.
     pub lightning_time: i32,
+    /// Current intensity of rain.
     pub rain_level: f32,
+    /// Ticks until the rain state changes.
     pub rain_time: i32,
+    /// Difficulty of the world.
     #[serde(rename = "Difficulty")]
     pub difficulty: i32,
+    /// Default game mode of the world.
     #[serde(rename = "GameType")]
     pub game_mode: i32,
+    /// World generator used for this world.
     #[serde(rename = "Generator")]
     pub generator: i32,
+    /// X coordinate of the origin of a limited world.
     #[serde(rename = "LimitedWorldOriginX")]
     pub limited_world_origin_x: i32,
+    /// Y coordinate of the origin of a limited world.
     #[serde(rename = "LimitedWorldOriginY")]
     pub limited_world_origin_y: i32,
+    /// Z coordinate of the origin of a limited world.
     #[serde(rename = "LimitedWorldOriginZ")]
     pub limited_world_origin_z: i32,
+    /// Depth of a limited world.
     pub limited_world_depth: i32,
+    /// Width of a limited world.
     pub limited_world_width: i32,
+    /// Oldest client version that can open this world.
     #[serde(rename = "MinimumCompatibleClientVersion")]
     pub minimum_compatible_client_version: [i32; 5],
     // pub minimum_compatible_client_version: f32,
+    /// Scale of the nether compared to the overworld.
     #[serde(rename = "NetherScale")]
     pub nether_scale: i32,
+    /// Network protocol version the world was last saved with.
     #[serde(rename = "NetworkVersion")]
     pub network_version: i32,
+    /// Platform the world was created on.
     #[serde(rename = "Platform")]
     pub platform: i32,
+    /// Platform broadcast intent of multiplayer games.
     #[serde(rename = "PlatformBroadcastIntent")]
     pub platform_broadcast_intent: i32,
+    /// Seed used to generate the world.
     #[serde(rename = "RandomSeed")]
     pub random_seed: i64,
+    /// Whether old villagers are spawned.
     #[serde(rename = "SpawnV1Villagers")]
     pub spawn_v1_villagers: bool,
+    /// X coordinate of the world spawn.
     #[serde(rename = "SpawnX")]
     pub spawn_x: i32,
+    /// Y coordinate of the world spawn.
     #[serde(rename = "SpawnY")]
     pub spawn_y: i32,
+    /// Z coordinate of the world spawn.
     #[serde(rename = "SpawnZ")]
     pub spawn_z: i32,
+    /// Version of the storage format.
     #[serde(rename = "StorageVersion")]
     pub storage_version: i32,
+    /// Current time of day in ticks.
     #[serde(rename = "Time")]
     pub time: i64,
+    /// Version of the world format.
     #[serde(rename = "WorldVersion")]
     pub world_version: i32,
+    /// Xbox Live broadcast intent of multiplayer games.
     #[serde(rename = "XBLBroadcastIntent")]
     pub xbox_broadcast_intent: i32,
+    /// Amount of ticks the world has been running.
     pub current_tick: i64,
+    /// Experiment settings.
     pub experiments: Experiments,
+    /// Default abilities of players.
     pub abilities: Abilities,
+    /// Education edition offer.
     pub edu_offer: i32,
+    /// Whether education edition features are enabled.
     pub education_features_enabled: bool,
+    /// Client version the world was last opened with.
     #[serde(rename = "lastOpenedWithVersion")]
     pub last_opened_with_version: [i32; 5],
+    /// Whether the bonus chest is enabled.
     pub bonus_chest_enabled: bool,
+    /// Whether the bonus chest has spawned.
     pub bonus_chest_spawned: bool,
+    /// The `commandblockoutput` gamerule.
     #[serde(rename = "commandblockoutput")]
     pub command_block_output: bool,
+    /// Whether maps are centered on the origin.
     #[serde(rename = "CenterMapsToOrigin")]
     pub center_maps_to_origin: bool,
+    /// The `commandblocksenabled` gamerule.
     #[serde(rename = "commandblocksenabled")]
     pub command_blocks_enabled: bool,
+    /// Whether commands are enabled.
     pub commands_enabled: bool,
+    /// Whether platform locked content has been confirmed.
     #[serde(rename = "ConfirmedPlatformLockedContent")]
     pub confirmed_platform_locked_content: bool,
+    /// Daylight cycle mode.
     pub daylight_cycle: i32,
+    /// The `dodaylightcycle` gamerule.
     #[serde(rename = "dodaylightcycle")]
     pub daylight_lock: bool,
+    /// The `dolimitedcrafting` gamerule.
     #[serde(rename = "dolimitedcrafting")]
     pub limited_crafting: bool,
+    /// The `doentitydrops` gamerule.
     #[serde(rename = "doentitydrops")]
     pub entity_drops: bool,
+    /// The `dofiretick` gamerule.
     #[serde(rename = "dofiretick")]
     pub fire_tick: bool,
+    /// The `doimmediaterespawn` gamerule.
     #[serde(rename = "doimmediaterespawn")]
     pub immediate_respawn: bool,
+    /// The `doinsomnia` gamerule.
     #[serde(rename = "doinsomnia")]
     pub insomnia: bool,
+    /// The `domobloot` gamerule.
     #[serde(rename = "domobloot")]
     pub mob_loot: bool,
+    /// The `domobspawning` gamerule.
     #[serde(rename = "domobspawning")]
     pub mob_spawning: bool,
+    /// The `dotiledrops` gamerule.
     #[serde(rename = "dotiledrops")]
     pub tile_drops: bool,
+    /// The `doweathercycle` gamerule.
     #[serde(rename = "doweathercycle")]
     pub weather_cycle: bool,
+    /// The `drowningdamage` gamerule.
     #[serde(rename = "drowningdamage")]
     pub drowning_damage: bool,
+    /// The `falldamage` gamerule.
     #[serde(rename = "falldamage")]
     pub fall_damage: bool,
+    /// The `firedamage` gamerule.
     #[serde(rename = "firedamage")]
     pub fire_damage: bool,
+    /// The `freezedamage` gamerule.
     #[serde(rename = "freezedamage")]
     pub freeze_damage: bool,
+    /// The `keepinventory` gamerule.
     #[serde(rename = "keepinventory")]
     pub keep_inventory: bool,
+    /// The `maxcommandchainlength` gamerule.
     #[serde(rename = "maxcommandchainlength")]
     pub max_command_chain_length: i32,
+    /// The `mobgriefing` gamerule.
     #[serde(rename = "mobgriefing")]
     pub mob_griefing: bool,
+    /// The `naturalregeneration` gamerule.
     #[serde(rename = "naturalregeneration")]
     pub natural_regeneration: bool,
+    /// The `functioncommandlimit` gamerule.
     #[serde(rename = "functioncommandlimit")]
     pub function_command_limit: i32,
+    /// The `pvp` gamerule.
     pub pvp: bool,
+    /// The `randomtickspeed` gamerule.
     #[serde(rename = "randomtickspeed")]
     pub random_tick_speed: i32,
+    /// The `respawnblocksexplode` gamerule.
     #[serde(rename = "respawnblocksexplode")]
     pub respawn_blocks_explode: bool,
+    /// The `sendcommandfeedback` gamerule.
     #[serde(rename = "sendcommandfeedback")]
     pub send_command_feedback: bool,
+    /// The `showbordereffect` gamerule.
     #[serde(rename = "showbordereffect")]
     pub show_border_effect: bool,
+    /// The `showcoordinates` gamerule.
     #[serde(rename = "showcoordinates")]
     pub show_coordinates: bool,
+    /// The `showdeathmessages` gamerule.
     #[serde(rename = "showdeathmessages")]
     pub show_death_messages: bool,
+    /// The `showtags` gamerule.
     #[serde(rename = "showtags")]
     pub show_tags: bool,
+    /// The `spawnradius` gamerule.
     #[serde(rename = "spawnradius")]
     pub spawn_radius: i32,
+    /// The `tntexplodes` gamerule.
     #[serde(rename = "tntexplodes")]
     pub tnt_explodes: bool,
+    /// Whether players are forced into the default game mode.
     #[serde(rename = "ForceGameType")]
     pub force_game_mode: bool,
+    /// Whether the world has ever been loaded in creative mode.
     pub has_been_loaded_in_creative: bool,
+    /// Whether the world has a locked behavior pack.
     pub has_locked_behavior_pack: bool,
+    /// Whether the world has a locked resource pack.
     pub has_locked_resource_pack: bool,
+    /// Whether the world cannot be modified.
     pub immutable_world: bool,
+    /// Whether the world was created from a locked template.
     pub is_from_locked_template: bool,
+    /// Whether the world was created from a template.
     pub is_from_world_template: bool,
+    /// Whether the world can only be used once.
     pub is_single_use_world: bool,
+    /// Whether the template options are locked.
     pub is_world_template_option_locked: bool,
+    /// Whether copied packs have to be checked for removal.
     pub requires_copied_pack_removal_check: bool,
+    /// Whether clients must accept the texture packs.
     pub texture_packs_required: bool,
+    /// Whether the world is broadcast over LAN.
     #[serde(rename = "LANBroadcast")]
     pub lan_broadcast: bool,
+    /// LAN broadcast intent.
     #[serde(rename = "LANBroadcastIntent")]
     pub lan_broadcast_intent: i8,
+    /// Whether this is a multiplayer game.
     #[serde(rename = "MultiplayerGame")]
     pub multiplayer_game: bool,
+    /// Multiplayer game intent.
     #[serde(rename = "MultiplayerGameIntent")]
     pub multiplayer_game_intent: i8,
+    /// Unix timestamp of when the world was last played.
     #[serde(rename = "LastPlayed")]
     pub last_played: i64,
+    /// Game version that the world behaves like.
     pub base_game_version: String,
+    /// Biome used for the entire world, if any.
     #[serde(rename = "BiomeOverride")]
     pub biome_override: String,
+    /// Layers used by the flat world generator, encoded as JSON.
     #[serde(rename = "FlatWorldLayers")]
     pub flat_world_layers: String,
+    /// Version of the inventory format.
     #[serde(rename = "InventoryVersion")]
     pub inventory_version: String,
+    /// Name of the world.
     #[serde(rename = "LevelName")]
     pub level_name: String,
+    /// Whether only Microsoft account gamertags are shown.
     pub use_msa_gamertags_only: bool,
+    /// Amount of times the world has been started.
     pub world_start_count: i64,
+    /// Whether players start with a map.
     pub start_with_map_enabled: bool,
+    /// Whether mobs can spawn.
     pub spawn_mobs: bool,
+    /// Radius around players in which chunks are ticked.
     pub server_chunk_tick_range: i32,
+    /// Permission level of the world owner.
     pub permissions_level: i32,
+    /// Default permission level of players.
     pub player_permissions_level: i32,
+    /// Unknown.
     pub prid: String,
+    /// World policies.
     #[serde(rename = "world_policies")]
     pub world_policies: Policies,
 }
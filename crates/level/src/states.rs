@@ -154,13 +154,16 @@
 //     }
 // }
 
-use std::{collections::HashMap, sync::atomic::Ordering};
+use std::{
+    collections::HashMap,
+    sync::{atomic::Ordering, PoisonError, RwLock},
+};
 
 use nohash_hasher::{BuildNoHashHasher, IntMap};
 use proto::bedrock::{ItemStack, ItemType, SHIELD_ID};
 use util::{BinaryRead, RString};
 
-use crate::PaletteEntry;
+use crate::{BlockUpgrader, PaletteEntry};
 
 const CREATIVE_ITEMS_RAW: &[u8] = include_bytes!("../include/creative_items.nbt");
 
@@ -301,11 +304,19 @@ impl ItemNetworkIds {
 const BLOCK_STATES_RAW: &[u8] = include_bytes!("../include/block_states.nbt");
 
 /// Maps block states to runtime IDs.
+///
+/// States that are not known are upgraded to the current format using a [`BlockUpgrader`] before they are looked up.
 #[derive(Debug, Default)]
 pub struct BlockStates {
     /// Converts state hashes to runtime IDs.
     runtime_hashes: HashMap<u64, u32, BuildNoHashHasher<u64>>,
     air_id: u32,
+    /// Upgrades outdated states.
+    upgrader: BlockUpgrader,
+    /// Runtime IDs of upgraded states, indexed by the hash of the outdated state.
+    upgraded: RwLock<HashMap<u64, Option<u32>, BuildNoHashHasher<u64>>>,
+    /// States that could not be mapped to a runtime ID, even after upgrading.
+    unmapped: RwLock<Vec<PaletteEntry>>,
 }
 
 impl BlockStates {
@@ -322,7 +333,7 @@ impl BlockStates {
 
         let mut states = Self {
             runtime_hashes: HashMap::with_capacity_and_hasher(STATE_COUNT, BuildNoHashHasher::default()),
-            air_id: 0,
+            ..Default::default()
         };
 
         while reader.remaining() > 0 {
@@ -347,9 +358,60 @@ impl BlockStates {
         self.runtime_hashes.get(&hash).copied()
     }
 
+    /// Returns the runtime ID of a block state.
+    ///
+    /// If the state is not known, it is upgraded first. States that are still unknown after upgrading
+    /// are reported once and can be retrieved using [`unmapped`](Self::unmapped).
     pub fn state(&self, state: &PaletteEntry) -> Option<u32> {
         let hash = state.hash();
-        self.runtime_hashes.get(&hash).copied()
+        if let Some(id) = self.runtime_hashes.get(&hash) {
+            return Some(*id);
+        }
+
+        self.upgraded_state(hash, state)
+    }
+
+    /// Looks up the runtime ID of an outdated state.
+    fn upgraded_state(&self, hash: u64, state: &PaletteEntry) -> Option<u32> {
+        if let Some(id) = self.upgraded.read().unwrap_or_else(PoisonError::into_inner).get(&hash) {
+            return *id;
+        }
+
+        let id = self
+            .upgrader
+            .upgrade(state)
+            .and_then(|upgraded| self.runtime_hashes.get(&upgraded.hash()).copied());
+
+        if id.is_none() {
+            tracing::warn!(
+                "Block state {} {:?} (version {:?}) could not be mapped to a runtime ID",
+                state.name,
+                state.states,
+                state.version
+            );
+            self.unmapped.write().unwrap_or_else(PoisonError::into_inner).push(state.clone());
+        }
+
+        self.upgraded.write().unwrap_or_else(PoisonError::into_inner).insert(hash, id);
+        id
+    }
+
+    /// Sets the upgrader used for states that are not known.
+    pub fn set_upgrader(&mut self, upgrader: BlockUpgrader) {
+        self.upgrader = upgrader;
+        self.upgraded.get_mut().unwrap_or_else(PoisonError::into_inner).clear();
+        self.unmapped.get_mut().unwrap_or_else(PoisonError::into_inner).clear();
+    }
+
+    /// Returns the upgrader used for states that are not known.
+    #[inline]
+    pub const fn upgrader(&self) -> &BlockUpgrader {
+        &self.upgrader
+    }
+
+    /// Returns the states that could not be mapped to a runtime ID, even after upgrading.
+    pub fn unmapped(&self) -> Vec<PaletteEntry> {
+        self.unmapped.read().unwrap_or_else(PoisonError::into_inner).clone()
     }
 
     pub const fn air(&self) -> u32 {
@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use util::RVec;
+
+use crate::database::{Database, Guard};
+use crate::{BatchOperation, DataKey, WriteBatch};
+
+/// Key-value store that world data is read from and written to.
+///
+/// [`Database`] implements this trait for LevelDB worlds on disk. [`MemoryStorage`] keeps all data
+/// in memory, which is useful for tests and temporary worlds.
+pub trait Storage: Send + Sync {
+    /// Loads the value stored at a raw key.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `None` if the key does not exist.
+    fn get_raw(&self, raw_key: &[u8]) -> anyhow::Result<Option<Guard<'_>>>;
+
+    /// Stores a value at a raw key, replacing any existing value.
+    fn put_raw(&self, raw_key: &[u8], value: &[u8]) -> anyhow::Result<()>;
+
+    /// Removes a raw key. Removing a key that does not exist is not an error.
+    fn delete_raw(&self, raw_key: &[u8]) -> anyhow::Result<()>;
+
+    /// Atomically executes all operations in the given batch.
+    fn execute(&self, batch: &WriteBatch) -> anyhow::Result<()>;
+
+    /// Returns all key-value pairs whose key starts with `prefix`, in key order.
+    fn iter_prefix<'a>(&'a self, prefix: &[u8]) -> Box<dyn Iterator<Item = (Guard<'a>, Guard<'a>)> + 'a>;
+
+    /// Returns all key-value pairs whose key is in the range `start..end`, in key order.
+    fn iter_range<'a>(&'a self, start: &[u8], end: &[u8]) -> Box<dyn Iterator<Item = (Guard<'a>, Guard<'a>)> + 'a>;
+
+    /// Returns all keys that start with `prefix`, in key order.
+    ///
+    /// Unlike [`iter_prefix`](Self::iter_prefix), this does not need to load the values.
+    fn iter_keys<'a>(&'a self, prefix: &[u8]) -> Box<dyn Iterator<Item = Guard<'a>> + 'a> {
+        Box::new(self.iter_prefix(prefix).map(|(key, _)| key))
+    }
+
+    /// Returns the LevelDB database backing this storage, if there is one.
+    ///
+    /// Some operations, such as backups, require direct access to the database files.
+    #[inline]
+    fn leveldb(&self) -> Option<&Database> {
+        None
+    }
+
+    /// Loads the value stored at the given key.
+    fn get(&self, key: DataKey) -> anyhow::Result<Option<Guard<'_>>> {
+        let mut raw_key = RVec::alloc_with_capacity(key.serialized_size());
+        key.serialize(&mut raw_key)?;
+
+        self.get_raw(&raw_key)
+    }
+
+    /// Stores a value at the given key.
+    fn put(&self, key: DataKey, value: &[u8]) -> anyhow::Result<()> {
+        let mut raw_key = RVec::alloc_with_capacity(key.serialized_size());
+        key.serialize(&mut raw_key)?;
+
+        self.put_raw(&raw_key, value)
+    }
+
+    /// Removes the given key.
+    fn delete(&self, key: DataKey) -> anyhow::Result<()> {
+        let mut raw_key = RVec::alloc_with_capacity(key.serialized_size());
+        key.serialize(&mut raw_key)?;
+
+        self.delete_raw(&raw_key)
+    }
+}
+
+impl Storage for Database {
+    #[inline]
+    fn get_raw(&self, raw_key: &[u8]) -> anyhow::Result<Option<Guard<'_>>> {
+        Database::get_raw(self, raw_key)
+    }
+
+    #[inline]
+    fn put_raw(&self, raw_key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        Database::put_raw(self, raw_key, value)
+    }
+
+    #[inline]
+    fn delete_raw(&self, raw_key: &[u8]) -> anyhow::Result<()> {
+        Database::delete_raw(self, raw_key)
+    }
+
+    #[inline]
+    fn execute(&self, batch: &WriteBatch) -> anyhow::Result<()> {
+        Database::execute(self, batch)
+    }
+
+    fn iter_prefix<'a>(&'a self, prefix: &[u8]) -> Box<dyn Iterator<Item = (Guard<'a>, Guard<'a>)> + 'a> {
+        Box::new(Database::iter_prefix(self, prefix).map(|pair| (pair.key(), pair.value())))
+    }
+
+    fn iter_range<'a>(&'a self, start: &[u8], end: &[u8]) -> Box<dyn Iterator<Item = (Guard<'a>, Guard<'a>)> + 'a> {
+        Box::new(Database::iter_range(self, start, end).map(|pair| (pair.key(), pair.value())))
+    }
+
+    fn iter_keys<'a>(&'a self, prefix: &[u8]) -> Box<dyn Iterator<Item = Guard<'a>> + 'a> {
+        Box::new(Database::iter_prefix(self, prefix).map(|pair| pair.key()))
+    }
+
+    #[inline]
+    fn leveldb(&self) -> Option<&Database> {
+        Some(self)
+    }
+}
+
+/// Storage that keeps all data in memory.
+///
+/// Data is lost when the storage is dropped.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    /// Stored key-value pairs, sorted by key.
+    entries: RwLock<BTreeMap<Vec<u8>, Vec<u8>>>,
+}
+
+impl MemoryStorage {
+    /// Creates an empty storage.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Amount of keys in this storage.
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    /// Whether this storage contains no keys.
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    /// Locks the entries for reading.
+    ///
+    /// The map cannot be left in an invalid state, so a poisoned lock is ignored.
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, BTreeMap<Vec<u8>, Vec<u8>>> {
+        self.entries.read().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Locks the entries for writing.
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, BTreeMap<Vec<u8>, Vec<u8>>> {
+        self.entries.write().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn get_raw(&self, raw_key: &[u8]) -> anyhow::Result<Option<Guard<'_>>> {
+        Ok(self.read().get(raw_key).map(|value| Guard::owned(value.clone())))
+    }
+
+    fn put_raw(&self, raw_key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        self.write().insert(raw_key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn delete_raw(&self, raw_key: &[u8]) -> anyhow::Result<()> {
+        self.write().remove(raw_key);
+        Ok(())
+    }
+
+    fn execute(&self, batch: &WriteBatch) -> anyhow::Result<()> {
+        // Holding the lock for the entire batch makes it atomic.
+        let mut entries = self.write();
+        for operation in batch.operations() {
+            match operation {
+                BatchOperation::Put { key, value } => {
+                    entries.insert(key.clone(), value.clone());
+                }
+                BatchOperation::Delete { key } => {
+                    entries.remove(key);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn iter_prefix<'a>(&'a self, prefix: &[u8]) -> Box<dyn Iterator<Item = (Guard<'a>, Guard<'a>)> + 'a> {
+        // The lock cannot be held by the iterator, so the matching pairs are copied.
+        let pairs = self
+            .read()
+            .range(prefix.to_vec()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (Guard::owned(key.clone()), Guard::owned(value.clone())))
+            .collect::<Vec<_>>();
+
+        Box::new(pairs.into_iter())
+    }
+
+    fn iter_range<'a>(&'a self, start: &[u8], end: &[u8]) -> Box<dyn Iterator<Item = (Guard<'a>, Guard<'a>)> + 'a> {
+        if start >= end {
+            return Box::new(std::iter::empty());
+        }
+
+        let pairs = self
+            .read()
+            .range(start.to_vec()..end.to_vec())
+            .map(|(key, value)| (Guard::owned(key.clone()), Guard::owned(value.clone())))
+            .collect::<Vec<_>>();
+
+        Box::new(pairs.into_iter())
+    }
+}
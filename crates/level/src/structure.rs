@@ -0,0 +1,720 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use proto::types::Dimension;
+use serde::{Deserialize, Serialize};
+use util::{RVec, Vector};
+
+use crate::provider::{Provider, CHUNK_VERSION};
+use crate::{subchunk_range, Actor, BiomeEncoding, Biomes, BlockEntity, PaletteEntry, Storage, SubChunk, SubStorage};
+
+/// Version of the `.mcstructure` format written by [`Structure::serialize`].
+const STRUCTURE_FORMAT_VERSION: i32 = 1;
+
+/// Rotation applied to a structure when it is placed, clockwise when looking down.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum StructureRotation {
+    /// The structure is placed as-is.
+    #[default]
+    None,
+    /// Rotated by 90 degrees clockwise.
+    Clockwise90,
+    /// Rotated by 180 degrees.
+    Clockwise180,
+    /// Rotated by 270 degrees clockwise.
+    Clockwise270,
+}
+
+impl StructureRotation {
+    /// Amount of clockwise quarter turns.
+    const fn turns(self) -> usize {
+        self as usize
+    }
+}
+
+/// Mirroring applied to a structure when it is placed, before it is rotated.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum StructureMirror {
+    /// The structure is not mirrored.
+    #[default]
+    None,
+    /// The X coordinates are flipped, swapping east and west.
+    X,
+    /// The Z coordinates are flipped, swapping north and south.
+    Z,
+}
+
+/// A structure in the Bedrock `.mcstructure` format.
+///
+/// Structures store a cuboid of blocks using a palette, the same way subchunks do.
+/// Blocks are indexed with Z changing fastest, followed by Y and then X.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Structure {
+    /// Size of the structure in blocks.
+    pub size: Vector<i32, 3>,
+    /// World position of the structure when it was saved.
+    pub origin: Vector<i32, 3>,
+    /// Indices into the palette for each layer, see [`SubChunk::layers`].
+    ///
+    /// `None` is a structure void, which leaves the existing block in place when the structure is placed.
+    pub layers: Vec<Vec<Option<u32>>>,
+    /// Blocks used in this structure.
+    pub palette: Vec<PaletteEntry>,
+    /// Block entities in the structure, with positions relative to the structure.
+    pub block_entities: Vec<BlockEntity>,
+    /// NBT of the actors in the structure, with their positions in world coordinates at the time of saving.
+    pub entities: Vec<HashMap<String, nbt::Value>>,
+}
+
+/// Data attached to a single block in a structure.
+#[derive(Serialize, Deserialize)]
+struct RawPositionData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    block_entity_data: Option<HashMap<String, nbt::Value>>,
+}
+
+/// The default palette of a structure.
+#[derive(Serialize, Deserialize)]
+struct RawPalette {
+    block_palette: Vec<PaletteEntry>,
+    #[serde(default)]
+    block_position_data: HashMap<String, RawPositionData>,
+}
+
+/// Structures can contain multiple palettes, but only `default` is used by the game.
+#[derive(Serialize, Deserialize)]
+struct RawPalettes {
+    default: RawPalette,
+}
+
+/// Contents of a structure.
+#[derive(Serialize, Deserialize)]
+struct RawContents {
+    block_indices: Vec<Vec<i32>>,
+    #[serde(default)]
+    entities: Vec<HashMap<String, nbt::Value>>,
+    palette: RawPalettes,
+}
+
+/// Root compound of an `.mcstructure` file.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "")]
+struct RawStructure {
+    format_version: i32,
+    size: Vec<i32>,
+    structure_world_origin: Vec<i32>,
+    structure: RawContents,
+}
+
+impl Structure {
+    /// Amount of blocks in a single layer of this structure.
+    #[inline]
+    pub fn volume(&self) -> usize {
+        self.size.as_ref().iter().map(|s| *s as usize).product()
+    }
+
+    /// Converts a position relative to the structure into an index into the layers.
+    #[inline]
+    pub fn index(&self, position: &Vector<i32, 3>) -> usize {
+        ((position.x * self.size.y + position.y) * self.size.z + position.z) as usize
+    }
+
+    /// Converts an index into the layers back into a position relative to the structure.
+    #[inline]
+    pub fn position(&self, index: usize) -> Vector<i32, 3> {
+        let index = index as i32;
+        Vector::from([
+            index / (self.size.y * self.size.z),
+            index / self.size.z % self.size.y,
+            index % self.size.z,
+        ])
+    }
+
+    /// Reads a structure from the contents of an `.mcstructure` file.
+    pub fn deserialize(mut bytes: &[u8]) -> anyhow::Result<Self> {
+        let (raw, _): (RawStructure, _) = nbt::from_le_bytes(&mut bytes)?;
+
+        let (Ok(size), Ok(origin)) = (<[i32; 3]>::try_from(raw.size), <[i32; 3]>::try_from(raw.structure_world_origin)) else {
+            anyhow::bail!("Structure size and origin should consist of three coordinates");
+        };
+
+        if size.iter().any(|s| *s < 0) {
+            anyhow::bail!(format!("Structure has a negative size: {size:?}"));
+        }
+
+        let mut structure = Self {
+            size: Vector::from(size),
+            origin: Vector::from(origin),
+            layers: Vec::with_capacity(raw.structure.block_indices.len()),
+            palette: raw.structure.palette.default.block_palette,
+            block_entities: Vec::new(),
+            entities: raw.structure.entities,
+        };
+
+        let volume = structure.volume();
+        for layer in raw.structure.block_indices {
+            if layer.len() != volume {
+                anyhow::bail!(format!("Structure layer contains {} blocks, expected {volume}", layer.len()));
+            }
+
+            let layer = layer
+                .into_iter()
+                .map(|index| match index {
+                    -1 => Ok(None),
+                    index if index >= 0 && (index as usize) < structure.palette.len() => Ok(Some(index as u32)),
+                    index => Err(anyhow::anyhow!("Structure contains out of range palette index {index}")),
+                })
+                .collect::<anyhow::Result<_>>()?;
+
+            structure.layers.push(layer);
+        }
+
+        for (index, data) in raw.structure.palette.default.block_position_data {
+            let Some(nbt) = data.block_entity_data else { continue };
+            let Some(index) = index.parse::<usize>().ok().filter(|index| *index < volume) else {
+                anyhow::bail!(format!("Structure contains block entity at invalid index {index}"));
+            };
+
+            // The stored coordinates are those at the time of saving, so the index is used instead.
+            let mut entity = BlockEntity::from_nbt(nbt)?;
+            entity.position = structure.position(index);
+            structure.block_entities.push(entity);
+        }
+
+        Ok(structure)
+    }
+
+    /// Encodes this structure into the `.mcstructure` format.
+    pub fn serialize(&self) -> anyhow::Result<RVec> {
+        let block_indices = self
+            .layers
+            .iter()
+            .map(|layer| layer.iter().map(|index| index.map_or(-1, |index| index as i32)).collect())
+            .collect();
+
+        let block_position_data = self
+            .block_entities
+            .iter()
+            .map(|entity| {
+                let mut world = entity.clone();
+                world.position = Vector::from([
+                    self.origin.x + entity.position.x,
+                    self.origin.y + entity.position.y,
+                    self.origin.z + entity.position.z,
+                ]);
+
+                let data = RawPositionData { block_entity_data: Some(world.to_nbt()) };
+                (self.index(&entity.position).to_string(), data)
+            })
+            .collect();
+
+        let raw = RawStructure {
+            format_version: STRUCTURE_FORMAT_VERSION,
+            size: self.size.as_ref().to_vec(),
+            structure_world_origin: self.origin.as_ref().to_vec(),
+            structure: RawContents {
+                block_indices,
+                entities: self.entities.clone(),
+                palette: RawPalettes {
+                    default: RawPalette {
+                        block_palette: self.palette.clone(),
+                        block_position_data,
+                    },
+                },
+            },
+        };
+
+        nbt::to_le_bytes(&raw)
+    }
+
+    /// Size of the structure after it has been rotated.
+    pub fn rotated_size(&self, rotation: StructureRotation) -> Vector<i32, 3> {
+        if rotation.turns() % 2 == 1 {
+            Vector::from([self.size.z, self.size.y, self.size.x])
+        } else {
+            self.size.clone()
+        }
+    }
+
+    /// Mirrors and rotates a position relative to this structure.
+    ///
+    /// The result is relative to the structure after the transformation, so it is always within [`rotated_size`](Self::rotated_size).
+    pub fn transform(&self, position: &Vector<i32, 3>, rotation: StructureRotation, mirror: StructureMirror) -> Vector<i32, 3> {
+        let (sx, sz) = (self.size.x, self.size.z);
+        let (mut x, y, mut z) = (position.x, position.y, position.z);
+
+        match mirror {
+            StructureMirror::None => {}
+            StructureMirror::X => x = sx - 1 - x,
+            StructureMirror::Z => z = sz - 1 - z,
+        }
+
+        (x, z) = match rotation {
+            StructureRotation::None => (x, z),
+            StructureRotation::Clockwise90 => (sz - 1 - z, x),
+            StructureRotation::Clockwise180 => (sx - 1 - x, sz - 1 - z),
+            StructureRotation::Clockwise270 => (z, sx - 1 - x),
+        };
+
+        Vector::from([x, y, z])
+    }
+}
+
+/// Horizontal directions in clockwise order.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Cardinal {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Cardinal {
+    /// All directions in clockwise order.
+    const ALL: [Self; 4] = [Self::North, Self::East, Self::South, Self::West];
+
+    /// Mirrors and then rotates this direction.
+    fn transform(self, rotation: StructureRotation, mirror: StructureMirror) -> Self {
+        let mirrored = match (mirror, self) {
+            (StructureMirror::X, Self::East) => Self::West,
+            (StructureMirror::X, Self::West) => Self::East,
+            (StructureMirror::Z, Self::North) => Self::South,
+            (StructureMirror::Z, Self::South) => Self::North,
+            (_, direction) => direction,
+        };
+
+        Self::ALL[(mirrored as usize + rotation.turns()) % 4]
+    }
+
+    /// Parses the name of a direction.
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "north" => Self::North,
+            "east" => Self::East,
+            "south" => Self::South,
+            "west" => Self::West,
+            _ => return None,
+        })
+    }
+
+    /// Name of this direction, as used in block states.
+    const fn name(self) -> &'static str {
+        match self {
+            Self::North => "north",
+            Self::East => "east",
+            Self::South => "south",
+            Self::West => "west",
+        }
+    }
+}
+
+/// Transforms a direction stored as an integer, where `order` lists the direction of each value starting at `base`.
+fn transform_int_direction(value: i32, base: i32, order: [Cardinal; 4], rotation: StructureRotation, mirror: StructureMirror) -> i32 {
+    let Some(direction) = usize::try_from(value - base).ok().and_then(|i| order.get(i)) else {
+        return value;
+    };
+
+    let transformed = direction.transform(rotation, mirror);
+    order.iter().position(|d| *d == transformed).map_or(value, |i| base + i as i32)
+}
+
+/// Rotates and mirrors the directional states of a block, such as the facing direction of stairs.
+fn transform_states(entry: &mut PaletteEntry, rotation: StructureRotation, mirror: StructureMirror) {
+    use Cardinal::{East, North, South, West};
+
+    for (key, value) in &mut entry.states {
+        match (key.as_str(), value) {
+            ("minecraft:cardinal_direction" | "minecraft:facing_direction", nbt::Value::String(name)) => {
+                if let Some(direction) = Cardinal::from_name(name) {
+                    *name = direction.transform(rotation, mirror).name().to_owned();
+                }
+            }
+            ("facing_direction", nbt::Value::Int(value)) => {
+                // 0 and 1 are down and up, which are not affected.
+                *value = transform_int_direction(*value, 2, [North, South, West, East], rotation, mirror);
+            }
+            ("weirdo_direction", nbt::Value::Int(value)) => {
+                *value = transform_int_direction(*value, 0, [East, West, South, North], rotation, mirror);
+            }
+            ("direction", nbt::Value::Int(value)) => {
+                *value = transform_int_direction(*value, 0, [South, West, North, East], rotation, mirror);
+            }
+            ("ground_sign_direction", nbt::Value::Int(value)) => {
+                // Sixteen directions, clockwise starting at south.
+                let mirrored = match mirror {
+                    StructureMirror::None => *value,
+                    StructureMirror::X => 16 - *value,
+                    StructureMirror::Z => 8 - *value,
+                };
+                *value = (mirrored + 4 * rotation.turns() as i32).rem_euclid(16);
+            }
+            ("pillar_axis", nbt::Value::String(axis)) if rotation.turns() % 2 == 1 => {
+                *axis = match axis.as_str() {
+                    "x" => String::from("z"),
+                    "z" => String::from("x"),
+                    other => other.to_owned(),
+                };
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A block placed by [`Structure::place`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlacedBlock {
+    /// World coordinates of the block.
+    pub position: Vector<i32, 3>,
+    /// Layer of the subchunk that the block was placed in.
+    pub layer: usize,
+    /// Index into [`Placement::palette`].
+    pub block: u32,
+}
+
+/// Result of placing a structure, before it has been written to the world.
+#[derive(Debug, Clone)]
+pub struct Placement {
+    /// Modified subchunks, indexed by subchunk coordinates.
+    pub subchunks: HashMap<Vector<i32, 3>, SubChunk>,
+    /// Palette of the structure with its directional states transformed.
+    pub palette: Vec<PaletteEntry>,
+    /// Every block that was placed, structure voids are not included.
+    pub blocks: Vec<PlacedBlock>,
+    /// Block entities of the structure, with world coordinates.
+    pub block_entities: Vec<BlockEntity>,
+}
+
+impl Placement {
+    /// World coordinates of the blocks whose existing block entities have to be removed.
+    pub fn replaced(&self) -> HashSet<Vector<i32, 3>> {
+        self.blocks.iter().filter(|block| block.layer == 0).map(|block| block.position.clone()).collect()
+    }
+
+    /// Horizontal chunk coordinates of every modified column.
+    pub fn columns(&self) -> HashSet<Vector<i32, 2>> {
+        self.subchunks.keys().map(|key| Vector::from([key.x, key.z])).collect()
+    }
+
+    /// Replaces the block entities of a column with those of the structure.
+    ///
+    /// `entities` are the existing block entities in the column.
+    pub fn merge_block_entities(&self, column: &Vector<i32, 2>, replaced: &HashSet<Vector<i32, 3>>, entities: &mut Vec<BlockEntity>) {
+        entities.retain(|entity| !replaced.contains(&entity.position));
+        entities.extend(
+            self.block_entities
+                .iter()
+                .filter(|entity| entity.position.x >> 4 == column.x && entity.position.z >> 4 == column.y)
+                .cloned(),
+        );
+    }
+}
+
+impl Structure {
+    /// Copies a cuboid of blocks into a structure.
+    ///
+    /// Both corners are inclusive and may be given in any order.
+    /// `subchunk` is called once for every subchunk in the cuboid with its subchunk coordinates
+    /// and returns `None` if the subchunk does not exist.
+    ///
+    /// Block entities and actors are not included, see [`include_block_entities`](Self::include_block_entities)
+    /// and [`include_actors`](Self::include_actors).
+    pub fn extract<F>(from: Vector<i32, 3>, to: Vector<i32, 3>, mut subchunk: F) -> anyhow::Result<Self>
+    where
+        F: FnMut(Vector<i32, 3>) -> anyhow::Result<Option<Arc<SubChunk>>>,
+    {
+        let min = Vector::from([from.x.min(to.x), from.y.min(to.y), from.z.min(to.z)]);
+        let max = Vector::from([from.x.max(to.x), from.y.max(to.y), from.z.max(to.z)]);
+
+        let mut structure = Structure {
+            size: Vector::from([max.x - min.x + 1, max.y - min.y + 1, max.z - min.z + 1]),
+            origin: min.clone(),
+            layers: Vec::new(),
+            palette: Vec::new(),
+            block_entities: Vec::new(),
+            entities: Vec::new(),
+        };
+
+        let volume = structure.volume();
+        let mut layers = vec![vec![None; volume], vec![None; volume]];
+
+        // Subchunks and the mapping of their palettes to the structure palette.
+        let mut loaded: HashMap<Vector<i32, 3>, Option<(Arc<SubChunk>, Vec<Vec<u32>>)>> = HashMap::new();
+        let mut air = None;
+
+        for index in 0..volume {
+            let relative = structure.position(index);
+            let (x, y, z) = (min.x + relative.x, min.y + relative.y, min.z + relative.z);
+
+            let key = Vector::from([x >> 4, y >> 4, z >> 4]);
+            if !loaded.contains_key(&key) {
+                let subchunk = subchunk(key.clone())?.map(|subchunk| {
+                    let remaps = subchunk
+                        .layers
+                        .iter()
+                        .map(|layer| layer.palette.iter().map(|entry| palette_index(&mut structure.palette, entry)).collect())
+                        .collect();
+
+                    (subchunk, remaps)
+                });
+
+                loaded.insert(key.clone(), subchunk);
+            }
+
+            let local = Vector::from([(x & 0xf) as u8, (y & 0xf) as u8, (z & 0xf) as u8]);
+            if let Some((subchunk, remaps)) = &loaded[&key] {
+                for (layer_index, layer) in subchunk.layers.iter().enumerate().take(2) {
+                    if layer.palette.is_empty() {
+                        continue;
+                    }
+
+                    let block = layer.indices[crate::to_offset(local.clone())] as usize;
+                    // Only the first layer stores air, other layers leave it out so that they do not overwrite anything.
+                    if layer_index == 0 || layer.palette[block].name != "minecraft:air" {
+                        layers[layer_index][index] = Some(remaps[layer_index][block]);
+                    }
+                }
+            }
+
+            if layers[0][index].is_none() {
+                layers[0][index] = Some(*air.get_or_insert_with(|| palette_index(&mut structure.palette, &PaletteEntry::air(None))));
+            }
+        }
+
+        if layers[1].iter().all(Option::is_none) {
+            layers.truncate(1);
+        }
+        structure.layers = layers;
+
+        Ok(structure)
+    }
+
+    /// Horizontal chunk coordinates of every column that the area of the structure overlaps,
+    /// with the structure at its [`origin`](Self::origin).
+    pub fn columns(&self) -> impl Iterator<Item = Vector<i32, 2>> {
+        let (min_x, max_x) = (self.origin.x >> 4, (self.origin.x + self.size.x - 1) >> 4);
+        let (min_z, max_z) = (self.origin.z >> 4, (self.origin.z + self.size.z - 1) >> 4);
+
+        (min_x..=max_x).flat_map(move |x| (min_z..=max_z).map(move |z| Vector::from([x, z])))
+    }
+
+    /// Whether the given world coordinates are within the area of the structure at its [`origin`](Self::origin).
+    fn contains(&self, x: f32, y: f32, z: f32) -> bool {
+        let (min, size) = (&self.origin, &self.size);
+        (min.x as f32..(min.x + size.x) as f32).contains(&x)
+            && (min.y as f32..(min.y + size.y) as f32).contains(&y)
+            && (min.z as f32..(min.z + size.z) as f32).contains(&z)
+    }
+
+    /// Adds the block entities that are within the area of the structure.
+    ///
+    /// The block entities should have world coordinates, they are converted to positions relative to the structure.
+    pub fn include_block_entities<I>(&mut self, entities: I)
+    where
+        I: IntoIterator<Item = BlockEntity>,
+    {
+        for mut entity in entities {
+            let pos = &entity.position;
+            if self.contains(pos.x as f32, pos.y as f32, pos.z as f32) {
+                entity.position = Vector::from([pos.x - self.origin.x, pos.y - self.origin.y, pos.z - self.origin.z]);
+                self.block_entities.push(entity);
+            }
+        }
+    }
+
+    /// Adds the actors that are within the area of the structure.
+    pub fn include_actors<I>(&mut self, actors: I)
+    where
+        I: IntoIterator<Item = Actor>,
+    {
+        for actor in actors {
+            if self.contains(actor.position.x, actor.position.y, actor.position.z) {
+                self.entities.push(actor.to_nbt());
+            }
+        }
+    }
+
+    /// Places the structure with its lowest corner at `origin`, without writing anything to the world.
+    ///
+    /// The structure is mirrored first and then rotated around its vertical axis.
+    /// Common directional block states, such as the direction of stairs, are transformed as well.
+    /// `subchunk` is called once for every affected subchunk with its subchunk coordinates
+    /// and returns `None` if the subchunk does not exist.
+    ///
+    /// Actors stored in the structure are not placed, as they would need new unique IDs.
+    pub fn place<F>(&self, origin: Vector<i32, 3>, rotation: StructureRotation, mirror: StructureMirror, mut subchunk: F) -> anyhow::Result<Placement>
+    where
+        F: FnMut(Vector<i32, 3>) -> anyhow::Result<Option<Arc<SubChunk>>>,
+    {
+        let palette = self
+            .palette
+            .iter()
+            .map(|entry| {
+                let mut entry = entry.clone();
+                transform_states(&mut entry, rotation, mirror);
+                entry
+            })
+            .collect::<Vec<_>>();
+
+        let world = |relative: &Vector<i32, 3>| {
+            let transformed = self.transform(relative, rotation, mirror);
+            Vector::from([origin.x + transformed.x, origin.y + transformed.y, origin.z + transformed.z])
+        };
+
+        let mut subchunks: HashMap<Vector<i32, 3>, SubChunk> = HashMap::new();
+        let mut blocks = Vec::new();
+
+        for (layer_index, layer) in self.layers.iter().enumerate() {
+            for (index, block) in layer.iter().enumerate() {
+                let Some(block) = block else { continue };
+                let Some(entry) = palette.get(*block as usize) else {
+                    anyhow::bail!(format!("Structure contains out of range palette index {block}"));
+                };
+
+                let position = world(&self.position(index));
+                let key = Vector::from([position.x >> 4, position.y >> 4, position.z >> 4]);
+                let Ok(subchunk_index) = i8::try_from(key.y) else {
+                    anyhow::bail!(format!("Structure block at {position:?} is outside of the world"));
+                };
+
+                let target = match subchunks.entry(key.clone()) {
+                    std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+                    std::collections::hash_map::Entry::Vacant(vacant) => {
+                        let existing = subchunk(key)?.map(|subchunk| SubChunk::clone(&subchunk));
+                        vacant.insert(existing.unwrap_or_else(|| SubChunk::empty(subchunk_index)))
+                    }
+                };
+
+                while target.layers.len() <= layer_index {
+                    target.layers.push(SubStorage::empty());
+                }
+
+                let local = Vector::from([(position.x & 0xf) as u8, (position.y & 0xf) as u8, (position.z & 0xf) as u8]);
+                place_block(&mut target.layers[layer_index], crate::to_offset(local), entry);
+
+                blocks.push(PlacedBlock {
+                    position,
+                    layer: layer_index,
+                    block: *block,
+                });
+            }
+        }
+
+        for subchunk in subchunks.values_mut() {
+            for layer in &mut subchunk.layers {
+                layer.compact();
+            }
+        }
+
+        let block_entities = self
+            .block_entities
+            .iter()
+            .map(|entity| {
+                let mut entity = entity.clone();
+                entity.position = world(&entity.position);
+                entity
+            })
+            .collect();
+
+        Ok(Placement {
+            subchunks,
+            palette,
+            blocks,
+            block_entities,
+        })
+    }
+}
+
+impl<S: Storage + ?Sized> Provider<S> {
+    /// Copies a cuboid of blocks from the world into a structure.
+    ///
+    /// Both corners are inclusive and may be given in any order.
+    /// Block entities and actors inside the cuboid are included as well.
+    ///
+    /// This reads directly from storage and is meant for offline tools. While the server is running,
+    /// use the level service instead, so that modifications that have not been written yet are included.
+    pub fn extract_structure(&self, from: Vector<i32, 3>, to: Vector<i32, 3>, dimension: Dimension) -> anyhow::Result<Structure> {
+        let mut structure = Structure::extract(from, to, |index| match i8::try_from(index.y) {
+            Ok(_) => Ok(self.subchunk(index, dimension)?.map(Arc::new)),
+            Err(_) => Ok(None),
+        })?;
+
+        for column in structure.columns().collect::<Vec<_>>() {
+            structure.include_block_entities(self.block_entities(column.clone(), dimension)?);
+            structure.include_actors(self.actors(column, dimension)?);
+        }
+
+        Ok(structure)
+    }
+
+    /// Places a structure in the world with its lowest corner at `origin`.
+    ///
+    /// See [`Structure::place`] for how the structure is transformed.
+    /// Existing block entities in the affected blocks are replaced by those in the structure.
+    ///
+    /// This writes directly to storage and is meant for offline tools. While the server is running,
+    /// use the level service instead, so that cached chunks and players are updated.
+    pub fn place_structure(
+        &self,
+        structure: &Structure,
+        origin: Vector<i32, 3>,
+        dimension: Dimension,
+        rotation: StructureRotation,
+        mirror: StructureMirror,
+    ) -> anyhow::Result<()> {
+        let placement = structure.place(origin, rotation, mirror, |index| Ok(self.subchunk(index, dimension)?.map(Arc::new)))?;
+
+        let mut batch = Provider::batch();
+        for (key, subchunk) in &placement.subchunks {
+            Provider::batch_subchunk(&mut batch, key.clone(), dimension, subchunk)?;
+        }
+
+        let replaced = placement.replaced();
+        for column in placement.columns() {
+            let mut entities = self.block_entities(column.clone(), dimension)?;
+            placement.merge_block_entities(&column, &replaced, &mut entities);
+            Provider::batch_block_entities(&mut batch, column.clone(), dimension, &entities)?;
+
+            // Mark new chunks as generated, so that the structure is not overwritten by the generator.
+            if self.version(column.clone(), dimension)?.is_none() {
+                let biomes = Biomes {
+                    heightmap: Box::new([[0; 16]; 16]),
+                    fragments: subchunk_range(dimension)
+                        .map(|_| BiomeEncoding::Single(crate::anvil::default_biome(dimension)))
+                        .collect(),
+                };
+
+                Provider::batch_biomes(&mut batch, column.clone(), dimension, &biomes)?;
+                Provider::batch_version(&mut batch, column, dimension, CHUNK_VERSION)?;
+            }
+        }
+
+        self.execute(&batch)
+    }
+}
+
+/// Returns the index of an entry in the structure palette, adding it if it does not exist yet.
+fn palette_index(palette: &mut Vec<PaletteEntry>, entry: &PaletteEntry) -> u32 {
+    let index = palette.iter().position(|e| e.same_state(entry)).unwrap_or_else(|| {
+        palette.push(entry.clone());
+        palette.len() - 1
+    });
+
+    index as u32
+}
+
+/// Sets a block in a layer without removing unused palette entries.
+///
+/// This is much faster than [`SubStorage::set`] when many blocks are replaced,
+/// the layer should be compacted afterwards.
+fn place_block(layer: &mut SubStorage, offset: usize, entry: &PaletteEntry) {
+    if layer.palette.is_empty() {
+        layer.palette.push(PaletteEntry::air(entry.version));
+    }
+
+    let index = layer.palette.iter().position(|e| e.same_state(entry)).unwrap_or_else(|| {
+        layer.palette.push(entry.clone());
+        layer.palette.len() - 1
+    });
+
+    layer.indices[offset] = index as u16;
+}
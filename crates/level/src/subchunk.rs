@@ -2,16 +2,20 @@ use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::iter::FusedIterator;
-use std::ops::{Index, IndexMut};
+use std::ops::{Index, IndexMut, Range};
 
+use proto::types::Dimension;
 use serde::{Deserialize, Serialize};
 
 use util::{BinaryRead, BinaryWrite};
 use util::{RVec, Vector};
 
-use crate::{BlockStates, PackedArrayReturn};
+use crate::{BlockStates, LegacyBlockMapping, PackedArrayReturn};
 
 /// Version of the subchunk.
+///
+/// Versions 0 and 2 to 7 store block IDs and metadata instead of a palette.
+/// These are converted to [`Limited`](Self::Limited) subchunks when loaded, see [`LegacyBlockMapping`].
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum SubChunkVersion {
     /// Legacy sub chunks are from before the Aquatic update.
@@ -65,8 +69,17 @@ mod block_version {
     }
 }
 
+/// Returns the range of vertical subchunk indices that exist in the given dimension.
+pub const fn subchunk_range(dimension: Dimension) -> Range<i8> {
+    match dimension {
+        Dimension::Overworld => -4..20,
+        Dimension::Nether => 0..8,
+        Dimension::End => 0..16,
+    }
+}
+
 /// Definition of block in the sub chunk block palette.
-#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(rename = "")]
 pub struct PaletteEntry {
     /// Name of the block.
@@ -91,6 +104,30 @@ impl PaletteEntry {
 
         hasher.finish()
     }
+
+    /// Creates an air block with the given block version.
+    pub fn air(version: Option<[u8; 4]>) -> Self {
+        Self {
+            name: String::from("minecraft:air"),
+            version,
+            states: HashMap::new(),
+        }
+    }
+
+    /// Whether this entry describes the same block state as `other`.
+    ///
+    /// Unlike the `PartialEq` implementation, this ignores the block version.
+    pub fn same_state(&self, other: &PaletteEntry) -> bool {
+        self.name == other.name && self.states == other.states
+    }
+
+    /// Whether this block stops sky light from passing through it.
+    ///
+    /// Air and fully transparent blocks such as glass, flowers and torches do not occlude.
+    /// The topmost occluding block in a column determines the height of the column's heightmap.
+    pub fn occludes(&self) -> bool {
+        self.light_filter() > 0
+    }
 }
 
 /// A layer in a sub chunk.
@@ -107,7 +144,7 @@ impl PaletteEntry {
 /// This is prefixed with a 32-bit little endian integer specifying the size of the palette.
 /// The rest of the palette then consists of `n` concatenated NBT compounds.
 #[doc(alias = "storage record")]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SubStorage {
     /// List of indices into the palette.
     ///
@@ -144,19 +181,83 @@ impl SubStorage {
         Some(&self.palette[index])
     }
 
-    // FIXME: Using this method will modify every block with the same index
-    // instead of only the block at the specified position.
-    // pub fn get_mut(&mut self, pos: Vector<u8, 3>) -> Option<&mut PaletteEntry> {
-    //     if pos.x > 16 || pos.y > 16 || pos.z > 16 {
-    //         return None;
-    //     }
+    /// Replaces the block at the given position inside the subchunk.
+    ///
+    /// The block is added to the palette if it is not present yet. If the replaced block
+    /// is no longer used anywhere in this layer, it is removed from the palette.
+    ///
+    /// Returns `false` if the position is out of bounds.
+    pub fn set<V>(&mut self, pos: V, entry: PaletteEntry) -> bool
+    where
+        V: Into<Vector<u8, 3>>,
+    {
+        let pos = pos.into();
 
-    //     let offset = to_offset(pos);
-    //     debug_assert!(offset < 4096);
+        if pos.x >= 16 || pos.y >= 16 || pos.z >= 16 {
+            return false;
+        }
 
-    //     let index = self.indices[offset] as usize;
-    //     Some(&mut self.palette[index])
-    // }
+        // An empty layer consists entirely of air, which has to be added to the palette
+        // before other blocks can be referenced.
+        if self.palette.is_empty() {
+            self.palette.push(PaletteEntry::air(entry.version));
+        }
+
+        let index = if let Some(index) = self.palette.iter().position(|e| e.same_state(&entry)) {
+            index
+        } else {
+            self.palette.push(entry);
+            self.palette.len() - 1
+        };
+
+        let offset = to_offset(pos);
+        let old = std::mem::replace(&mut self.indices[offset], index as u16);
+
+        if old as usize != index && !self.indices.contains(&old) {
+            self.remove_palette_entry(old);
+        }
+
+        true
+    }
+
+    /// Removes all unused and duplicate entries from the palette.
+    pub fn compact(&mut self) {
+        let mut remap = vec![u16::MAX; self.palette.len()];
+        let mut used = vec![false; self.palette.len()];
+        for &index in self.indices.iter() {
+            used[index as usize] = true;
+        }
+
+        let mut palette: Vec<PaletteEntry> = Vec::with_capacity(self.palette.len());
+        for (old, entry) in std::mem::take(&mut self.palette).into_iter().enumerate() {
+            if !used[old] {
+                continue;
+            }
+
+            if let Some(new) = palette.iter().position(|e| e.same_state(&entry)) {
+                remap[old] = new as u16;
+            } else {
+                remap[old] = palette.len() as u16;
+                palette.push(entry);
+            }
+        }
+
+        for index in self.indices.iter_mut() {
+            *index = remap[*index as usize];
+        }
+
+        self.palette = palette;
+    }
+
+    /// Removes a palette entry that is no longer referenced by any index.
+    fn remove_palette_entry(&mut self, removed: u16) {
+        self.palette.remove(removed as usize);
+        for index in self.indices.iter_mut() {
+            if *index > removed {
+                *index -= 1;
+            }
+        }
+    }
 
     /// Returns a reference to the block palette.
     pub fn palette(&self) -> &[PaletteEntry] {
@@ -309,7 +410,7 @@ pub fn from_offset(offset: usize) -> Vector<u8, 3> {
 /// A Minecraft sub chunk.
 ///
 /// Every world contains
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SubChunk {
     /// Version of the sub chunk.
     ///
@@ -375,11 +476,30 @@ impl SubChunk {
     }
 
     /// Deserialize a full sub chunk from the given buffer.
-    pub fn deserialize_disk<'a, R>(mut reader: R) -> anyhow::Result<Self>
+    ///
+    /// Sub chunks in the pre-palette formats are converted using the [built-in](LegacyBlockMapping::builtin) legacy mapping.
+    pub fn deserialize_disk<'a, R>(reader: R) -> anyhow::Result<Self>
     where
         R: BinaryRead<'a> + Copy + 'a,
     {
-        let version = SubChunkVersion::try_from(reader.read_u8()?)?;
+        Self::deserialize_disk_with(reader, LegacyBlockMapping::builtin())
+    }
+
+    /// Deserialize a full sub chunk from the given buffer.
+    ///
+    /// Sub chunks in the pre-palette formats (versions 0 and 2 to 7) store block IDs and metadata,
+    /// which are converted to palette entries using `mapping`.
+    pub fn deserialize_disk_with<'a, R>(mut reader: R, mapping: &LegacyBlockMapping) -> anyhow::Result<Self>
+    where
+        R: BinaryRead<'a> + Copy + 'a,
+    {
+        let raw_version = reader.read_u8()?;
+        if raw_version == 0 || (2..=7).contains(&raw_version) {
+            let remaining = reader.remaining();
+            return Self::deserialize_legacy_ids(reader.take_n(remaining)?, mapping);
+        }
+
+        let version = SubChunkVersion::try_from(raw_version)?;
         let layer_count = match version {
             SubChunkVersion::Legacy => 1,
             _ => reader.read_u8()?,
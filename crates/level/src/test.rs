@@ -1,9 +1,9 @@
-use std::sync::Mutex;
+use std::{collections::HashMap, sync::Mutex};
 
 use proto::types::Dimension;
 use util::Vector;
 
-use crate::{database::Database, provider::Provider, SubChunk};
+use crate::{database::Database, provider::Provider, Actor, BlockEntity, LevelSettings, LightEngine, PaletteEntry, SubChunk, SubStorage};
 
 // digp [x] [z] [?dimension]
 // contains two int32
@@ -21,6 +21,215 @@ fn level_settings() {
     let provider = Provider::open("../../resources/level").unwrap();
     let _settings = provider.settings().unwrap();
 }
+
+#[test]
+fn level_settings_roundtrip() {
+    let _lock = LOCK.lock().unwrap();
+    let provider = Provider::open("../../resources/level").unwrap();
+    let settings = provider.settings().unwrap();
+
+    let encoded = nbt::to_le_bytes(&settings).unwrap();
+    let (decoded, _): (LevelSettings, _) = nbt::from_le_bytes(&mut encoded.as_ref()).unwrap();
+    assert_eq!(settings, decoded);
+}
+
+#[test]
+fn level_experiments() {
+    use crate::Experiments;
+
+    let mut experiments = Experiments {
+        experiments_ever_used: true,
+        saved_with_toggled_experiments: true,
+        toggles: Default::default(),
+    };
+    experiments.toggles.insert("data_driven_biomes".to_owned(), 1);
+    experiments.toggles.insert("gametest".to_owned(), 0);
+
+    let encoded = nbt::to_le_bytes(&experiments).unwrap();
+    let (decoded, _): (Experiments, _) = nbt::from_le_bytes(&mut encoded.as_ref()).unwrap();
+    assert_eq!(decoded, experiments);
+    assert_eq!(decoded.enabled().collect::<Vec<_>>(), ["data_driven_biomes"]);
+}
+
+#[test]
+fn substorage_set() {
+    let stone = PaletteEntry {
+        name: String::from("minecraft:stone"),
+        version: None,
+        states: Default::default(),
+    };
+
+    let mut layer = SubStorage::empty();
+    assert!(layer.set([1, 2, 3], stone.clone()));
+    assert_eq!(layer.palette().len(), 2);
+    assert_eq!(layer[[1, 2, 3]], stone);
+    assert_eq!(layer[[0, 0, 0]].name, "minecraft:air");
+
+    // Replacing the only stone block should remove it from the palette.
+    assert!(layer.set([1, 2, 3], PaletteEntry::air(None)));
+    assert_eq!(layer.palette().len(), 1);
+    assert!(layer.indices().iter().all(|&i| i == 0));
+
+    assert!(!layer.set([16, 0, 0], stone));
+}
+
+#[test]
+fn block_entities() {
+    let sign = BlockEntity {
+        id: "Sign".to_owned(),
+        position: Vector::from([-17, -60, 33]),
+        is_movable: true,
+        custom_name: None,
+        data: HashMap::from([("TextOwner".to_owned(), nbt::Value::String(String::new()))]),
+    };
+    let chest = BlockEntity {
+        id: "Chest".to_owned(),
+        position: Vector::from([-18, 70, 34]),
+        is_movable: false,
+        custom_name: Some("Loot".to_owned()),
+        data: HashMap::new(),
+    };
+
+    assert_eq!(BlockEntity::from_nbt(sign.to_nbt()).unwrap(), sign);
+    assert!(BlockEntity::from_nbt(HashMap::new()).is_err());
+
+    let entities = vec![sign, chest];
+    let serialized = BlockEntity::serialize_all(&entities).unwrap();
+    let deserialized = BlockEntity::deserialize_all(serialized.as_ref()).unwrap();
+    assert_eq!(deserialized, entities);
+
+    assert!(crate::has_block_entity("minecraft:chest"));
+    assert!(crate::has_block_entity("minecraft:oak_hanging_sign"));
+    assert!(crate::has_block_entity("minecraft:red_shulker_box"));
+    assert!(!crate::has_block_entity("minecraft:stone"));
+    assert!(!crate::has_block_entity("minecraft:air"));
+}
+
+#[test]
+fn block_entity_client_edit() {
+    let text = |value: &str| nbt::Value::Compound(HashMap::from([("Text".to_owned(), nbt::Value::String(value.to_owned()))]));
+    let entity = |id: &str, data: HashMap<String, nbt::Value>| BlockEntity {
+        id: id.to_owned(),
+        position: Vector::from([4, 64, -9]),
+        is_movable: true,
+        custom_name: None,
+        data,
+    };
+
+    // Only the text of a sign is copied, other fields sent by the client are ignored.
+    let mut sign = entity("Sign", HashMap::from([("FrontText".to_owned(), text("old"))]));
+    let edit = entity(
+        "Sign",
+        HashMap::from([
+            ("FrontText".to_owned(), text("new")),
+            ("BackText".to_owned(), text("back")),
+            ("IsWaxed".to_owned(), nbt::Value::Byte(0)),
+        ]),
+    );
+    sign.apply_client_edit(&edit).unwrap();
+    assert_eq!(sign.data["FrontText"], text("new"));
+    assert_eq!(sign.data["BackText"], text("back"));
+    assert!(!sign.data.contains_key("IsWaxed"));
+
+    // Clients cannot change the items of a chest.
+    let mut chest = entity("Chest", HashMap::from([("Items".to_owned(), nbt::Value::List(Vec::new()))]));
+    let items = entity(
+        "Chest",
+        HashMap::from([("Items".to_owned(), nbt::Value::List(vec![nbt::Value::Compound(HashMap::new())]))]),
+    );
+    assert!(chest.apply_client_edit(&items).is_err());
+    assert_eq!(chest.data["Items"], nbt::Value::List(Vec::new()));
+
+    // Nor turn a sign into another block entity or edit a waxed sign.
+    assert!(sign.apply_client_edit(&items).is_err());
+    sign.data.insert("IsWaxed".to_owned(), nbt::Value::Byte(1));
+    assert!(sign.apply_client_edit(&edit).is_err());
+    assert_eq!(sign.data["BackText"], text("back"));
+}
+
+#[test]
+fn actors() {
+    let cow = Actor {
+        identifier: "minecraft:cow".to_owned(),
+        unique_id: -4294967295,
+        position: Vector::from([0.5, -59.0, 12.5]),
+        rotation: Vector::from([90.0, 0.0]),
+        motion: Vector::from([0.0, -0.08, 0.0]),
+        data: HashMap::from([("Health".to_owned(), nbt::Value::Float(10.0))]),
+    };
+
+    assert_eq!(Actor::from_nbt(cow.to_nbt()).unwrap(), cow);
+
+    // Static actors such as item frames are stored without rotation and motion.
+    let mut nbt = cow.to_nbt();
+    nbt.remove("Rotation");
+    nbt.remove("Motion");
+    let frame = Actor::from_nbt(nbt).unwrap();
+    assert_eq!(frame.rotation, Vector::from([0.0, 0.0]));
+    assert_eq!(frame.motion, Vector::from([0.0, 0.0, 0.0]));
+
+    let legacy = nbt::to_le_bytes(&cow.to_nbt()).unwrap();
+    assert_eq!(Actor::deserialize_legacy(legacy.as_ref()).unwrap(), vec![cow.clone()]);
+
+    let digest = [1i64, cow.unique_id].iter().flat_map(|id| id.to_le_bytes()).collect::<Vec<_>>();
+    assert_eq!(Actor::deserialize_digest(digest.as_slice()).unwrap(), vec![1, cow.unique_id]);
+
+    let key = crate::digest_key(&Vector::from([1, -1]), Dimension::Nether).unwrap();
+    assert_eq!(key.as_ref(), b"digp\x01\x00\x00\x00\xff\xff\xff\xff\x01\x00\x00\x00");
+    assert_eq!(crate::actor_key(1).unwrap().as_ref(), b"actorprefix\x01\x00\x00\x00\x00\x00\x00\x00");
+}
+
+#[test]
+fn light_engine() {
+    let block = |name: &str| PaletteEntry {
+        name: name.to_owned(),
+        version: None,
+        states: Default::default(),
+    };
+
+    // Solid ground up to and including y = 4.
+    let mut subchunks: Vec<SubChunk> = (-4..1)
+        .map(|index| {
+            let mut subchunk = SubChunk::empty(index);
+            for offset in 0..4096 {
+                let position = crate::from_offset(offset);
+                if index < 0 || position.y < 5 {
+                    subchunk[0].set(position, block("minecraft:stone"));
+                }
+            }
+            subchunk
+        })
+        .collect();
+
+    let mut engine = LightEngine::new(-4..20, true);
+    engine.light_column(&Vector::from([0, 0]), subchunks.iter());
+
+    assert_eq!(engine.sky_light(&Vector::from([3, 5, 3])), Some(15));
+    assert_eq!(engine.sky_light(&Vector::from([3, 4, 3])), Some(0));
+    assert_eq!(engine.block_light(&Vector::from([3, 5, 3])), Some(0));
+    assert_eq!(engine.sky_light(&Vector::from([16, 5, 3])), None);
+
+    // Dig a hole with a roof and place a torch inside of it.
+    for (position, name) in [([3, 4, 3], "minecraft:air"), ([3, 3, 3], "minecraft:air"), ([3, 5, 3], "minecraft:stone"), ([3, 3, 3], "minecraft:torch")] {
+        subchunks[4][0].set(position, block(name));
+        engine.update_block(&Vector::from([position[0] as i32, position[1] as i32, position[2] as i32]), &subchunks[4]);
+    }
+
+    assert_eq!(engine.sky_light(&Vector::from([3, 4, 3])), Some(0));
+    assert_eq!(engine.block_light(&Vector::from([3, 3, 3])), Some(14));
+    assert_eq!(engine.block_light(&Vector::from([3, 4, 3])), Some(13));
+
+    // Incremental updates produce the same light as lighting the column from scratch.
+    let mut full = LightEngine::new(-4..20, true);
+    full.light_column(&Vector::from([0, 0]), subchunks.iter());
+    for offset in 0..4096 {
+        let position = crate::from_offset(offset);
+        let position = Vector::from([i32::from(position.x), i32::from(position.y), i32::from(position.z)]);
+
+        assert_eq!(engine.sky_light(&position), full.sky_light(&position));
+        assert_eq!(engine.block_light(&position), full.block_light(&position));
+    }
+}
 //
 #[test]
 fn chunk_version() {
@@ -157,3 +366,627 @@ fn subchunks() {
 //
 //     assert_eq!(entry, de);
 // }
+
+#[test]
+fn database_seek() {
+    use crate::{DataKey, DbKey, KeyType};
+
+    let root = std::env::temp_dir().join(format!("mirai-database-seek-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    let provider = Provider::open(&root).unwrap();
+    let database = provider.database();
+
+    for key in [&b"alpha"[..], b"beta", b"beta1", b"beta2", b"gamma", b"player_a", b"player_b"] {
+        database.put_raw(key, key).unwrap();
+    }
+
+    let keys = |iter: crate::database::Keys| iter.map(|pair| pair.key().to_vec()).collect::<Vec<_>>();
+    // The prefix iterator starts at the first matching key and stops after the last one.
+    assert_eq!(keys(database.iter_prefix(b"beta")), vec![b"beta".to_vec(), b"beta1".to_vec(), b"beta2".to_vec()]);
+    assert_eq!(keys(database.iter_prefix(b"player_")), vec![b"player_a".to_vec(), b"player_b".to_vec()]);
+    assert!(keys(database.iter_prefix(b"delta")).is_empty());
+    assert!(keys(database.iter_prefix(b"zeta")).is_empty());
+
+    // The range iterator excludes its end.
+    assert_eq!(keys(database.iter_range(b"b", b"beta2")), vec![b"beta".to_vec(), b"beta1".to_vec()]);
+    assert_eq!(keys(database.iter_range(b"beta3", b"player_b")), vec![b"gamma".to_vec(), b"player_a".to_vec()]);
+    assert!(keys(database.iter_range(b"c", b"d")).is_empty());
+    assert_eq!(keys(database.iter()).len(), 7);
+
+    let version = |x, z, dimension| DataKey {
+        coordinates: [x, z].into(),
+        dimension,
+        data: KeyType::ChunkVersion,
+    };
+    database.put(version(0, 0, Dimension::Overworld), [40]).unwrap();
+    database.put(version(0, 0, Dimension::Nether), [40]).unwrap();
+    database.put(version(-3, 5, Dimension::Overworld), [40]).unwrap();
+
+    let entries = provider.chunk_entries([0, 0], Dimension::Overworld).unwrap().map(|(ty, _)| ty).collect::<Vec<_>>();
+    assert_eq!(entries, vec![KeyType::ChunkVersion]);
+
+    let mut chunks = provider.chunks(Dimension::Overworld);
+    chunks.sort_by_key(|chunk| (chunk.x, chunk.y));
+    assert_eq!(chunks, vec![Vector::from([-3, 5]), Vector::from([0, 0])]);
+    assert_eq!(provider.chunks(Dimension::Nether), vec![Vector::from([0, 0])]);
+
+    let players = provider.entries_with_prefix(crate::PLAYER_PREFIX).map(|(key, _)| key).collect::<Vec<_>>();
+    assert_eq!(players, vec![DbKey::Player { id: "a".to_owned() }, DbKey::Player { id: "b".to_owned() }]);
+
+    drop(provider);
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn anvil_packed_longs() {
+    // Since 1.16, indices do not span multiple longs: 5-bit indices leave the top 4 bits unused.
+    let values = (0..13).map(|i| i as u64 % 32).collect::<Vec<_>>();
+    let packed = values.iter().take(12).enumerate().fold(0u64, |acc, (i, v)| acc | v << (i * 5)) as i64;
+    let unpacked = crate::anvil::unpack_longs(&[packed, 12], 5, 13, false).unwrap();
+    assert_eq!(unpacked, values.iter().map(|v| *v as u16).collect::<Vec<_>>());
+
+    // Before 1.16, the 13th index spans both longs.
+    let spanning = crate::anvil::unpack_longs(&[(0b1111u64 << 60) as i64, 0b1], 5, 13, true).unwrap();
+    assert_eq!(spanning[11], 0);
+    assert_eq!(spanning[12], 0b11111);
+}
+
+#[test]
+fn anvil_block_mapping() {
+    use crate::anvil::{BlockMapping, JavaBlockState};
+
+    let json = r#"{
+        "minecraft:oak_stairs[waterlogged=false,facing=east]": {
+            "bedrock_identifier": "minecraft:oak_stairs",
+            "bedrock_states": { "weirdo_direction": 0, "upside_down_bit": false }
+        },
+        "minecraft:stone": { "name": "minecraft:stone" }
+    }"#;
+    let mapping = BlockMapping::from_json(json, None).unwrap();
+
+    let stairs = JavaBlockState {
+        name: String::from("minecraft:oak_stairs"),
+        properties: HashMap::from([(String::from("facing"), String::from("east")), (String::from("waterlogged"), String::from("true"))]),
+    };
+    let translated = mapping.translate(&stairs).unwrap();
+    assert_eq!(translated.name, "minecraft:oak_stairs");
+    assert_eq!(translated.states["weirdo_direction"], nbt::Value::Int(0));
+    assert_eq!(translated.states["upside_down_bit"], nbt::Value::Byte(0));
+
+    let cave_air = JavaBlockState { name: String::from("minecraft:cave_air"), properties: HashMap::new() };
+    assert_eq!(mapping.translate(&cave_air).unwrap().name, "minecraft:air");
+
+    let unknown = JavaBlockState { name: String::from("minecraft:copper_bulb"), properties: HashMap::new() };
+    assert!(mapping.translate(&unknown).is_none());
+}
+
+#[test]
+fn anvil_convert_chunk() {
+    use crate::anvil::{BlockMapping, ChunkConverter, JavaBiomes, JavaBlockState, JavaChunk, JavaSection, UNKNOWN_BLOCK};
+    use crate::BiomeEncoding;
+
+    let json = r#"{
+        "minecraft:oak_stairs[waterlogged=false,facing=east]": { "name": "minecraft:oak_stairs" },
+        "minecraft:stone": { "name": "minecraft:stone" }
+    }"#;
+    let mapping = BlockMapping::from_json(json, None).unwrap();
+
+    let state = |name: &str, properties: &[(&str, &str)]| JavaBlockState {
+        name: String::from(name),
+        properties: properties.iter().map(|(k, v)| (String::from(*k), String::from(*v))).collect(),
+    };
+    let section = |y: i8, palette: Vec<JavaBlockState>, indices: Box<[u16; 4096]>| JavaSection {
+        y,
+        palette,
+        indices,
+        biome_palette: vec![String::from("minecraft:plains")],
+        biome_indices: Box::new([0; 64]),
+    };
+
+    // Java indices are in YZX order.
+    let mut indices = Box::new([0u16; 4096]);
+    indices[0] = 1;
+    indices[1] = 2;
+    indices[2 << 8] = 3;
+
+    let palette = vec![
+        state("minecraft:air", &[]),
+        state("minecraft:stone", &[]),
+        state("minecraft:copper_bulb", &[]),
+        state("minecraft:oak_stairs", &[("facing", "east"), ("waterlogged", "true")]),
+    ];
+    let chunk = JavaChunk {
+        data_version: 3700,
+        is_full: true,
+        sections: vec![
+            section(0, palette, indices),
+            // Sections that only contain air and sections below the world are dropped.
+            section(1, vec![state("minecraft:air", &[])], Box::new([0; 4096])),
+            section(-5, vec![state("minecraft:stone", &[])], Box::new([0; 4096])),
+        ],
+        biomes: JavaBiomes::Sectioned,
+    };
+
+    let mut converter = ChunkConverter::new(mapping);
+    let converted = converter.convert(&chunk, Dimension::Overworld);
+
+    assert_eq!(converted.subchunks.len(), 1);
+    let subchunk = &converted.subchunks[0];
+    assert_eq!(subchunk.index, 0);
+    assert_eq!(subchunk[0][Vector::from([0, 0, 0])].name, "minecraft:stone");
+    assert_eq!(subchunk[0][Vector::from([1, 0, 0])].name, UNKNOWN_BLOCK);
+    assert_eq!(subchunk[0][Vector::from([0, 2, 0])].name, "minecraft:oak_stairs");
+    assert_eq!(subchunk[0][Vector::from([0, 1, 0])].name, "minecraft:air");
+
+    // The water of waterlogged blocks is stored in the second layer.
+    assert_eq!(subchunk[1][Vector::from([0, 2, 0])].name, "minecraft:water");
+    assert_eq!(subchunk[1][Vector::from([0, 0, 0])].name, "minecraft:air");
+
+    // Heights are relative to the bottom of the world at Y -64.
+    assert_eq!(converted.biomes.heightmap[0][0], 67);
+    assert_eq!(converted.biomes.heightmap[0][1], 65);
+    assert_eq!(converted.biomes.heightmap[1][0], 0);
+
+    assert_eq!(converted.biomes.fragments.len(), 24);
+    assert!(converted.biomes.fragments.iter().all(|fragment| *fragment == BiomeEncoding::Single(1)));
+
+    let report = converter.report();
+    assert_eq!(report.unknown_blocks, 1);
+    assert_eq!(report.unmapped_blocks.get("minecraft:copper_bulb"), Some(&1));
+    assert!(report.unmapped_biomes.is_empty());
+}
+
+#[test]
+fn structure_roundtrip() {
+    use crate::Structure;
+
+    let stone = PaletteEntry {
+        name: String::from("minecraft:stone"),
+        version: Some([1, 20, 60, 1]),
+        states: HashMap::new(),
+    };
+
+    let chest = BlockEntity {
+        id: String::from("Chest"),
+        position: Vector::from([1, 0, 2]),
+        is_movable: true,
+        custom_name: None,
+        data: HashMap::new(),
+    };
+
+    let structure = Structure {
+        size: Vector::from([2, 1, 3]),
+        origin: Vector::from([100, 64, -20]),
+        layers: vec![vec![Some(0), Some(1), None, Some(1), Some(1), Some(0)]],
+        palette: vec![PaletteEntry::air(Some([1, 20, 60, 1])), stone],
+        block_entities: vec![chest],
+        entities: Vec::new(),
+    };
+
+    let encoded = structure.serialize().unwrap();
+    let decoded = Structure::deserialize(&encoded).unwrap();
+    assert_eq!(structure, decoded);
+}
+
+#[test]
+fn structure_place_extract() {
+    use crate::{MemoryStorage, Structure, StructureMirror, StructureRotation};
+
+    let stone = PaletteEntry {
+        name: String::from("minecraft:stone"),
+        version: Some([1, 20, 60, 1]),
+        states: HashMap::new(),
+    };
+
+    let chest = BlockEntity {
+        id: String::from("Chest"),
+        position: Vector::from([1, 0, 2]),
+        is_movable: true,
+        custom_name: None,
+        data: HashMap::new(),
+    };
+
+    let structure = Structure {
+        size: Vector::from([2, 1, 3]),
+        origin: Vector::from([0, 0, 0]),
+        layers: vec![vec![Some(0), Some(1), None, Some(1), Some(1), Some(0)]],
+        palette: vec![PaletteEntry::air(Some([1, 20, 60, 1])), stone],
+        block_entities: vec![chest.clone()],
+        entities: Vec::new(),
+    };
+
+    // The structure crosses the border of four chunks, but only contains a structure void in one of them.
+    let provider = Provider::with_storage("memory", MemoryStorage::new());
+    provider
+        .place_structure(&structure, Vector::from([-1, 70, 14]), Dimension::Overworld, StructureRotation::None, StructureMirror::None)
+        .unwrap();
+
+    for column in [[-1, 0], [0, 0], [0, 1]] {
+        assert!(provider.version(column, Dimension::Overworld).unwrap().is_some());
+        assert_eq!(provider.biomes(column, Dimension::Overworld).unwrap().unwrap().fragments.len(), 24);
+    }
+    assert!(provider.version([-1, 1], Dimension::Overworld).unwrap().is_none());
+
+    let extracted = provider
+        .extract_structure(Vector::from([0, 70, 16]), Vector::from([-1, 70, 14]), Dimension::Overworld)
+        .unwrap();
+    assert_eq!(extracted.origin, Vector::from([-1, 70, 14]));
+    assert_eq!(extracted.size, structure.size);
+
+    // The structure void keeps the existing block, which is air.
+    let names = extracted.layers[0]
+        .iter()
+        .map(|block| extracted.palette[block.unwrap() as usize].name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["minecraft:air", "minecraft:stone", "minecraft:air", "minecraft:stone", "minecraft:stone", "minecraft:air"]);
+    assert_eq!(extracted.block_entities, vec![chest]);
+}
+
+#[test]
+fn structure_transform() {
+    use crate::{Structure, StructureMirror, StructureRotation};
+
+    let structure = Structure {
+        size: Vector::from([4, 1, 2]),
+        origin: Vector::from([0, 0, 0]),
+        layers: vec![vec![None; 8]],
+        palette: Vec::new(),
+        block_entities: Vec::new(),
+        entities: Vec::new(),
+    };
+
+    let corner = Vector::from([3, 0, 0]);
+    assert_eq!(structure.rotated_size(StructureRotation::Clockwise90), Vector::from([2, 1, 4]));
+    assert_eq!(structure.transform(&corner, StructureRotation::Clockwise90, StructureMirror::None), Vector::from([1, 0, 3]));
+    assert_eq!(structure.transform(&corner, StructureRotation::Clockwise180, StructureMirror::None), Vector::from([0, 0, 1]));
+    assert_eq!(structure.transform(&corner, StructureRotation::Clockwise270, StructureMirror::None), Vector::from([0, 0, 0]));
+    assert_eq!(structure.transform(&corner, StructureRotation::None, StructureMirror::X), Vector::from([0, 0, 0]));
+    assert_eq!(structure.transform(&corner, StructureRotation::None, StructureMirror::Z), Vector::from([3, 0, 1]));
+
+    for index in 0..structure.volume() {
+        assert_eq!(structure.index(&structure.position(index)), index);
+    }
+}
+
+#[test]
+fn backup_names() {
+    assert_eq!(crate::backup_name(0), "1970-01-01_00-00-00");
+    assert_eq!(crate::backup_name(951_827_696), "2000-02-29_12-34-56");
+    assert_eq!(crate::backup_name(1_725_113_109), "2024-08-31_14-05-09");
+}
+
+#[test]
+fn backup_pruning() {
+    let root = std::env::temp_dir().join(format!("mirai-backup-pruning-{}", std::process::id()));
+    // Backups made within the same second are ordered by their counter, not lexicographically.
+    let names = [
+        "2024-01-01_00-00-00",
+        "2024-01-02_00-00-00",
+        "2024-01-02_00-00-00-1",
+        "2024-01-02_00-00-00-2",
+        "2024-01-02_00-00-00-10",
+        "2024-01-03_00-00-00",
+    ];
+    for name in names {
+        std::fs::create_dir_all(root.join(name)).unwrap();
+    }
+    // Directories that were not created by a backup should be left alone.
+    std::fs::create_dir_all(root.join("other")).unwrap();
+    std::fs::create_dir_all(root.join("2024-01-04_00-00-00.partial")).unwrap();
+
+    assert!(crate::prune_backups(&root, 0).unwrap().is_empty());
+
+    let removed = crate::prune_backups(&root, 2).unwrap();
+    assert_eq!(removed, names[..4].iter().map(|name| root.join(name)).collect::<Vec<_>>());
+    assert!(root.join(names[4]).exists());
+    assert!(root.join(names[5]).exists());
+    assert!(root.join("other").exists());
+    assert!(root.join("2024-01-04_00-00-00.partial").exists());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn backup_concurrent() {
+    let root = std::env::temp_dir().join(format!("mirai-backup-concurrent-{}", std::process::id()));
+    let world = root.join("world");
+    std::fs::create_dir_all(world.join("db")).unwrap();
+    std::fs::write(world.join("db").join("000001.log"), b"log").unwrap();
+    std::fs::write(world.join("level.dat"), b"settings").unwrap();
+
+    let provider = Provider::open(&world).unwrap();
+    let backups = root.join("backups");
+
+    // Backups made within the same second must not write into the same directory.
+    let mut targets = std::thread::scope(|scope| {
+        let handles = (0..4).map(|_| scope.spawn(|| provider.backup(&backups).unwrap())).collect::<Vec<_>>();
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect::<Vec<_>>()
+    });
+    targets.sort();
+    targets.dedup();
+    assert_eq!(targets.len(), 4);
+
+    for target in &targets {
+        assert_eq!(std::fs::read(target.join("db").join("000001.log")).unwrap(), b"log");
+        assert_eq!(std::fs::read(target.join("level.dat")).unwrap(), b"settings");
+    }
+    assert_eq!(std::fs::read_dir(&backups).unwrap().count(), 4);
+
+    drop(provider);
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn legacy_block_mapping() {
+    let mapping = crate::LegacyBlockMapping::builtin();
+
+    let log = mapping.translate(17, 5).unwrap();
+    assert_eq!(log.name, "minecraft:log");
+    assert_eq!(log.states["old_log_type"], nbt::Value::String("spruce".to_owned()));
+    assert_eq!(log.states["pillar_axis"], nbt::Value::String("x".to_owned()));
+
+    // Metadata without its own entry falls back to the entry of the ID.
+    assert_eq!(mapping.translate(45, 3).unwrap().name, "minecraft:brick_block");
+    assert!(mapping.translate(255, 0).is_none());
+    assert_eq!(mapping.translate_or_unknown(255, 0).name, "minecraft:info_update");
+}
+
+#[test]
+fn legacy_subchunk() {
+    let mut data = vec![0u8; 1 + 4096 + 2048];
+    // Wool with metadata 14 at (1, 2, 3).
+    let offset = crate::to_offset(Vector::from([1, 2, 3]));
+    data[1 + offset] = 35;
+    data[1 + 4096 + offset / 2] = 14 << ((offset & 1) * 4);
+
+    let subchunk = SubChunk::deserialize_disk(data.as_slice()).unwrap();
+    let layer = subchunk.layer(0).unwrap();
+    assert_eq!(layer.palette().len(), 2);
+    assert_eq!(layer[[0, 0, 0]].name, "minecraft:air");
+
+    let wool = &layer[[1, 2, 3]];
+    assert_eq!(wool.name, "minecraft:wool");
+    assert_eq!(wool.states["color"], nbt::Value::String("red".to_owned()));
+}
+
+#[test]
+fn provider_subchunk_index() {
+    use crate::{DataKey, KeyType, MemoryStorage, Storage, SubChunkVersion};
+
+    let provider = Provider::with_storage("memory", MemoryStorage::new());
+    let key = |y| DataKey {
+        coordinates: [2, -5].into(),
+        dimension: Dimension::Overworld,
+        data: KeyType::SubChunk { index: y },
+    };
+
+    // Version 8 does not store the index.
+    let mut blocks = [0u8; 4096];
+    blocks[crate::to_offset(Vector::from([1, 2, 3]))] = 1;
+    let mut limited = SubChunk::from_legacy_ids(0, &blocks, &[0; 2048], crate::LegacyBlockMapping::builtin());
+    limited.version = SubChunkVersion::Limited;
+    provider.database().put(key(3), limited.serialize_disk().unwrap().as_ref()).unwrap();
+
+    // Neither does the block ID format of versions 0 and 2 to 7.
+    let mut legacy = vec![2u8; 1 + 4096 + 2048];
+    legacy[1..].fill(0);
+    provider.database().put(key(-2), &legacy).unwrap();
+
+    let limited = provider.subchunk([2, 3, -5], Dimension::Overworld).unwrap().unwrap();
+    assert_eq!(limited.index(), 3);
+    assert_eq!(limited[0][[1, 2, 3]].name, "minecraft:stone");
+
+    let legacy = provider.subchunk([2, -2, -5], Dimension::Overworld).unwrap().unwrap();
+    assert_eq!(legacy.index(), -2);
+    assert!(provider.subchunk([2, 4, -5], Dimension::Overworld).unwrap().is_none());
+}
+
+#[test]
+fn legacy_terrain() {
+    let mut data = vec![0u8; 32768 + 3 * 16384 + 256 + 1024];
+    // Stone at (4, 20, 9), which is in the second subchunk.
+    data[4 << 11 | 9 << 7 | 20] = 1;
+    // Biome of column (4, 9).
+    data[32768 + 3 * 16384 + 256 + (9 * 16 + 4) * 4] = 2;
+
+    let terrain = crate::LegacyTerrain::deserialize(&data, crate::LegacyBlockMapping::builtin()).unwrap();
+    assert_eq!(terrain.subchunks.len(), 8);
+    assert_eq!(terrain.biomes[9 * 16 + 4], 2);
+
+    let subchunk = &terrain.subchunks[1];
+    assert_eq!(subchunk.index(), 1);
+    assert_eq!(subchunk[0][[4, 4, 9]].name, "minecraft:stone");
+    assert_eq!(subchunk[0][[4, 5, 9]].name, "minecraft:air");
+    assert!(terrain.subchunks[0].layer(0).unwrap().iter().all(|b| b.name == "minecraft:air"));
+}
+
+#[test]
+fn block_upgrade_schema() {
+    const SCHEMA: &str = r#"{
+        "maxVersionMajor": 1,
+        "maxVersionMinor": 20,
+        "maxVersionPatch": 50,
+        "maxVersionRevision": 0,
+        "renamedIds": { "minecraft:grass": "minecraft:grass_block" },
+        "addedProperties": { "minecraft:log": { "stripped_bit": { "byte": 0 } } },
+        "removedProperties": { "minecraft:log": ["old_property"] },
+        "renamedProperties": { "minecraft:log": { "old_log_type": "wood_type" } },
+        "remappedPropertyValues": { "minecraft:log": { "old_log_type": "log_types" } },
+        "remappedPropertyValuesIndex": {
+            "log_types": [{ "old": { "string": "oak" }, "new": { "string": "birch" } }]
+        },
+        "remappedStates": {
+            "minecraft:stone": [
+                { "oldState": { "stone_type": { "string": "granite" } }, "newName": "minecraft:granite", "newState": {} }
+            ]
+        }
+    }"#;
+
+    let mut upgrader = crate::BlockUpgrader::new();
+    upgrader.add_schema(crate::UpgradeSchema::from_json(SCHEMA).unwrap());
+
+    let entry = |name: &str, states: &[(&str, nbt::Value)], version| PaletteEntry {
+        name: name.to_owned(),
+        version,
+        states: states.iter().map(|(k, v)| ((*k).to_owned(), v.clone())).collect(),
+    };
+
+    let grass = upgrader.upgrade(&entry("minecraft:grass", &[], None)).unwrap();
+    assert_eq!(grass.name, "minecraft:grass_block");
+    assert_eq!(grass.version, Some([1, 20, 50, 0]));
+
+    let granite = upgrader
+        .upgrade(&entry("minecraft:stone", &[("stone_type", nbt::Value::String("granite".to_owned()))], Some([1, 12, 0, 0])))
+        .unwrap();
+    assert_eq!(granite.name, "minecraft:granite");
+    assert!(granite.states.is_empty());
+
+    let log = upgrader
+        .upgrade(&entry(
+            "minecraft:log",
+            &[
+                ("old_log_type", nbt::Value::String("oak".to_owned())),
+                ("old_property", nbt::Value::Int(1)),
+            ],
+            None,
+        ))
+        .unwrap();
+    assert_eq!(
+        log.states,
+        HashMap::from([
+            ("wood_type".to_owned(), nbt::Value::String("birch".to_owned())),
+            ("stripped_bit".to_owned(), nbt::Value::Byte(0)),
+        ])
+    );
+
+    // Blocks that are newer than every schema are left alone.
+    assert!(upgrader.upgrade(&entry("minecraft:grass", &[], Some([1, 21, 0, 0]))).is_none());
+}
+
+#[test]
+fn memory_storage() {
+    use crate::{DataKey, KeyType, MemoryStorage, Storage};
+
+    let provider = Provider::with_storage("memory", MemoryStorage::new());
+    let storage = provider.database();
+
+    storage.put_raw(b"alpha", b"1").unwrap();
+    storage.put_raw(b"alpha2", b"2").unwrap();
+    storage.put_raw(b"beta", b"3").unwrap();
+    assert_eq!(&*storage.get_raw(b"alpha").unwrap().unwrap(), b"1");
+    assert!(storage.get_raw(b"gamma").unwrap().is_none());
+
+    let pairs = storage
+        .iter_prefix(b"alpha")
+        .map(|(key, value)| (key.to_vec(), value.to_vec()))
+        .collect::<Vec<_>>();
+    assert_eq!(pairs, vec![(b"alpha".to_vec(), b"1".to_vec()), (b"alpha2".to_vec(), b"2".to_vec())]);
+
+    let mut batch = Provider::batch();
+    batch.put(b"gamma", b"4");
+    batch.delete(b"alpha");
+    provider.execute(&batch).unwrap();
+    assert!(storage.get_raw(b"alpha").unwrap().is_none());
+    assert_eq!(&*storage.get_raw(b"gamma").unwrap().unwrap(), b"4");
+
+    storage.delete_raw(b"beta").unwrap();
+    assert_eq!(storage.len(), 2);
+
+    let mut data = vec![0u8; 1 + 4096 + 2048];
+    data[1 + crate::to_offset(Vector::from([1, 2, 3]))] = 1;
+    let subchunk = SubChunk::deserialize_disk(data.as_slice()).unwrap();
+
+    let mut batch = Provider::batch();
+    Provider::batch_subchunk(&mut batch, [4, 0, -2], Dimension::Overworld, &subchunk).unwrap();
+    Provider::batch_version(&mut batch, [4, -2], Dimension::Overworld, crate::provider::CHUNK_VERSION).unwrap();
+    provider.execute(&batch).unwrap();
+
+    assert_eq!(provider.version([4, -2], Dimension::Overworld).unwrap(), Some(crate::provider::CHUNK_VERSION));
+    let loaded = provider.subchunk([4, 0, -2], Dimension::Overworld).unwrap().unwrap();
+    assert_eq!(loaded.layer(0).unwrap()[[1, 2, 3]].name, "minecraft:stone");
+
+    storage
+        .delete(DataKey {
+            coordinates: [4, -2].into(),
+            dimension: Dimension::Overworld,
+            data: KeyType::ChunkVersion,
+        })
+        .unwrap();
+    assert!(provider.version([4, -2], Dimension::Overworld).unwrap().is_none());
+}
+
+#[test]
+fn database_keys() {
+    use crate::{DataKey, DbKey, KeyType, MemoryStorage, Storage};
+
+    let keys = [
+        DbKey::Chunk(DataKey {
+            coordinates: [3, -7].into(),
+            dimension: Dimension::Overworld,
+            data: KeyType::SubChunk { index: -4 },
+        }),
+        DbKey::Chunk(DataKey {
+            coordinates: [3, -7].into(),
+            dimension: Dimension::Nether,
+            data: KeyType::ChunkVersion,
+        }),
+        DbKey::Digest {
+            coordinates: [3, -7].into(),
+            dimension: Dimension::End,
+        },
+        DbKey::Actor { unique_id: -12 },
+        DbKey::Player { id: "server_1234".to_owned() },
+        DbKey::BiomeData,
+        DbKey::LocalPlayer,
+        DbKey::Other(b"portals".to_vec()),
+    ];
+
+    for key in keys {
+        assert_eq!(DbKey::parse(&key.serialize().unwrap()), key);
+    }
+
+    let provider = Provider::with_storage("memory", MemoryStorage::new());
+    let version = |x, z, dimension| {
+        DbKey::Chunk(DataKey {
+            coordinates: [x, z].into(),
+            dimension,
+            data: KeyType::ChunkVersion,
+        })
+        .serialize()
+        .unwrap()
+    };
+
+    let database = provider.database();
+    database.put_raw(&version(0, 0, Dimension::Overworld), &[40]).unwrap();
+    database.put_raw(&version(0, 0, Dimension::Nether), &[40]).unwrap();
+    database.put_raw(&version(1, 0, Dimension::Overworld), &[40]).unwrap();
+    database
+        .put(
+            DataKey {
+                coordinates: [1, 0].into(),
+                dimension: Dimension::Overworld,
+                data: KeyType::LegacyVersion,
+            },
+            &[7],
+        )
+        .unwrap();
+    database.put_raw(b"player_server_1234", b"").unwrap();
+    database.put_raw(b"player_server_5678", b"").unwrap();
+    database.put_raw(b"portals", b"").unwrap();
+
+    let players = provider.entries_with_prefix(crate::PLAYER_PREFIX).map(|(key, _)| key).collect::<Vec<_>>();
+    assert_eq!(
+        players,
+        vec![DbKey::Player { id: "server_1234".to_owned() }, DbKey::Player { id: "server_5678".to_owned() }]
+    );
+
+    let entries = provider.chunk_entries([0, 0], Dimension::Overworld).unwrap().map(|(ty, _)| ty).collect::<Vec<_>>();
+    assert_eq!(entries, vec![KeyType::ChunkVersion]);
+
+    assert_eq!(provider.chunks(Dimension::Overworld), vec![Vector::from([0, 0]), Vector::from([1, 0])]);
+    assert_eq!(provider.chunks(Dimension::Nether), vec![Vector::from([0, 0])]);
+    assert!(provider.chunks(Dimension::End).is_empty());
+
+    let range = database.iter_range(b"player_", b"player_server_2").count();
+    assert_eq!(range, 1);
+}
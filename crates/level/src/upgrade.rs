@@ -0,0 +1,397 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::PaletteEntry;
+
+/// A state value in a block upgrade schema, such as `{"string": "oak"}`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SchemaTag {
+    Byte(i8),
+    Int(i32),
+    String(String),
+}
+
+impl From<SchemaTag> for nbt::Value {
+    fn from(tag: SchemaTag) -> Self {
+        match tag {
+            SchemaTag::Byte(v) => nbt::Value::Byte(v),
+            SchemaTag::Int(v) => nbt::Value::Int(v),
+            SchemaTag::String(v) => nbt::Value::String(v),
+        }
+    }
+}
+
+/// Replacement of a single property value.
+#[derive(Deserialize)]
+struct RawValueRemap {
+    old: SchemaTag,
+    new: SchemaTag,
+}
+
+/// Derives the name of a block from the value of one of its properties.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FlattenRule {
+    prefix: String,
+    flattened_property: String,
+    suffix: String,
+    #[serde(default)]
+    flattened_value_remaps: HashMap<String, String>,
+}
+
+impl FlattenRule {
+    /// Creates the flattened name, returning `None` if the property is missing or has an unsupported type.
+    fn name(&self, states: &HashMap<String, nbt::Value>) -> Option<String> {
+        let value = match states.get(&self.flattened_property)? {
+            nbt::Value::String(value) => value.clone(),
+            nbt::Value::Byte(value) => value.to_string(),
+            nbt::Value::Int(value) => value.to_string(),
+            _ => return None,
+        };
+
+        let value = self.flattened_value_remaps.get(&value).unwrap_or(&value);
+        Some(format!("{}{value}{}", self.prefix, self.suffix))
+    }
+}
+
+/// Replacement of a specific block state by an entirely new state.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawStateRemap {
+    old_state: Option<HashMap<String, SchemaTag>>,
+    new_name: Option<String>,
+    new_flattened_name: Option<FlattenRule>,
+    new_state: Option<HashMap<String, SchemaTag>>,
+    #[serde(default)]
+    copied_state: Vec<String>,
+}
+
+/// A block upgrade schema in the JSON format used by the vanilla block upgrade schemas.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawSchema {
+    max_version_major: u8,
+    max_version_minor: u8,
+    max_version_patch: u8,
+    max_version_revision: u8,
+    #[serde(default)]
+    renamed_ids: HashMap<String, String>,
+    #[serde(default)]
+    added_properties: HashMap<String, HashMap<String, SchemaTag>>,
+    #[serde(default)]
+    removed_properties: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    renamed_properties: HashMap<String, HashMap<String, String>>,
+    #[serde(default)]
+    remapped_property_values: HashMap<String, HashMap<String, String>>,
+    #[serde(default)]
+    remapped_property_values_index: HashMap<String, Vec<RawValueRemap>>,
+    #[serde(default)]
+    flattened_properties: HashMap<String, FlattenRule>,
+    #[serde(default)]
+    remapped_states: HashMap<String, Vec<RawStateRemap>>,
+}
+
+/// Replacement of a specific block state by an entirely new state.
+#[derive(Debug, Clone)]
+struct StateRemap {
+    /// Properties that the old state must have for this remap to apply.
+    old_state: HashMap<String, nbt::Value>,
+    /// Name of the new block.
+    new_name: NewName,
+    /// Properties of the new state.
+    new_state: HashMap<String, nbt::Value>,
+    /// Properties that are copied from the old state.
+    copied_state: Vec<String>,
+}
+
+/// Name of a remapped block.
+#[derive(Debug, Clone)]
+enum NewName {
+    /// The block is given a fixed name.
+    Fixed(String),
+    /// The name is derived from one of the old properties.
+    Flattened(FlattenRule),
+}
+
+/// Describes how blocks changed in a single game version.
+///
+/// Schemas are loaded from JSON in the format of the vanilla block upgrade schemas, which contain the fields
+/// `renamedIds`, `addedProperties`, `removedProperties`, `renamedProperties`, `remappedPropertyValues`,
+/// `remappedPropertyValuesIndex`, `flattenedProperties` and `remappedStates`.
+#[derive(Debug, Clone)]
+pub struct UpgradeSchema {
+    /// Version of the blocks produced by this schema.
+    version: [u8; 4],
+    /// New names of renamed blocks.
+    renamed_ids: HashMap<String, String>,
+    /// Properties that are added to a block if it does not have them yet.
+    added_properties: HashMap<String, HashMap<String, nbt::Value>>,
+    /// Properties that are removed from a block.
+    removed_properties: HashMap<String, Vec<String>>,
+    /// New names of renamed properties, indexed by block and old property name.
+    renamed_properties: HashMap<String, HashMap<String, String>>,
+    /// Old and new property values, indexed by block and property name.
+    remapped_values: HashMap<String, HashMap<String, Vec<(nbt::Value, nbt::Value)>>>,
+    /// Blocks that are split into multiple blocks based on the value of a property.
+    flattened_properties: HashMap<String, FlattenRule>,
+    /// Specific states that are replaced entirely.
+    remapped_states: HashMap<String, Vec<StateRemap>>,
+}
+
+impl UpgradeSchema {
+    /// Loads a schema from JSON.
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let raw: RawSchema = serde_json::from_str(json)?;
+
+        let convert = |states: HashMap<String, SchemaTag>| states.into_iter().map(|(k, v)| (k, v.into())).collect::<HashMap<_, _>>();
+
+        let index = raw.remapped_property_values_index;
+        let mut remapped_values = HashMap::with_capacity(raw.remapped_property_values.len());
+        for (block, properties) in raw.remapped_property_values {
+            let mut remaps = HashMap::with_capacity(properties.len());
+            for (property, key) in properties {
+                let Some(values) = index.get(&key) else {
+                    anyhow::bail!(format!("Property value remap {key} of {block} does not exist"));
+                };
+
+                let values = values.iter().map(|remap| (remap.old.clone().into(), remap.new.clone().into())).collect();
+                remaps.insert(property, values);
+            }
+
+            remapped_values.insert(block, remaps);
+        }
+
+        let mut remapped_states = HashMap::with_capacity(raw.remapped_states.len());
+        for (block, remaps) in raw.remapped_states {
+            let remaps = remaps
+                .into_iter()
+                .map(|remap| {
+                    let new_name = match (remap.new_name, remap.new_flattened_name) {
+                        (Some(name), _) => NewName::Fixed(name),
+                        (None, Some(rule)) => NewName::Flattened(rule),
+                        (None, None) => anyhow::bail!(format!("State remap of {block} is missing a new name")),
+                    };
+
+                    Ok(StateRemap {
+                        old_state: remap.old_state.map(convert).unwrap_or_default(),
+                        new_name,
+                        new_state: remap.new_state.map(convert).unwrap_or_default(),
+                        copied_state: remap.copied_state,
+                    })
+                })
+                .collect::<anyhow::Result<_>>()?;
+
+            remapped_states.insert(block, remaps);
+        }
+
+        Ok(Self {
+            version: [
+                raw.max_version_major,
+                raw.max_version_minor,
+                raw.max_version_patch,
+                raw.max_version_revision,
+            ],
+            renamed_ids: raw.renamed_ids,
+            added_properties: raw.added_properties.into_iter().map(|(k, v)| (k, convert(v))).collect(),
+            removed_properties: raw.removed_properties,
+            renamed_properties: raw.renamed_properties,
+            remapped_values,
+            flattened_properties: raw.flattened_properties,
+            remapped_states,
+        })
+    }
+
+    /// Version of the blocks produced by this schema.
+    #[inline]
+    pub const fn version(&self) -> [u8; 4] {
+        self.version
+    }
+
+    /// Applies this schema to a block, returning whether the block was changed.
+    ///
+    /// The version of the block is not modified.
+    pub fn apply(&self, entry: &mut PaletteEntry) -> bool {
+        if self.apply_remapped_state(entry) {
+            return true;
+        }
+
+        let mut changed = false;
+        if let Some(added) = self.added_properties.get(&entry.name) {
+            for (property, value) in added {
+                if !entry.states.contains_key(property) {
+                    entry.states.insert(property.clone(), value.clone());
+                    changed = true;
+                }
+            }
+        }
+
+        if let Some(removed) = self.removed_properties.get(&entry.name) {
+            for property in removed {
+                changed |= entry.states.remove(property).is_some();
+            }
+        }
+
+        // Values are remapped using the old property names.
+        if let Some(remaps) = self.remapped_values.get(&entry.name) {
+            for (property, values) in remaps {
+                let Some(value) = entry.states.get_mut(property) else {
+                    continue;
+                };
+
+                if let Some((_, new)) = values.iter().find(|(old, _)| old == value) {
+                    *value = new.clone();
+                    changed = true;
+                }
+            }
+        }
+
+        if let Some(renamed) = self.renamed_properties.get(&entry.name) {
+            for (old, new) in renamed {
+                if let Some(value) = entry.states.remove(old) {
+                    entry.states.insert(new.clone(), value);
+                    changed = true;
+                }
+            }
+        }
+
+        if let Some(rule) = self.flattened_properties.get(&entry.name) {
+            if let Some(name) = rule.name(&entry.states) {
+                entry.states.remove(&rule.flattened_property);
+                entry.name = name;
+                return true;
+            }
+        }
+
+        if let Some(name) = self.renamed_ids.get(&entry.name) {
+            entry.name.clone_from(name);
+            changed = true;
+        }
+
+        changed
+    }
+
+    /// Replaces the entire state if it matches one of the remapped states.
+    fn apply_remapped_state(&self, entry: &mut PaletteEntry) -> bool {
+        let Some(remaps) = self.remapped_states.get(&entry.name) else {
+            return false;
+        };
+
+        let Some(remap) = remaps
+            .iter()
+            .find(|remap| remap.old_state.iter().all(|(k, v)| entry.states.get(k) == Some(v)))
+        else {
+            return false;
+        };
+
+        let name = match &remap.new_name {
+            NewName::Fixed(name) => name.clone(),
+            NewName::Flattened(rule) => match rule.name(&entry.states) {
+                Some(name) => name,
+                None => return false,
+            },
+        };
+
+        let mut states = remap.new_state.clone();
+        for property in &remap.copied_state {
+            if let Some(value) = entry.states.get(property) {
+                states.insert(property.clone(), value.clone());
+            }
+        }
+
+        entry.name = name;
+        entry.states = states;
+
+        true
+    }
+}
+
+/// Upgrades blocks from older versions of the game to the current format.
+///
+/// Blocks are upgraded by applying every schema with a version at least as new as the version of the block, in order.
+/// Schemas with the same version are included because some changes were made without increasing the version.
+/// Blocks without a version are considered to be older than every schema.
+#[derive(Debug, Clone, Default)]
+pub struct BlockUpgrader {
+    /// Loaded schemas, sorted by version.
+    schemas: Vec<UpgradeSchema>,
+}
+
+impl BlockUpgrader {
+    /// Creates an upgrader without any schemas.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads every `.json` schema in the given directory.
+    pub fn load_dir<P>(path: P) -> anyhow::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let mut upgrader = Self::new();
+        for entry in std::fs::read_dir(path)? {
+            let path = entry?.path();
+            if path.extension().map_or(true, |ext| ext != "json") {
+                continue;
+            }
+
+            let json = std::fs::read_to_string(&path)?;
+            let schema = UpgradeSchema::from_json(&json).map_err(|err| {
+                tracing::error!("Failed to load block upgrade schema {}: {err:#}", path.display());
+                err.context(format!("Failed to load block upgrade schema {}", path.display()))
+            })?;
+
+            upgrader.add_schema(schema);
+        }
+
+        Ok(upgrader)
+    }
+
+    /// Adds a schema to the upgrader.
+    pub fn add_schema(&mut self, schema: UpgradeSchema) {
+        // Schemas with the same version are applied in the order they were added.
+        let index = self.schemas.partition_point(|s| s.version <= schema.version);
+        self.schemas.insert(index, schema);
+    }
+
+    /// Amount of loaded schemas.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.schemas.len()
+    }
+
+    /// Whether no schemas have been loaded.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.schemas.is_empty()
+    }
+
+    /// Version of the newest schema.
+    pub fn latest_version(&self) -> Option<[u8; 4]> {
+        self.schemas.last().map(UpgradeSchema::version)
+    }
+
+    /// Upgrades a block to the newest schema version.
+    ///
+    /// Returns `None` if there are no schemas that apply to the block.
+    pub fn upgrade(&self, entry: &PaletteEntry) -> Option<PaletteEntry> {
+        let start = match entry.version {
+            Some(version) => self.schemas.partition_point(|s| s.version < version),
+            None => 0,
+        };
+
+        let schemas = self.schemas.get(start..).filter(|schemas| !schemas.is_empty())?;
+
+        let mut upgraded = entry.clone();
+        for schema in schemas {
+            schema.apply(&mut upgraded);
+        }
+        upgraded.version = self.latest_version();
+
+        Some(upgraded)
+    }
+}
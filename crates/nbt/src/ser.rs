@@ -412,6 +412,17 @@ where
     #[inline]
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
         if let Some(len) = len {
+            // The element type and length are normally written together with the first element,
+            // so empty lists have to write them here.
+            if len == 0 {
+                self.writer.write_u8(FieldType::End as u8)?;
+                match M::AS_ENUM {
+                    Variant::BigEndian => self.writer.write_i32_be(0),
+                    Variant::LittleEndian => self.writer.write_i32_le(0),
+                    Variant::Variable => self.writer.write_var_i32(0),
+                }?;
+            }
+
             self.len = len;
             Ok(self)
         } else {
@@ -35,6 +35,26 @@ fn read_write_option() {
     };
 }
 
+#[test]
+fn read_write_empty_list() {
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Lists {
+        empty: Vec<i32>,
+        filled: Vec<i32>,
+    }
+
+    let lists = Lists { empty: Vec::new(), filled: vec![1, 2, 3] };
+
+    let le = to_le_bytes(&lists).unwrap();
+    assert_eq!(lists, from_le_bytes::<Lists, _>(&mut le.as_slice()).unwrap().0);
+
+    let be = to_be_bytes(&lists).unwrap();
+    assert_eq!(lists, from_be_bytes::<Lists, _>(&mut be.as_slice()).unwrap().0);
+
+    let var = to_var_bytes(&lists).unwrap();
+    assert_eq!(lists, from_var_bytes::<Lists, _>(&mut var.as_slice()).unwrap().0);
+}
+
 #[test]
 fn read_write_all() {
     let value = Value::Compound(HashMap::from([
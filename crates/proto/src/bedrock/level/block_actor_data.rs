@@ -0,0 +1,37 @@
+use std::collections::HashMap;
+
+use util::{BinaryRead, BinaryWrite, BlockPosition, Deserialize, Serialize};
+
+use crate::bedrock::ConnectedPacket;
+
+/// Sets the data of a block actor (also known as a block entity), such as the contents of a sign.
+///
+/// The server sends this when a block actor is loaded or modified.
+/// The client sends this when it modifies a block actor itself, for example when editing a sign.
+#[derive(Debug, Clone)]
+pub struct BlockActorData {
+    /// Position of the block that the actor belongs to.
+    pub position: BlockPosition,
+    /// NBT data of the block actor.
+    pub nbt: HashMap<String, nbt::Value>
+}
+
+impl ConnectedPacket for BlockActorData {
+    const ID: u32 = 0x38;
+}
+
+impl Serialize for BlockActorData {
+    fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writer.write_block_pos(&self.position)?;
+        nbt::to_var_bytes_in(writer, &self.nbt)
+    }
+}
+
+impl<'a> Deserialize<'a> for BlockActorData {
+    fn deserialize_from<R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
+        let position = reader.read_block_pos()?;
+        let (nbt, _) = nbt::from_var_bytes(reader)?;
+
+        Ok(Self { position, nbt })
+    }
+}
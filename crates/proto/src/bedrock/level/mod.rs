@@ -3,4 +3,5 @@ use util::glob_export;
 glob_export!(sub_chunk_response);
 glob_export!(level_chunk);
 glob_export!(sub_chunk_request);
-glob_export!(update_block);
\ No newline at end of file
+glob_export!(update_block);
+glob_export!(block_actor_data);
\ No newline at end of file
@@ -6,7 +6,7 @@ use util::Deserialize;
 
 use crate::bedrock::ConnectedPacket;
 use crate::crypto::{
-    BedrockIdentity, BedrockClientInfo, self,
+    BedrockIdentity, BedrockClientInfo, ChainValidation, self,
 };
 use crate::bedrock::Skin;
 
@@ -60,24 +60,41 @@ impl ConnectedPacket for Login {
     const ID: u32 = 0x01;
 }
 
-impl<'a> Deserialize<'a> for Login {
-    fn deserialize_from<R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
+impl Login {
+    /// Deserializes a login packet, validating the identity chain with the given options.
+    ///
+    /// The [`Deserialize`] implementation of this packet uses the default [`ChainValidation`] options.
+    pub fn deserialize_with<'a, R: BinaryRead<'a>>(reader: &mut R, options: &ChainValidation) -> anyhow::Result<Self> {
         // Skip protocol version, use the one in RequestNetworkSettings instead.
         let _version = reader.read_u32_be()?; 
         reader.read_var_u32()?;
 
-        let identity_data = crypto::parse_identity_data(reader)?;
+        let identity_data = crypto::parse_identity_data(reader, options)?;
         let data = crypto::parse_user_data(reader, &identity_data.public_key)?;
 
+        let name = identity_data.client_data.display_name;
+        let (xuid, uuid) = if identity_data.authenticated {
+            (identity_data.client_data.xuid.parse()?, identity_data.client_data.uuid)
+        } else {
+            BedrockIdentity::offline_ids(&name)
+        };
+
         Ok(Self {
             identity: BedrockIdentity {
-                uuid: identity_data.client_data.uuid,
-                xuid: identity_data.client_data.xuid.parse()?,
-                name: identity_data.client_data.display_name,
+                uuid,
+                xuid,
+                name,
                 public_key: identity_data.public_key,
+                authenticated: identity_data.authenticated,
             },
             client_info: data.data,
             skin: data.skin,
         })
     }
 }
+
+impl<'a> Deserialize<'a> for Login {
+    fn deserialize_from<R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<Self> {
+        Self::deserialize_with(reader, &ChainValidation::default())
+    }
+}
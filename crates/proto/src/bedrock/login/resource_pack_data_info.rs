@@ -0,0 +1,75 @@
+use util::{BinaryWrite, VarString};
+
+use util::Serialize;
+
+use crate::bedrock::ConnectedPacket;
+
+/// Type of pack described by a [`ResourcePackDataInfo`] packet.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ResourcePackType {
+    /// Invalid pack type.
+    Invalid,
+    /// A pack containing addons.
+    Addon,
+    /// A pack containing cached data.
+    Cached,
+    /// A pack containing copy-protected data.
+    CopyProtected,
+    /// A behavior pack.
+    Behavior,
+    /// A pack containing persona pieces.
+    PersonaPiece,
+    /// A resource pack.
+    Resources,
+    /// A pack containing skins.
+    Skins,
+    /// A world template.
+    WorldTemplate,
+}
+
+/// Sent in response to a [`ResourcePackClientResponse`](crate::bedrock::ResourcePackClientResponse) with a
+/// [`SendPacks`](crate::bedrock::ResourcePackStatus::SendPacks) status.
+///
+/// Informs the client about the size of the pack and the amount of chunks it will be split into.
+/// The client will then request each of these chunks using a [`ResourcePackChunkRequest`](crate::bedrock::ResourcePackChunkRequest).
+#[derive(Debug)]
+pub struct ResourcePackDataInfo<'a> {
+    /// ID of the pack, formatted as `uuid_version`.
+    pub pack_id: &'a str,
+    /// Maximum size of a single chunk in bytes.
+    pub max_chunk_size: u32,
+    /// Amount of chunks the pack has been split into.
+    pub chunk_count: u32,
+    /// Total size of the pack in bytes.
+    pub pack_size: u64,
+    /// SHA-256 hash of the entire pack.
+    pub hash: &'a [u8],
+    /// Whether this is a premium pack.
+    pub premium: bool,
+    /// Type of the pack.
+    pub pack_type: ResourcePackType,
+}
+
+impl ConnectedPacket for ResourcePackDataInfo<'_> {
+    const ID: u32 = 0x52;
+
+    fn serialized_size(&self) -> usize {
+        self.pack_id.var_len() + 4 + 4 + 8 +
+            util::size_of_varint(self.hash.len() as u32) + self.hash.len() +
+            1 + 1
+    }
+}
+
+impl Serialize for ResourcePackDataInfo<'_> {
+    fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writer.write_str(self.pack_id)?;
+        writer.write_u32_le(self.max_chunk_size)?;
+        writer.write_u32_le(self.chunk_count)?;
+        writer.write_u64_le(self.pack_size)?;
+        writer.write_var_u32(self.hash.len() as u32)?;
+        writer.write_all(self.hash)?;
+        writer.write_bool(self.premium)?;
+        writer.write_u8(self.pack_type as u8)
+    }
+}
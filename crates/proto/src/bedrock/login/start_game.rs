@@ -23,6 +23,21 @@ pub enum WorldGenerator {
     End,
 }
 
+impl TryFrom<i32> for WorldGenerator {
+    type Error = anyhow::Error;
+
+    fn try_from(value: i32) -> anyhow::Result<Self> {
+        Ok(match value {
+            0 => Self::OldLimited,
+            1 => Self::Infinite,
+            2 => Self::Flat,
+            3 => Self::Nether,
+            4 => Self::End,
+            _ => anyhow::bail!("Invalid world generator type {value}"),
+        })
+    }
+}
+
 impl WorldGenerator {
     /// Serializes the enum.
     pub fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
@@ -211,6 +226,21 @@ pub enum BroadcastIntent {
     Public,
 }
 
+impl TryFrom<i32> for BroadcastIntent {
+    type Error = anyhow::Error;
+
+    fn try_from(value: i32) -> anyhow::Result<Self> {
+        Ok(match value {
+            0 => Self::NoMultiplayer,
+            1 => Self::InviteOnly,
+            2 => Self::FriendsOnly,
+            3 => Self::FriendsOfFriends,
+            4 => Self::Public,
+            _ => anyhow::bail!("Invalid broadcast intent {value}"),
+        })
+    }
+}
+
 impl Serialize for BroadcastIntent {
     fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
         writer.write_var_u32(*self as u32)
@@ -225,6 +255,19 @@ pub enum EditorWorldType {
     TestLevel
 }
 
+impl TryFrom<i32> for EditorWorldType {
+    type Error = anyhow::Error;
+
+    fn try_from(value: i32) -> anyhow::Result<Self> {
+        Ok(match value {
+            0 => Self::NotEditor,
+            1 => Self::Project,
+            2 => Self::TestLevel,
+            _ => anyhow::bail!("Invalid editor world type {value}"),
+        })
+    }
+}
+
 impl Serialize for EditorWorldType {
     fn serialize_into<W: BinaryWrite>(&self, writer: &mut W) -> anyhow::Result<()> {
         writer.write_var_i32(*self as i32)
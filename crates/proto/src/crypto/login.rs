@@ -1,22 +1,160 @@
 
+use std::fmt;
+use std::time::Duration;
+
 use base64::Engine;
 use jsonwebtoken::{Algorithm, DecodingKey, Validation};
 use p384::pkcs8::spki;
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
 use util::{BinaryRead};
 
 use crate::bedrock::Skin;
-use crate::bedrock::{DeviceOS, UiProfile};
+use crate::bedrock::{DeviceOS, DisconnectReason, UiProfile};
 
 /// Mojang's public key.
 /// Used to verify the second token in the identity chain.
+///
+/// This is the default trusted root key. See [`ChainValidation::trusted_keys`] to configure others.
 // OLD KEY: MHYwEAYHKoZIzj0CAQYFK4EEACIDYgAE8ELkixyLcwlZryUQcu1TvPOmI2B7vX83ndnWRUaXm74wFfa5f/lwQNTfrLVHa2PmenpGI6JhIMUJaWZrjmMj90NoKNFSNBuKdm8rYiXsfaz3K36x/1U26HpG0ZxK/V1V
 pub const MOJANG_PUBLIC_KEY: &str = "MHYwEAYHKoZIzj0CAQYFK4EEACIDYgAECRXueJeTDqNRRgJi/vlRufByu/2G0i2Ebt6YMar5QX/R0DIIyrJMcUpruK4QveTfJSTp3Shlq4Gk34cD/4GUWwkv0DVuzeuB+tXija7HBxii03NHDbPAD0AKnLr2wdAp";
 
 /// Use the default Base64 format with no padding.
 const BASE64_ENGINE: base64::engine::GeneralPurpose = base64::engine::general_purpose::STANDARD_NO_PAD;
 
+/// Issuer of the tokens that have been signed by Mojang.
+const MOJANG_ISSUER: &str = "Mojang";
+/// Amount of tokens in an identity chain of a client that is signed into Xbox Live.
+const MOJANG_CHAIN_LENGTH: usize = 3;
+
+/// Prefix that is hashed together with the username to create offline identities.
+const OFFLINE_IDENTITY_PREFIX: &str = "OfflinePlayer:";
+
+/// Determines which identity chains are accepted during login.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum AuthMode {
+    /// Only chains that have been signed by Mojang are accepted.
+    /// This requires every client to be signed into Xbox Live.
+    #[default]
+    Online,
+    /// Self-signed chains are accepted as well.
+    ///
+    /// This is meant for local development, automated clients and CI.
+    /// Clients that log in with a self-signed chain receive an offline XUID and UUID
+    /// derived from their name and are marked as unauthenticated.
+    Offline,
+}
+
+/// Options that determine how the identity chain is validated during login.
+#[derive(Debug, Clone)]
+pub struct ChainValidation {
+    /// Which chains are accepted. See [`AuthMode`].
+    pub mode: AuthMode,
+    /// Base64 encoded root keys that are trusted to sign identity tokens.
+    ///
+    /// By default this only contains [`MOJANG_PUBLIC_KEY`]. Adding keys here allows
+    /// the Mojang key to be rotated without recompiling the server.
+    pub trusted_keys: Vec<String>,
+    /// Amount of clock skew between client, Mojang and server that is tolerated
+    /// when checking the `exp` and `nbf` claims.
+    pub clock_skew: Duration,
+    /// Maximum amount of tokens that an identity chain may contain.
+    pub max_chain_length: usize,
+}
+
+impl ChainValidation {
+    /// Whether the given Base64 encoded key is one of the trusted root keys.
+    #[inline]
+    pub fn is_trusted(&self, key: &str) -> bool {
+        self.trusted_keys.iter().any(|trusted| trusted == key)
+    }
+}
+
+impl Default for ChainValidation {
+    fn default() -> Self {
+        Self {
+            mode: AuthMode::Online,
+            trusted_keys: vec![MOJANG_PUBLIC_KEY.to_owned()],
+            clock_skew: Duration::from_secs(60),
+            max_chain_length: MOJANG_CHAIN_LENGTH,
+        }
+    }
+}
+
+/// Reason why an identity chain was rejected.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChainError {
+    /// The chain, one of its tokens or one of its keys could not be decoded.
+    Malformed,
+    /// The chain contains an invalid amount of tokens.
+    InvalidLength(usize),
+    /// The client is not signed into Xbox Live while the server is in online mode.
+    Unauthenticated,
+    /// None of the keys in the chain is a trusted root key.
+    UntrustedRoot,
+    /// The signature of one of the tokens does not match its key.
+    InvalidSignature,
+    /// One of the tokens has expired.
+    Expired,
+    /// One of the tokens is not valid yet.
+    NotYetValid,
+    /// One of the tokens was not issued by Mojang.
+    InvalidIssuer,
+}
+
+impl ChainError {
+    /// The disconnect reason that should be sent to a client whose chain was rejected for this reason.
+    ///
+    /// Every failure maps to a different reason, so that the cause can be told apart on the client.
+    pub const fn disconnect_reason(&self) -> DisconnectReason {
+        match self {
+            Self::Malformed => DisconnectReason::BadPacket,
+            Self::InvalidLength(_) => DisconnectReason::LoginPacketNoCert,
+            Self::Unauthenticated => DisconnectReason::NotAuthenticated,
+            // The chain does not contain an authentication issued by Mojang.
+            Self::UntrustedRoot => DisconnectReason::EmptyAuthFromDiscovery,
+            Self::InvalidSignature => DisconnectReason::InvalidPlayer,
+            Self::Expired => DisconnectReason::ExpiredAuthFromDiscovery,
+            // Usually caused by a clock that is out of sync, the client may not join until the token is valid.
+            Self::NotYetValid => DisconnectReason::NotAllowed,
+            Self::InvalidIssuer => DisconnectReason::InvalidTenant,
+        }
+    }
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Malformed => write!(fmt, "Identity chain is malformed"),
+            Self::InvalidLength(len) => write!(fmt, "Identity chain has an invalid length of {len}"),
+            Self::Unauthenticated => write!(fmt, "User must be authenticated with Microsoft services"),
+            Self::UntrustedRoot => write!(fmt, "Identity chain was not signed by a trusted key"),
+            Self::InvalidSignature => write!(fmt, "Identity token has an invalid signature"),
+            Self::Expired => write!(fmt, "Identity token has expired"),
+            Self::NotYetValid => write!(fmt, "Identity token is not valid yet"),
+            Self::InvalidIssuer => write!(fmt, "Identity token was not issued by Mojang"),
+        }
+    }
+}
+
+impl std::error::Error for ChainError {}
+
+impl From<&jsonwebtoken::errors::ErrorKind> for ChainError {
+    fn from(kind: &jsonwebtoken::errors::ErrorKind) -> Self {
+        use jsonwebtoken::errors::ErrorKind;
+
+        match kind {
+            ErrorKind::InvalidSignature => Self::InvalidSignature,
+            ErrorKind::ExpiredSignature => Self::Expired,
+            ErrorKind::ImmatureSignature => Self::NotYetValid,
+            ErrorKind::InvalidIssuer => Self::InvalidIssuer,
+            ErrorKind::MissingRequiredClaim(claim) if claim == "iss" => Self::InvalidIssuer,
+            _ => Self::Malformed,
+        }
+    }
+}
+
 /// Data contained in the identity token chain.
 #[derive(Debug, Clone)]
 pub struct BedrockIdentity {
@@ -28,6 +166,35 @@ pub struct BedrockIdentity {
     pub name: String,
     /// Public key used for token verification and encryption.
     pub public_key: String,
+    /// Whether the identity chain was signed by Mojang.
+    ///
+    /// This is always `true` in [`AuthMode::Online`]. In [`AuthMode::Offline`] it is `false` for clients
+    /// that logged in with a self-signed chain, in which case the XUID and UUID are offline identifiers.
+    pub authenticated: bool,
+}
+
+impl BedrockIdentity {
+    /// Derives the deterministic offline XUID and UUID for the given username.
+    ///
+    /// The same name always results in the same identifiers, which allows offline clients to
+    /// keep their data across sessions. The highest bit of the XUID is always set so that offline
+    /// XUIDs can never collide with real Xbox account IDs.
+    pub fn offline_ids(name: &str) -> (u64, Uuid) {
+        let mut hasher = Sha256::new();
+        hasher.update(OFFLINE_IDENTITY_PREFIX);
+        hasher.update(name);
+        let digest = hasher.finalize();
+
+        let mut uuid_bytes = [0; 16];
+        uuid_bytes.copy_from_slice(&digest[..16]);
+        let uuid = uuid::Builder::from_custom_bytes(uuid_bytes).into_uuid();
+
+        let mut xuid_bytes = [0; 8];
+        xuid_bytes.copy_from_slice(&digest[16..24]);
+        let xuid = u64::from_be_bytes(xuid_bytes) | 1 << 63;
+
+        (xuid, uuid)
+    }
 }
 
 /// Used to extract data from the user data token.
@@ -76,7 +243,7 @@ struct KeyTokenPayload {
 #[derive(serde::Deserialize, Debug)]
 pub struct RawIdentityData {
     /// The Xbox user ID of the client. This is what uniquely identifies a user and is used in several packets.
-    #[serde(rename = "XUID")]
+    #[serde(rename = "XUID", default)]
     pub xuid: String,
     /// The display name of the user. This is their Xbox gamertag.
     #[serde(rename = "displayName")]
@@ -98,6 +265,9 @@ pub struct IdentityTokenPayload {
     /// Contains the user's public key. This is used for encryption.
     #[serde(rename = "identityPublicKey")]
     pub public_key: String,
+    /// Whether this identity was signed by Mojang.
+    #[serde(skip)]
+    pub authenticated: bool,
 }
 
 /// Data structure that splits the user data token into separate [`Skin`] and
@@ -112,190 +282,178 @@ pub struct UserDataTokenPayload {
     pub skin: Skin,
 }
 
-/// First token in the chain holds the client's self-signed public key in the X5U.
-/// It is extracted from the header of the token and used to verify its signature.
-/// The payload of the token contains a new key which is used to verify the next token.
+/// Verifies and decodes the user data token.
 #[tracing::instrument(
     skip_all,
-    name = "crypto::parse_initial_token"
+    name = "crypto::parse_user_data_token"
 )]
-fn parse_initial_token(token: &str) -> anyhow::Result<String> {
-    // Decode JWT header to get X5U.
-    let header = match jsonwebtoken::decode_header(token) {
-        Ok(header) => header,
-        Err(err) => {
-            tracing::error!("Unable to parse initial JWT header | {err:#}");
-            anyhow::bail!("Unable to parse initial JWT header | {err:#}");
-        }
-    };
-
-    let Some(base64_x5u) = header.x5u else {
-        tracing::error!("Missing X.509 certificate in initial JWT");
-        anyhow::bail!("Missing X.509 certificate in initial JWT");
-    };
-    let bytes = BASE64_ENGINE.decode(base64_x5u)?;
-
-    // Public key that can be used to verify the token.
+fn parse_user_data_token(token: &str, key: &str) -> anyhow::Result<UserDataTokenPayload> {
+    let bytes = BASE64_ENGINE.decode(key)?;
     let public_key = match spki::SubjectPublicKeyInfoRef::try_from(bytes.as_ref()) {
         Ok(p) => p,
         Err(e) => {
-            tracing::error!("The first public key received during login is invalid");
+            tracing::error!("User data token public key is invalid");
             anyhow::bail!("Invalid client public key: {e}")
         }
     };
 
     let decoding_key = DecodingKey::from_ec_der(public_key.subject_public_key.raw_bytes());
     let mut validation = Validation::new(Algorithm::ES384);
-    validation.validate_exp = true;
-    validation.validate_nbf = true;
 
-    let payload = match jsonwebtoken::decode::<KeyTokenPayload>(token, &decoding_key, &validation) {
+    // No special header data include in this token, don't verify anything.
+    validation.required_spec_claims.clear();
+
+    let payload = match jsonwebtoken::decode::<UserDataTokenPayload>(token, &decoding_key, &validation) {
         Ok(payload) => payload,
         Err(err) => {
-            tracing::error!("Unable to decode initial JWT | {err:#}");
-            anyhow::bail!("Unable to decode initial JWT | {err:#}");
+            tracing::error!("Unable to decode user data JWT | {err:#}");
+            anyhow::bail!("Unable to decode user data JWT | {err:#}");
         }
     };
 
-    Ok(payload.claims.public_key)
+    Ok(payload.claims)
 }
 
-/// The second token in the chain can be verified using Mojang's public key
-/// (or the identityPublicKey from the previous token).
-/// This token contains another identityPublicKey which is the public key for the third token.
-#[tracing::instrument(
-    skip_all,
-    name = "crypto::parse_mojang_token"
-)]
-fn parse_mojang_token(token: &str, key: &str) -> anyhow::Result<String> {
-    let bytes = BASE64_ENGINE.decode(key)?;
+/// Decodes a Base64 encoded public key into a key that can verify tokens.
+fn decoding_key(key: &str) -> Result<DecodingKey, ChainError> {
+    let bytes = BASE64_ENGINE.decode(key).map_err(|_| ChainError::Malformed)?;
     let public_key = match spki::SubjectPublicKeyInfoRef::try_from(bytes.as_ref()) {
         Ok(p) => p,
-        Err(e) => {
-            tracing::error!("The second public key received during login is invalid");
-            anyhow::bail!("Invalid client public key: {e}")
+        Err(err) => {
+            tracing::error!("Public key received during login is invalid: {err}");
+            return Err(ChainError::Malformed);
         }
     };
 
-    let decoding_key = DecodingKey::from_ec_der(public_key.subject_public_key.raw_bytes());
-    let mut validation = Validation::new(Algorithm::ES384);
-    validation.set_issuer(&["Mojang"]);
-    validation.validate_nbf = true;
-    validation.validate_exp = true;
+    Ok(DecodingKey::from_ec_der(public_key.subject_public_key.raw_bytes()))
+}
 
-    let payload = match jsonwebtoken::decode::<KeyTokenPayload>(token, &decoding_key, &validation) {
-        Ok(payload) => payload,
+/// Extracts the Base64 encoded public key from the X5U header of a token.
+///
+/// The first token in the chain holds the client's self-signed public key in the X5U.
+/// This key is used to verify the signature of that same token.
+fn parse_x5u(token: &str) -> Result<String, ChainError> {
+    let header = match jsonwebtoken::decode_header(token) {
+        Ok(header) => header,
         Err(err) => {
-            tracing::error!("Unable to decode second JWT | {err:#}");
-            anyhow::bail!("Unable to decode second JWT | {err:#}")
+            tracing::error!("Unable to parse initial JWT header | {err:#}");
+            return Err(ChainError::Malformed);
         }
     };
 
-    Ok(payload.claims.public_key)
+    header.x5u.ok_or_else(|| {
+        tracing::error!("Missing X.509 certificate in initial JWT");
+        ChainError::Malformed
+    })
 }
 
-/// The third token contains the client's actual public key and extra data.
-/// The extraData field contains the XUID, client identity (UUID) and the display name.
+/// Verifies a single token in the chain using the given key and decodes its payload.
 ///
-/// Just like the second one, this token can be verified using the identityPublicKey from the last token.
-#[tracing::instrument(
-    skip_all,
-    name = "crypto::parse_identity_token"
-)]
-fn parse_identity_token(token: &str, key: &str) -> anyhow::Result<IdentityTokenPayload> {
-    let bytes = BASE64_ENGINE.decode(key)?;
-    let public_key = match spki::SubjectPublicKeyInfoRef::try_from(bytes.as_ref()) {
-        Ok(p) => p,
-        Err(e) => {
-            tracing::error!("The third public key received during login is invalid");
-            anyhow::bail!("Invalid client public key: {e}")
-        }
-    };
+/// The `exp` claim is required and both `exp` and `nbf` are checked with the clock skew tolerance
+/// configured in `options`. If an issuer is given, the `iss` claim is required and must match it.
+fn verify_token<T>(token: &str, key: &str, issuer: Option<&str>, options: &ChainValidation) -> Result<T, ChainError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let decoding_key = decoding_key(key)?;
 
-    let decoding_key = DecodingKey::from_ec_der(public_key.subject_public_key.raw_bytes());
     let mut validation = Validation::new(Algorithm::ES384);
-    validation.set_issuer(&["Mojang"]);
-    validation.validate_nbf = true;
+    validation.leeway = options.clock_skew.as_secs();
     validation.validate_exp = true;
+    validation.validate_nbf = true;
+    if let Some(issuer) = issuer {
+        validation.set_issuer(&[issuer]);
+        validation.set_required_spec_claims(&["exp", "iss"]);
+    }
 
-    let payload = match jsonwebtoken::decode::<IdentityTokenPayload>(token, &decoding_key, &validation) {
-        Ok(payload) => payload,
+    match jsonwebtoken::decode::<T>(token, &decoding_key, &validation) {
+        Ok(payload) => Ok(payload.claims),
         Err(err) => {
-            tracing::error!("Unable to decode identity JWT | {err:#}");
-            anyhow::bail!("Unable to decode identity JWT | {err:#}")
+            tracing::error!("Unable to verify JWT in identity chain | {err:#}");
+            Err(ChainError::from(err.kind()))
         }
-    };
-
-    Ok(payload.claims)
+    }
 }
 
-/// Verifies and decodes the user data token.
+/// Verifies the full identity chain.
+///
+/// The first token is verified using the key in its own X5U header. Every following token
+/// is verified with the `identityPublicKey` of the token before it. As soon as a token is signed
+/// by one of the trusted root keys, every following token must have been issued by Mojang.
+///
+/// The returned payload is marked as authenticated if the identity token was signed by a chain
+/// that contains a trusted root key.
 #[tracing::instrument(
     skip_all,
-    name = "crypto::parse_user_data_token"
+    name = "crypto::verify_chain"
 )]
-fn parse_user_data_token(token: &str, key: &str) -> anyhow::Result<UserDataTokenPayload> {
-    let bytes = BASE64_ENGINE.decode(key)?;
-    let public_key = match spki::SubjectPublicKeyInfoRef::try_from(bytes.as_ref()) {
-        Ok(p) => p,
-        Err(e) => {
-            tracing::error!("User data token public key is invalid");
-            anyhow::bail!("Invalid client public key: {e}")
+fn verify_chain(chain: &[String], options: &ChainValidation) -> Result<IdentityTokenPayload, ChainError> {
+    let len = chain.len();
+    if len == 0 || len > options.max_chain_length {
+        tracing::error!("Received invalid amount of tokens. Got {len}, expected at most {}", options.max_chain_length);
+        return Err(ChainError::InvalidLength(len));
+    }
+
+    if options.mode == AuthMode::Online {
+        if len == 1 {
+            // Client is not signed into Xbox.
+            tracing::warn!("User is not authenticated with Microsoft services");
+            return Err(ChainError::Unauthenticated);
+        }
+
+        if len != MOJANG_CHAIN_LENGTH {
+            tracing::error!("Received invalid amount of tokens. Got {len}, expected {MOJANG_CHAIN_LENGTH}");
+            return Err(ChainError::InvalidLength(len));
         }
+    }
+
+    // `split_last` cannot fail because the length was checked above.
+    let Some((last, rest)) = chain.split_last() else {
+        return Err(ChainError::InvalidLength(len));
     };
 
-    let decoding_key = DecodingKey::from_ec_der(public_key.subject_public_key.raw_bytes());
-    let mut validation = Validation::new(Algorithm::ES384);
+    let mut key = parse_x5u(&chain[0])?;
+    let mut trusted = false;
 
-    // No special header data include in this token, don't verify anything.
-    validation.required_spec_claims.clear();
+    for token in rest {
+        trusted |= options.is_trusted(&key);
 
-    let payload = match jsonwebtoken::decode::<UserDataTokenPayload>(token, &decoding_key, &validation) {
-        Ok(payload) => payload,
-        Err(err) => {
-            tracing::error!("Unable to decode user data JWT | {err:#}");
-            anyhow::bail!("Unable to decode user data JWT | {err:#}");
-        }
-    };
+        let issuer = trusted.then_some(MOJANG_ISSUER);
+        let payload: KeyTokenPayload = verify_token(token, &key, issuer, options)?;
+        key = payload.public_key;
+    }
 
-    Ok(payload.claims)
+    trusted |= options.is_trusted(&key);
+    if !trusted && options.mode == AuthMode::Online {
+        tracing::error!("Attempt to login using a token that was not created by Mojang");
+        return Err(ChainError::UntrustedRoot);
+    }
+
+    let issuer = trusted.then_some(MOJANG_ISSUER);
+    let mut identity: IdentityTokenPayload = verify_token(last, &key, issuer, options)?;
+    identity.authenticated = trusted;
+
+    Ok(identity)
 }
 
 /// Parses the identification data contained in the first token chain.
 ///
 /// This contains such as the XUID, display name and public key.
-pub fn parse_identity_data<'a, R: BinaryRead<'a>>(reader: &mut R) -> anyhow::Result<IdentityTokenPayload> {
+/// Which chains are accepted depends on the given [`ChainValidation`] options.
+///
+/// # Errors
+///
+/// If the chain is rejected, the returned error contains a [`ChainError`] describing why.
+pub fn parse_identity_data<'a, R: BinaryRead<'a>>(reader: &mut R, options: &ChainValidation) -> anyhow::Result<IdentityTokenPayload> {
     let token_length = reader.read_u32_le()?;
     let token_chain = reader.take_n(token_length as usize)?;
 
-    let tokens = serde_json::from_slice::<TokenChain>(token_chain)?;
-    let identity_data = match tokens.chain.len() {
-        1 => {
-            // Client is not signed into Xbox.
-            tracing::warn!("User is not authenticated with Microsoft services");
-            anyhow::bail!("User must be authenticated with Microsoft services");
-        }
-        3 => {
-            // Verify the first token and decode the public key for the next token.
-            // This public key must be equal to Mojang's public key to verify that the second
-            // token was signed by Mojang.
-
-            let mut key = parse_initial_token(&tokens.chain[0])?;
-            if !key.eq(MOJANG_PUBLIC_KEY) {
-                tracing::error!("Attempt to login using a token that was not created by Mojang");
-                anyhow::bail!("Identity token was not signed by Mojang");
-            }
-
-            key = parse_mojang_token(&tokens.chain[1], &key)?;
-            parse_identity_token(&tokens.chain[2], &key)?
-        }
-        len => {
-            tracing::error!("Received invalid amount of tokens. Got {len}, expected 3");
-            anyhow::bail!("Received invalid amount of tokens. Got {len}, expected 3")
-        }
+    let Ok(tokens) = serde_json::from_slice::<TokenChain>(token_chain) else {
+        tracing::error!("Unable to parse identity chain");
+        return Err(ChainError::Malformed.into());
     };
 
-    Ok(identity_data)
+    Ok(verify_chain(&tokens.chain, options)?)
 }
 
 /// Parses the user data token from the login packet.
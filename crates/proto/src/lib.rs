@@ -47,6 +47,9 @@ pub mod crypto;
 pub mod raknet;
 pub mod types;
 
+#[cfg(test)]
+mod test;
+
 // pub mod xbox;
 
 pub use base64;
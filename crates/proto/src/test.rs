@@ -0,0 +1,294 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use p384::pkcs8::{EncodePrivateKey, EncodePublicKey, LineEnding};
+use serde_json::json;
+use util::BinaryWrite;
+
+use crate::crypto::{self, AuthMode, BedrockIdentity, ChainError, ChainValidation};
+
+const BASE64_ENGINE: base64::engine::GeneralPurpose = base64::engine::general_purpose::STANDARD_NO_PAD;
+
+/// A key pair used to sign test tokens.
+struct Signer {
+    encoding: EncodingKey,
+    public: String,
+}
+
+impl Signer {
+    fn new() -> Signer {
+        let secret = p384::SecretKey::random(&mut rand::thread_rng());
+        let pem = secret.to_pkcs8_pem(LineEnding::LF).unwrap();
+        let der = secret.public_key().to_public_key_der().unwrap();
+
+        Signer {
+            encoding: EncodingKey::from_ec_pem(pem.as_bytes()).unwrap(),
+            public: BASE64_ENGINE.encode(der.as_bytes()),
+        }
+    }
+
+    fn sign(&self, claims: serde_json::Value) -> String {
+        let mut header = Header::new(Algorithm::ES384);
+        header.x5u = Some(self.public.clone());
+
+        jsonwebtoken::encode(&header, &claims, &self.encoding).unwrap()
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64
+}
+
+/// Keys of a chain that imitates the structure of an Xbox Live chain.
+struct Chain {
+    client: Signer,
+    root: Signer,
+    intermediate: Signer,
+}
+
+impl Chain {
+    fn new() -> Chain {
+        Chain {
+            client: Signer::new(),
+            root: Signer::new(),
+            intermediate: Signer::new(),
+        }
+    }
+
+    fn tokens(&self, exp: i64, nbf: i64, issuer: &str) -> Vec<String> {
+        vec![
+            self.client.sign(json!({
+                "certificateAuthority": true,
+                "identityPublicKey": self.root.public,
+                "exp": exp,
+                "nbf": nbf
+            })),
+            self.root.sign(json!({
+                "identityPublicKey": self.intermediate.public,
+                "iss": issuer,
+                "exp": exp,
+                "nbf": nbf
+            })),
+            self.intermediate.sign(json!({
+                "extraData": {
+                    "XUID": "2535412345678901",
+                    "displayName": "Steve",
+                    "identity": "9a7b5e0e-7f56-4f86-a2c6-4c4a0a3d8e8b"
+                },
+                "identityPublicKey": self.client.public,
+                "iss": issuer,
+                "exp": exp,
+                "nbf": nbf
+            })),
+        ]
+    }
+
+    fn valid_tokens(&self) -> Vec<String> {
+        self.tokens(now() + 3600, now() - 60, "Mojang")
+    }
+
+    fn options(&self) -> ChainValidation {
+        ChainValidation {
+            trusted_keys: vec![self.root.public.clone()],
+            ..Default::default()
+        }
+    }
+}
+
+fn encode_chain(tokens: &[String]) -> Vec<u8> {
+    let chain = serde_json::to_vec(&json!({ "chain": tokens })).unwrap();
+
+    let mut buffer = Vec::new();
+    buffer.write_u32_le(chain.len() as u32).unwrap();
+    buffer.extend_from_slice(&chain);
+    buffer
+}
+
+fn verify(tokens: &[String], options: &ChainValidation) -> Result<crypto::IdentityTokenPayload, ChainError> {
+    let buffer = encode_chain(tokens);
+    crypto::parse_identity_data(&mut buffer.as_slice(), options).map_err(|err| *err.downcast_ref::<ChainError>().unwrap())
+}
+
+#[test]
+fn chain_valid() {
+    let chain = Chain::new();
+    let identity = verify(&chain.valid_tokens(), &chain.options()).unwrap();
+
+    assert!(identity.authenticated, "Chain signed by trusted root should be authenticated");
+    assert_eq!(identity.client_data.display_name, "Steve");
+    assert_eq!(identity.public_key, chain.client.public);
+}
+
+#[test]
+fn chain_untrusted_root() {
+    let chain = Chain::new();
+    let err = verify(&chain.valid_tokens(), &ChainValidation::default()).unwrap_err();
+
+    assert_eq!(err, ChainError::UntrustedRoot);
+}
+
+#[test]
+fn chain_rotated_root() {
+    let chain = Chain::new();
+    let mut options = ChainValidation::default();
+    options.trusted_keys.push(chain.root.public.clone());
+
+    let identity = verify(&chain.valid_tokens(), &options).unwrap();
+    assert!(identity.authenticated, "Chain signed by added root key should be authenticated");
+}
+
+#[test]
+fn chain_tampered() {
+    let chain = Chain::new();
+    let mut tokens = chain.valid_tokens();
+
+    // Replace the payload of the identity token without re-signing it.
+    let parts: Vec<&str> = tokens[2].split('.').collect();
+    let payload = json!({
+        "extraData": {
+            "XUID": "2535412345678902",
+            "displayName": "Alex",
+            "identity": "9a7b5e0e-7f56-4f86-a2c6-4c4a0a3d8e8b"
+        },
+        "identityPublicKey": chain.client.public,
+        "iss": "Mojang",
+        "exp": now() + 3600,
+        "nbf": now() - 60
+    });
+    let encoded = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(serde_json::to_vec(&payload).unwrap());
+    tokens[2] = format!("{}.{}.{}", parts[0], encoded, parts[2]);
+
+    let err = verify(&tokens, &chain.options()).unwrap_err();
+    assert_eq!(err, ChainError::InvalidSignature);
+    assert_ne!(err.disconnect_reason(), ChainError::Expired.disconnect_reason());
+}
+
+#[test]
+fn chain_expired() {
+    let chain = Chain::new();
+    let tokens = chain.tokens(now() - 3600, now() - 7200, "Mojang");
+
+    let err = verify(&tokens, &chain.options()).unwrap_err();
+    assert_eq!(err, ChainError::Expired);
+}
+
+#[test]
+fn chain_clock_skew() {
+    let chain = Chain::new();
+
+    // Expired 30 seconds ago, which falls within the default skew of 60 seconds.
+    let tokens = chain.tokens(now() - 30, now() - 3600, "Mojang");
+    verify(&tokens, &chain.options()).unwrap();
+
+    let mut options = chain.options();
+    options.clock_skew = Duration::ZERO;
+    assert_eq!(verify(&tokens, &options).unwrap_err(), ChainError::Expired);
+}
+
+#[test]
+fn chain_not_yet_valid() {
+    let chain = Chain::new();
+    let tokens = chain.tokens(now() + 7200, now() + 3600, "Mojang");
+
+    let err = verify(&tokens, &chain.options()).unwrap_err();
+    assert_eq!(err, ChainError::NotYetValid);
+}
+
+#[test]
+fn chain_invalid_issuer() {
+    let chain = Chain::new();
+    let tokens = chain.tokens(now() + 3600, now() - 60, "Notch");
+
+    let err = verify(&tokens, &chain.options()).unwrap_err();
+    assert_eq!(err, ChainError::InvalidIssuer);
+}
+
+#[test]
+fn chain_truncated() {
+    let chain = Chain::new();
+    let mut tokens = chain.valid_tokens();
+
+    tokens.pop();
+    assert_eq!(verify(&tokens, &chain.options()).unwrap_err(), ChainError::InvalidLength(2));
+
+    tokens.truncate(1);
+    assert_eq!(verify(&tokens, &chain.options()).unwrap_err(), ChainError::Unauthenticated);
+
+    assert_eq!(verify(&[], &chain.options()).unwrap_err(), ChainError::InvalidLength(0));
+
+    // Cut off the signature of the identity token.
+    let mut tokens = chain.valid_tokens();
+    let cut = tokens[2].rfind('.').unwrap();
+    tokens[2].truncate(cut + 10);
+    assert_eq!(verify(&tokens, &chain.options()).unwrap_err(), ChainError::Malformed);
+}
+
+#[test]
+fn chain_disconnect_reason() {
+    use crate::bedrock::DisconnectReason;
+
+    let failures = [
+        ChainError::Malformed,
+        ChainError::InvalidLength(2),
+        ChainError::Unauthenticated,
+        ChainError::UntrustedRoot,
+        ChainError::InvalidSignature,
+        ChainError::Expired,
+        ChainError::NotYetValid,
+        ChainError::InvalidIssuer,
+    ];
+    for (i, first) in failures.iter().enumerate() {
+        for second in &failures[i + 1..] {
+            assert_ne!(first.disconnect_reason(), second.disconnect_reason(), "{first:?} and {second:?}");
+        }
+    }
+
+    assert_eq!(ChainError::Unauthenticated.disconnect_reason(), DisconnectReason::NotAuthenticated);
+    assert_eq!(ChainError::Expired.disconnect_reason(), DisconnectReason::ExpiredAuthFromDiscovery);
+}
+
+#[test]
+fn chain_too_long() {
+    let chain = Chain::new();
+    let mut tokens = chain.valid_tokens();
+    tokens.insert(0, tokens[0].clone());
+
+    let err = verify(&tokens, &chain.options()).unwrap_err();
+    assert_eq!(err, ChainError::InvalidLength(4));
+}
+
+#[test]
+fn chain_self_signed() {
+    let client = Signer::new();
+    let tokens = vec![client.sign(json!({
+        "extraData": {
+            "displayName": "Bot",
+            "identity": "9a7b5e0e-7f56-4f86-a2c6-4c4a0a3d8e8b"
+        },
+        "identityPublicKey": client.public,
+        "exp": now() + 3600,
+        "nbf": now() - 60
+    }))];
+
+    assert_eq!(verify(&tokens, &ChainValidation::default()).unwrap_err(), ChainError::Unauthenticated);
+
+    let options = ChainValidation {
+        mode: AuthMode::Offline,
+        ..Default::default()
+    };
+    let identity = verify(&tokens, &options).unwrap();
+    assert!(!identity.authenticated, "Self-signed chain should not be authenticated");
+}
+
+#[test]
+fn offline_ids() {
+    let (xuid1, uuid1) = BedrockIdentity::offline_ids("Bot");
+    let (xuid2, uuid2) = BedrockIdentity::offline_ids("Bot");
+    let (xuid3, uuid3) = BedrockIdentity::offline_ids("Steve");
+
+    assert_eq!((xuid1, uuid1), (xuid2, uuid2));
+    assert_ne!(xuid1, xuid3);
+    assert_ne!(uuid1, uuid3);
+    assert_ne!(xuid1 & 1 << 63, 0);
+}